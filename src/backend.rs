@@ -0,0 +1,89 @@
+use crate::error::Result;
+use crate::git::{CommitInfo, GitManager};
+use crate::report::SyncReport;
+use crate::sync::SyncEvent;
+use std::future::Future;
+use tokio::sync::mpsc::Sender;
+
+/// A way of bringing source commits into the target. Backends share the
+/// progress events, the report and the journal, so the interface, the
+/// `--report` file and the exit code don't depend on which one ran.
+pub trait SyncBackend {
+    /// Name of the backend, as given to `--engine` and recorded in the report
+    fn name(&self) -> &'static str;
+
+    /// What a run over `commits` would do, without touching either repository
+    fn plan(&self, commits: &[CommitInfo]) -> SyncPlan;
+
+    /// Runs the sync, sending progress to `tx`. The report is also sent as the
+    /// final `Completed` event.
+    fn execute(
+        &mut self,
+        git_manager: &GitManager,
+        commits: &[CommitInfo],
+        tx: Sender<SyncEvent>,
+    ) -> impl Future<Output = Result<SyncReport>> + Send;
+}
+
+/// Runs `backend` on a blocking thread and hands the `GitManager` back for the
+/// work after the run. Patch generation and `git am` block for as long as a
+/// commit takes, which on a runtime worker would hold up every other task
+/// scheduled there, the interface's event forwarding included.
+pub async fn execute_blocking<B>(
+    mut backend: B,
+    git_manager: GitManager,
+    commits: Vec<CommitInfo>,
+    tx: Sender<SyncEvent>,
+) -> (GitManager, Result<SyncReport>)
+where
+    B: SyncBackend + Send + 'static,
+{
+    let runtime = tokio::runtime::Handle::current();
+    let run = tokio::task::spawn_blocking(move || {
+        let result = runtime.block_on(backend.execute(&git_manager, &commits, tx));
+        (git_manager, result)
+    });
+    match run.await {
+        Ok(done) => done,
+        // Blocking tasks are never cancelled, so this is a panic of the backend
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Steps a backend intends to take, shown before the run starts
+#[derive(Debug, Clone, Default)]
+pub struct SyncPlan {
+    /// Source commits whose changes reach the target
+    pub applied: usize,
+    /// Source commits left out, such as merges the patch engine skips
+    pub skipped: usize,
+    /// Commits the run creates in the target
+    pub target_commits: usize,
+}
+
+/// Backend chosen with `--engine`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Engine {
+    /// Replays every commit as a patch, keeping the history
+    #[default]
+    Patch,
+    /// Copies the directory as of the last selected commit in one commit
+    Copy,
+}
+
+impl Engine {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "patch" => Ok(Self::Patch),
+            "copy" => Ok(Self::Copy),
+            other => Err(anyhow::anyhow!("Invalid engine: {} (expected patch or copy)", other)),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Patch => "patch",
+            Self::Copy => "copy",
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::error::{Result, SyncError};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// File name of the manifest inside an exported bundle directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Bumped whenever the manifest layout changes incompatibly
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Describes an exported patch bundle: where the patches came from and the
+/// target commit they were generated against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub created: String,
+    pub source_repo: PathBuf,
+    pub subdir: String,
+    /// Directory of the target repository the patches apply below, empty for the root
+    #[serde(default)]
+    pub target_dir: String,
+    /// Target branch head the patches apply on top of
+    pub target_base: String,
+    pub patches: Vec<BundlePatch>,
+}
+
+/// A patch file in the bundle, in application order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundlePatch {
+    /// File name relative to the bundle directory
+    pub file: String,
+    pub source: String,
+    pub subject: String,
+}
+
+impl Manifest {
+    /// Reads the manifest of the bundle in `dir`, rejecting unknown versions
+    pub fn read(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|_| SyncError::PathNotFound(path.clone()))?;
+        let manifest: Self = serde_json::from_str(&content)
+            .map_err(|e| SyncError::Anyhow(anyhow::anyhow!("Invalid manifest {}: {}", path.display(), e)))?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(SyncError::Anyhow(anyhow::anyhow!(
+                "Unsupported manifest version {} (expected {})",
+                manifest.version,
+                MANIFEST_VERSION
+            )));
+        }
+        for patch in &manifest.patches {
+            if !dir.join(&patch.file).is_file() {
+                return Err(SyncError::PathNotFound(dir.join(&patch.file)));
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| SyncError::Anyhow(e.into()))?;
+        std::fs::write(dir.join(MANIFEST_FILE), json)?;
+        Ok(())
+    }
+}
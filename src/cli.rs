@@ -1,27 +1,216 @@
-use clap::{Arg, ArgMatches, Command};
-use std::path::PathBuf;
+use clap::{Arg, ArgGroup, ArgMatches, Command};
+use crate::backend::Engine;
+use crate::git::{BranchTip, CommitOrder, OctopusPolicy, RevRange};
+use crate::paths;
+use crate::redact::SecretScanMode;
+use crate::sync::{KeepPatches, RetryPolicy, SquashBy};
+use crate::tags::TagSync;
+use crate::theme::Appearance;
+use crate::version::BumpPolicy;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Options that can be stored in a TOML file passed with `--config`.
+/// Values from the command line are combined with or take precedence over these.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FileConfig {
+    pub msg_rewrite: Vec<String>,
+    pub redact_path: Vec<String>,
+    pub redact_content: Vec<String>,
+    pub secret_scan: Option<String>,
+    pub secret_allowlist: Option<PathBuf>,
+    pub synced_from: Option<bool>,
+    pub subject_prefix: Option<String>,
+    pub notes: Option<bool>,
+    pub source_url: Option<String>,
+    pub tmp_dir: Option<PathBuf>,
+}
+
+impl FileConfig {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read config file {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))
+    }
+}
+
+/// Range picked from the sync journal (`--pending`, `--latest`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuickRange {
+    /// Every commit after the last one a completed sync got to
+    Pending,
+    /// Only the newest of those commits
+    Latest,
+}
+
+impl QuickRange {
+    fn from_matches(matches: &ArgMatches) -> Option<Self> {
+        if matches.get_flag("pending") {
+            Some(Self::Pending)
+        } else if matches.get_flag("latest") {
+            Some(Self::Latest)
+        } else {
+            None
+        }
+    }
+}
+
+/// How to treat uncommitted changes in the source repository. Only commits are
+/// read there, so the changes never affect a sync.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DirtySourcePolicy {
+    Proceed,
+    Abort,
+    /// Deprecated: the worktree is no longer touched, so there is nothing to
+    /// stash. Behaves like `Proceed` and warns that it has no effect.
+    Stash,
+}
+
+impl DirtySourcePolicy {
+    fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "proceed" => Ok(Self::Proceed),
+            "stash" => Ok(Self::Stash),
+            "abort" => Ok(Self::Abort),
+            other => Err(anyhow::anyhow!("Invalid source dirty policy: {}", other)),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct Config {
     pub source_repo: PathBuf,
+    /// Empty with `--package` until the member is resolved
     pub subdir: String,
     pub target_repo: PathBuf,
+    /// More target repositories synced with the same selection (`--target`)
+    pub extra_targets: Vec<PathBuf>,
+    /// Empty with `--pending` or `--latest` until the journal is read
     pub start_commit: String,
+    /// Start commit taken from the journal instead of the arguments
+    pub quick: Option<QuickRange>,
     pub source_branch: Option<String>,
     pub target_branch: Option<String>,
+    /// Directory of the target repository the subdir is synced into, empty for the root
+    pub target_dir: String,
     pub end_commit: Option<String>,
     pub create_branch: Option<bool>,
+    pub base: Option<String>,
+    /// Keep the local target branch or move it to the remote one when the remote
+    /// has commits it lacks; `None` refuses to pick
+    pub target_tip: Option<BranchTip>,
     pub include_start: Option<bool>,
     pub no_merge: Option<bool>,
     pub sync_delete: Option<bool>,
     pub auto_stash: Option<bool>,
+    pub authors: Vec<String>,
+    pub grep: Vec<String>,
+    pub limit: Option<usize>,
+    /// Refuse to start when the range holds more commits than this
+    pub max_commits: Option<usize>,
+    /// Archived run reports kept in the target's `.git/sync-subdir/runs`; 0 archives nothing
+    pub keep_runs: usize,
+    /// Warn on the config screen when the range holds more commits than this
+    pub commit_warning: usize,
+    pub msg_rewrite: Vec<String>,
+    pub redact_paths: Vec<String>,
+    pub redact_content: Vec<String>,
+    pub secret_scan: SecretScanMode,
+    pub secret_allowlist: Option<PathBuf>,
+    pub synced_from: bool,
+    /// Put in front of the subject of every target commit
+    pub subject_prefix: Option<String>,
+    /// Note the source commits on each target commit under `refs/notes/sync-subdir`
+    pub notes: bool,
+    /// Tag the target commits of source commits that have tags
+    pub sync_tags: bool,
+    /// Source and target tag name prefixes for `--sync-tags`
+    pub tag_prefix: Option<(String, String)>,
+    /// Sign the tags created by `--sync-tags`
+    pub sign_tags: bool,
+    pub source_url: Option<String>,
+    /// Parent directory of the run's patches and apply worktree (default: system temp)
+    pub tmp_dir: Option<PathBuf>,
+    pub keep_patches: Option<KeepPatches>,
+    pub retry: RetryPolicy,
+    pub format_patch_args: Vec<String>,
+    pub am_args: Vec<String>,
+    /// Skip commits whose patch conflicts and report them instead of stopping
+    pub continue_on_conflict: bool,
+    /// Apply to a throwaway branch and fast-forward the target branch only if every commit applied
+    pub staging_branch: bool,
+    /// Compare the target directory with the source directory after the run
+    pub verify: bool,
+    /// Drop file mode changes, for targets where the executable bit is noise
+    pub no_mode_changes: bool,
+    /// Wait for another sync holding the target's lock instead of failing
+    pub wait: bool,
+    /// Unix socket path or `host:port` streaming sync events as JSON lines
+    pub progress_socket: Option<String>,
+    pub invert_grep: bool,
+    pub source_dirty: DirtySourcePolicy,
+    pub order: CommitOrder,
+    pub octopus: OctopusPolicy,
+    pub squash_by: Option<SquashBy>,
+    /// Fold `fixup!`/`squash!` commits into the commit they name
+    pub autosquash: bool,
+    /// Todo file giving the selection, order and folding of the commits
+    pub todo: Option<PathBuf>,
+    /// Write the pending commits as a todo file (`-` for stdout) instead of syncing
+    pub export_todo: Option<PathBuf>,
+    /// Commit a version bump of the target after the synced commits
+    pub bump_version: Option<BumpPolicy>,
+    /// Version file for `--bump-version`, relative to the target repository root
+    pub version_file: Option<String>,
+    /// Replay commits as patches or copy the directory in one commit (`--engine`)
+    pub engine: Engine,
+    pub respect_export_ignore: bool,
+    pub follow_dir: bool,
+    /// Start at the subdir's creation commit when it is missing at the start commit
+    pub root: bool,
+    pub ignore_whitespace_only: bool,
+    /// CODEOWNERS owner whose files a commit must touch to start out selected
+    pub codeowners_filter: Option<String>,
+    /// Cargo or npm workspace member synced instead of a named directory
+    pub package: Option<String>,
+    /// With `package`, also sync the directories of its path dependencies
+    pub with_path_deps: bool,
+    pub edit: bool,
+    /// Start the sync without asking, from `--yes` or `confirm = false` in the preferences
+    pub yes: bool,
+    pub report: Option<PathBuf>,
+    pub export_bundle: Option<PathBuf>,
+    /// Write the patches into one mbox file instead of applying them
+    pub export_mbox: Option<PathBuf>,
+    pub notify: bool,
+    /// Plain patch previews, from `--no-color` or a non-empty `NO_COLOR`
+    pub no_color: bool,
+    pub appearance: Appearance,
+    pub create_pr: bool,
+    pub pr_base: Option<String>,
+    pub pr_title_template: Option<String>,
     pub dry_run: bool,
+    /// Print a summary of the pending commits instead of starting the TUI
+    pub stat_only: bool,
+    /// Browse the commit range in the TUI without writing to either repository
+    pub inspect: bool,
     pub verbose: bool,
 }
 
-impl Config {
-    pub fn from_matches(matches: ArgMatches) -> anyhow::Result<Self> {
+/// Repository arguments shared by the sync command and its subcommands
+#[derive(Debug, Clone)]
+pub struct RepoArgs {
+    pub source_repo: PathBuf,
+    pub subdir: String,
+    pub target_repo: PathBuf,
+}
+
+impl RepoArgs {
+    pub fn from_matches(matches: &ArgMatches) -> anyhow::Result<Self> {
         let source_repo = matches
             .get_one::<String>("source_repo")
             .ok_or_else(|| anyhow::anyhow!("Missing source repository path"))?;
@@ -31,31 +220,178 @@ impl Config {
         let target_repo = matches
             .get_one::<String>("target_repo")
             .ok_or_else(|| anyhow::anyhow!("Missing target repository path"))?;
-        let start_commit = matches
-            .get_one::<String>("start_commit")
-            .ok_or_else(|| anyhow::anyhow!("Missing start commit"))?;
 
         Ok(Self {
             source_repo: PathBuf::from(source_repo),
             subdir: subdir.to_string(),
             target_repo: PathBuf::from(target_repo),
+        })
+    }
+}
+
+impl Config {
+    pub fn from_matches(matches: ArgMatches) -> anyhow::Result<Self> {
+        let source_repo = matches
+            .get_one::<String>("source_repo")
+            .ok_or_else(|| anyhow::anyhow!("Missing source repository path"))?;
+        let package = matches.get_one::<String>("package").cloned();
+        let quick = QuickRange::from_matches(&matches);
+        // A package takes the place of the subdir and --pending/--latest that of
+        // the start commit, so the arguments after them move up
+        let mut positional = ["subdir", "target_repo", "start_commit"]
+            .into_iter()
+            .filter_map(|id| matches.get_one::<String>(id));
+        let subdir = match package {
+            Some(_) => "",
+            None => positional.next().ok_or_else(|| anyhow::anyhow!("Missing subdirectory name"))?.as_str(),
+        };
+        let target_repo = positional.next().ok_or_else(|| anyhow::anyhow!("Missing target repository path"))?;
+        let start_commit = match quick {
+            Some(_) => "",
+            None => positional.next().ok_or_else(|| anyhow::anyhow!("Missing start commit"))?.as_str(),
+        };
+        if let Some(extra) = positional.next() {
+            return Err(anyhow::anyhow!(
+                "Unexpected argument {}: pass <源仓库> {}<目标仓库>{} (--package replaces the subdirectory, --pending/--latest the start commit)",
+                extra,
+                if package.is_some() { "" } else { "<子目录> " },
+                if quick.is_some() { "" } else { " <起始commit>" }
+            ));
+        }
+
+        let file_config = match matches.get_one::<String>("config") {
+            Some(path) => FileConfig::load(Path::new(path))?,
+            None => FileConfig::default(),
+        };
+
+        let with_cli = |mut values: Vec<String>, id: &str| {
+            values.extend(matches.get_many::<String>(id).into_iter().flatten().cloned());
+            values
+        };
+        let msg_rewrite = with_cli(file_config.msg_rewrite, "msg_rewrite");
+        let redact_paths = with_cli(file_config.redact_path, "redact_path");
+        let redact_content = with_cli(file_config.redact_content, "redact_content");
+
+        Ok(Self {
+            source_repo: PathBuf::from(source_repo),
+            subdir: subdir.to_string(),
+            target_repo: PathBuf::from(target_repo),
+            extra_targets: matches.get_many::<String>("extra_targets").map(|v| v.map(PathBuf::from).collect()).unwrap_or_default(),
             start_commit: start_commit.to_string(),
+            quick,
             source_branch: matches.get_one::<String>("source_branch").cloned(),
             target_branch: matches.get_one::<String>("target_branch").cloned(),
+            target_dir: paths::normalize_dir(matches.get_one::<String>("target_dir").map(String::as_str).unwrap_or(""))?,
             end_commit: matches.get_one::<String>("end_commit").cloned(),
             create_branch: matches.get_flag("create_branch").then_some(true)
                 .or(matches.get_flag("no_create_branch").then_some(false)),
+            base: matches.get_one::<String>("base").cloned(),
+            target_tip: matches.get_one::<String>("target_tip").map(String::as_str).map(BranchTip::parse).transpose()?,
             include_start: matches.get_flag("include_start").then_some(true)
                 .or(matches.get_flag("no_include_start").then_some(false)),
             no_merge: matches.get_flag("no_merge").then_some(true),
             sync_delete: matches.get_flag("delete").then_some(true)
                 .or(matches.get_flag("no_delete").then_some(false)),
             auto_stash: matches.get_flag("stash").then_some(true),
+            authors: matches.get_many::<String>("author").map(|v| v.cloned().collect()).unwrap_or_default(),
+            grep: matches.get_many::<String>("grep").map(|v| v.cloned().collect()).unwrap_or_default(),
+            invert_grep: matches.get_flag("invert_grep"),
+            limit: matches.get_one::<usize>("limit").copied(),
+            max_commits: matches.get_one::<usize>("max_commits").copied(),
+            keep_runs: *matches.get_one::<usize>("keep_runs").unwrap(),
+            commit_warning: *matches.get_one::<usize>("commit_warning").unwrap(),
+            msg_rewrite,
+            redact_paths,
+            redact_content,
+            secret_scan: SecretScanMode::parse(
+                matches.get_one::<String>("secret_scan")
+                    .or(file_config.secret_scan.as_ref())
+                    .map(String::as_str)
+                    .unwrap_or("off"),
+            )?,
+            secret_allowlist: matches.get_one::<String>("secret_allowlist")
+                .map(PathBuf::from)
+                .or(file_config.secret_allowlist),
+            synced_from: matches.get_flag("synced_from") || file_config.synced_from.unwrap_or(false),
+            subject_prefix: matches.get_one::<String>("subject_prefix")
+                .cloned()
+                .or(file_config.subject_prefix)
+                .filter(|prefix| !prefix.is_empty()),
+            notes: matches.get_flag("notes") || file_config.notes.unwrap_or(false),
+            sync_tags: matches.get_flag("sync_tags"),
+            tag_prefix: matches.get_one::<String>("tag_prefix").map(|v| TagSync::parse_prefix(v)).transpose()?,
+            sign_tags: matches.get_flag("sign_tags"),
+            source_url: matches.get_one::<String>("source_url").cloned().or(file_config.source_url),
+            tmp_dir: matches.get_one::<String>("tmp_dir").map(PathBuf::from).or(file_config.tmp_dir),
+            keep_patches: matches.get_one::<String>("keep_patches").map(|v| KeepPatches::parse(v)).transpose()?,
+            retry: retry_policy(&matches),
+            format_patch_args: parse_git_args(
+                "--format-patch-args",
+                matches.get_one::<String>("format_patch_args"),
+                RESERVED_FORMAT_PATCH_ARGS,
+            )?,
+            am_args: parse_git_args("--am-args", matches.get_one::<String>("am_args"), RESERVED_AM_ARGS)?,
+            continue_on_conflict: matches.get_flag("continue_on_conflict"),
+            staging_branch: matches.get_flag("staging_branch"),
+            verify: matches.get_flag("verify"),
+            no_mode_changes: matches.get_flag("no_mode_changes"),
+            wait: matches.get_flag("wait"),
+            progress_socket: matches.get_one::<String>("progress_socket").cloned(),
+            order: CommitOrder::parse(matches.get_one::<String>("order").map(String::as_str).unwrap_or("topo"))?,
+            octopus: OctopusPolicy::parse(matches.get_one::<String>("octopus").map(String::as_str).unwrap_or("skip"))?,
+            squash_by: matches.get_one::<String>("squash_by").map(|v| SquashBy::parse(v)).transpose()?,
+            autosquash: matches.get_flag("autosquash"),
+            todo: matches.get_one::<String>("todo").map(PathBuf::from),
+            export_todo: matches.get_one::<String>("export_todo").map(PathBuf::from),
+            bump_version: matches.get_one::<String>("bump_version").map(|v| BumpPolicy::parse(v)).transpose()?,
+            version_file: matches.get_one::<String>("version_file").cloned(),
+            engine: Engine::parse(matches.get_one::<String>("engine").map(String::as_str).unwrap_or("patch"))?,
+            source_dirty: DirtySourcePolicy::parse(
+                matches.get_one::<String>("source_dirty").map(String::as_str).unwrap_or("proceed"),
+            )?,
+            respect_export_ignore: matches.get_flag("respect_export_ignore"),
+            follow_dir: matches.get_flag("follow_dir"),
+            root: matches.get_flag("root"),
+            ignore_whitespace_only: matches.get_flag("ignore_whitespace_only"),
+            codeowners_filter: matches.get_one::<String>("codeowners_filter").cloned(),
+            package,
+            with_path_deps: matches.get_flag("with_path_deps"),
+            edit: matches.get_flag("edit"),
+            yes: matches.get_flag("yes"),
+            report: matches.get_one::<String>("report").map(PathBuf::from),
+            export_bundle: matches.get_one::<String>("export_bundle").map(PathBuf::from),
+            export_mbox: matches.get_one::<String>("export_mbox").map(PathBuf::from),
+            notify: matches.get_flag("notify"),
+            no_color: matches.get_flag("no_color") || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()),
+            appearance: Appearance::from_matches(&matches)?,
+            create_pr: matches.get_flag("create_pr"),
+            pr_base: matches.get_one::<String>("pr_base").cloned(),
+            pr_title_template: matches.get_one::<String>("pr_title_template").cloned(),
             dry_run: matches.get_flag("dry_run"),
+            stat_only: matches.get_flag("stat_only"),
+            inspect: matches.get_flag("inspect"),
             verbose: matches.get_flag("verbose"),
         })
     }
 
+    /// Takes the bounds of a range given as the start commit. The lower bound
+    /// is excluded unless `--include-start` asks for it.
+    pub fn set_range(&mut self, range: RevRange) {
+        self.start_commit = range.start;
+        self.end_commit = Some(range.end);
+        self.include_start.get_or_insert(false);
+    }
+
+    /// Revision the range ends at: `--end-commit`, else the tip of
+    /// `--source-branch`, read from its ref without checking it out, else the
+    /// source HEAD
+    pub fn end_rev(&self) -> String {
+        self.end_commit
+            .clone()
+            .or_else(|| self.source_branch.clone())
+            .unwrap_or_else(|| "HEAD".to_string())
+    }
+
     pub fn get_default_target_branch(&self) -> String {
         self.target_branch
             .clone()
@@ -63,40 +399,484 @@ impl Config {
     }
 }
 
-pub fn build_cli() -> Command {
-    Command::new("sync-subdir")
-        .version("0.1.0")
-        .author("Claude <noreply@anthropic.com>")
-        .about("A TUI tool for syncing subdirectory changes between Git repositories")
-        .long_about(
-            "将源仓库中某个子目录的变更同步到独立的目标仓库。\n\n\
-             这个工具提供了交互式 TUI 界面，支持分支管理、commit 范围选择、\n\
-             merge 排除、删除操作同步等功能。",
+fn tmp_dir_arg() -> Arg {
+    Arg::new("tmp_dir")
+        .long("tmp-dir")
+        .help("存放补丁和临时 worktree 的目录 (默认: 系统临时目录，即 $TMPDIR)")
+        .value_name("目录")
+}
+
+/// Options of `git format-patch` the tool sets itself or whose output it could not find
+const RESERVED_FORMAT_PATCH_ARGS: &[&str] = &["-o", "--output-directory", "--stdout", "--relative", "--root", "--"];
+/// Options of `git am` that drive the session the tool manages
+const RESERVED_AM_ARGS: &[&str] = &["--abort", "--continue", "--skip", "--quit", "--resolved", "-r", "--directory", "--"];
+
+/// Splits `--format-patch-args`/`--am-args` on whitespace. Only options are
+/// accepted, and none the tool relies on controlling itself; a revision count
+/// such as `-3` would also select more than the one commit being synced.
+fn parse_git_args(option: &str, value: Option<&String>, reserved: &[&str]) -> anyhow::Result<Vec<String>> {
+    let args: Vec<String> = value.map(|v| v.split_whitespace().map(str::to_string).collect()).unwrap_or_default();
+    for arg in &args {
+        let name = arg.split_once('=').map_or(arg.as_str(), |(name, _)| name);
+        if !arg.starts_with('-') {
+            return Err(anyhow::anyhow!("{}: only options are accepted, got '{}'", option, arg));
+        }
+        if reserved.contains(&name) || name[1..].chars().all(|c| c.is_ascii_digit()) {
+            return Err(anyhow::anyhow!("{}: '{}' cannot be overridden", option, arg));
+        }
+    }
+    Ok(args)
+}
+
+/// `--retries` and `--retry-delay`, shared by sync runs and `apply-bundle`
+fn retry_args() -> [Arg; 2] {
+    [
+        Arg::new("retries")
+            .long("retries")
+            .help("生成或应用补丁因文件被占用等临时原因失败时的重试次数 (0 表示不重试)")
+            .value_name("N")
+            .default_value("3")
+            .value_parser(clap::value_parser!(u32)),
+        Arg::new("retry_delay")
+            .long("retry-delay")
+            .help("第一次重试前等待的毫秒数，之后每次加倍")
+            .value_name("毫秒")
+            .default_value("500")
+            .value_parser(clap::value_parser!(u64)),
+    ]
+}
+
+/// Retry policy given by [`retry_args`]
+pub fn retry_policy(matches: &ArgMatches) -> RetryPolicy {
+    RetryPolicy {
+        retries: *matches.get_one::<u32>("retries").unwrap(),
+        delay: Duration::from_millis(*matches.get_one::<u64>("retry_delay").unwrap()),
+    }
+}
+
+fn repo_args() -> [Arg; 3] {
+    [
+        Arg::new("source_repo")
+            .help("源 Git 仓库路径")
+            .required(true)
+            .index(1),
+        Arg::new("subdir")
+            .help("源仓库中要同步的子目录名称")
+            .required(true)
+            .index(2),
+        Arg::new("target_repo")
+            .help("目标 Git 仓库路径")
+            .required(true)
+            .index(3),
+    ]
+}
+
+fn build_diff_command() -> Command {
+    Command::new("diff")
+        .about("比较源仓库子目录与目标仓库工作区的差异")
+        .args(repo_args())
+        .arg(
+            Arg::new("commit")
+                .long("commit")
+                .help("源仓库中用于比较的 commit")
+                .value_name("commit")
+                .default_value("HEAD"),
         )
         .arg(
-            Arg::new("source_repo")
-                .help("源 Git 仓库路径")
+            Arg::new("target_dir")
+                .long("target-dir")
+                .help("子目录在目标仓库中的位置，只比较该目录 (默认: 仓库根目录)")
+                .value_name("目录"),
+        )
+        .arg(
+            Arg::new("redact_path")
+                .long("redact-path")
+                .help("不参与比较的路径 (相对子目录的 glob)，可重复指定")
+                .value_name("GLOB")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("respect_export_ignore")
+                .long("respect-export-ignore")
+                .help("不比较子目录 .gitattributes 中标记为 export-ignore 的路径")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("stat")
+                .long("stat")
+                .help("仅输出变更统计摘要")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("将结果写入文件而不是标准输出")
+                .value_name("文件"),
+        )
+}
+
+fn build_apply_bundle_command() -> Command {
+    Command::new("apply-bundle")
+        .about("将 --export-bundle 导出的补丁包应用到目标仓库当前分支")
+        .arg(
+            Arg::new("bundle")
+                .help("补丁包目录 (包含 manifest.json)")
                 .required(true)
                 .index(1),
         )
         .arg(
-            Arg::new("subdir")
-                .help("源仓库中要同步的子目录名称")
+            Arg::new("target_repo")
+                .help("目标仓库路径")
                 .required(true)
                 .index(2),
         )
+        .arg(
+            Arg::new("allow_base_mismatch")
+                .long("allow-base-mismatch")
+                .help("目标 HEAD 与导出时的基点不一致时仍然尝试应用")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(tmp_dir_arg())
+        .args(retry_args())
+}
+
+fn build_apply_mbox_command() -> Command {
+    Command::new("apply-mbox")
+        .about("用 git am 将 mbox 中的补丁 (--export-mbox 或 git format-patch 生成) 逐个应用到目标仓库当前分支")
+        .arg(
+            Arg::new("mbox")
+                .help("mbox 文件")
+                .required(true)
+                .index(1),
+        )
         .arg(
             Arg::new("target_repo")
-                .help("目标 Git 仓库路径")
+                .help("目标仓库路径")
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::new("target_dir")
+                .long("target-dir")
+                .help("补丁应用到目标仓库中的哪个目录 (默认: 仓库根目录)")
+                .value_name("目录"),
+        )
+        .arg(tmp_dir_arg())
+        .args(retry_args())
+}
+
+fn build_daemon_command() -> Command {
+    Command::new("daemon")
+        .about("按计划运行任务文件中的多个同步任务，并显示各任务的状态")
+        .arg(
+            Arg::new("jobs")
+                .help("任务文件 (TOML，每个 [[job]] 一个任务)")
                 .required(true)
-                .index(3),
+                .index(1),
         )
+}
+
+fn build_snapshot_command() -> Command {
+    Command::new("snapshot")
+        .about("将目标仓库根目录变为源仓库子目录在指定 commit 时的内容，生成一个提交 (用于初始化新的镜像仓库)")
+        .args(repo_args())
         .arg(
-            Arg::new("start_commit")
-                .help("起始 commit hash")
+            Arg::new("commit")
+                .help("源仓库中的 commit")
                 .required(true)
                 .index(4),
         )
+        .arg(
+            Arg::new("redact_path")
+                .long("redact-path")
+                .help("不复制的路径 (相对子目录的 glob)，可重复指定")
+                .value_name("GLOB")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("respect_export_ignore")
+                .long("respect-export-ignore")
+                .help("排除子目录 .gitattributes 中标记为 export-ignore 的路径")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_delete")
+                .long("no-delete")
+                .help("保留目标仓库中源子目录没有的文件")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .short('d')
+                .help("只列出会改动的文件，不生成提交")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn build_verify_command() -> Command {
+    Command::new("verify")
+        .about("检查目标分支上的目录是否与源子目录在最近同步的 commit 时一致，不一致时列出文件并以退出码 1 结束")
+        .args(repo_args())
+        .arg(
+            Arg::new("commit")
+                .long("commit")
+                .help("源仓库中用于比较的 commit (默认: 同步日志中最近一次同步到的 commit，没有时为 HEAD)")
+                .value_name("commit"),
+        )
+        .arg(
+            Arg::new("target_dir")
+                .long("target-dir")
+                .help("子目录在目标仓库中的位置 (默认: 仓库根目录)")
+                .value_name("目录"),
+        )
+        .arg(
+            Arg::new("redact_path")
+                .long("redact-path")
+                .help("不参与比较的路径 (相对子目录的 glob)，可重复指定")
+                .value_name("GLOB")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("respect_export_ignore")
+                .long("respect-export-ignore")
+                .help("不比较子目录 .gitattributes 中标记为 export-ignore 的路径")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_mode_changes")
+                .long("no-mode-changes")
+                .help("只比较文件内容，不比较文件权限 (与以 --no-mode-changes 同步的目标仓库比较时使用)")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn build_extract_command() -> Command {
+    Command::new("extract")
+        .about("将子目录的完整历史重写到一个还没有提交的目标仓库，并写入同步日志，之后可直接增量同步")
+        .args(repo_args())
+        .arg(
+            Arg::new("end")
+                .long("end")
+                .short('e')
+                .help("提取到源仓库的哪个 commit 为止")
+                .value_name("commit")
+                .default_value("HEAD"),
+        )
+        .arg(
+            Arg::new("target_dir")
+                .long("target-dir")
+                .help("子目录在目标仓库中的位置 (默认: 仓库根目录)")
+                .value_name("目录"),
+        )
+}
+
+fn build_map_command() -> Command {
+    Command::new("map")
+        .about("从同步日志输出源提交与目标提交的对应关系")
+        .arg(
+            Arg::new("target_repo")
+                .help("目标仓库路径")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("输出格式")
+                .value_parser(["text", "csv", "json"])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::new("commit")
+                .long("commit")
+                .help("只输出源提交或目标提交 ID 以此开头的记录，没有匹配时退出码为 1")
+                .value_name("commit"),
+        )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .help("只输出该日期及之后同步的记录 (YYYY-MM-DD 或 RFC 3339 时间)")
+                .value_name("日期"),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .help("只输出该日期及之前同步的记录 (YYYY-MM-DD 或 RFC 3339 时间)")
+                .value_name("日期"),
+        )
+        .arg(
+            Arg::new("range")
+                .long("range")
+                .help("只输出源提交在该范围内的记录，如 v1.0..v1.1 (需要 --source-repo)")
+                .value_name("范围")
+                .requires("source_repo"),
+        )
+        .arg(
+            Arg::new("source_repo")
+                .long("source-repo")
+                .help("解析 --range 使用的源仓库路径")
+                .value_name("路径"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("将结果写入文件而不是标准输出")
+                .value_name("文件"),
+        )
+        .arg(
+            Arg::new("from_notes")
+                .long("from-notes")
+                .help("从目标提交的 git notes 而不是同步日志读取对应关系，适用于没有同步日志的克隆")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn build_journal_command() -> Command {
+    Command::new("journal")
+        .about("维护目标仓库的同步日志")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("repair")
+                .about("对照目标仓库的历史检查同步日志，删除无法解析的行，把已不在任何分支上的目标提交标记为 LOST 并修正续传点")
+                .arg(
+                    Arg::new("target_repo")
+                        .help("目标仓库路径")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry-run")
+                        .short('d')
+                        .help("只列出问题，不修改同步日志 (有问题时退出码为 1)")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+}
+
+fn build_runs_command() -> Command {
+    let target_repo = Arg::new("target_repo").help("目标仓库路径").required(true).index(1);
+    Command::new("runs")
+        .about("查看每次同步后归档的运行报告")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("list")
+                .about("按时间倒序列出归档的运行：结果、同步与跳过的提交数、用时、子目录和目标仓库")
+                .arg(target_repo.clone())
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .short('n')
+                        .help("只列出最近 N 次运行")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize)),
+                ),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("以 JSON 输出一次运行的完整报告 (与 --report 相同)")
+                .arg(target_repo)
+                .arg(
+                    Arg::new("run")
+                        .help("归档时间 (如 20260101-120000) 或运行 ID 的前缀，默认最近一次")
+                        .index(2),
+                ),
+        )
+}
+
+fn build_notes_command() -> Command {
+    let target_repo = Arg::new("target_repo").help("目标仓库路径").required(true).index(1);
+    let remote = Arg::new("remote")
+        .long("remote")
+        .help("远程仓库名")
+        .value_name("远程")
+        .default_value("origin");
+    Command::new("notes")
+        .about("管理目标提交上记录源提交的 git notes (refs/notes/sync-subdir)")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("write")
+                .about("按同步日志为全部目标提交写入 notes，用于补齐未使用 --notes 同步的提交")
+                .arg(target_repo.clone()),
+        )
+        .subcommand(
+            Command::new("push")
+                .about("把 notes 推送到远程仓库")
+                .arg(target_repo.clone())
+                .arg(remote.clone()),
+        )
+        .subcommand(
+            Command::new("fetch")
+                .about("获取远程仓库的 notes 并合并到本地")
+                .arg(target_repo)
+                .arg(remote),
+        )
+}
+
+fn build_config_command() -> Command {
+    Command::new("config")
+        .about("查看和修改用户偏好设置 (~/.config/sync-subdir/config.toml)")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("get")
+                .about("输出偏好设置的值，不指定键时输出全部")
+                .arg(Arg::new("key").help("theme、ascii、confirm、recent 或 keys").index(1)),
+        )
+        .subcommand(
+            Command::new("set")
+                .about("修改一项偏好设置")
+                .arg(Arg::new("key").help("theme、ascii 或 confirm").required(true).index(1))
+                .arg(Arg::new("value").help("新值").required(true).index(2)),
+        )
+        .subcommand(Command::new("edit").about("用 $VISUAL/$EDITOR 编辑偏好设置文件"))
+}
+
+/// Adds the positional arguments and options of a sync run to `cmd`
+fn with_sync_args(cmd: Command) -> Command {
+    cmd
+        .args(repo_args())
+        // With both --package and --pending/--latest only two paths are left
+        .mut_arg("target_repo", |arg| arg.required(false).required_unless_present_all(["package", "quick"]))
+        .arg(
+            Arg::new("start_commit")
+                .help("起始 commit，或 A..B / A...B 形式的提交范围")
+                .required_unless_present_any(["package", "quick"])
+                .index(4),
+        )
+        .arg(
+            Arg::new("pending")
+                .long("pending")
+                .help("不指定起始 commit，同步日志中上次同步之后的全部提交")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("latest")
+                .long("latest")
+                .help("不指定起始 commit，只同步上次同步之后最新的一个提交")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .group(
+            ArgGroup::new("quick")
+                .args(["pending", "latest"])
+                .conflicts_with_all(["include_start", "no_include_start"]),
+        )
+        .arg(
+            Arg::new("package")
+                .long("package")
+                .short('p')
+                .help("按名称同步 Cargo/npm 工作区成员，目录从工作区配置解析；此时省略子目录参数: <源仓库> <目标仓库> <起始commit>")
+                .value_name("名称"),
+        )
+        .arg(
+            Arg::new("with_path_deps")
+                .long("with-path-deps")
+                .help("与 --package 一起使用，连同它通过路径依赖的其他成员目录一起同步")
+                .requires("package")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("source_branch")
                 .long("source-branch")
@@ -111,6 +891,12 @@ pub fn build_cli() -> Command {
                 .help("目标仓库分支")
                 .value_name("分支"),
         )
+        .arg(
+            Arg::new("target_dir")
+                .long("target-dir")
+                .help("同步到目标仓库中的目录 (默认: 仓库根目录)")
+                .value_name("目录"),
+        )
         .arg(
             Arg::new("end_commit")
                 .long("end")
@@ -132,6 +918,20 @@ pub fn build_cli() -> Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with("create_branch"),
         )
+        .arg(
+            Arg::new("base")
+                .long("base")
+                .help("新建目标分支的基点 (默认: origin 上的同名分支，没有时为目标仓库当前 HEAD)")
+                .value_name("ref")
+                .conflicts_with("no_create_branch"),
+        )
+        .arg(
+            Arg::new("target_tip")
+                .long("target-tip")
+                .help("目标分支在本地和远程都存在且双方各有独有的提交时使用哪一个: local 保持本地分支, remote 把本地分支移到远程分支 (本地独有的提交只保留在 reflog 中)；只是落后于远程时直接快进")
+                .value_name("分支")
+                .value_parser(["local", "remote"]),
+        )
         .arg(
             Arg::new("include_start")
                 .long("include-start")
@@ -153,6 +953,49 @@ pub fn build_cli() -> Command {
                 .help("排除 merge 引入的变更")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("author")
+                .long("author")
+                .help("只同步作者 (Name <email>) 匹配该正则的提交，可重复指定")
+                .value_name("正则")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("grep")
+                .long("grep")
+                .help("只同步提交信息匹配该正则的提交，可重复指定")
+                .value_name("正则")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("invert_grep")
+                .long("invert-grep")
+                .help("反转 --grep，只同步提交信息不匹配的提交")
+                .action(clap::ArgAction::SetTrue)
+                .requires("grep"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .help("每次运行最多同步 N 个提交 (从最早的待同步提交开始)")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("max_commits")
+                .long("max-commits")
+                .help("提交范围超过 N 个提交时直接报错退出 (防止起始 commit 写错选中整个历史)")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("commit_warning")
+                .long("commit-warning")
+                .help("提交范围超过 N 个提交时在配置页显示警告")
+                .value_name("N")
+                .default_value("500")
+                .value_parser(clap::value_parser!(usize)),
+        )
         .arg(
             Arg::new("delete")
                 .long("delete")
@@ -172,6 +1015,342 @@ pub fn build_cli() -> Command {
                 .help("自动 stash 目标仓库未提交变更")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("order")
+                .long("order")
+                .help("提交的排列与应用顺序: topo 保证父提交先于子提交, time 按提交时间 (--follow-dir 时总是 topo)")
+                .value_name("顺序")
+                .value_parser(["topo", "time"])
+                .default_value("topo"),
+        )
+        .arg(
+            Arg::new("octopus")
+                .long("octopus")
+                .help("范围内 octopus merge (多于两个父提交) 的处理方式: skip 与普通 merge 一样跳过, first-parent 将其相对第一父提交的变更同步为一个提交, stop 拒绝同步")
+                .value_name("策略")
+                .value_parser(["skip", "first-parent", "stop"])
+                .default_value("skip"),
+        )
+        .arg(
+            Arg::new("engine")
+                .long("engine")
+                .help("同步方式: patch 逐个提交应用补丁并保留历史, copy 将目标目录变为所选最后一个提交时的内容并生成一个提交 (不保留历史)")
+                .value_name("方式")
+                .value_parser(["patch", "copy"])
+                .default_value("patch"),
+        )
+        .arg(
+            Arg::new("squash_by")
+                .long("squash-by")
+                .help("把连续的提交合并为一个目标提交: day 按提交日期, author 按作者, 数字 N 每 N 个提交")
+                .value_name("分组"),
+        )
+        .arg(
+            Arg::new("autosquash")
+                .long("autosquash")
+                .help("像 git rebase --autosquash 一样，把 fixup!/squash!/amend! 提交并入它们所指的提交，只生成一个目标提交")
+                .conflicts_with("squash_by")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("todo")
+                .long("todo")
+                .help("按 git rebase -i 格式的 todo 文件 (pick/reword/squash/fixup/drop) 选择、排序和合并提交")
+                .value_name("文件")
+                .conflicts_with_all(["squash_by", "autosquash"]),
+        )
+        .arg(
+            Arg::new("export_todo")
+                .long("export-todo")
+                .help("把待同步的提交写成 todo 文件后退出，不同步 (- 表示标准输出)，编辑后用 --todo 读入")
+                .value_name("文件")
+                .conflicts_with("todo"),
+        )
+        .arg(
+            Arg::new("bump_version")
+                .long("bump-version")
+                .help("同步完成后提升目标仓库的版本号并单独提交: patch 1.2.3→1.2.4, minor 1.2.3→1.3.0, date 按日期 (如 2024.315.0)")
+                .value_name("策略")
+                .value_parser(["patch", "minor", "date"]),
+        )
+        .arg(
+            Arg::new("version_file")
+                .long("version-file")
+                .help("--bump-version 修改的版本文件 (相对目标仓库根目录)，默认在目标目录中依次查找 Cargo.toml、package.json、VERSION")
+                .value_name("路径")
+                .requires("bump_version"),
+        )
+        .arg(
+            Arg::new("source_dirty")
+                .long("source-dirty")
+                .help("源仓库存在未提交变更时的处理方式 (同步只读取已提交的对象；stash 已弃用，不起作用)")
+                .value_name("策略")
+                .value_parser(["stash", "proceed", "abort"])
+                .default_value("proceed"),
+        )
+        .arg(
+            Arg::new("respect_export_ignore")
+                .long("respect-export-ignore")
+                .help("排除子目录 .gitattributes 中标记为 export-ignore 的路径")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("follow_dir")
+                .long("follow-dir")
+                .help("跟踪子目录在历史中的重命名，按提交自动调整路径前缀")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("root")
+                .long("root")
+                .help("起始 commit 中不存在子目录时，自动从子目录的创建提交开始同步")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("ignore_whitespace_only")
+                .long("ignore-whitespace-only")
+                .help("识别只改动空白的提交，在列表中灰色显示并默认不选中")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("codeowners_filter")
+                .long("codeowners-filter")
+                .help("按源仓库的 CODEOWNERS，没有改动该团队 (如 @org/team) 所拥有文件的提交灰色显示并默认不选中")
+                .value_name("团队"),
+        )
+        .arg(
+            Arg::new("msg_rewrite")
+                .long("msg-rewrite")
+                .help("用 sed 风格规则改写提交信息，如 's/INTERNAL-[0-9]+//g'，可重复指定")
+                .value_name("规则")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("redact_path")
+                .long("redact-path")
+                .help("永不同步的路径 (相对子目录的 glob)，可重复指定")
+                .value_name("GLOB")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("redact_content")
+                .long("redact-content")
+                .help("新增内容匹配该正则时中止同步并生成报告，可重复指定")
+                .value_name("正则")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("secret_scan")
+                .long("secret-scan")
+                .help("扫描补丁中的疑似密钥: off 不扫描, warn 仅报告, block 中止同步 (默认 off)")
+                .value_name("模式")
+                .value_parser(["off", "warn", "block"]),
+        )
+        .arg(
+            Arg::new("secret_allowlist")
+                .long("secret-allowlist")
+                .help("密钥扫描白名单文件，每行一个正则，匹配密钥文本或文件路径时忽略")
+                .value_name("文件"),
+        )
+        .arg(
+            Arg::new("synced_from")
+                .long("synced-from")
+                .help("在目标提交信息末尾追加 Synced-from: <源提交链接>")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("subject_prefix")
+                .long("subject-prefix")
+                .help("在每个目标提交的标题前加上前缀，如 \"[sync] \"，便于在目标仓库的 git log 中辨认同步的提交")
+                .value_name("前缀"),
+        )
+        .arg(
+            Arg::new("notes")
+                .long("notes")
+                .help("在每个目标提交上写入 git notes (refs/notes/sync-subdir)，记录源提交 ID 与运行 ID")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("sync_tags")
+                .long("sync-tags")
+                .help("源提交上有标签时，在对应的目标提交上创建同名标签 (附注标签保留说明)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("tag_prefix")
+                .long("tag-prefix")
+                .help("--sync-tags 的标签名映射: <源前缀>=<目标前缀> (如 v=mono-v，只同步以源前缀开头的标签)，或只写目标前缀")
+                .value_name("映射")
+                .requires("sync_tags"),
+        )
+        .arg(
+            Arg::new("sign_tags")
+                .long("sign-tags")
+                .help("用 GPG 签名 --sync-tags 创建的标签 (git tag -s)")
+                .requires("sync_tags")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("source_url")
+                .long("source-url")
+                .help("源仓库的网页地址，默认从 origin 推断；可包含 {sha} 占位符")
+                .value_name("URL"),
+        )
+        .arg(tmp_dir_arg())
+        .args(retry_args())
+        .arg(
+            Arg::new("format_patch_args")
+                .long("format-patch-args")
+                .help("追加给 git format-patch 的额外参数 (以空格分隔，只接受选项)")
+                .value_name("参数")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::new("am_args")
+                .long("am-args")
+                .help("追加给 git am 的额外参数 (以空格分隔，只接受选项)")
+                .value_name("参数")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::new("continue_on_conflict")
+                .long("continue-on-conflict")
+                .help("补丁冲突时记录冲突、中止 git am 并继续同步其余提交，最后列出需要手动处理的提交")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("staging_branch")
+                .long("staging-branch")
+                .help("先应用到从目标分支创建的临时分支，全部提交成功后才快进目标分支；否则保留临时分支以便检查")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("verify")
+                .long("verify")
+                .help("同步后比较目标目录与源子目录 (按对象 id，忽略排除的路径)，列出不一致的文件")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_mode_changes")
+                .long("no-mode-changes")
+                .help("不同步文件权限变更 (100644↔100755)，目标文件保留原有权限，适用于权限无意义的目标仓库 (如在 Windows 上维护)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("wait")
+                .long("wait")
+                .help("目标仓库正被另一个同步进程使用时等待其结束")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_wait")
+                .long("no-wait")
+                .help("目标仓库正被另一个同步进程使用时立即退出 (默认)")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("wait"),
+        )
+        .arg(
+            Arg::new("keep_patches")
+                .long("keep-patches")
+                .help("把生成的补丁保存到 .sync-subdir/patches/<运行 id>/ 便于排查: all 保存全部, failed 只保存失败提交的补丁 (默认)")
+                .value_name("范围")
+                .value_parser(["all", "failed"])
+                .num_args(0..=1)
+                .default_missing_value("failed"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .help("从 TOML 配置文件读取选项")
+                .value_name("文件"),
+        )
+        .arg(
+            Arg::new("edit")
+                .long("edit")
+                .help("开始同步前用 $EDITOR 逐个编辑所选提交的提交信息")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("report")
+                .long("report")
+                .help("同步结束后将统计报告写入 JSON 文件")
+                .value_name("文件"),
+        )
+        .arg(
+            Arg::new("keep_runs")
+                .long("keep-runs")
+                .help("每次同步的报告压缩保存在目标仓库的 .git/sync-subdir/runs/ 中，最多保留最近 N 个 (0 表示不保存)，可用 runs 子命令查看")
+                .value_name("N")
+                .default_value("100")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("export_bundle")
+                .long("export-bundle")
+                .help("不应用补丁，而是将所选提交导出为编号补丁和 manifest.json 到指定目录 (用于离线环境)")
+                .value_name("目录")
+                .conflicts_with("create_pr"),
+        )
+        .arg(
+            Arg::new("export_mbox")
+                .long("export-mbox")
+                .help("不应用补丁，而是将所选提交按顺序写入一个 mbox 文件 (可用 git send-email 发送，或用 apply-mbox 导入)")
+                .value_name("文件")
+                .conflicts_with_all(["create_pr", "export_bundle"]),
+        )
+        .arg(
+            Arg::new("extra_targets")
+                .long("target")
+                .help("同时将所选提交同步到另一个目标仓库，可重复指定，各仓库并行执行")
+                .value_name("目标仓库")
+                .action(clap::ArgAction::Append)
+                .conflicts_with_all(["export_bundle", "export_mbox"]),
+        )
+        .arg(
+            Arg::new("create_pr")
+                .long("create-pr")
+                .help("同步后推送目标分支到 origin 并在 GitHub/GitLab 上创建 PR (令牌取自 GITHUB_TOKEN/GH_TOKEN/GITLAB_TOKEN)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("pr_base")
+                .long("pr-base")
+                .help("PR 的目标分支 (默认 main)")
+                .value_name("分支")
+                .requires("create_pr"),
+        )
+        .arg(
+            Arg::new("pr_title_template")
+                .long("pr-title-template")
+                .help("PR 标题模板，支持 {subdir} {branch} {count} (默认 \"Sync {subdir} ({count} commits)\")")
+                .value_name("模板")
+                .requires("create_pr"),
+        )
+        .arg(
+            Arg::new("notify")
+                .long("notify")
+                .help("同步完成或失败时发送桌面通知并响铃")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("progress_socket")
+                .long("progress-socket")
+                .help("在该 unix socket 路径 (或 host:port TCP 地址) 上以 JSON 行流式输出同步事件，供外部界面显示进度")
+                .value_name("路径"),
+        )
+        .arg(
+            Arg::new("no_color")
+                .long("no-color")
+                .help("补丁预览不使用颜色和语法高亮 (也可设置 NO_COLOR 环境变量)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("yes")
+                .long("yes")
+                .short('y')
+                .help("跳过开始同步前的确认")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("dry_run")
                 .long("dry-run")
@@ -179,6 +1358,19 @@ pub fn build_cli() -> Command {
                 .help("预览模式，不实际执行")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("stat_only")
+                .long("stat-only")
+                .help("不启动 TUI，只输出待同步提交的摘要 (各作者的提交数、改动最多的文件)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("inspect")
+                .long("inspect")
+                .help("只读检查模式：只加载并浏览提交范围、提交详情和差异，不切换分支、不 stash、不写入任何文件，也不能开始同步")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["extra_targets", "create_pr", "progress_socket", "export_bundle", "export_mbox"]),
+        )
         .arg(
             Arg::new("verbose")
                 .long("verbose")
@@ -186,9 +1378,67 @@ pub fn build_cli() -> Command {
                 .help("详细输出")
                 .action(clap::ArgAction::SetTrue),
         )
+}
+
+fn build_check_command() -> Command {
+    with_sync_args(Command::new("check"))
+        .about("运行全部配置校验并输出检查清单，不启动 TUI")
+}
+
+pub fn build_cli() -> Command {
+    let cmd = Command::new("sync-subdir")
+        .version("0.1.0")
+        .author("Claude <noreply@anthropic.com>")
+        .about("A TUI tool for syncing subdirectory changes between Git repositories")
+        .long_about(
+            "将源仓库中某个子目录的变更同步到独立的目标仓库。\n\n\
+             这个工具提供了交互式 TUI 界面，支持分支管理、commit 范围选择、\n\
+             merge 排除、删除操作同步等功能。",
+        )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .subcommand(build_diff_command())
+        .subcommand(build_check_command())
+        .subcommand(build_apply_bundle_command())
+        .subcommand(build_apply_mbox_command())
+        .subcommand(build_daemon_command())
+        .subcommand(build_extract_command())
+        .subcommand(build_map_command())
+        .subcommand(build_journal_command())
+        .subcommand(build_notes_command())
+        .subcommand(build_runs_command())
+        .subcommand(build_snapshot_command())
+        .subcommand(build_verify_command())
+        .subcommand(build_config_command())
+        .arg(
+            Arg::new("otlp_endpoint")
+                .long("otlp-endpoint")
+                .help("将各同步阶段的 tracing span 通过 OTLP 导出到该地址 (需使用 --features otlp 编译)")
+                .value_name("URL")
+                .global(true),
+        )
+        .arg(
+            Arg::new("theme")
+                .long("theme")
+                .help("界面配色: default 或 high-contrast (黑底白字，不依赖颜色区分状态)")
+                .value_name("主题")
+                .value_parser(["default", "high-contrast"])
+                .global(true),
+        )
+        .arg(
+            Arg::new("ascii")
+                .long("ascii")
+                .help("只用 ASCII 字符绘制边框、选择标记和进度条，适用于不支持制表符的终端")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+        );
+
+    with_sync_args(cmd)
         .after_help(
             "示例:\n  \
              sync-subdir /repo/main submodule /repo/sub abc123\n  \
-             sync-subdir -b feature/x -n /repo/main submodule /repo/sub abc123",
+             sync-subdir -b feature/x -n /repo/main submodule /repo/sub abc123\n  \
+             sync-subdir diff --stat /repo/main submodule /repo/sub\n  \
+             sync-subdir check /repo/main submodule /repo/sub abc123",
         )
 }
\ No newline at end of file
@@ -0,0 +1,98 @@
+use regex::Regex;
+
+use crate::error::{Result, SyncError};
+
+/// Where GitHub looks for the CODEOWNERS file, first match wins
+pub const LOCATIONS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Ownership rules of a CODEOWNERS file; the last rule matching a path decides
+/// its owners, as on GitHub
+#[derive(Debug, Clone)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: Regex,
+    owners: Vec<String>,
+}
+
+impl CodeOwners {
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for line in content.lines() {
+            let line = line.split_once(" #").map_or(line, |(rule, _)| rule).trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next() else { continue };
+            rules.push(Rule {
+                pattern: pattern_regex(pattern)?,
+                owners: fields.map(normalize_owner).collect(),
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    /// Owners of `path`, relative to the repository root; empty when no rule
+    /// matches or the last matching rule names nobody
+    pub fn owners(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.pattern.is_match(path))
+            .map_or(&[], |rule| rule.owners.as_slice())
+    }
+
+    /// Whether `owner` (a `@org/team`, `@user` or email, the `@` optional) owns `path`
+    pub fn owned_by(&self, path: &str, owner: &str) -> bool {
+        let owner = normalize_owner(owner);
+        self.owners(path).contains(&owner)
+    }
+}
+
+/// Owners compare without the leading `@` and case-insensitively, like GitHub handles
+fn normalize_owner(owner: &str) -> String {
+    owner.trim_start_matches('@').to_lowercase()
+}
+
+/// Regex for a CODEOWNERS pattern, which follows `.gitignore` rules: a pattern
+/// with a slash other than at its end is anchored at the root, `*` stays within
+/// one path component, `**` crosses them, and a pattern naming a directory owns
+/// everything below it. `dir/*` only owns the files directly in `dir`.
+fn pattern_regex(pattern: &str) -> Result<Regex> {
+    let body = pattern.trim_start_matches('/').trim_end_matches('/');
+    let anchored = pattern.starts_with('/') || body.contains('/');
+
+    let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut rest = body;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("**/") {
+            regex.push_str("(?:.*/)?");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("**") {
+            regex.push_str(".*");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('*') {
+            regex.push_str("[^/]*");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('?') {
+            regex.push_str("[^/]");
+            rest = after;
+        } else {
+            let c = rest.chars().next().expect("rest is not empty");
+            regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    regex.push_str(if pattern.ends_with('/') {
+        "/.*$"
+    } else if body.ends_with("/*") {
+        "$"
+    } else {
+        "(?:/.*)?$"
+    });
+    Regex::new(&regex).map_err(|e| SyncError::InvalidPattern(format!("CODEOWNERS pattern {}: {}", pattern, e)))
+}
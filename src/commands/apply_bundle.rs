@@ -0,0 +1,83 @@
+use clap::ArgMatches;
+use std::path::PathBuf;
+
+use crate::cli;
+use crate::bundle::Manifest;
+use crate::error::{Result, SyncError};
+use crate::git::{short_id, GitManager, OctopusPolicy};
+use crate::journal::Journal;
+use crate::lock::SyncLock;
+use crate::redact::Redaction;
+use crate::sync::{SyncConfig, SyncEngine};
+
+/// `sync-subdir apply-bundle`: applies a bundle written by `--export-bundle`
+/// to the checked-out branch of the target repository and returns the run's exit code
+pub fn run(matches: &ArgMatches) -> Result<i32> {
+    let bundle_dir = PathBuf::from(matches.get_one::<String>("bundle").expect("required argument"));
+    let target_repo = PathBuf::from(matches.get_one::<String>("target_repo").expect("required argument"));
+
+    let manifest = Manifest::read(&bundle_dir)?;
+    let git_manager = GitManager::open_target(&target_repo)?;
+    let _sync_lock = SyncLock::acquire(&target_repo, false)?;
+
+    // The patches were generated against a specific target commit
+    let head = git_manager.resolve_commit(false, "HEAD")?;
+    if head != manifest.target_base {
+        if !matches.get_flag("allow_base_mismatch") {
+            return Err(SyncError::Anyhow(anyhow::anyhow!(
+                "Target HEAD {} does not match the bundle base {} (use --allow-base-mismatch to apply anyway)",
+                short_id(&head),
+                short_id(&manifest.target_base)
+            )));
+        }
+        println!("警告: 目标 HEAD {} 与补丁包基点 {} 不一致", short_id(&head), short_id(&manifest.target_base));
+    }
+
+    println!(
+        "应用补丁包: {} 个补丁 (源 {} 的子目录 {})",
+        manifest.patches.len(),
+        manifest.source_repo.display(),
+        manifest.subdir
+    );
+
+    let config = SyncConfig {
+        subdir: manifest.subdir.clone(),
+        target_dir: manifest.target_dir.clone(),
+        excludes: Vec::new(),
+        redaction: Redaction::default(),
+        secrets: None,
+        block_secrets: false,
+        deferred_commits: 0,
+        messages: Default::default(),
+        message_rewrites: Vec::new(),
+        synced_from: None,
+        subject_prefix: None,
+        octopus: OctopusPolicy::Skip,
+        squash_by: None,
+        autosquash: false,
+        todo: None,
+        notes: false,
+        tags: None,
+        bump_version: None,
+        version_file: None,
+        tmp_dir: matches.get_one::<String>("tmp_dir").map(PathBuf::from),
+        keep_patches: None,
+        retry: cli::retry_policy(matches),
+        format_patch_args: Vec::new(),
+        am_args: Vec::new(),
+        continue_on_conflict: false,
+        staging_branch: false,
+        sync_delete: true,
+        verify: false,
+        no_mode_changes: false,
+    };
+    let mut engine = SyncEngine::new(config, false).with_journal(Journal::open(&target_repo)?);
+
+    let total = manifest.patches.len();
+    let report = engine.apply_bundle(&git_manager, &manifest, &bundle_dir, |current, patch, status| {
+        println!("[{}/{}] [{}] {}", current, total, status, patch.subject);
+    })?;
+
+    println!("\n{}", report);
+    Ok(report.exit_code())
+}
@@ -0,0 +1,80 @@
+use clap::ArgMatches;
+use std::path::PathBuf;
+
+use crate::bundle::{Manifest, MANIFEST_VERSION};
+use crate::cli;
+use crate::error::Result;
+use crate::git::{GitManager, OctopusPolicy};
+use crate::journal::{self, Journal};
+use crate::lock::SyncLock;
+use crate::mbox;
+use crate::paths;
+use crate::redact::Redaction;
+use crate::sync::{SyncConfig, SyncEngine};
+use crate::tmpdir;
+
+/// `sync-subdir apply-mbox`: applies the patches of an mbox, one `git am` per
+/// message, to the checked-out branch of the target repository the same way
+/// `apply-bundle` does, and returns the run's exit code
+pub fn run(matches: &ArgMatches) -> Result<i32> {
+    let mbox_path = PathBuf::from(matches.get_one::<String>("mbox").expect("required argument"));
+    let target_repo = PathBuf::from(matches.get_one::<String>("target_repo").expect("required argument"));
+    let target_dir = paths::normalize_dir(matches.get_one::<String>("target_dir").map(String::as_str).unwrap_or(""))?;
+    let tmp_dir = matches.get_one::<String>("tmp_dir").map(PathBuf::from);
+
+    let git_manager = GitManager::open_target(&target_repo)?;
+    let _sync_lock = SyncLock::acquire(&target_repo, false)?;
+
+    let split_dir = tmpdir::create(tmp_dir.as_deref())?;
+    let manifest = Manifest {
+        version: MANIFEST_VERSION,
+        created: journal::timestamp(),
+        source_repo: mbox_path.clone(),
+        subdir: String::new(),
+        target_dir: target_dir.clone(),
+        target_base: git_manager.resolve_commit(false, "HEAD")?,
+        patches: mbox::split(&mbox_path, split_dir.path())?,
+    };
+    println!("应用 mbox: {} 个补丁 ({})", manifest.patches.len(), mbox_path.display());
+
+    let config = SyncConfig {
+        subdir: String::new(),
+        target_dir,
+        excludes: Vec::new(),
+        redaction: Redaction::default(),
+        secrets: None,
+        block_secrets: false,
+        deferred_commits: 0,
+        messages: Default::default(),
+        message_rewrites: Vec::new(),
+        synced_from: None,
+        subject_prefix: None,
+        octopus: OctopusPolicy::Skip,
+        squash_by: None,
+        autosquash: false,
+        todo: None,
+        notes: false,
+        tags: None,
+        bump_version: None,
+        version_file: None,
+        tmp_dir,
+        keep_patches: None,
+        retry: cli::retry_policy(matches),
+        format_patch_args: Vec::new(),
+        am_args: Vec::new(),
+        continue_on_conflict: false,
+        staging_branch: false,
+        sync_delete: true,
+        verify: false,
+        no_mode_changes: false,
+    };
+    let mut engine = SyncEngine::new(config, false).with_journal(Journal::open(&target_repo)?);
+
+    let total = manifest.patches.len();
+    let report = engine.apply_bundle(&git_manager, &manifest, split_dir.path(), |current, patch, status| {
+        println!("[{}/{}] [{}] {}", current, total, status, patch.subject);
+    })?;
+
+    println!("\n{}", report);
+    Ok(report.exit_code())
+}
@@ -0,0 +1,174 @@
+use clap::ArgMatches;
+
+use crate::cli::Config;
+use crate::error::{Result, SyncError};
+use crate::git::GitManager;
+use crate::journal::Journal;
+use crate::workspace;
+
+/// Outcome of a single validation step
+struct CheckItem {
+    name: String,
+    result: std::result::Result<(), String>,
+    /// Failures of warning items are reported but do not fail the check run
+    warning: bool,
+}
+
+impl CheckItem {
+    fn pass(name: &str) -> Self {
+        Self { name: name.to_string(), result: Ok(()), warning: false }
+    }
+
+    fn fail(name: &str, reason: impl ToString) -> Self {
+        Self { name: name.to_string(), result: Err(reason.to_string()), warning: false }
+    }
+
+    fn warn(name: &str, reason: impl ToString) -> Self {
+        Self { name: name.to_string(), result: Err(reason.to_string()), warning: true }
+    }
+
+    fn from_result<T, E: ToString>(name: &str, result: std::result::Result<T, E>) -> Self {
+        match result {
+            Ok(_) => Self::pass(name),
+            Err(e) => Self::fail(name, e),
+        }
+    }
+}
+
+/// `sync-subdir check`: runs every validation and prints a pass/fail checklist
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let config = Config::from_matches(matches.clone()).map_err(SyncError::Anyhow)?;
+    let items = run_checks(&config);
+
+    let mut failed = 0;
+    for item in &items {
+        match &item.result {
+            Ok(()) => println!("[✓] {}", item.name),
+            Err(reason) if item.warning => println!("[!] {}: {}", item.name, reason),
+            Err(reason) => {
+                failed += 1;
+                println!("[✗] {}: {}", item.name, reason);
+            }
+        }
+    }
+    println!("\n{} 项检查, {} 项失败", items.len(), failed);
+
+    if failed > 0 {
+        return Err(SyncError::ValidationFailed(failed));
+    }
+    Ok(())
+}
+
+fn run_checks(config: &Config) -> Vec<CheckItem> {
+    let mut items = Vec::new();
+
+    for (label, path) in [("源仓库", &config.source_repo), ("目标仓库", &config.target_repo)] {
+        if !path.exists() {
+            items.push(CheckItem::fail(&format!("{}路径存在", label), SyncError::PathNotFound(path.clone())));
+        } else {
+            // Opened like the sync opens it, so worktrees and separate git dirs pass and broken repositories fail
+            items.push(CheckItem::from_result(&format!("{}是 Git 仓库", label), GitManager::open(path)));
+        }
+    }
+
+    let git_manager = match GitManager::new(&config.source_repo, &config.target_repo) {
+        Ok(gm) => gm,
+        Err(e) => {
+            items.push(CheckItem::fail("打开仓库", e));
+            return items;
+        }
+    };
+
+    if let Some(ref branch) = config.source_branch {
+        let exists = git_manager.branch_exists(true, branch);
+        items.push(match exists {
+            Ok(true) => CheckItem::pass("源分支存在"),
+            Ok(false) => CheckItem::fail("源分支存在", SyncError::BranchNotFound(branch.clone())),
+            Err(e) => CheckItem::fail("源分支存在", e),
+        });
+    }
+
+    let target_branch = config.get_default_target_branch();
+    items.push(match git_manager.branch_exists(false, &target_branch) {
+        Ok(true) => CheckItem::pass("目标分支存在"),
+        Ok(false) if config.create_branch.unwrap_or(true) => CheckItem::pass("目标分支将被创建"),
+        Ok(false) => CheckItem::fail("目标分支存在", SyncError::BranchNotFound(target_branch)),
+        Err(e) => CheckItem::fail("目标分支存在", e),
+    });
+
+    if let Some(ref base) = config.base {
+        items.push(CheckItem::from_result("新分支基点可解析", git_manager.resolve_commit(false, base)));
+    }
+
+    // A range given as the start commit brings its own end
+    let mut config = config.clone();
+    let split = git_manager.split_range(&config.start_commit, config.end_commit.as_deref());
+    let range_parsed = split.is_ok();
+    match split {
+        Ok(Some(range)) => config.set_range(range),
+        Ok(None) => {}
+        Err(e) => items.push(CheckItem::fail("提交范围可解析", e)),
+    }
+    if config.package.is_some() {
+        let rev = config.end_rev();
+        items.push(CheckItem::from_result("工作区成员可解析", workspace::apply(&mut config, &git_manager, &rev)));
+    }
+    let end_commit = &config.end_rev();
+    let start = git_manager.resolve_commit(true, &config.start_commit);
+    let end = git_manager.resolve_commit(true, end_commit);
+    let range_valid = range_parsed && start.is_ok() && end.is_ok();
+    if range_parsed {
+        items.push(CheckItem::from_result("起始 commit 可解析", start));
+        items.push(CheckItem::from_result("结束 commit 可解析", end));
+    }
+
+    if range_valid {
+        items.push(match git_manager.is_ancestor(true, &config.start_commit, end_commit) {
+            Ok(true) => CheckItem::pass("起始 commit 是结束 commit 的祖先"),
+            Ok(false) => CheckItem::fail("起始 commit 是结束 commit 的祖先", "提交范围为空或方向错误"),
+            Err(e) => CheckItem::fail("起始 commit 是结束 commit 的祖先", e),
+        });
+        items.push(CheckItem::from_result(
+            "子目录在结束 commit 中存在",
+            git_manager.subdir_tree_id(end_commit, &config.subdir),
+        ));
+        if let Err(e) = git_manager.subdir_tree_id(&config.start_commit, &config.subdir) {
+            items.push(CheckItem::warn("子目录在起始 commit 中存在", e));
+        }
+        let case_check = git_manager
+            .case_collisions(end_commit, &config.subdir)
+            .and_then(|collisions| Ok((collisions, git_manager.target_ignores_case()?)));
+        items.push(match case_check {
+            Ok((collisions, _)) if collisions.is_empty() => CheckItem::pass("子目录中无仅大小写不同的路径"),
+            Ok((collisions, ignores_case)) => {
+                let listed = collisions.iter().map(|group| group.join(" / ")).collect::<Vec<_>>().join("; ");
+                if ignores_case {
+                    CheckItem::fail("子目录中无仅大小写不同的路径", SyncError::CaseCollision(listed))
+                } else {
+                    CheckItem::warn("子目录中无仅大小写不同的路径", listed)
+                }
+            }
+            Err(e) => CheckItem::warn("子目录中无仅大小写不同的路径", e),
+        });
+    }
+
+    items.push(match git_manager.am_in_progress() {
+        Ok(false) => CheckItem::pass("目标仓库无进行中的 git am"),
+        Ok(true) => CheckItem::fail("目标仓库无进行中的 git am", "请先执行 git am --abort 或 --continue"),
+        Err(e) => CheckItem::fail("目标仓库无进行中的 git am", e),
+    });
+
+    items.push(CheckItem::from_result(
+        "同步日志可读",
+        Journal::open(&config.target_repo).and_then(|journal| journal.entries()),
+    ));
+
+    items.push(match git_manager.has_uncommitted_changes(false) {
+        Ok(false) => CheckItem::pass("目标仓库工作区干净"),
+        Ok(true) if config.auto_stash.unwrap_or(true) => CheckItem::pass("目标仓库有未提交变更 (将自动 stash)"),
+        Ok(true) => CheckItem::fail("目标仓库工作区干净", SyncError::DirtyRepository(config.target_repo.clone())),
+        Err(e) => CheckItem::fail("目标仓库工作区干净", e),
+    });
+
+    items
+}
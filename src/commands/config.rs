@@ -0,0 +1,86 @@
+use clap::ArgMatches;
+
+use crate::error::{Result, SyncError};
+use crate::prefs::{Preferences, SETTABLE_KEYS};
+
+/// Written to a new preferences file opened by `config edit`
+const TEMPLATE: &str = "\
+# sync-subdir 用户偏好设置，作为对应命令行选项的默认值
+# theme = \"high-contrast\"   # --theme
+# ascii = true               # --ascii
+# confirm = false            # false 时默认 --yes，开始同步前不再确认
+#
+# [keys]                     # 提交列表按键，替换对应动作的默认按键
+# toggle = \"x\"
+# top = [\"g\", \"Home\"]
+";
+
+/// `sync-subdir config`: shows and changes the user preferences
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("get", sub_matches)) => get(sub_matches.get_one::<String>("key").map(String::as_str)),
+        Some(("set", sub_matches)) => {
+            let key = sub_matches.get_one::<String>("key").expect("required argument");
+            let value = sub_matches.get_one::<String>("value").expect("required argument");
+            Preferences::set(key, value).map_err(SyncError::Anyhow)
+        }
+        Some(("edit", _)) => edit(),
+        _ => unreachable!("config requires a subcommand"),
+    }
+}
+
+fn get(key: Option<&str>) -> Result<()> {
+    let prefs = Preferences::load().map_err(SyncError::Anyhow)?;
+    match key {
+        Some(key) => {
+            if let Some(value) = prefs.get(key).map_err(SyncError::Anyhow)? {
+                println!("{}", value);
+            }
+        }
+        None => {
+            if let Some(path) = Preferences::path() {
+                println!("# {}", path.display());
+            }
+            for key in SETTABLE_KEYS {
+                if let Some(value) = prefs.get(key).map_err(SyncError::Anyhow)? {
+                    println!("{} = {}", key, value);
+                }
+            }
+            for recent in &prefs.recent {
+                println!("recent = {} {} {}", recent.source.display(), recent.subdir, recent.target.display());
+            }
+            for (action, binding) in &prefs.keys {
+                println!("keys.{} = {}", action, binding.keys().join(", "));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Opens the preferences file in the editor and checks it once the editor exits
+fn edit() -> Result<()> {
+    let path = Preferences::path()
+        .ok_or_else(|| SyncError::Anyhow(anyhow::anyhow!("Neither XDG_CONFIG_HOME nor HOME is set")))?;
+    if !path.exists() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, TEMPLATE)?;
+    }
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status()?;
+    if !status.success() {
+        return Err(SyncError::Anyhow(anyhow::anyhow!("Editor {} exited with an error", editor)));
+    }
+
+    Preferences::load_from(&path).map_err(SyncError::Anyhow)?;
+    Ok(())
+}
@@ -0,0 +1,384 @@
+use chrono::{DateTime, Local};
+use clap::ArgMatches;
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table};
+use ratatui::Frame;
+use serde::Deserialize;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::backend;
+use crate::error::{Result, SyncError};
+use crate::git::{CommitFilter, CommitOrder, GitManager, OctopusPolicy, RangeQuery};
+use crate::journal::Journal;
+use crate::lock::SyncLock;
+use crate::redact::Redaction;
+use crate::schedule::Schedule;
+use crate::report::SyncReport;
+use crate::runs;
+use crate::sync::{RetryPolicy, SyncConfig, SyncEngine, SyncEvent};
+use crate::theme::Appearance;
+use crate::tui::TuiManager;
+
+/// Jobs file of `sync-subdir daemon`, one `[[job]]` table per sync job
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobsFile {
+    #[serde(rename = "job")]
+    jobs: Vec<JobConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct JobConfig {
+    name: String,
+    source: PathBuf,
+    subdir: String,
+    target: PathBuf,
+    /// First commit of the first run; later runs continue after the last synced commit
+    start: String,
+    /// Source ref synced up to, `HEAD` when omitted
+    #[serde(default)]
+    branch: Option<String>,
+    #[serde(default)]
+    target_dir: String,
+    schedule: String,
+    /// Archived run reports kept in the target, 0 for none
+    #[serde(default = "default_keep_runs")]
+    keep_runs: usize,
+}
+
+fn default_keep_runs() -> usize {
+    runs::DEFAULT_KEEP
+}
+
+/// A configured job and what the daemon knows about its runs
+struct Job {
+    config: JobConfig,
+    schedule: Schedule,
+    next_run: Option<DateTime<Local>>,
+    last_run: Option<DateTime<Local>>,
+    last_result: Option<std::result::Result<String, String>>,
+    /// Cancel flag of the running sync, `None` while the job is idle
+    running: Option<Arc<AtomicBool>>,
+    /// Commits processed and total of the running sync
+    progress: Option<(usize, usize)>,
+}
+
+impl Job {
+    fn due(&self, now: DateTime<Local>) -> bool {
+        self.running.is_none() && self.next_run.is_some_and(|next| next <= now)
+    }
+
+    fn start(&mut self, index: usize, updates: mpsc::Sender<JobUpdate>) {
+        let now = Local::now();
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.last_run = Some(now);
+        self.next_run = self.schedule.next_after(now);
+        self.running = Some(cancel.clone());
+        self.progress = None;
+
+        let config = self.config.clone();
+        tokio::spawn(async move {
+            let result = run_job(index, &config, cancel, &updates).await;
+            let _ = updates.send(JobUpdate::Finished(index, result.map(Box::new))).await;
+        });
+    }
+
+    fn finish(&mut self, result: Result<Box<SyncReport>>) {
+        self.running = None;
+        self.progress = None;
+        // Scheduled runs that found nothing to sync would crowd out the others
+        let keep = self.config.keep_runs;
+        if let Some(report) = result.as_ref().ok().filter(|report| report.total_commits > 0 && keep > 0) {
+            if let Err(e) = runs::dir(&self.config.target).and_then(|dir| runs::archive(&dir, report, keep)) {
+                tracing::warn!("Failed to archive the run report: {}", e);
+            }
+        }
+        self.last_result = Some(match result {
+            Ok(report) if report.total_commits == 0 => Ok("无新提交".to_string()),
+            Ok(report) if report.aborted => Ok(format!("已中断: 同步 {}, 跳过 {}", report.synced_commits, report.skipped_commits)),
+            Ok(report) => Ok(format!("同步 {}, 跳过 {}", report.synced_commits, report.skipped_commits)),
+            Err(e) => Err(format!("失败: {}", e.to_string().lines().next().unwrap_or_default())),
+        });
+    }
+
+    fn cancel(&self) {
+        if let Some(flag) = &self.running {
+            flag.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Sent by running jobs to the daemon loop
+enum JobUpdate {
+    Progress(usize, usize, usize),
+    Finished(usize, Result<Box<SyncReport>>),
+}
+
+/// `sync-subdir daemon`: runs the jobs of a jobs file on their schedules, with a
+/// status screen when attached to a terminal and one log line per run otherwise
+pub async fn run(matches: &ArgMatches) -> Result<()> {
+    let path = PathBuf::from(matches.get_one::<String>("jobs").expect("required argument"));
+    let mut jobs = load_jobs(&path)?;
+    let (tx, mut rx) = mpsc::channel(jobs.len() * 4);
+
+    if !std::io::stdout().is_terminal() {
+        return run_headless(jobs, tx, rx).await;
+    }
+
+    let appearance = Appearance::from_matches(matches).map_err(SyncError::Anyhow)?;
+    let mut tui_manager = TuiManager::new(appearance).map_err(SyncError::Anyhow)?;
+    let mut selected = 0;
+    let mut quitting = false;
+    loop {
+        if !quitting {
+            let now = Local::now();
+            for (i, job) in jobs.iter_mut().enumerate() {
+                if job.due(now) {
+                    job.start(i, tx.clone());
+                }
+            }
+        } else if jobs.iter().all(|job| job.running.is_none()) {
+            break;
+        }
+
+        tui_manager.draw_with(|f| draw_status(f, &path, &jobs, selected, quitting)).map_err(SyncError::Anyhow)?;
+
+        tokio::select! {
+            Ok(has_event) = tokio::task::spawn_blocking(|| event::poll(Duration::from_millis(200))) => {
+                let Ok(true) = has_event else { continue };
+                let Ok(Event::Key(key)) = event::read() else { continue };
+                match key.code {
+                    KeyCode::Up => selected = selected.saturating_sub(1),
+                    KeyCode::Down => selected = (selected + 1).min(jobs.len() - 1),
+                    KeyCode::Char('r') if !quitting && jobs[selected].running.is_none() => jobs[selected].start(selected, tx.clone()),
+                    KeyCode::Char('q') | KeyCode::Esc => quitting = true,
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => quitting = true,
+                    _ => {}
+                }
+                // Running syncs stop at their next commit boundary
+                if quitting {
+                    jobs.iter().for_each(Job::cancel);
+                }
+            }
+            Some(update) = rx.recv() => match update {
+                JobUpdate::Progress(i, current, total) => jobs[i].progress = Some((current, total)),
+                JobUpdate::Finished(i, result) => jobs[i].finish(result),
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Daemon loop without a terminal, e.g. under systemd: stops on SIGINT
+async fn run_headless(mut jobs: Vec<Job>, tx: mpsc::Sender<JobUpdate>, mut rx: mpsc::Receiver<JobUpdate>) -> Result<()> {
+    for job in &jobs {
+        println!("{}: 下次运行 {}", job.config.name, format_time(job.next_run));
+    }
+    let mut quitting = false;
+    loop {
+        if !quitting {
+            let now = Local::now();
+            for (i, job) in jobs.iter_mut().enumerate() {
+                if job.due(now) {
+                    job.start(i, tx.clone());
+                }
+            }
+        } else if jobs.iter().all(|job| job.running.is_none()) {
+            return Ok(());
+        }
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c(), if !quitting => {
+                quitting = true;
+                jobs.iter().for_each(Job::cancel);
+            }
+            Some(update) = rx.recv() => {
+                if let JobUpdate::Finished(i, result) = update {
+                    let job = &mut jobs[i];
+                    job.finish(result);
+                    let (Some(Ok(message)) | Some(Err(message))) = &job.last_result else { continue };
+                    println!(
+                        "[{}] {}: {} (下次运行 {})",
+                        format_time(job.last_run),
+                        job.config.name,
+                        message,
+                        format_time(job.next_run)
+                    );
+                }
+            }
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+        }
+    }
+}
+
+fn load_jobs(path: &Path) -> Result<Vec<Job>> {
+    let content = std::fs::read_to_string(path)?;
+    let file: JobsFile = toml::from_str(&content)
+        .map_err(|e| SyncError::Anyhow(anyhow::anyhow!("Invalid jobs file {}: {}", path.display(), e)))?;
+    if file.jobs.is_empty() {
+        return Err(SyncError::Anyhow(anyhow::anyhow!("Jobs file {} declares no [[job]]", path.display())));
+    }
+
+    let now = Local::now();
+    file.jobs
+        .into_iter()
+        .map(|config| {
+            let schedule = Schedule::parse(&config.schedule)
+                .map_err(|e| SyncError::Anyhow(e.context(format!("job {}", config.name))))?;
+            Ok(Job {
+                next_run: schedule.next_after(now),
+                schedule,
+                config,
+                last_run: None,
+                last_result: None,
+                running: None,
+                progress: None,
+            })
+        })
+        .collect()
+}
+
+/// Syncs the commits added since the job's last run onto the checked-out branch
+/// of its target, the way `apply-bundle` does
+async fn run_job(index: usize, job: &JobConfig, cancel: Arc<AtomicBool>, updates: &mpsc::Sender<JobUpdate>) -> Result<SyncReport> {
+    let git_manager = GitManager::new(&job.source, &job.target)?;
+    // Another job or an interactive run syncing into the same target goes first; the next schedule retries
+    let _sync_lock = SyncLock::acquire(&job.target, false)?;
+    if git_manager.am_in_progress()? {
+        return Err(SyncError::Anyhow(anyhow::anyhow!("git am in progress in {}", job.target.display())));
+    }
+
+    let journal = Journal::open(&job.target)?;
+    let resume = journal.resume_point(&job.source, &job.subdir)?;
+    let query = RangeQuery {
+        subdir: job.subdir.clone(),
+        start: resume.clone().unwrap_or_else(|| job.start.clone()),
+        end: job.branch.clone().unwrap_or_else(|| "HEAD".to_string()),
+        include_start: resume.is_none(),
+        first_parent: true,
+        follow_dir: false,
+        order: CommitOrder::Topo,
+    };
+    // A run that failed halfway left no summary; skip what it already applied
+    let applied = journal.applied_sources()?;
+    let commits: Vec<_> = git_manager
+        .get_commits_in_range(&query, &CommitFilter::default())?
+        .into_iter()
+        .filter(|commit| !applied.contains(&commit.id))
+        .collect();
+
+    let config = SyncConfig {
+        subdir: job.subdir.clone(),
+        target_dir: job.target_dir.clone(),
+        excludes: Vec::new(),
+        redaction: Redaction::default(),
+        secrets: None,
+        block_secrets: false,
+        deferred_commits: 0,
+        messages: Default::default(),
+        message_rewrites: Vec::new(),
+        synced_from: None,
+        subject_prefix: None,
+        octopus: OctopusPolicy::Skip,
+        squash_by: None,
+        autosquash: false,
+        todo: None,
+        notes: false,
+        tags: None,
+        bump_version: None,
+        version_file: None,
+        tmp_dir: None,
+        keep_patches: None,
+        retry: RetryPolicy::default(),
+        format_patch_args: Vec::new(),
+        am_args: Vec::new(),
+        continue_on_conflict: false,
+        staging_branch: false,
+        sync_delete: true,
+        verify: false,
+        no_mode_changes: false,
+    };
+    let engine = SyncEngine::new(config, false).with_cancel(cancel).with_journal(journal);
+
+    let (tx, mut rx) = mpsc::channel(64);
+    let forward = {
+        let updates = updates.clone();
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                if let SyncEvent::Progress { current, total, .. } = event {
+                    let _ = updates.send(JobUpdate::Progress(index, current, total)).await;
+                }
+            }
+        })
+    };
+    let (_, result) = backend::execute_blocking(engine, git_manager, commits, tx).await;
+    let _ = forward.await;
+    result
+}
+
+fn format_time(time: Option<DateTime<Local>>) -> String {
+    time.map_or_else(|| "-".to_string(), |t| t.format("%m-%d %H:%M").to_string())
+}
+
+fn draw_status(f: &mut Frame, path: &Path, jobs: &[Job], selected: usize, quitting: bool) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(5), Constraint::Length(1)])
+        .split(f.size());
+
+    let title = Paragraph::new(format!("同步任务 - {}", path.display()))
+        .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .block(Block::default().borders(Borders::ALL))
+        .alignment(ratatui::layout::Alignment::Center);
+    f.render_widget(title, chunks[0]);
+
+    let header = Row::new(vec!["任务", "计划", "上次运行", "结果", "下次运行"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows: Vec<Row> = jobs.iter().enumerate().map(|(i, job)| {
+        let (result, color) = match (&job.running, job.progress, &job.last_result) {
+            (Some(_), Some((current, total)), _) => (format!("运行中 {}/{}", current, total), Color::Cyan),
+            (Some(_), None, _) => ("运行中...".to_string(), Color::Cyan),
+            (None, _, Some(Ok(message))) => (message.clone(), Color::Green),
+            (None, _, Some(Err(message))) => (message.clone(), Color::Red),
+            (None, _, None) => ("-".to_string(), Color::Gray),
+        };
+        let row = Row::new(vec![
+            Cell::from(job.config.name.clone()),
+            Cell::from(job.config.schedule.clone()),
+            Cell::from(format_time(job.last_run)),
+            Cell::from(result).style(Style::default().fg(color)),
+            Cell::from(format_time(job.next_run)),
+        ]);
+        if i == selected {
+            row.style(Style::default().add_modifier(Modifier::REVERSED))
+        } else {
+            row
+        }
+    }).collect();
+    let table = Table::new(rows)
+        .header(header)
+        .widths(&[
+            Constraint::Percentage(20),
+            Constraint::Percentage(15),
+            Constraint::Length(12),
+            Constraint::Percentage(40),
+            Constraint::Length(12),
+        ])
+        .block(Block::default().borders(Borders::ALL).title("任务"));
+    f.render_widget(table, chunks[1]);
+
+    let help = if quitting {
+        "正在等待运行中的任务在当前提交后停止..."
+    } else {
+        "↑/↓ 选择 | r 立即运行 | q 退出"
+    };
+    f.render_widget(Paragraph::new(help).style(Style::default().fg(Color::Gray)), chunks[2]);
+}
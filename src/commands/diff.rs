@@ -0,0 +1,30 @@
+use clap::ArgMatches;
+
+use crate::cli::RepoArgs;
+use crate::error::{Result, SyncError};
+use crate::git::GitManager;
+use crate::paths;
+
+/// `sync-subdir diff`: shows how far the target has drifted from the source subdir
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let args = RepoArgs::from_matches(matches).map_err(SyncError::Anyhow)?;
+    let commit = matches.get_one::<String>("commit").map(String::as_str).unwrap_or("HEAD");
+    let stat_only = matches.get_flag("stat");
+    let subdir = paths::normalize_dir(&args.subdir)?;
+    let target_dir = paths::normalize_dir(matches.get_one::<String>("target_dir").map(String::as_str).unwrap_or(""))?;
+
+    let git_manager = GitManager::new(&args.source_repo, &args.target_repo)?;
+    let mut excludes: Vec<String> = matches.get_many::<String>("redact_path").map(|v| v.cloned().collect()).unwrap_or_default();
+    if matches.get_flag("respect_export_ignore") {
+        excludes.extend(git_manager.export_ignore_patterns(commit, &subdir)?);
+    }
+    let output = git_manager.diff_subdir_to_target(commit, &subdir, &target_dir, &excludes, stat_only)?;
+
+    match matches.get_one::<String>("output") {
+        Some(path) => std::fs::write(path, output)?,
+        None if output.is_empty() => println!("无差异"),
+        None => print!("{}", output),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use clap::ArgMatches;
+
+use crate::cli::RepoArgs;
+use crate::error::{Result, SyncError};
+use crate::git::{short_id, GitManager};
+use crate::journal::{self, Journal, JournalEntry};
+use crate::lock::SyncLock;
+use crate::paths;
+
+/// `sync-subdir extract`: rewrites the whole history of the subdir into a target
+/// repository without commits and records every commit in the journal, so later
+/// runs continue after the extracted end commit
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let args = RepoArgs::from_matches(matches).map_err(SyncError::Anyhow)?;
+    let end = matches.get_one::<String>("end").map(String::as_str).unwrap_or("HEAD");
+    let subdir = paths::normalize_dir(&args.subdir)?;
+    let target_dir = paths::normalize_dir(matches.get_one::<String>("target_dir").map(String::as_str).unwrap_or(""))?;
+
+    let git_manager = GitManager::new(&args.source_repo, &args.target_repo)?;
+    let _sync_lock = SyncLock::acquire(&args.target_repo, false)?;
+    let journal = Journal::open(&args.target_repo)?;
+
+    println!("提取 {} 中子目录 {} 的历史 (到 {})", args.source_repo.display(), args.subdir, end);
+    let run_id = journal::new_run_id();
+    let mut count = 0;
+    let summary = git_manager.extract_history(&subdir, end, &target_dir, |commit| {
+        count += 1;
+        println!("[{}] {} -> {} {}", count, short_id(&commit.source), short_id(&commit.target), commit.subject);
+    })?;
+
+    for commit in &summary.commits {
+        let entry = JournalEntry::Commit {
+            run_id: run_id.clone(),
+            timestamp: journal::timestamp(),
+            source: commit.source.clone(),
+            target: Some(commit.target.clone()),
+            status: "EXTRACTED".to_string(),
+            subject: commit.subject.clone(),
+        };
+        journal.append(&entry)?;
+    }
+    journal.append(&JournalEntry::Run {
+        run_id,
+        timestamp: journal::timestamp(),
+        source_repo: args.source_repo.clone(),
+        subdir: args.subdir.clone(),
+        synced: summary.commits.len(),
+        skipped: summary.walked - summary.commits.len(),
+        pending: 0,
+        resume_from: Some(summary.end.clone()),
+        format_patch_args: Vec::new(),
+        am_args: Vec::new(),
+    })?;
+
+    println!(
+        "\n提取完成: 检查 {} 个提交, 生成 {} 个提交, 目标分支 {} 对应源提交 {}",
+        summary.walked,
+        summary.commits.len(),
+        git_manager.target_repo_info.current_branch,
+        short_id(&summary.end)
+    );
+    Ok(())
+}
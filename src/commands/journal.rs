@@ -0,0 +1,146 @@
+use clap::ArgMatches;
+use git2::{BranchType, Oid, Repository};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::error::Result;
+use crate::git::short_id;
+use crate::journal::{Journal, JournalEntry};
+use crate::lock::SyncLock;
+
+/// Status given to a journal entry whose target commit is gone
+const LOST_STATUS: &str = "LOST";
+
+/// `sync-subdir journal`: maintenance of the target's sync journal
+pub fn run(matches: &ArgMatches) -> Result<i32> {
+    match matches.subcommand() {
+        Some(("repair", sub_matches)) => repair(sub_matches),
+        _ => unreachable!("journal requires a subcommand"),
+    }
+}
+
+/// `journal repair`: checks the journal against the target repository and
+/// rewrites it without the lines that do not parse and with the target commits
+/// that are no longer on any branch marked lost, so their source commits are
+/// synced again. With `--dry-run` only lists the problems, exiting with 1 when
+/// there are any.
+fn repair(matches: &ArgMatches) -> Result<i32> {
+    let target_repo = PathBuf::from(matches.get_one::<String>("target_repo").expect("required argument"));
+    let dry_run = matches.get_flag("dry_run");
+
+    // A running sync has journal entries whose commits are not on a branch yet
+    let _sync_lock = SyncLock::acquire(&target_repo, false)?;
+    let journal = Journal::open(&target_repo)?;
+    let repo = Repository::open(&target_repo)?;
+    let history = History::new(&repo)?;
+
+    let (mut entries, corrupt) = journal.read()?;
+    let mut problems: Vec<String> = corrupt
+        .iter()
+        .map(|line| format!("第 {} 行无法解析: {}", line.number, line.error))
+        .collect();
+
+    // Commits folded by --squash-by only live on in the squashed commit
+    let mut squashed: HashSet<(String, String)> = HashSet::new();
+    let mut lost: HashSet<(String, String)> = HashSet::new();
+    entries.retain(|entry| match entry {
+        JournalEntry::Squash { run_id, sources, target, .. } => {
+            squashed.extend(sources.iter().map(|source| (run_id.clone(), source.clone())));
+            if history.contains(target) {
+                return true;
+            }
+            problems.push(format!("合并提交 {} 不在目标仓库的任何分支上 ({} 个源提交)", short_id(target), sources.len()));
+            lost.extend(sources.iter().map(|source| (run_id.clone(), source.clone())));
+            false
+        }
+        _ => true,
+    });
+    for entry in &mut entries {
+        if let JournalEntry::Commit { run_id, source, target: target @ Some(_), status, .. } = entry {
+            let key = (run_id.clone(), source.clone());
+            let gone = if squashed.contains(&key) {
+                lost.contains(&key)
+            } else {
+                let id = target.as_deref().unwrap_or_default();
+                let gone = !history.contains(id);
+                if gone {
+                    problems.push(format!("源提交 {} 的目标提交 {} 不在目标仓库的任何分支上", short_id(source), short_id(id)));
+                    lost.insert(key);
+                }
+                gone
+            };
+            if gone {
+                *target = None;
+                *status = LOST_STATUS.to_string();
+            }
+        }
+    }
+
+    // A run must resume before its first lost commit
+    let mut run_sources: HashMap<String, Vec<String>> = HashMap::new();
+    for entry in &entries {
+        if let JournalEntry::Commit { run_id, source, .. } = entry {
+            run_sources.entry(run_id.clone()).or_default().push(source.clone());
+        }
+    }
+    for entry in &mut entries {
+        let JournalEntry::Run { run_id, resume_from, .. } = entry else { continue };
+        let Some(sources) = run_sources.get(run_id.as_str()) else { continue };
+        let Some(first_lost) = sources.iter().position(|source| lost.contains(&(run_id.clone(), source.clone()))) else {
+            continue;
+        };
+        let resumed = resume_from.as_ref().and_then(|commit| sources.iter().position(|source| source == commit));
+        if resumed.is_none_or(|resumed| resumed >= first_lost) {
+            let earlier = first_lost.checked_sub(1).map(|i| sources[i].clone());
+            problems.push(format!(
+                "运行 {} 的续传点移到 {}",
+                run_id,
+                earlier.as_deref().map(short_id).unwrap_or("上一次运行")
+            ));
+            *resume_from = earlier;
+        }
+    }
+
+    if problems.is_empty() {
+        println!("同步日志与目标仓库一致");
+        return Ok(0);
+    }
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    if dry_run {
+        println!("\n发现 {} 个问题 (预览模式，未修改同步日志)", problems.len());
+        return Ok(1);
+    }
+    let backup = journal.rewrite(&entries)?;
+    println!("\n已修复 {} 个问题，原日志备份在 {}", problems.len(), backup.display());
+    Ok(0)
+}
+
+/// Commits reachable from the target's local branches
+struct History<'r> {
+    repo: &'r Repository,
+    tips: Vec<Oid>,
+}
+
+impl<'r> History<'r> {
+    fn new(repo: &'r Repository) -> Result<Self> {
+        let mut tips = Vec::new();
+        for branch in repo.branches(Some(BranchType::Local))? {
+            if let Some(tip) = branch?.0.get().target() {
+                tips.push(tip);
+            }
+        }
+        Ok(Self { repo, tips })
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        let Ok(oid) = Oid::from_str(id) else { return false };
+        if self.repo.find_commit(oid).is_err() {
+            return false;
+        }
+        self.tips
+            .iter()
+            .any(|&tip| tip == oid || self.repo.graph_descendant_of(tip, oid).unwrap_or(false))
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, TimeZone};
+use clap::ArgMatches;
+use git2::Repository;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, SyncError};
+use crate::journal::{CommitMapping, Journal};
+use crate::notes;
+
+/// `sync-subdir map`: prints which target commit each synced source commit
+/// became, from the target's sync journal or with `--from-notes` its git notes.
+/// Exits with 1 when `--commit` matches
+/// nothing, so scripts can tell an unsynced commit apart.
+pub fn run(matches: &ArgMatches) -> Result<i32> {
+    let target_repo = PathBuf::from(matches.get_one::<String>("target_repo").expect("required argument"));
+    let since = matches.get_one::<String>("since").map(|v| parse_date(v, false)).transpose()?;
+    let until = matches.get_one::<String>("until").map(|v| parse_date(v, true)).transpose()?;
+    let in_range = match matches.get_one::<String>("range") {
+        Some(range) => {
+            let source_repo = matches.get_one::<String>("source_repo").expect("required by --range");
+            Some(range_commits(Path::new(source_repo), range)?)
+        }
+        None => None,
+    };
+    let commit = matches.get_one::<String>("commit");
+
+    let mappings = if matches.get_flag("from_notes") {
+        notes::read(&target_repo)?
+    } else {
+        Journal::open(&target_repo)?.mappings()?
+    };
+    let mappings: Vec<CommitMapping> = mappings
+        .into_iter()
+        .filter(|m| commit.is_none_or(|c| m.source.starts_with(c.as_str()) || m.target.starts_with(c.as_str())))
+        .filter(|m| in_range.as_ref().is_none_or(|commits| commits.contains(&m.source)))
+        .filter(|m| {
+            if since.is_none() && until.is_none() {
+                return true;
+            }
+            let Ok(time) = DateTime::parse_from_rfc3339(&m.timestamp) else { return false };
+            since.is_none_or(|since| time >= since) && until.is_none_or(|until| time < until)
+        })
+        .collect();
+
+    let output = match matches.get_one::<String>("format").map(String::as_str) {
+        Some("csv") => to_csv(&mappings),
+        Some("json") => {
+            let mut json = serde_json::to_string_pretty(&mappings).map_err(|e| SyncError::Anyhow(e.into()))?;
+            json.push('\n');
+            json
+        }
+        _ => to_text(&mappings),
+    };
+    match matches.get_one::<String>("output") {
+        Some(path) => std::fs::write(path, output)?,
+        None => print!("{}", output),
+    }
+
+    if let (Some(commit), true) = (commit, mappings.is_empty()) {
+        eprintln!("同步日志中没有与 {} 对应的提交", commit);
+        return Ok(1);
+    }
+    Ok(0)
+}
+
+/// Start of the day for `--since`, start of the next day for `--until`, so both
+/// ends of a date range are inclusive. Full RFC 3339 times are taken as they are.
+fn parse_date(value: &str, end: bool) -> Result<DateTime<FixedOffset>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time);
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| SyncError::Anyhow(anyhow::anyhow!("Invalid date: {} (expected YYYY-MM-DD or RFC 3339)", value)))?;
+    let date = if end { date.succ_opt().unwrap_or(date) } else { date };
+    let midnight = date.and_hms_opt(0, 0, 0).expect("midnight exists");
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|time| time.fixed_offset())
+        .ok_or_else(|| SyncError::Anyhow(anyhow::anyhow!("Invalid local date: {}", value)))
+}
+
+/// Source commits of `range` (`A..B`, or a single revision for all its ancestors)
+fn range_commits(source_repo: &Path, range: &str) -> Result<HashSet<String>> {
+    let repo = Repository::open(source_repo).map_err(|_| SyncError::NotARepository(source_repo.to_path_buf()))?;
+    let mut walk = repo.revwalk()?;
+    if range.contains("..") {
+        walk.push_range(range).map_err(|e| SyncError::InvalidRange(format!("{}: {}", range, e.message())))?;
+    } else {
+        let commit = repo
+            .revparse_single(range)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(range.to_string()))?;
+        walk.push(commit.id())?;
+    }
+    walk.map(|id| Ok(id?.to_string())).collect()
+}
+
+fn to_text(mappings: &[CommitMapping]) -> String {
+    mappings
+        .iter()
+        .map(|m| format!("{}  {}  {}\n", m.source, m.target, m.subject))
+        .collect()
+}
+
+fn to_csv(mappings: &[CommitMapping]) -> String {
+    let mut csv = String::from("source,target,subject,timestamp,run_id\n");
+    for m in mappings {
+        let fields = [&m.source, &m.target, &m.subject, &m.timestamp, &m.run_id];
+        csv.push_str(&fields.map(|field| csv_field(field)).join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quotes a field containing a separator, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,14 @@
+pub mod apply_bundle;
+pub mod apply_mbox;
+pub mod check;
+pub mod config;
+pub mod daemon;
+pub mod diff;
+pub mod extract;
+pub mod journal;
+pub mod map;
+pub mod notes;
+pub mod runs;
+pub mod snapshot;
+pub mod stat;
+pub mod verify;
@@ -0,0 +1,31 @@
+use clap::ArgMatches;
+use std::path::PathBuf;
+
+use crate::error::Result;
+use crate::journal::Journal;
+use crate::notes;
+
+/// `sync-subdir notes`: writes, pushes and fetches the notes that record the
+/// source commits of the target commits
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let (name, sub_matches) = matches.subcommand().expect("notes requires a subcommand");
+    let target_repo = PathBuf::from(sub_matches.get_one::<String>("target_repo").expect("required argument"));
+    match name {
+        "write" => {
+            let written = notes::write(&target_repo, &Journal::open(&target_repo)?.mappings()?)?;
+            println!("已为 {} 个目标提交写入 notes ({})", written, notes::NOTES_REF);
+        }
+        "push" | "fetch" => {
+            let remote = sub_matches.get_one::<String>("remote").expect("has a default");
+            if name == "push" {
+                notes::push(&target_repo, remote)?;
+                println!("已将 {} 推送到 {}", notes::NOTES_REF, remote);
+            } else {
+                notes::fetch(&target_repo, remote)?;
+                println!("已获取并合并 {} 的 {}", remote, notes::NOTES_REF);
+            }
+        }
+        _ => unreachable!("unknown notes subcommand"),
+    }
+    Ok(())
+}
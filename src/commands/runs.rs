@@ -0,0 +1,86 @@
+use clap::ArgMatches;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, SyncError};
+use crate::runs::{self, ArchivedRun};
+
+/// `sync-subdir runs`: browses the reports archived after each sync
+pub fn run(matches: &ArgMatches) -> Result<i32> {
+    match matches.subcommand() {
+        Some(("list", sub_matches)) => list(sub_matches),
+        Some(("show", sub_matches)) => show(sub_matches),
+        _ => unreachable!("runs requires a subcommand"),
+    }
+}
+
+/// `runs list`: one line per archived run, newest first
+fn list(matches: &ArgMatches) -> Result<i32> {
+    let archived = runs::list(&archive_dir(matches)?)?;
+    let limit = matches.get_one::<usize>("limit").copied().unwrap_or(usize::MAX);
+    if archived.is_empty() {
+        println!("没有已归档的运行");
+        return Ok(0);
+    }
+    for run in archived.iter().rev().take(limit) {
+        match run.read() {
+            Ok(report) => println!("{}  {}", run.time(), summary(&report)),
+            Err(e) => println!("{}  无法读取: {}", run.time(), e),
+        }
+    }
+    Ok(0)
+}
+
+/// `runs show`: the full report of one archived run as pretty-printed JSON
+fn show(matches: &ArgMatches) -> Result<i32> {
+    let archived = runs::list(&archive_dir(matches)?)?;
+    let run = match matches.get_one::<String>("run") {
+        Some(wanted) => find(&archived, wanted)?,
+        None => archived.last().ok_or_else(|| SyncError::Anyhow(anyhow::anyhow!("No archived runs")))?,
+    };
+    let report = run.read()?;
+    println!("{}", serde_json::to_string_pretty(&report).map_err(|e| SyncError::Anyhow(e.into()))?);
+    Ok(0)
+}
+
+fn archive_dir(matches: &ArgMatches) -> Result<PathBuf> {
+    runs::dir(Path::new(matches.get_one::<String>("target_repo").expect("required argument")))
+}
+
+/// The newest run whose name or run id starts with `wanted`
+fn find<'a>(archived: &'a [ArchivedRun], wanted: &str) -> Result<&'a ArchivedRun> {
+    archived
+        .iter()
+        .rev()
+        .find(|run| {
+            run.name.starts_with(wanted)
+                || run.read().is_ok_and(|report| report["run_id"].as_str().is_some_and(|id| id.starts_with(wanted)))
+        })
+        .ok_or_else(|| SyncError::Anyhow(anyhow::anyhow!("No archived run matches {}", wanted)))
+}
+
+/// Run id, outcome, subdir and target of an archived report on one line
+fn summary(report: &Value) -> String {
+    let count = |field: &str| report[field].as_u64().unwrap_or(0);
+    let conflicts = report["conflicts"].as_array().map_or(0, Vec::len);
+    let outcome = if report["aborted"].as_bool().unwrap_or(false) {
+        "中断".to_string()
+    } else if conflicts > 0 {
+        format!("{} 个冲突", conflicts)
+    } else if report["config"]["dry_run"].as_bool().unwrap_or(false) {
+        "预览".to_string()
+    } else {
+        "完成".to_string()
+    };
+    format!(
+        "{}  {}  同步 {}/{}, 跳过 {}  {:.1} 秒  {} → {}",
+        report["run_id"].as_str().unwrap_or("-"),
+        outcome,
+        count("synced_commits"),
+        count("total_commits"),
+        count("skipped_commits"),
+        count("duration_ms") as f64 / 1000.0,
+        report["config"]["subdir"].as_str().unwrap_or(""),
+        report["config"]["target_repo"].as_str().unwrap_or(""),
+    )
+}
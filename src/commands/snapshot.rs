@@ -0,0 +1,95 @@
+use clap::ArgMatches;
+use tokio::sync::mpsc;
+
+use crate::backend::SyncBackend;
+use crate::cli::RepoArgs;
+use crate::copy::CopyEngine;
+use crate::error::{Result, SyncError};
+use crate::git::{short_id, GitManager, OctopusPolicy};
+use crate::journal::Journal;
+use crate::lock::SyncLock;
+use crate::paths;
+use crate::redact::Redaction;
+use crate::sync::{RetryPolicy, SyncConfig, SyncEvent};
+
+/// `sync-subdir snapshot`: makes the target repository root match the source
+/// subdir at one commit, in a single commit, and returns the run's exit code.
+/// The journal records the commit as the resume point of later syncs.
+pub async fn run(matches: &ArgMatches) -> Result<i32> {
+    let args = RepoArgs::from_matches(matches).map_err(SyncError::Anyhow)?;
+    let spec = matches.get_one::<String>("commit").expect("required argument");
+    let dry_run = matches.get_flag("dry_run");
+
+    let git_manager = GitManager::new(&args.source_repo, &args.target_repo)?;
+    let _sync_lock = SyncLock::acquire(&args.target_repo, false)?;
+    let subdir = paths::normalize_dir(&args.subdir)?;
+    let commit = git_manager.commit_info(spec, &subdir)?;
+
+    let mut excludes: Vec<String> = matches.get_many::<String>("redact_path").map(|v| v.cloned().collect()).unwrap_or_default();
+    if matches.get_flag("respect_export_ignore") {
+        excludes.extend(git_manager.export_ignore_patterns(&commit.id, &subdir)?);
+    }
+
+    println!(
+        "快照: {} 的子目录 {} 于 {} {}",
+        args.source_repo.display(),
+        if subdir.is_empty() { "/" } else { &subdir },
+        short_id(&commit.id),
+        commit.subject
+    );
+
+    let config = SyncConfig {
+        subdir: subdir.clone(),
+        target_dir: String::new(),
+        excludes,
+        redaction: Redaction::default(),
+        secrets: None,
+        block_secrets: false,
+        deferred_commits: 0,
+        messages: Default::default(),
+        message_rewrites: Vec::new(),
+        synced_from: None,
+        subject_prefix: None,
+        octopus: OctopusPolicy::Skip,
+        squash_by: None,
+        autosquash: false,
+        todo: None,
+        notes: false,
+        tags: None,
+        bump_version: None,
+        version_file: None,
+        tmp_dir: None,
+        keep_patches: None,
+        retry: RetryPolicy::default(),
+        format_patch_args: Vec::new(),
+        am_args: Vec::new(),
+        continue_on_conflict: false,
+        staging_branch: false,
+        sync_delete: !matches.get_flag("no_delete"),
+        verify: false,
+        no_mode_changes: false,
+    };
+    let label = if subdir.is_empty() { "repository root" } else { &subdir };
+    let mut engine = CopyEngine::new(config, dry_run)
+        .with_message(format!("Snapshot of {} at {}", label, commit.id))
+        .with_journal(Journal::open(&args.target_repo)?);
+
+    let (tx, mut rx) = mpsc::channel(64);
+    let printer = tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            if let SyncEvent::Progress { status, subject, .. } = event {
+                println!("[{}] {}", status, subject);
+            }
+        }
+    });
+    let result = engine.execute(&git_manager, std::slice::from_ref(&commit), tx).await;
+    let _ = printer.await;
+    let report = result?;
+
+    match report.commits.first().and_then(|c| c.target.as_deref()) {
+        Some(target) => println!("\n已生成快照提交 {}", short_id(target)),
+        None if dry_run => println!("\n预览模式，未生成提交"),
+        None => println!("\n目标仓库已与快照一致，未生成提交"),
+    }
+    Ok(report.exit_code())
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use crate::error::Result;
+use crate::git::{short_id, CommitInfo, GitManager};
+
+/// Most touched files listed; the totals count all of them
+const FILES_SHOWN: usize = 20;
+
+#[derive(Default)]
+struct FileTouches {
+    commits: usize,
+    insertions: usize,
+    deletions: usize,
+}
+
+/// `--stat-only`: prints the pending commits as a `git shortlog -sn` style count
+/// per author and the files they touch most under the synced directory, instead
+/// of starting the TUI
+pub fn print(commits: &[CommitInfo], git_manager: &GitManager) -> Result<()> {
+    let (Some(first), Some(last)) = (commits.first(), commits.last()) else {
+        println!("未发现任何相关提交历史");
+        return Ok(());
+    };
+
+    let mut authors: HashMap<&str, usize> = HashMap::new();
+    let mut files: HashMap<String, FileTouches> = HashMap::new();
+    for commit in commits {
+        *authors.entry(commit.author.as_str()).or_default() += 1;
+        for file in git_manager.commit_details(&commit.id, &commit.subdir)?.files {
+            let touches = files.entry(file.path).or_default();
+            touches.commits += 1;
+            touches.insertions += file.insertions;
+            touches.deletions += file.deletions;
+        }
+    }
+
+    println!("{} 个提交 ({}..{})", commits.len(), short_id(&first.id), short_id(&last.id));
+
+    let mut authors: Vec<_> = authors.into_iter().collect();
+    authors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    println!("\n作者:");
+    for (author, count) in &authors {
+        println!("{:>6}  {}", count, author);
+    }
+
+    let insertions: usize = files.values().map(|f| f.insertions).sum();
+    let deletions: usize = files.values().map(|f| f.deletions).sum();
+    let mut files: Vec<_> = files.into_iter().collect();
+    files.sort_by(|a, b| {
+        b.1.commits
+            .cmp(&a.1.commits)
+            .then((b.1.insertions + b.1.deletions).cmp(&(a.1.insertions + a.1.deletions)))
+            .then(a.0.cmp(&b.0))
+    });
+    println!("\n改动最多的文件:");
+    for (path, touches) in files.iter().take(FILES_SHOWN) {
+        println!("{:>6}  {} (+{} -{})", touches.commits, path, touches.insertions, touches.deletions);
+    }
+    if files.len() > FILES_SHOWN {
+        println!("  ... 另有 {} 个", files.len() - FILES_SHOWN);
+    }
+
+    println!(
+        "\n合计: {} 个作者, {} 个文件, +{} -{}",
+        authors.len(),
+        files.len(),
+        insertions,
+        deletions
+    );
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use clap::ArgMatches;
+
+use crate::cli::RepoArgs;
+use crate::copy;
+use crate::error::{Result, SyncError};
+use crate::git::{short_id, GitManager};
+use crate::journal::Journal;
+use crate::paths;
+use crate::report::Divergence;
+
+/// `sync-subdir verify`: compares the committed target directory with the source
+/// subdir at the last synced commit and lists the paths that differ. Exits with
+/// 1 when the mirror has drifted, so scheduled jobs can catch commits made
+/// directly to it.
+pub fn run(matches: &ArgMatches) -> Result<i32> {
+    let args = RepoArgs::from_matches(matches).map_err(SyncError::Anyhow)?;
+    let subdir = paths::normalize_dir(&args.subdir)?;
+    let target_dir = paths::normalize_dir(matches.get_one::<String>("target_dir").map(String::as_str).unwrap_or(""))?;
+
+    let git_manager = GitManager::new(&args.source_repo, &args.target_repo)?;
+    let spec = match matches.get_one::<String>("commit") {
+        Some(commit) => commit.clone(),
+        None => match Journal::open(&args.target_repo)?.resume_point(&args.source_repo, &args.subdir)? {
+            Some(commit) => commit,
+            None => {
+                println!("同步日志中没有已完成的同步，与源仓库 HEAD 比较");
+                "HEAD".to_string()
+            }
+        },
+    };
+    let commit = git_manager.commit_info(&spec, &subdir)?;
+
+    let mut excludes: Vec<String> = matches.get_many::<String>("redact_path").map(|v| v.cloned().collect()).unwrap_or_default();
+    if matches.get_flag("respect_export_ignore") {
+        excludes.extend(git_manager.export_ignore_patterns(&commit.id, &subdir)?);
+    }
+
+    let divergence: Vec<Divergence> = copy::compare(&git_manager, &commit, &target_dir, &excludes, matches.get_flag("no_mode_changes"))?
+        .into_iter()
+        .map(Divergence::from)
+        .collect();
+    let branch = &git_manager.target_repo_info.current_branch;
+    if divergence.is_empty() {
+        println!("目标分支 {} 与源提交 {} 的子目录一致", branch, short_id(&commit.id));
+        return Ok(0);
+    }
+
+    for entry in &divergence {
+        println!("{} {}", entry.label(), entry.path);
+    }
+    let count = |kind| divergence.iter().filter(|d| d.kind == kind).count();
+    println!(
+        "\n目标分支 {} 与源提交 {} 的子目录有 {} 个文件不一致: 缺少 {}, 多出 {}, 不同 {}",
+        branch,
+        short_id(&commit.id),
+        divergence.len(),
+        count("missing"),
+        count("extra"),
+        count("modified")
+    );
+    Ok(1)
+}
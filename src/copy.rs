@@ -0,0 +1,696 @@
+use crate::backend::{SyncBackend, SyncPlan};
+use crate::error::{Result, SyncError};
+use crate::git::{short_id, CommitInfo, GitManager, TreeEdit};
+use crate::journal::{self, Journal, JournalEntry};
+use crate::notes;
+use crate::patch;
+use crate::redact::Finding;
+use crate::report::{CommitResult, Divergence, ReportConfig, RunWarning, SyncReport};
+use crate::session::Label;
+use crate::sync::{self, SyncConfig, SyncEvent};
+use crate::tags;
+use git2::{ObjectType, Oid, Pathspec, PathspecFlags, Repository, Tree};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc::Sender;
+use tracing::instrument;
+
+/// How a file of the target directory changes when the source is copied over it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileStatus {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl FileStatus {
+    /// Status shown in the progress log, in `git status --short` style
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Added => "A",
+            Self::Modified => "M",
+            Self::Deleted => "D",
+        }
+    }
+}
+
+/// One file the copy writes or removes, relative to the synced directory
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    /// Path for display, with bytes that aren't UTF-8 replaced
+    pub path: String,
+    /// The exact path, as git stores it
+    raw_path: Vec<u8>,
+    pub status: FileStatus,
+    /// Source blob and mode, `None` for deletions
+    source: Option<(Oid, i32)>,
+}
+
+impl From<FileChange> for Divergence {
+    fn from(change: FileChange) -> Self {
+        let kind = match change.status {
+            FileStatus::Added => "missing",
+            FileStatus::Modified => "modified",
+            FileStatus::Deleted => "extra",
+        };
+        Self { path: change.path, kind }
+    }
+}
+
+/// Makes the target directory match the source directory as of the last
+/// selected commit, in a single target commit. History is not kept: the
+/// commits in between only show up in the commit message.
+///
+/// The commit is built from the source blobs in the object database and reaches
+/// the user's checkout through git, so untracked and ignored files there (a
+/// local `.env`, build output) are never removed, and neither are the
+/// directories holding them. Tracked files that match the target's ignore rules
+/// are kept as well, see [`CopyEngine::keep_ignored`].
+pub struct CopyEngine {
+    config: SyncConfig,
+    dry_run: bool,
+    journal: Option<Journal>,
+    cancel: Option<Arc<AtomicBool>>,
+    /// Carried into the run's report
+    warnings: Vec<String>,
+    /// Carried into the run's report
+    labels: BTreeMap<String, Label>,
+    /// Replaces the generated commit message
+    message: Option<String>,
+    /// Non-fatal issues not yet in the report, see [`CopyEngine::warn`]
+    issues: Vec<RunWarning>,
+}
+
+impl CopyEngine {
+    pub fn new(config: SyncConfig, dry_run: bool) -> Self {
+        Self {
+            config,
+            dry_run,
+            journal: None,
+            cancel: None,
+            warnings: Vec::new(),
+            labels: BTreeMap::new(),
+            message: None,
+            issues: Vec::new(),
+        }
+    }
+
+    /// Stops the run before the commit is created once `flag` is set; nothing
+    /// reaches the target then
+    pub fn with_cancel(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// Records the copied commits and the run summary in `journal`
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Warnings the user confirmed before the run, listed in its report
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Labels the user gave the commits on the selection screen, listed in the run's report
+    pub fn with_labels(mut self, labels: BTreeMap<String, Label>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Uses `message` for the target commit instead of listing the source commits
+    pub fn with_message(mut self, message: String) -> Self {
+        self.message = Some(message);
+        self
+    }
+
+    fn record(&mut self, entry: JournalEntry) {
+        if let Some(Err(e)) = self.journal.as_ref().map(|journal| journal.append(&entry)) {
+            self.warn(format!("写入同步日志失败: {}", e));
+        }
+    }
+
+    /// Notes a non-fatal issue for the report and the interface
+    fn warn(&mut self, message: String) {
+        tracing::warn!("{}", message);
+        self.issues.push(RunWarning { commit: None, message });
+    }
+
+    /// Changes that make the target directory match `commit`'s copy of the
+    /// synced directory; deletions only when the config syncs them
+    pub fn changes(&self, git_manager: &GitManager, commit: &CommitInfo) -> Result<Vec<FileChange>> {
+        let mut changes = compare(
+            git_manager,
+            commit,
+            &self.config.target_dir,
+            &self.config.excludes,
+            self.config.no_mode_changes,
+        )?;
+        if !self.config.sync_delete {
+            changes.retain(|change| change.status != FileStatus::Deleted);
+        }
+        Ok(changes)
+    }
+
+    /// Drops the deletions of files the target's ignore rules match. Such files
+    /// were added to the target on purpose (`git add -f`), usually local settings
+    /// the source never had; each one kept is reported.
+    fn keep_ignored(&mut self, git_manager: &GitManager, changes: &mut Vec<FileChange>) -> Result<()> {
+        let mut kept = Vec::new();
+        {
+            let repo = git_manager.repository(false);
+            if repo.is_bare() {
+                return Ok(());
+            }
+            for change in changes.iter().filter(|change| change.status == FileStatus::Deleted) {
+                if repo.is_path_ignored(bytes_path(&self.target_path(&change.raw_path)))? {
+                    kept.push(change.raw_path.clone());
+                }
+            }
+        }
+        if kept.is_empty() {
+            return Ok(());
+        }
+        changes.retain(|change| change.status != FileStatus::Deleted || !kept.contains(&change.raw_path));
+        for path in kept {
+            let path = String::from_utf8_lossy(&self.target_path(&path)).into_owned();
+            self.warn(format!("保留 {}: 该文件匹配目标仓库的忽略规则，未同步删除", path));
+        }
+        Ok(())
+    }
+
+    /// Checks `message` and the files the copy writes against the redaction rules
+    /// and the secret scanner, like the patch engine checks each patch. Files are
+    /// scanned whole, binary files are skipped; nothing is checked when there is
+    /// nothing to commit. Returns the secrets found when
+    /// they don't block the run.
+    fn scan(&self, git_manager: &GitManager, last: &CommitInfo, changes: &[FileChange], message: &str) -> Result<Vec<Finding>> {
+        let redaction = &self.config.redaction;
+        if changes.is_empty() || (redaction.is_empty() && self.config.secrets.is_none()) {
+            return Ok(Vec::new());
+        }
+        let repo = git_manager.repository(true);
+        let mut files = vec![(None, message.to_string())];
+        for change in changes {
+            if let Some((blob, _)) = change.source {
+                let blob = repo.find_blob(blob)?;
+                if !blob.is_binary() {
+                    let path = String::from_utf8_lossy(&self.target_path(&change.raw_path)).into_owned();
+                    files.push((Some(path), String::from_utf8_lossy(blob.content()).into_owned()));
+                }
+            }
+        }
+
+        let findings: Vec<Finding> = files
+            .iter()
+            .flat_map(|(location, content)| redaction.scan_file(location.as_deref(), content))
+            .collect();
+        if !findings.is_empty() {
+            return Err(SyncError::RedactionViolation(sync::blocked_message(last, &findings)));
+        }
+        let secrets: Vec<Finding> = match &self.config.secrets {
+            Some(scanner) => files
+                .iter()
+                .flat_map(|(location, content)| scanner.scan_file(location.as_deref(), content))
+                .collect(),
+            None => Vec::new(),
+        };
+        if self.config.block_secrets && !secrets.is_empty() {
+            return Err(SyncError::SecretsDetected(sync::blocked_message(last, &secrets)));
+        }
+        Ok(secrets)
+    }
+
+    /// `path` of the synced directory as a path of the target repository
+    fn target_path(&self, path: &[u8]) -> Vec<u8> {
+        match self.config.target_dir.as_str() {
+            "" => path.to_vec(),
+            dir => [dir.as_bytes(), b"/", path].concat(),
+        }
+    }
+
+    /// Tree edits that apply `changes` below the target directory
+    fn edits(&self, changes: &[FileChange]) -> Vec<TreeEdit> {
+        changes
+            .iter()
+            .map(|change| {
+                let path = self.target_path(&change.raw_path);
+                match change.source {
+                    Some((blob, mode)) => TreeEdit::Write { path, blob, mode },
+                    None => TreeEdit::Remove(path),
+                }
+            })
+            .collect()
+    }
+
+    /// Message of the target commit: the copied revision and the source commits
+    /// it stands for, with their subjects edited like the patch engine edits
+    /// whole messages
+    fn message(&self, git_manager: &GitManager, commits: &[CommitInfo], last: &CommitInfo) -> Result<String> {
+        let prefix = self.config.subject_prefix.as_deref().unwrap_or("");
+        if let Some(message) = &self.message {
+            return Ok(patch::prefix_subject(message, prefix));
+        }
+        let subdir = match last.subdir.as_str() {
+            "" | "." => "repository root",
+            subdir => subdir,
+        };
+        let mut message = format!("{}Sync {} as of {}\n\n", prefix, subdir, short_id(&last.id));
+        for commit in commits {
+            let subject = if self.config.edits_message(&commit.id) {
+                let edited = self.config.edited_message(&commit.id, &git_manager.commit_message(&commit.id)?);
+                edited.lines().next().unwrap_or_default().to_string()
+            } else {
+                commit.subject.clone()
+            };
+            message.push_str(&format!("- {} {}\n", short_id(&commit.id), subject));
+        }
+        if let Some(template) = &self.config.synced_from {
+            message = patch::add_trailer(&message, "Synced-from", &template.replace("{sha}", &last.id));
+        }
+        Ok(message)
+    }
+}
+
+impl SyncBackend for CopyEngine {
+    fn name(&self) -> &'static str {
+        "copy"
+    }
+
+    fn plan(&self, commits: &[CommitInfo]) -> SyncPlan {
+        SyncPlan {
+            applied: commits.len(),
+            skipped: 0,
+            target_commits: usize::from(!commits.is_empty() && !self.dry_run),
+        }
+    }
+
+    #[instrument(name = "copy", skip_all, fields(commits = commits.len(), dry_run = self.dry_run))]
+    async fn execute(&mut self, git_manager: &GitManager, commits: &[CommitInfo], tx: Sender<SyncEvent>) -> Result<SyncReport> {
+        let started_run = Instant::now();
+        let run_id = journal::new_run_id();
+        let mut report = SyncReport {
+            run_id: run_id.clone(),
+            config: ReportConfig {
+                source_repo: git_manager.source_repo_info.path.clone(),
+                target_repo: git_manager.target_repo_info.path.clone(),
+                subdir: self.config.subdir.clone(),
+                target_dir: self.config.target_dir.clone(),
+                engine: self.name().to_string(),
+                dry_run: self.dry_run,
+                export_dir: None,
+            },
+            total_commits: commits.len(),
+            warnings: self.warnings.clone(),
+            labels: self.labels.clone(),
+            ..Default::default()
+        };
+        let Some(last) = commits.last() else {
+            let _ = tx.send(SyncEvent::Completed(Box::new(report.clone()))).await;
+            return Ok(report);
+        };
+
+        let changes = match self.changes(git_manager, last).and_then(|mut changes| {
+            self.keep_ignored(git_manager, &mut changes)?;
+            Ok(changes)
+        }) {
+            Ok(changes) => changes,
+            Err(e) => {
+                let _ = tx.send(SyncEvent::Error(format!("比较目录失败 {}: {}", last.id, e))).await;
+                return Err(e);
+            }
+        };
+
+        if self.cancelled() {
+            report.aborted = true;
+            report.duration_ms = started_run.elapsed().as_millis() as u64;
+            let _ = tx.send(SyncEvent::Completed(Box::new(report.clone()))).await;
+            return Ok(report);
+        }
+
+        let checked = self.message(git_manager, commits, last).and_then(|message| {
+            let secrets = self.scan(git_manager, last, &changes, &message)?;
+            Ok((message, secrets))
+        });
+        let message = match checked {
+            Ok((message, secrets)) => {
+                report.flag(last, secrets);
+                message
+            }
+            Err(e) => {
+                let _ = tx.send(SyncEvent::Error(format!("同步提交失败 {}: {}", last.id, e))).await;
+                return Err(e);
+            }
+        };
+
+        let target = if self.dry_run || changes.is_empty() {
+            None
+        } else {
+            match git_manager.commit_to_target(&self.edits(&changes), &message) {
+                Ok(target) => target,
+                Err(e) => {
+                    let _ = tx.send(SyncEvent::Error(format!("提交复制的文件失败: {}", e))).await;
+                    return Err(e);
+                }
+            }
+        };
+        for (i, change) in changes.iter().enumerate() {
+            let _ = tx.send(SyncEvent::Progress {
+                current: i + 1,
+                total: changes.len(),
+                subject: change.path.clone(),
+                status: change.status.as_str().to_string(),
+            }).await;
+        }
+
+        let status = match &target {
+            _ if self.dry_run => "PREVIEW",
+            Some(_) => "COPIED",
+            None => "UNCHANGED (SKIPPED)",
+        };
+        if target.is_some() || self.dry_run {
+            report.synced_commits = commits.len();
+        } else {
+            report.skipped_commits = commits.len();
+        }
+        for commit in commits {
+            if !self.dry_run {
+                self.record(JournalEntry::Commit {
+                    run_id: run_id.clone(),
+                    timestamp: journal::timestamp(),
+                    source: commit.id.clone(),
+                    target: target.clone(),
+                    status: status.to_string(),
+                    subject: commit.subject.clone(),
+                });
+            }
+            report.commits.push(CommitResult {
+                id: commit.id.clone(),
+                subject: commit.subject.clone(),
+                status: status.to_string(),
+                target: target.clone(),
+                duration_ms: 0,
+                patch_bytes: 0,
+                patch_stats: None,
+            });
+        }
+        if !self.dry_run {
+            if let Some(journal) = self.journal.as_ref().filter(|_| self.config.notes) {
+                if let Err(e) = notes::write_run(journal, &git_manager.target_repo_info.path, &run_id) {
+                    self.warn(format!("写入 git notes 失败: {}", e));
+                }
+            }
+            if let (Some(journal), Some(tags)) = (self.journal.as_ref(), self.config.tags.as_ref()) {
+                match tags::sync_run(journal, git_manager, &run_id, tags) {
+                    Ok((synced, skipped)) => {
+                        report.tags = synced;
+                        report.skipped_tags = skipped;
+                    }
+                    Err(e) => self.warn(format!("同步标签失败: {}", e)),
+                }
+            }
+            self.record(JournalEntry::Run {
+                run_id,
+                timestamp: journal::timestamp(),
+                source_repo: git_manager.source_repo_info.path.clone(),
+                subdir: self.config.subdir.clone(),
+                synced: report.synced_commits,
+                skipped: report.skipped_commits,
+                pending: self.config.deferred_commits,
+                resume_from: Some(last.id.clone()),
+                format_patch_args: Vec::new(),
+                am_args: Vec::new(),
+            });
+        }
+        if self.config.verify && !self.dry_run {
+            report.divergence = verify(git_manager, last, &self.config);
+        }
+
+        report.duration_ms = started_run.elapsed().as_millis() as u64;
+        sync::flush_warnings(&mut self.issues, &mut report, &tx).await;
+        let _ = tx.send(SyncEvent::Completed(Box::new(report.clone()))).await;
+        Ok(report)
+    }
+}
+
+/// Files that differ between `commit`'s copy of the synced directory and
+/// `target_dir` at the target HEAD, as the changes that make the target match:
+/// `Deleted` files exist only in the target. Paths matching `excludes` are left
+/// alone on both sides.
+pub fn compare(
+    git_manager: &GitManager,
+    commit: &CommitInfo,
+    target_dir: &str,
+    excludes: &[String],
+    ignore_modes: bool,
+) -> Result<Vec<FileChange>> {
+    let excludes = if excludes.is_empty() {
+        None
+    } else {
+        Some(Pathspec::new(excludes)?)
+    };
+
+    let source_tree = git_manager.subdir_tree_id(&commit.id, &commit.subdir)?;
+    let source = {
+        let repo = git_manager.repository(true);
+        let tree = repo.find_tree(source_tree)?;
+        tree_files(&repo, &tree, excludes.as_ref())?
+    };
+    let target = {
+        let repo = git_manager.repository(false);
+        let head = match repo.head() {
+            Ok(head) => Some(head.peel_to_tree()?),
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+            Err(e) => return Err(e.into()),
+        };
+        let tree = if target_dir.is_empty() {
+            head
+        } else {
+            // A target directory that doesn't exist yet is simply empty
+            head.and_then(|head| {
+                let entry = head.get_path(Path::new(target_dir)).ok()?;
+                entry.to_object(&repo).ok()?.into_tree().ok()
+            })
+        };
+        match tree {
+            Some(tree) => tree_files(&repo, &tree, excludes.as_ref())?,
+            None => BTreeMap::new(),
+        }
+    };
+
+    let mut changes = Vec::new();
+    for (raw_path, &(blob, mode)) in &source {
+        let (status, mode) = match target.get(raw_path) {
+            None => (FileStatus::Added, mode),
+            // Without mode changes the file keeps the mode the target gave it
+            Some(&(existing, existing_mode)) if ignore_modes && existing != blob => (FileStatus::Modified, existing_mode),
+            Some(_) if ignore_modes => continue,
+            Some(&existing) if existing != (blob, mode) => (FileStatus::Modified, mode),
+            Some(_) => continue,
+        };
+        changes.push(FileChange {
+            path: String::from_utf8_lossy(raw_path).into_owned(),
+            raw_path: raw_path.clone(),
+            status,
+            source: Some((blob, mode)),
+        });
+    }
+    changes.extend(target.keys().filter(|path| !source.contains_key(*path)).map(|raw_path| FileChange {
+        path: String::from_utf8_lossy(raw_path).into_owned(),
+        raw_path: raw_path.clone(),
+        status: FileStatus::Deleted,
+        source: None,
+    }));
+    Ok(changes)
+}
+
+/// `--verify`: the paths where the target directory differs from `commit`'s copy
+/// of the synced directory. A check that fails is logged and yields no result,
+/// the run itself already succeeded.
+pub fn verify(git_manager: &GitManager, commit: &CommitInfo, config: &SyncConfig) -> Option<Vec<Divergence>> {
+    match compare(git_manager, commit, &config.target_dir, &config.excludes, config.no_mode_changes) {
+        Ok(changes) => {
+            if !changes.is_empty() {
+                tracing::warn!("Target differs from {} in {} files", short_id(&commit.id), changes.len());
+            }
+            Some(changes.into_iter().map(Divergence::from).collect())
+        }
+        Err(e) => {
+            tracing::error!("Failed to verify the target against {}: {}", commit.id, e);
+            None
+        }
+    }
+}
+
+/// Blobs below `tree` with their id and mode, keyed by path relative to it.
+/// Paths are kept as bytes, git doesn't require them to be UTF-8. Submodules
+/// have no content to copy and are left out.
+fn tree_files(repo: &Repository, tree: &Tree, excludes: Option<&Pathspec>) -> Result<BTreeMap<Vec<u8>, (Oid, i32)>> {
+    fn walk(repo: &Repository, tree: &Tree, prefix: &[u8], excludes: Option<&Pathspec>, files: &mut BTreeMap<Vec<u8>, (Oid, i32)>) -> Result<()> {
+        for entry in tree.iter() {
+            let path = [prefix, entry.name_bytes()].concat();
+            match entry.kind() {
+                Some(ObjectType::Tree) => {
+                    let subtree = repo.find_tree(entry.id())?;
+                    walk(repo, &subtree, &[path.as_slice(), b"/"].concat(), excludes, files)?;
+                }
+                Some(ObjectType::Blob) if !excludes.is_some_and(|spec| spec.matches_path(&bytes_path(&path), PathspecFlags::DEFAULT)) => {
+                    files.insert(path, (entry.id(), entry.filemode()));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = BTreeMap::new();
+    walk(repo, tree, b"", excludes, &mut files)?;
+    Ok(files)
+}
+
+/// A git path as a file system path. Unix paths are bytes too; elsewhere bytes
+/// that aren't UTF-8 are replaced.
+#[cfg(unix)]
+fn bytes_path(path: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(path))
+}
+
+#[cfg(not(unix))]
+fn bytes_path(path: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(path).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::MessageRewrite;
+    use crate::redact::{Redaction, SecretScanner};
+    use crate::testutil::{self, TestRepo, FILE};
+
+    #[tokio::test]
+    async fn deletions_keep_ignored_and_untracked_files() {
+        let source = TestRepo::new();
+        let change = source.commit(&[], &[("sub/.gitignore", b"*.env\n", FILE), ("sub/b", b"2\n", FILE)], 2_000, "Change b");
+        let target = TestRepo::new();
+        let files = [(".gitignore", &b"*.env\n"[..], FILE), ("b", b"1\n", FILE), ("keep.env", b"x\n", FILE), ("local/a", b"a\n", FILE)];
+        target.commit(&[], &files, 1_000, "init");
+        std::fs::write(target.path().join("local/.env"), "SECRET=1\n").unwrap();
+        std::fs::write(target.path().join("local/notes"), "todo\n").unwrap();
+
+        let report = testutil::copy(&source, &target, testutil::sync_config("sub", ""), &[change]).await;
+
+        assert_eq!(target.file("b").unwrap().0, b"2\n");
+        assert!(target.file("local/a").is_none());
+        assert!(!target.path().join("local/a").exists());
+        // Tracked but ignored: kept in the commit and reported
+        assert!(target.file("keep.env").is_some());
+        assert_eq!(report.run_warnings.len(), 1);
+        assert!(report.run_warnings[0].message.contains("keep.env"));
+        // Ignored and untracked files in the checkout, and their directory, survive
+        assert_eq!(std::fs::read(target.path().join("local/.env")).unwrap(), b"SECRET=1\n");
+        assert_eq!(std::fs::read(target.path().join("local/notes")).unwrap(), b"todo\n");
+    }
+
+    #[tokio::test]
+    async fn message_lists_edited_and_rewritten_subjects() {
+        let source = TestRepo::new();
+        let first = source.commit(&[], &[("sub/a", b"1\n", FILE)], 1_000, "Fix INTERNAL-12: the parser\n\nDetails");
+        let second = source.commit(&[first], &[("sub/a", b"2\n", FILE)], 2_000, "wip");
+        let target = TestRepo::new();
+        target.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+
+        let config = SyncConfig {
+            messages: [(second.to_string(), "Speed up INTERNAL-7 lookups\n\nBody".to_string())].into(),
+            message_rewrites: vec![MessageRewrite::parse("s/INTERNAL-[0-9]+:? *//g").unwrap()],
+            subject_prefix: Some("[sync] ".to_string()),
+            ..testutil::sync_config("sub", "")
+        };
+        testutil::copy(&source, &target, config, &[first, second]).await;
+
+        let expected = format!(
+            "[sync] Sync sub as of {}\n\n- {} Fix the parser\n- {} Speed up lookups\n",
+            short_id(&second.to_string()),
+            short_id(&first.to_string()),
+            short_id(&second.to_string()),
+        );
+        assert_eq!(target.head_message(), expected);
+    }
+
+    #[tokio::test]
+    async fn redaction_and_secret_scan_check_copied_files() {
+        let token = format!("ghp_{}", "a1B2".repeat(9));
+        let source = TestRepo::new();
+        let content = format!("host = corp.example.com\ntoken = {}\n", token);
+        let change = source.commit(&[], &[("sub/a", content.as_bytes(), FILE), ("sub/b", b"plain\n", FILE)], 1_000, "Add a");
+        let target = TestRepo::new();
+        let init = target.commit(&[], &[("b", b"plain\n", FILE)], 1_000, "init");
+        let remove_report = || {
+            let path = std::env::current_dir().unwrap().join(".sync-subdir/redactions").join(format!("{}.txt", change));
+            std::fs::remove_file(path).unwrap();
+        };
+
+        let redaction = Redaction::new(&["corp\\.example\\.com".to_string()]).unwrap();
+        let config = SyncConfig { redaction, ..testutil::sync_config("sub", "") };
+        let result = testutil::try_copy(&source, &target, config, &[change]).await;
+        assert!(matches!(result, Err(SyncError::RedactionViolation(ref message)) if message.contains("a (line 1)")));
+        assert_eq!(target.repo.head().unwrap().target(), Some(init));
+        remove_report();
+
+        let secrets = Some(SecretScanner::new(Vec::new()));
+        let config = SyncConfig { secrets: secrets.clone(), block_secrets: true, ..testutil::sync_config("sub", "") };
+        let result = testutil::try_copy(&source, &target, config, &[change]).await;
+        assert!(matches!(result, Err(SyncError::SecretsDetected(ref message)) if message.contains("a (line 2)")));
+        assert_eq!(target.repo.head().unwrap().target(), Some(init));
+        remove_report();
+
+        let config = SyncConfig { secrets, ..testutil::sync_config("sub", "") };
+        let report = testutil::copy(&source, &target, config, &[change]).await;
+        assert_eq!(report.flagged_commits[0].id, change.to_string());
+        assert_eq!(target.file("a").unwrap().0, content.as_bytes());
+    }
+
+    /// Commits `files`, each holding "1\n", to a repository; their names can be
+    /// any bytes
+    fn commit_raw(repo: &TestRepo, files: &[&[u8]]) -> Oid {
+        let r = &repo.repo;
+        let empty = r.find_tree(r.treebuilder(None).unwrap().write().unwrap()).unwrap();
+        let mut update = git2::build::TreeUpdateBuilder::new();
+        for path in files {
+            update.upsert(*path, r.blob(b"1\n").unwrap(), git2::FileMode::Blob);
+        }
+        let tree = r.find_tree(update.create_updated(r, &empty).unwrap()).unwrap();
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        let id = r.commit(None, &signature, &signature, "raw names", &tree, &[]).unwrap();
+        repo.checkout(id);
+        id
+    }
+
+    #[tokio::test]
+    async fn names_that_are_not_utf8_are_copied_exactly() {
+        let source = TestRepo::new();
+        let change = commit_raw(&source, &[b"sub/caf\xe9.txt", b"sub/d\xff/x", b"sub/a"]);
+        let target = TestRepo::new();
+        commit_raw(&target, &[b"a", b"old\xfe"]);
+
+        let report = testutil::copy(&source, &target, testutil::sync_config("sub", ""), &[change]).await;
+
+        assert_eq!(report.commits[0].status, "COPIED");
+        let tree = target.repo.head().unwrap().peel_to_tree().unwrap();
+        for path in [&b"a"[..], b"caf\xe9.txt", b"d\xff/x"] {
+            assert!(tree.get_path(&bytes_path(path)).is_ok(), "{}", String::from_utf8_lossy(path));
+            assert!(target.path().join(bytes_path(path)).exists());
+        }
+        assert!(tree.get_path(&bytes_path(b"old\xfe")).is_err());
+        assert!(!target.path().join(bytes_path(b"old\xfe")).exists());
+    }
+}
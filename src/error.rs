@@ -1,4 +1,5 @@
 use thiserror::Error;
+use crate::git::ObjectFormat;
 use std::path::PathBuf;
 
 #[derive(Error, Debug)]
@@ -15,6 +16,9 @@ pub enum SyncError {
     #[error("Invalid commit hash: {0}")]
     InvalidCommit(String),
 
+    #[error("Invalid range: {0}")]
+    InvalidRange(String),
+
     #[error("Path does not exist: {0}")]
     PathNotFound(PathBuf),
 
@@ -33,8 +37,88 @@ pub enum SyncError {
     #[error("Branch not found: {0}")]
     BranchNotFound(String),
 
+    #[error("Branch {branch} has {ahead} commit(s) that {remote} lacks and lacks {behind} of its commit(s); choose one with --target-tip local|remote")]
+    BranchDiverged { branch: String, remote: String, ahead: usize, behind: usize },
+
     #[error("Failed to generate patch: {0}")]
     PatchGenerationFailed(String),
+
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(String),
+
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+
+    #[error("Redaction rule matched: {0}")]
+    RedactionViolation(String),
+
+    #[error("Possible secrets detected: {0}")]
+    SecretsDetected(String),
+
+    #[error("Remote operation failed: {0}")]
+    Remote(String),
+
+    #[error("Authentication failed: {0}")]
+    AuthenticationFailed(String),
+
+    #[error("Network error: {0}")]
+    Network(String),
+
+    #[error("Commit range contains {count} commits, more than --max-commits {max}")]
+    TooManyCommits { count: usize, max: usize },
+
+    #[error("Commit range contains octopus merge {0} (see --octopus)")]
+    OctopusMerge(String),
+
+    #[error("Paths differing only by case would collide on the case-insensitive target filesystem: {0}")]
+    CaseCollision(String),
+
+    #[error("{error}\n补丁已保存到: {}", path.display())]
+    PatchKept { error: Box<SyncError>, path: PathBuf },
+
+    #[error("{error}\n暂存分支 {branch} 已保留以便检查，目标分支未改动")]
+    StagingKept { error: Box<SyncError>, branch: String },
+
+    #[error("Not enough free space in {path}: about {needed_mib} MiB needed, {available_mib} MiB available (see --tmp-dir)")]
+    InsufficientSpace { path: PathBuf, needed_mib: u64, available_mib: u64 },
+
+    #[error("Target repository is locked by another sync (pid {pid}); use --wait, or remove {} if that process is gone", path.display())]
+    Locked { pid: u32, path: PathBuf },
+
+    #[error("Source repository uses {source_format} object ids and target repository {target_format}; syncing between them is not supported")]
+    ObjectFormatMismatch { source_format: ObjectFormat, target_format: ObjectFormat },
+
+    #[error("{} is a {format} repository, which this build of libgit2 cannot open", path.display())]
+    UnsupportedObjectFormat { path: PathBuf, format: ObjectFormat },
+
+    #[error("{0} validation check(s) failed")]
+    ValidationFailed(usize),
+
+    #[error("Transient failure: {0}")]
+    Transient(String),
+
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+impl SyncError {
+    /// Failures that may pass on their own, such as a file briefly locked by a
+    /// virus scanner or a sync client, and are worth retrying
+    pub fn is_transient(&self) -> bool {
+        match self {
+            SyncError::Transient(_) => true,
+            SyncError::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::PermissionDenied
+                    | std::io::ErrorKind::ResourceBusy
+                    | std::io::ErrorKind::WouldBlock
+                    | std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::TimedOut
+            ),
+            SyncError::Git(e) => e.code() == git2::ErrorCode::Locked,
+            _ => false,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, SyncError>;
@@ -0,0 +1,157 @@
+use crate::credentials::Secret;
+use crate::error::{Result, SyncError};
+use crate::git::{parse_remote_url, short_id};
+use crate::report::SyncReport;
+use serde_json::{json, Value};
+
+/// Hosting platforms pull requests can be opened on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForgeKind {
+    GitHub,
+    GitLab,
+}
+
+/// A repository on a code hosting platform, derived from a remote URL
+#[derive(Debug, Clone)]
+pub struct Forge {
+    pub kind: ForgeKind,
+    host: String,
+    /// `owner/repo` on GitHub, the full project path on GitLab
+    project: String,
+}
+
+/// What to open once the synced branch has been pushed
+#[derive(Debug, Clone)]
+pub struct PullRequest {
+    pub head: String,
+    pub base: String,
+    pub title: String,
+    pub body: String,
+}
+
+impl Forge {
+    /// Recognises GitHub and GitLab remotes; self-hosted instances are detected by
+    /// the host name containing `github` or `gitlab`
+    pub fn from_remote_url(url: &str) -> Option<Self> {
+        let (host, project) = parse_remote_url(url)?;
+        let kind = if host.contains("gitlab") {
+            ForgeKind::GitLab
+        } else if host.contains("github") {
+            ForgeKind::GitHub
+        } else {
+            return None;
+        };
+        Some(Self { kind, host, project })
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Environment variables checked, in order, for an API token
+    pub fn token_vars(&self) -> &'static [&'static str] {
+        match self.kind {
+            ForgeKind::GitHub => &["GITHUB_TOKEN", "GH_TOKEN"],
+            ForgeKind::GitLab => &["GITLAB_TOKEN"],
+        }
+    }
+
+    fn api_base(&self) -> String {
+        match (self.kind, self.host.as_str()) {
+            (ForgeKind::GitHub, "github.com") => "https://api.github.com".to_string(),
+            (ForgeKind::GitHub, host) => format!("https://{}/api/v3", host),
+            (ForgeKind::GitLab, host) => format!("https://{}/api/v4", host),
+        }
+    }
+
+    /// Opens the pull (merge) request and returns its web URL
+    pub fn create_pull_request(&self, token: &Secret, pr: &PullRequest) -> Result<String> {
+        let (request, payload, url_field) = match self.kind {
+            ForgeKind::GitHub => (
+                ureq::post(&format!("{}/repos/{}/pulls", self.api_base(), self.project))
+                    .set("Authorization", &format!("Bearer {}", token.expose()))
+                    .set("Accept", "application/vnd.github+json"),
+                json!({ "title": pr.title, "head": pr.head, "base": pr.base, "body": pr.body }),
+                "html_url",
+            ),
+            ForgeKind::GitLab => (
+                ureq::post(&format!(
+                    "{}/projects/{}/merge_requests",
+                    self.api_base(),
+                    self.project.replace('/', "%2F")
+                ))
+                .set("PRIVATE-TOKEN", token.expose()),
+                json!({
+                    "title": pr.title,
+                    "source_branch": pr.head,
+                    "target_branch": pr.base,
+                    "description": pr.body,
+                }),
+                "web_url",
+            ),
+        };
+
+        let response: Value = request
+            .set("User-Agent", "sync-subdir")
+            .send_json(payload)
+            .map_err(|e| match e {
+                ureq::Error::Status(code @ (401 | 403), _) => SyncError::AuthenticationFailed(format!(
+                    "{} rejected the API token (HTTP {})",
+                    self.host, code
+                )),
+                ureq::Error::Status(code, response) => SyncError::Remote(format!(
+                    "{} returned HTTP {}: {}",
+                    self.host,
+                    code,
+                    response.into_string().unwrap_or_default()
+                )),
+                ureq::Error::Transport(transport) => SyncError::Network(format!("{}: {}", self.host, transport)),
+            })?
+            .into_json()?;
+
+        Ok(response[url_field].as_str().unwrap_or_default().to_string())
+    }
+}
+
+/// Expands `{subdir}`, `{branch}` and `{count}` in a pull request title template
+pub fn render_title(template: &str, subdir: &str, branch: &str, count: usize) -> String {
+    template
+        .replace("{subdir}", subdir)
+        .replace("{branch}", branch)
+        .replace("{count}", &count.to_string())
+}
+
+/// Markdown summary of a sync run, used as the pull request description
+pub fn report_body(report: &SyncReport) -> String {
+    let mut body = format!(
+        "由 sync-subdir 同步子目录 `{}`\n\n- 总计: {}\n- 同步: {}\n- 跳过: {}\n\n### 提交\n\n",
+        report.config.subdir, report.total_commits, report.synced_commits, report.skipped_commits
+    );
+    for commit in &report.commits {
+        body.push_str(&format!("- `{}` [{}] {}\n", short_id(&commit.id), commit.status, commit.subject));
+    }
+    if !report.warnings.is_empty() || !report.run_warnings.is_empty() {
+        body.push_str("\n### 警告\n\n");
+        for warning in &report.warnings {
+            body.push_str(&format!("- {}\n", warning));
+        }
+        for warning in &report.run_warnings {
+            body.push_str(&format!("- {}\n", warning));
+        }
+    }
+    if !report.conflicts.is_empty() {
+        body.push_str("\n### 冲突 (未同步)\n\n");
+        for commit in &report.conflicts {
+            body.push_str(&format!("- `{}` {}\n", short_id(&commit.id), commit.subject));
+        }
+    }
+    if !report.flagged_commits.is_empty() {
+        body.push_str("\n### 疑似密钥\n\n");
+        for commit in &report.flagged_commits {
+            for finding in &commit.findings {
+                body.push_str(&format!("- `{}` {}\n", short_id(&commit.id), finding));
+            }
+        }
+    }
+    body
+}
@@ -1,7 +1,13 @@
+use crate::codeowners::{self, CodeOwners};
+use crate::credentials;
 use crate::error::{SyncError, Result};
-use tracing::{debug, error};
-use git2::{Repository, StatusOptions, Commit, DiffDelta, Signature};
+use crate::paths::PathMapping;
+use tracing::{debug, error, instrument};
+use git2::{Repository, StatusOptions, Commit, Signature};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
 
 #[derive(Debug, Clone)]
 pub struct CommitInfo {
@@ -10,6 +16,393 @@ pub struct CommitInfo {
     pub author: String,
     pub date: String,
     pub is_merge: bool,
+    /// Number of parents; more than two means an octopus merge
+    pub parents: usize,
+    /// Only changes whitespace under `subdir`; set by `--ignore-whitespace-only`
+    pub whitespace_only: bool,
+    /// Source path of the synced directory at this commit
+    pub subdir: String,
+    /// Previous path when this commit moved the directory to `subdir`
+    pub renamed_from: Option<String>,
+    /// Lines added and removed under `subdir`; computed on demand by
+    /// [`GitManager::commit_stats`], since diffing every commit up front is slow
+    pub stats: Option<DiffStats>,
+    /// The other commit of a commit and revert pair in the loaded range, see
+    /// [`GitManager::revert_pairs`]
+    pub revert_pair: Option<String>,
+    /// Changes no file under `subdir` that the `--codeowners-filter` owner owns
+    pub unowned: bool,
+}
+
+impl CommitInfo {
+    /// Whitespace-only commits, commits undone within the range and commits
+    /// outside the `--codeowners-filter` owner's files start out deselected
+    pub fn selected_by_default(&self) -> bool {
+        !self.whitespace_only && self.revert_pair.is_none() && !self.unowned
+    }
+}
+
+/// Size of a commit's change under the synced directory, against its first parent
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiffStats {
+    pub files: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+/// A file changed by a commit under the synced directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangedFile {
+    /// Path relative to the synced directory
+    pub path: String,
+    /// Status letter as in `git diff --name-status`, e.g. `M`
+    pub status: char,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+/// Full message and changed files of a commit, shown in the context pane of
+/// the selection screen; computed on demand by [`GitManager::commit_details`]
+#[derive(Debug, Clone, Default)]
+pub struct CommitDetails {
+    pub message: String,
+    pub files: Vec<ChangedFile>,
+}
+
+impl CommitInfo {
+    pub fn is_octopus(&self) -> bool {
+        self.parents > 2
+    }
+}
+
+/// Messages of git failures caused by a file another process holds for a moment
+const TRANSIENT_FAILURES: &[&str] = &[
+    ".lock': File exists",
+    "Permission denied",
+    "Device or resource busy",
+    "Resource temporarily unavailable",
+    "Text file busy",
+];
+
+fn is_transient_failure(stderr: &str) -> bool {
+    TRANSIENT_FAILURES.iter().any(|marker| stderr.contains(marker))
+}
+
+fn abort_am(workdir: &Path) {
+    let _ = std::process::Command::new("git")
+        .arg("-C")
+        .arg(workdir)
+        .args(["am", "--abort"])
+        .output();
+}
+
+/// Diff of the `subdir` tree of `commit` against the one of its first parent
+/// (with nothing for a root commit or where the directory is missing)
+fn subdir_diff<'r>(repo: &'r Repository, commit: &Commit<'_>, subdir: &str) -> Result<git2::Diff<'r>> {
+    let (old, new) = subdir_trees(repo, commit, subdir)?;
+    Ok(repo.diff_tree_to_tree(old.as_ref(), new.as_ref(), None)?)
+}
+
+/// Patch id of the change `commit` makes under `subdir`, or of its inverse
+/// with `inverse`; a revert's inverse has the patch id of the reverted commit
+fn subdir_patch_id(repo: &Repository, commit: &Commit<'_>, subdir: &str, inverse: bool) -> Result<git2::Oid> {
+    let (old, new) = subdir_trees(repo, commit, subdir)?;
+    let diff = if inverse {
+        repo.diff_tree_to_tree(new.as_ref(), old.as_ref(), None)?
+    } else {
+        repo.diff_tree_to_tree(old.as_ref(), new.as_ref(), None)?
+    };
+    Ok(diff.patchid(None)?)
+}
+
+/// The `subdir` trees of `commit`'s first parent and of `commit`
+fn subdir_trees<'r>(
+    repo: &'r Repository,
+    commit: &Commit<'_>,
+    subdir: &str,
+) -> Result<(Option<git2::Tree<'r>>, Option<git2::Tree<'r>>)> {
+    let subdir = subdir.trim_matches('/');
+    let subtree = |tree: git2::Tree<'_>| -> Option<git2::Oid> {
+        if subdir.is_empty() || subdir == "." {
+            return Some(tree.id());
+        }
+        tree.get_path(Path::new(subdir)).ok().map(|entry| entry.id())
+    };
+    let old = match commit.parents().next() {
+        Some(parent) => subtree(parent.tree()?).and_then(|id| repo.find_tree(id).ok()),
+        None => None,
+    };
+    let new = subtree(commit.tree()?).and_then(|id| repo.find_tree(id).ok());
+    Ok((old, new))
+}
+
+/// Letter `git diff --name-status` prints for a change
+fn status_letter(delta: git2::Delta) -> char {
+    match delta {
+        git2::Delta::Added => 'A',
+        git2::Delta::Deleted => 'D',
+        git2::Delta::Modified => 'M',
+        git2::Delta::Renamed => 'R',
+        git2::Delta::Copied => 'C',
+        git2::Delta::Typechange => 'T',
+        git2::Delta::Unmodified => ' ',
+        _ => 'X',
+    }
+}
+
+/// Length of abbreviated commit ids in messages, file names and tables
+const SHORT_ID_LEN: usize = 7;
+
+/// Abbreviated form of a full object id, whatever its hash function
+pub fn short_id(id: &str) -> &str {
+    &id[..id.len().min(SHORT_ID_LEN)]
+}
+
+/// Hash function a repository names its objects with (`extensions.objectFormat`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjectFormat {
+    Sha1,
+    Sha256,
+}
+
+impl ObjectFormat {
+    /// Reads the object format from the config of an opened repository
+    fn of(repo: &Repository) -> Self {
+        let configured = repo.config().and_then(|config| config.get_string("extensions.objectformat"));
+        match configured {
+            Ok(format) if format.eq_ignore_ascii_case("sha256") => Self::Sha256,
+            _ => Self::Sha1,
+        }
+    }
+
+    /// Asks git for the object format of the repository at `path`, for when
+    /// libgit2 could not open it: it refuses SHA-256 repositories. Anything git
+    /// cannot answer for is treated as SHA-1.
+    pub fn detect(path: &Path) -> Self {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .args(["rev-parse", "--show-object-format"])
+            .output();
+        match output {
+            Ok(output) if output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "sha256" => Self::Sha256,
+            _ => Self::Sha1,
+        }
+    }
+
+    /// Id of the empty tree, which git knows without it being stored in the repository
+    pub fn empty_tree_id(self) -> &'static str {
+        match self {
+            Self::Sha1 => "4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+            Self::Sha256 => "6ef19b41225c5369f1c104d45d8d85efa9b057b53b14b4b9b939dd74decc5321",
+        }
+    }
+}
+
+impl std::fmt::Display for ObjectFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Sha1 => "SHA-1",
+            Self::Sha256 => "SHA-256",
+        })
+    }
+}
+
+/// Describes the commit range to list and how to walk it
+#[derive(Debug, Clone)]
+pub struct RangeQuery {
+    pub subdir: String,
+    pub start: String,
+    pub end: String,
+    pub include_start: bool,
+    pub first_parent: bool,
+    /// Follow directory renames of `subdir` back through history
+    pub follow_dir: bool,
+    pub order: CommitOrder,
+}
+
+/// How far a range walk got, see [`GitManager::walk_commits_in_range`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WalkProgress {
+    /// Commits of the range looked at so far
+    pub examined: usize,
+    /// Commits in the range
+    pub total: usize,
+    /// Commits kept so far
+    pub matched: usize,
+}
+
+/// Commits walked between two progress reports
+const WALK_PROGRESS_INTERVAL: usize = 500;
+
+/// Bounds of a range given as the start commit, as `A..B` or `A...B`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RevRange {
+    /// Lower bound, never part of the range itself
+    pub start: String,
+    pub end: String,
+}
+
+/// Order in which commits of a range are listed and applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommitOrder {
+    /// Parents always before their children, whatever the commit timestamps say
+    Topo,
+    /// By commit time; children can come before parents in branchy histories
+    /// with skewed clocks or rebased commits
+    Time,
+}
+
+impl CommitOrder {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "topo" => Ok(Self::Topo),
+            "time" => Ok(Self::Time),
+            other => Err(anyhow::anyhow!("Invalid commit order: {}", other)),
+        }
+    }
+}
+
+/// What to do with octopus merges (more than two parents) in the range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OctopusPolicy {
+    /// Leave them out like any other merge
+    Skip,
+    /// Sync their diff against the first parent as a single commit
+    FirstParent,
+    /// Refuse to sync a range that contains one
+    Stop,
+}
+
+impl OctopusPolicy {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "skip" => Ok(Self::Skip),
+            "first-parent" => Ok(Self::FirstParent),
+            "stop" => Ok(Self::Stop),
+            other => Err(anyhow::anyhow!("Invalid octopus policy: {}", other)),
+        }
+    }
+
+    /// Refuses a range containing an octopus merge under `Stop`
+    pub fn check(self, commits: &[CommitInfo]) -> Result<()> {
+        match commits.iter().find(|commit| commit.is_octopus()) {
+            Some(octopus) if self == Self::Stop => Err(SyncError::OctopusMerge(octopus.id.clone())),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Which tip the target branch keeps when it exists both locally and on a
+/// remote, and the remote has commits the local branch lacks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BranchTip {
+    /// Sync onto the local branch as it is
+    Local,
+    /// Move the local branch to the remote-tracking branch first
+    Remote,
+}
+
+impl BranchTip {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "local" => Ok(Self::Local),
+            "remote" => Ok(Self::Remote),
+            other => Err(anyhow::anyhow!("Invalid branch tip: {}", other)),
+        }
+    }
+}
+
+/// Criteria a commit must satisfy, besides touching the subdir, to be listed
+#[derive(Debug, Clone, Default)]
+pub struct CommitFilter {
+    /// Matched against `Name <email>`; a commit passes if any pattern matches
+    pub authors: Vec<Regex>,
+    /// Matched against the full commit message; a commit passes if any pattern matches
+    pub grep: Vec<Regex>,
+    /// Keep commits whose message matches none of the `grep` patterns instead
+    pub invert_grep: bool,
+}
+
+impl CommitFilter {
+    fn matches(&self, commit: &Commit) -> bool {
+        if !self.authors.is_empty() {
+            let author = commit.author();
+            let ident = format!(
+                "{} <{}>",
+                author.name().unwrap_or(""),
+                author.email().unwrap_or("")
+            );
+            if !self.authors.iter().any(|re| re.is_match(&ident)) {
+                return false;
+            }
+        }
+        if !self.grep.is_empty() {
+            let message = commit.message().unwrap_or("");
+            let matched = self.grep.iter().any(|re| re.is_match(message));
+            if matched == self.invert_grep {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub fn compile_patterns(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|p| Regex::new(p).map_err(|e| SyncError::InvalidPattern(format!("{}: {}", p, e))))
+        .collect()
+}
+
+/// Splits a clone URL such as `git@github.com:org/mono.git` into its host and
+/// repository path (`github.com`, `org/mono`). Local paths yield `None`.
+pub fn parse_remote_url(remote_url: &str) -> Option<(String, String)> {
+    let url = remote_url.trim().trim_end_matches('/').trim_end_matches(".git");
+    let (host, path) = if let Some(rest) = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://")) {
+        rest.split_once('/')?
+    } else if let Some(rest) = url.strip_prefix("ssh://") {
+        let (host, path) = rest.split_once('/')?;
+        (host.rsplit('@').next()?.split(':').next()?, path)
+    } else if let Some((user_host, path)) = url.split_once(':') {
+        // scp-like syntax: user@host:path
+        if !user_host.contains('@') {
+            return None;
+        }
+        (user_host.rsplit('@').next()?, path)
+    } else {
+        return None;
+    };
+    let host = host.rsplit('@').next()?;
+    Some((host.to_string(), path.trim_matches('/').to_string()))
+}
+
+/// Turns a clone URL into a commit link template with a `{sha}` placeholder,
+/// or `None` for local or unknown URLs
+pub fn forge_commit_url(remote_url: &str) -> Option<String> {
+    let (host, path) = parse_remote_url(remote_url)?;
+    let commit_path = if host.contains("gitlab") { "-/commit" } else { "commit" };
+    Some(format!("https://{}/{}/{}/{{sha}}", host, path, commit_path))
+}
+
+/// How a local branch compares to the branch it tracks
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpstreamStatus {
+    /// Short name of the upstream, e.g. `origin/main`
+    pub name: String,
+    /// Commits on the branch that the upstream lacks
+    pub ahead: usize,
+    /// Commits on the upstream that the branch lacks
+    pub behind: usize,
+}
+
+/// A tag of the source repository and the commit it points at
+#[derive(Debug, Clone)]
+pub struct SourceTag {
+    pub name: String,
+    pub commit: String,
+    /// Message of an annotated tag, `None` for a lightweight one
+    pub message: Option<String>,
 }
 
 #[derive(Debug)]
@@ -17,11 +410,23 @@ pub struct RepoInfo {
     pub path: PathBuf,
     pub current_branch: String,
     pub original_branch: String,
+    pub object_format: ObjectFormat,
 }
 
 pub struct GitManager {
     pub source_repo_info: RepoInfo,
     pub target_repo_info: RepoInfo,
+    /// Handles opened once in `new` and reused by every method. The mutexes make
+    /// the manager `Sync`, so the background sync task can keep a `&GitManager`
+    /// across awaits; that task builds its own manager, so the TUI never waits on it.
+    source_repo: Mutex<Repository>,
+    target_repo: Mutex<Repository>,
+}
+
+/// Target tree as a dry run would leave it, kept in an index file outside the repository
+#[derive(Debug)]
+pub struct ScratchIndex {
+    path: PathBuf,
 }
 
 /// RAII guard to ensure stash is popped when dropped
@@ -85,12 +490,275 @@ impl Drop for BranchGuard {
     }
 }
 
+/// Detached worktree of the target repository used to build commits. The target
+/// branch only moves once a commit exists, and the user's working tree is brought
+/// up to date in one step by `finish`. Removed from the repository when dropped.
+pub struct ApplyWorktree {
+    repo_path: PathBuf,
+    pub path: PathBuf,
+    /// Branch advanced as commits are created: the target branch, or the staging
+    /// branch until `promote`
+    branch_ref: String,
+    /// Target branch waiting for the staging branch to be promoted
+    staged_for: Option<String>,
+    original: git2::Oid,
+    head: git2::Oid,
+}
+
+impl ApplyWorktree {
+    /// Advances the target branch to the commit just created in the worktree and
+    /// returns its id. The update only succeeds if nobody else moved the branch in
+    /// the meantime.
+    pub fn commit_applied(&mut self) -> Result<String> {
+        let worktree = Repository::open(&self.path)?;
+        let new_head = worktree.head()?.peel_to_commit()?.id();
+        if new_head != self.head {
+            let repo = Repository::open(&self.repo_path)?;
+            repo.reference_matching(&self.branch_ref, new_head, true, self.head, "sync-subdir: apply patch")?;
+            self.head = new_head;
+        }
+        Ok(new_head.to_string())
+    }
+
+    /// Current tip of the applied commits
+    pub fn head(&self) -> String {
+        self.head.to_string()
+    }
+
+    /// Replaces the commits applied since `base` with a single commit that has
+    /// the tree and author of the newest one and `message`, and moves the target
+    /// branch to it. Returns the new commit id.
+    pub fn squash_since(&mut self, base: &str, message: &str) -> Result<String> {
+        self.fold_since(base, message, false)
+    }
+
+    /// Like [`ApplyWorktree::squash_since`], but the commit keeps the author of
+    /// the oldest commit, the one the others were fixups of
+    pub fn fixup_since(&mut self, base: &str, message: &str) -> Result<String> {
+        self.fold_since(base, message, true)
+    }
+
+    /// Messages of the commits applied since `base`, oldest first
+    pub fn messages_since(&self, base: &str) -> Result<Vec<String>> {
+        let worktree = Repository::open(&self.path)?;
+        let base = git2::Oid::from_str(base)?;
+        let mut messages = Vec::new();
+        let mut commit = worktree.find_commit(self.head)?;
+        while commit.id() != base {
+            messages.push(String::from_utf8_lossy(commit.message_bytes()).into_owned());
+            commit = commit.parent(0)?;
+        }
+        messages.reverse();
+        Ok(messages)
+    }
+
+    fn fold_since(&mut self, base: &str, message: &str, oldest_author: bool) -> Result<String> {
+        let worktree = Repository::open(&self.path)?;
+        let newest = worktree.find_commit(self.head)?;
+        let base = worktree.find_commit(git2::Oid::from_str(base)?)?;
+        let mut author = newest.clone();
+        while oldest_author && author.parent_id(0)? != base.id() {
+            author = author.parent(0)?;
+        }
+        // The worktree already holds the newest tree, only HEAD has to move
+        let squashed = worktree.commit(None, &author.author(), &newest.committer(), message, &newest.tree()?, &[&base])?;
+        worktree.set_head_detached(squashed)?;
+
+        let repo = Repository::open(&self.repo_path)?;
+        repo.reference_matching(&self.branch_ref, squashed, true, self.head, "sync-subdir: squash")?;
+        self.head = squashed;
+        Ok(squashed.to_string())
+    }
+
+    /// Writes `content` to `path` (relative to the repository root) and commits
+    /// that change on top of the applied commits, advancing the target branch.
+    /// Returns the new commit id.
+    pub fn commit_file(&mut self, path: &str, content: &str, message: &str) -> Result<String> {
+        std::fs::write(self.path.join(path), content)?;
+        let worktree = Repository::open(&self.path)?;
+        let mut index = worktree.index()?;
+        index.add_path(Path::new(path))?;
+        index.write()?;
+        let tree = worktree.find_tree(index.write_tree()?)?;
+        let parent = worktree.find_commit(self.head)?;
+        let signature = worktree.signature()
+            .or_else(|_| Signature::now("sync-subdir", "sync-subdir@example.com"))?;
+        let commit = worktree.commit(None, &signature, &signature, message, &tree, &[&parent])?;
+        worktree.set_head_detached(commit)?;
+
+        let repo = Repository::open(&self.repo_path)?;
+        repo.reference_matching(&self.branch_ref, commit, true, self.head, "sync-subdir: bump version")?;
+        self.head = commit;
+        Ok(commit.to_string())
+    }
+
+    /// Staging branch the commits are applied to until they are promoted
+    pub fn staging_branch(&self) -> Option<&str> {
+        self.staged_for.as_ref()?;
+        self.branch_ref.strip_prefix("refs/heads/")
+    }
+
+    /// Fast-forwards the target branch to the commits applied on the staging
+    /// branch and deletes the staging branch. Fails if the target branch moved
+    /// since the worktree was created.
+    pub fn promote(&mut self) -> Result<()> {
+        let Some(target_ref) = self.staged_for.clone() else { return Ok(()) };
+        let repo = Repository::open(&self.repo_path)?;
+        if self.head != self.original {
+            repo.reference_matching(&target_ref, self.head, true, self.original, "sync-subdir: fast-forward from staging branch")?;
+        }
+        repo.find_reference(&self.branch_ref)?.delete()?;
+        self.branch_ref = target_ref;
+        self.staged_for = None;
+        Ok(())
+    }
+
+    /// Drops the `git am` session a conflicting patch left behind, returning the
+    /// worktree to the last applied commit
+    pub fn abort_am(&self) -> Result<()> {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&self.path)
+            .args(["am", "--abort"])
+            .output()?;
+        if !output.status.success() {
+            return Err(SyncError::Anyhow(anyhow::anyhow!(
+                "Failed to abort git am: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Brings the user's index and working tree from the original commit to the
+    /// new branch tip, keeping any unrelated local changes. Does nothing while the
+    /// commits are only on the staging branch.
+    pub fn finish(&self) -> Result<()> {
+        if self.head == self.original || self.staged_for.is_some() {
+            return Ok(());
+        }
+        update_checkout(&self.repo_path, &self.original.to_string(), &self.head.to_string())
+    }
+}
+
+/// Brings the index and working tree of the repository at `repo_path` from
+/// `from` to `to` (commits or trees) in one two-way merge. Unrelated local
+/// changes stay, and untracked or ignored files are never removed.
+fn update_checkout(repo_path: &Path, from: &str, to: &str) -> Result<()> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(["read-tree", "-m", "-u", from, to])
+        .output()?;
+    if !output.status.success() {
+        return Err(SyncError::Anyhow(anyhow::anyhow!(
+            "Failed to update target working tree: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(())
+}
+
+/// Copies `id` and, for trees, everything below it from the source object
+/// database into `target`, skipping objects copied before or already present
+fn copy_objects(source: &Repository, target: &git2::Odb, id: git2::Oid, copied: &mut HashSet<git2::Oid>) -> Result<()> {
+    if !copied.insert(id) || target.exists(id) {
+        return Ok(());
+    }
+    let odb = source.odb()?;
+    let object = odb.read(id)?;
+    target.write(object.kind(), object.data())?;
+    if object.kind() == git2::ObjectType::Tree {
+        for entry in source.find_tree(id)?.iter() {
+            // Submodule entries point at commits of another repository
+            if entry.kind() != Some(git2::ObjectType::Commit) {
+                copy_objects(source, target, entry.id(), copied)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Wraps `tree` in one tree per component of `dir`, so it ends up at `dir`
+fn nest_tree(repo: &Repository, tree: git2::Oid, dir: &str) -> Result<git2::Oid> {
+    let mut id = tree;
+    for component in dir.rsplit('/').filter(|c| !c.is_empty()) {
+        let mut builder = repo.treebuilder(None)?;
+        builder.insert(component, id, 0o040000)?;
+        id = builder.write()?;
+    }
+    Ok(id)
+}
+
+/// A source commit rewritten by [`GitManager::extract_history`]
+#[derive(Debug, Clone)]
+pub struct ExtractedCommit {
+    pub source: String,
+    pub target: String,
+    pub subject: String,
+}
+
+/// Outcome of [`GitManager::extract_history`]
+#[derive(Debug, Clone, Default)]
+pub struct ExtractSummary {
+    /// Rewritten commits, oldest first
+    pub commits: Vec<ExtractedCommit>,
+    /// Source commits looked at, including the dropped ones
+    pub walked: usize,
+    /// Source commit the target branch now corresponds to
+    pub end: String,
+}
+
+/// Change to one path of the target tree, see [`GitManager::commit_to_target`]
+#[derive(Debug, Clone)]
+pub enum TreeEdit {
+    /// Writes the source blob `blob` at `path` with the git file `mode`. Paths
+    /// are bytes, like git's own, so names that aren't UTF-8 survive.
+    Write { path: Vec<u8>, blob: git2::Oid, mode: i32 },
+    Remove(Vec<u8>),
+}
+
+impl Drop for ApplyWorktree {
+    fn drop(&mut self) {
+        debug!("ApplyWorktree: Removing {}", self.path.display());
+        let result = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&self.repo_path)
+            .args(["worktree", "remove", "--force"])
+            .arg(&self.path)
+            .output();
+        if let Err(e) = result {
+            error!("Failed to remove apply worktree in drop: {}", e);
+        }
+    }
+}
+
+fn commit_info(commit: &Commit, subdir: String, renamed_from: Option<String>) -> CommitInfo {
+    CommitInfo {
+        id: commit.id().to_string(),
+        subject: commit.summary().unwrap_or("No subject").to_string(),
+        author: commit.author().name().unwrap_or("Unknown").to_string(),
+        date: chrono::DateTime::<chrono::Utc>::from_timestamp(commit.time().seconds(), 0)
+            .unwrap_or_default()
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        is_merge: commit.parents().len() > 1,
+        parents: commit.parents().len(),
+        whitespace_only: false,
+        subdir,
+        renamed_from,
+        stats: None,
+        revert_pair: None,
+        unowned: false,
+    }
+}
+
 impl GitManager {
     pub fn new(source_path: &Path, target_path: &Path) -> Result<Self> {
-        let source_repo = Repository::open(source_path)
-            .map_err(|_| SyncError::NotARepository(source_path.to_path_buf()))?;
-        let target_repo = Repository::open(target_path)
-            .map_err(|_| SyncError::NotARepository(target_path.to_path_buf()))?;
+        let source_repo = Self::open(source_path)?;
+        let target_repo = Self::open(target_path)?;
+        let source_format = ObjectFormat::of(&source_repo);
+        let target_format = ObjectFormat::of(&target_repo);
 
         let source_current_branch = Self::get_current_branch(&source_repo)?;
         let target_current_branch = Self::get_current_branch(&target_repo)?;
@@ -100,16 +768,57 @@ impl GitManager {
                 path: source_path.to_path_buf(),
                 current_branch: source_current_branch.clone(),
                 original_branch: source_current_branch,
+                object_format: source_format,
             },
             target_repo_info: RepoInfo {
                 path: target_path.to_path_buf(),
                 current_branch: target_current_branch.clone(),
                 original_branch: target_current_branch,
+                object_format: target_format,
             },
+            source_repo: Mutex::new(source_repo),
+            target_repo: Mutex::new(target_repo),
+        })
+    }
+
+    /// Opens the repository at `path`; git is only asked for its object format
+    /// when libgit2 cannot open it
+    pub fn open(path: &Path) -> Result<Repository> {
+        Repository::open(path).map_err(|_| match ObjectFormat::detect(path) {
+            ObjectFormat::Sha1 => SyncError::NotARepository(path.to_path_buf()),
+            format => SyncError::UnsupportedObjectFormat { path: path.to_path_buf(), format },
         })
     }
 
-    pub fn get_repository(&self, is_source: bool) -> Result<Repository> {
+    /// Refuses to link source and target ids when the repositories hash objects
+    /// differently. The journal ledger and Synced-from trailers record source ids
+    /// in the target, so runs that write or read them check this first.
+    pub fn check_object_formats(&self) -> Result<()> {
+        let source_format = self.source_repo_info.object_format;
+        let target_format = self.target_repo_info.object_format;
+        if source_format != target_format {
+            return Err(SyncError::ObjectFormatMismatch { source_format, target_format });
+        }
+        Ok(())
+    }
+
+    /// Opens only a target repository, for operations that need no source such as
+    /// applying an exported bundle. Source-side methods then act on the target.
+    pub fn open_target(target_path: &Path) -> Result<Self> {
+        Self::new(target_path, target_path)
+    }
+
+    /// The shared handle of the source or target repository. Don't call another
+    /// method that locks the same repository while holding it.
+    pub fn repository(&self, is_source: bool) -> MutexGuard<'_, Repository> {
+        let repo = if is_source { &self.source_repo } else { &self.target_repo };
+        // A panic while holding the lock leaves the handle itself usable
+        repo.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Opens a separate handle, for owners that outlive the manager (guards) or
+    /// that modify the handle itself
+    pub fn open_repository(&self, is_source: bool) -> Result<Repository> {
         let path = if is_source {
             &self.source_repo_info.path
         } else {
@@ -119,7 +828,16 @@ impl GitManager {
     }
 
     fn get_current_branch(repo: &Repository) -> Result<String> {
-        let head = repo.head()?;
+        let head = match repo.head() {
+            Ok(head) => head,
+            // A repository without commits yet: HEAD names the branch the first one goes to
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+                let head = repo.find_reference("HEAD")?;
+                let target = head.symbolic_target().unwrap_or("HEAD");
+                return Ok(target.strip_prefix("refs/heads/").unwrap_or(target).to_string());
+            }
+            Err(e) => return Err(e.into()),
+        };
 
         if let Some(name) = head.shorthand() {
             Ok(name.to_string())
@@ -131,46 +849,128 @@ impl GitManager {
     }
 
     pub fn switch_branch(&mut self, is_source: bool, branch_name: &str) -> Result<()> {
-        let repo = self.get_repository(is_source)?;
-        let branch_ref = format!("refs/heads/{}", branch_name);
+        {
+            let repo = self.repository(is_source);
+            let branch_ref = format!("refs/heads/{}", branch_name);
 
-        // Check if branch exists
-        let _branch_oid = repo.revparse_single(&branch_ref)
-            .map_err(|_| SyncError::BranchNotFound(branch_name.to_string()))?
-            .id();
-
-        // Checkout the branch
-        repo.set_head(&branch_ref)?;
+            // Check if branch exists
+            let _branch_oid = repo.revparse_single(&branch_ref)
+                .map_err(|_| SyncError::BranchNotFound(branch_name.to_string()))?
+                .id();
 
-        // Update current branch info
-        if is_source {
-            self.source_repo_info.current_branch = branch_name.to_string();
-        } else {
-            self.target_repo_info.current_branch = branch_name.to_string();
+            // Checkout the branch
+            repo.set_head(&branch_ref)?;
         }
 
+        self.set_current_branch(is_source, branch_name);
         Ok(())
     }
 
-    pub fn create_branch(&mut self, is_target: bool, branch_name: &str) -> Result<()> {
-        let repo = self.get_repository(is_target)?;
-        let head = repo.head()?;
-        let head_commit = head.peel_to_commit()?;
+    fn set_current_branch(&mut self, is_source: bool, branch_name: &str) {
+        let info = if is_source { &mut self.source_repo_info } else { &mut self.target_repo_info };
+        info.current_branch = branch_name.to_string();
+    }
+
+    /// Creates `branch_name` and checks it out. It starts at `base`, else at the
+    /// remote-tracking branch of the same name (see [`GitManager::remote_branch`]),
+    /// which it then tracks, else at the current HEAD. Returns the name of the
+    /// start point and its commit id.
+    pub fn create_branch(&mut self, is_source: bool, branch_name: &str, base: Option<&str>) -> Result<(String, String)> {
+        let remote = match base {
+            Some(_) => None,
+            None => self.remote_branch(is_source, branch_name)?,
+        };
+        let base_id = {
+            let repo = self.repository(is_source);
+            let base_commit = match (base, &remote) {
+                (Some(base), _) => repo.revparse_single(base)
+                    .and_then(|obj| obj.peel_to_commit())
+                    .map_err(|_| SyncError::InvalidCommit(base.to_string()))?,
+                (None, Some((_, id))) => repo.find_commit(git2::Oid::from_str(id)?)?,
+                (None, None) => repo.head()?.peel_to_commit()?,
+            };
 
-        let _branch = repo.branch(branch_name, &head_commit, false)?;
+            let mut branch = repo.branch(branch_name, &base_commit, false)?;
+            if let Some((name, _)) = &remote {
+                branch.set_upstream(Some(name))?;
+            }
+
+            // Checkout the new branch
+            repo.checkout_tree(base_commit.as_object(), Some(git2::build::CheckoutBuilder::new().safe()))?;
+            repo.set_head(&format!("refs/heads/{}", branch_name))?;
+            base_commit.id()
+        };
+
+        self.set_current_branch(is_source, branch_name);
+        let base_name = match (base, remote) {
+            (Some(base), _) => base.to_string(),
+            (None, Some((name, _))) => name,
+            (None, None) => "HEAD".to_string(),
+        };
+        Ok((base_name, base_id.to_string()))
+    }
+
+    /// Remote-tracking branch named like the local `branch_name`, as last fetched:
+    /// the one on `origin`, else the first remote's in name order. Returns its
+    /// short name (e.g. `origin/main`) and commit id.
+    pub fn remote_branch(&self, is_source: bool, branch_name: &str) -> Result<Option<(String, String)>> {
+        let repo = self.repository(is_source);
+        let mut found = Vec::new();
+        for branch in repo.branches(Some(git2::BranchType::Remote))? {
+            let (branch, _) = branch?;
+            let Some(name) = branch.name()?.map(str::to_string) else { continue };
+            // Symbolic refs such as origin/HEAD have no target of their own
+            let Some(id) = branch.get().target() else { continue };
+            // Remote names may contain slashes; the fetch refspecs tell where the
+            // remote's part ends. Refs no configured remote fetches are skipped.
+            let Some(refname) = branch.get().name() else { continue };
+            let Ok(remote) = repo.branch_remote_name(refname) else { continue };
+            let Some(remote) = remote.as_str() else { continue };
+            if name.strip_prefix(remote).and_then(|rest| rest.strip_prefix('/')) == Some(branch_name) {
+                found.push((remote != "origin", name, id.to_string()));
+            }
+        }
+        found.sort();
+        Ok(found.into_iter().next().map(|(_, name, id)| (name, id)))
+    }
 
-        // Checkout the new branch
-        repo.set_head(&format!("refs/heads/{}", branch_name))?;
+    /// Compares local `branch_name` with the remote-tracking branch of the same
+    /// name, whether or not it is configured as its upstream. `None` when no
+    /// remote has the branch.
+    pub fn remote_branch_status(&self, is_source: bool, branch_name: &str) -> Result<Option<UpstreamStatus>> {
+        let Some((name, remote_id)) = self.remote_branch(is_source, branch_name)? else {
+            return Ok(None);
+        };
+        let repo = self.repository(is_source);
+        let local = repo.find_branch(branch_name, git2::BranchType::Local)
+            .map_err(|_| SyncError::BranchNotFound(branch_name.to_string()))?;
+        let Some(local_id) = local.get().target() else {
+            return Ok(None);
+        };
+        let (ahead, behind) = repo.graph_ahead_behind(local_id, git2::Oid::from_str(&remote_id)?)?;
+        Ok(Some(UpstreamStatus { name, ahead, behind }))
+    }
 
-        if is_target {
-            self.target_repo_info.current_branch = branch_name.to_string();
+    /// Moves `branch_name` to `commit` and checks it out. The checkout is safe,
+    /// so it fails rather than overwrite local changes.
+    pub fn reset_branch(&mut self, is_source: bool, branch_name: &str, commit: &str) -> Result<()> {
+        {
+            let repo = self.repository(is_source);
+            let commit = repo.revparse_single(commit)
+                .and_then(|obj| obj.peel_to_commit())
+                .map_err(|_| SyncError::InvalidCommit(commit.to_string()))?;
+            let branch_ref = format!("refs/heads/{}", branch_name);
+            repo.checkout_tree(commit.as_object(), Some(git2::build::CheckoutBuilder::new().safe()))?;
+            repo.reference(&branch_ref, commit.id(), true, &format!("sync-subdir: reset to {}", commit.id()))?;
+            repo.set_head(&branch_ref)?;
         }
 
+        self.set_current_branch(is_source, branch_name);
         Ok(())
     }
 
-    pub fn has_uncommitted_changes(&self, is_target: bool) -> Result<bool> {
-        let repo = self.get_repository(is_target)?;
+    pub fn has_uncommitted_changes(&self, is_source: bool) -> Result<bool> {
+        let repo = self.repository(is_source);
         let mut status_options = StatusOptions::new();
         status_options.include_untracked(true);
 
@@ -179,8 +979,8 @@ impl GitManager {
         Ok(!statuses.is_empty())
     }
 
-    pub fn stash_changes(&self, is_target: bool, message: &str) -> Result<()> {
-        let mut repo = self.get_repository(is_target)?;
+    pub fn stash_changes(&self, is_source: bool, message: &str) -> Result<()> {
+        let mut repo = self.repository(is_source);
 
         // Get current signature
         let signature = repo.signature()
@@ -190,7 +990,7 @@ impl GitManager {
         match repo.stash_save(&signature, message, None) {
             Ok(_) => Ok(()),
             Err(e) if e.code() == git2::ErrorCode::NotFound => {
-                debug!("Nothing to stash in {} repo", if is_target { "target" } else { "source" });
+                debug!("Nothing to stash in {} repo", if is_source { "source" } else { "target" });
                 Ok(())
             }
             Err(e) => Err(SyncError::Git(e)),
@@ -199,109 +999,928 @@ impl GitManager {
 
 
     pub fn validate_commit(&self, is_source: bool, commit_hash: &str) -> Result<()> {
-        let repo = self.get_repository(is_source)?;
-        repo.revparse_single(commit_hash)
-            .map_err(|_| SyncError::InvalidCommit(commit_hash.to_string()))?;
+        self.resolve_commit(is_source, commit_hash)?;
         Ok(())
     }
 
-    pub fn get_commits_in_range(
-        &self,
-        subdir: &str,
-        start_commit: &str,
-        end_commit: &str,
-        include_start: bool,
-        first_parent: bool,
-    ) -> Result<Vec<CommitInfo>> {
-        debug!("get_commits_in_range: subdir={}, start={}, end={}, include_start={}, first_parent={}", 
-               subdir, start_commit, end_commit, include_start, first_parent);
-        let repo = self.get_repository(true)?;
-
-        let start_obj = repo.revparse_single(start_commit)
-            .map_err(|_| SyncError::InvalidCommit(start_commit.to_string()))?;
-        let end_obj = repo.revparse_single(end_commit)
-            .map_err(|_| SyncError::InvalidCommit(end_commit.to_string()))?;
+    /// Resolves any revspec (branch, remote-tracking ref, annotated tag chain, sha,
+    /// `HEAD~N`, reflog or `@{upstream}` syntax) to the full id of the commit it
+    /// ultimately points at. Ranges are rejected; see [`Self::split_range`].
+    pub fn resolve_commit(&self, is_source: bool, spec: &str) -> Result<String> {
+        if spec.contains("..") {
+            return Err(SyncError::InvalidRange(format!(
+                "{}: a range is only accepted as the start commit",
+                spec
+            )));
+        }
+        let repo = self.repository(is_source);
+        let commit = repo.revparse_single(spec)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|e| SyncError::InvalidCommit(format!("{} ({})", spec, e.message())))?;
+        Ok(commit.id().to_string())
+    }
 
-        let start_oid = start_obj.id();
-        let end_oid = end_obj.id();
+    /// Splits a start commit written as a range of the source repository. `A..B`
+    /// selects the commits reachable from B but not from A; `A...B` those on B
+    /// since it diverged from A, so its lower bound is the merge base. A missing
+    /// side means HEAD, as in git. Returns `None` for a single commit.
+    pub fn split_range(&self, spec: &str, end: Option<&str>) -> Result<Option<RevRange>> {
+        let (from, to, symmetric) = match spec.split_once("...") {
+            Some((from, to)) => (from, to, true),
+            None => match spec.split_once("..") {
+                Some((from, to)) => (from, to, false),
+                None => return Ok(None),
+            },
+        };
+        if end.is_some() {
+            return Err(SyncError::InvalidRange(format!("{}: --end cannot be combined with a range", spec)));
+        }
+        let or_head = |side: &str| if side.is_empty() { "HEAD".to_string() } else { side.to_string() };
+        let (from, to) = (or_head(from), or_head(to));
+        if to.contains("..") {
+            return Err(SyncError::InvalidRange(format!("{}: expected A..B or A...B", spec)));
+        }
 
-        let start_commit_obj = start_obj.peel_to_commit()?;
-        
-        // Determine the commit range starting point
-        let range_start = if include_start {
-            if let Ok(parent) = start_commit_obj.parent(0) {
-                parent.id()
-            } else {
-                start_oid // Root commit
+        let start = if symmetric {
+            let from_id = git2::Oid::from_str(&self.resolve_commit(true, &from)?)?;
+            let to_id = git2::Oid::from_str(&self.resolve_commit(true, &to)?)?;
+            match self.repository(true).merge_base(from_id, to_id) {
+                Ok(base) => base.to_string(),
+                Err(e) if e.code() == git2::ErrorCode::NotFound => {
+                    return Err(SyncError::InvalidRange(format!("{}: {} and {} have no common ancestor", spec, from, to)));
+                }
+                Err(e) => return Err(e.into()),
             }
         } else {
-            start_oid
+            self.resolve_commit(true, &from)?;
+            from
         };
+        Ok(Some(RevRange { start, end: to }))
+    }
 
-        let mut revwalk = repo.revwalk()?;
-        revwalk.push_range(&format!("{}..{}", range_start, end_oid))?;
-        if first_parent {
-            revwalk.simplify_first_parent()?;
-        }
-        revwalk.set_sorting(git2::Sort::REVERSE | git2::Sort::TIME)?;
+    /// Details of the source commit `spec` resolves to, with `subdir` as its synced directory
+    pub fn commit_info(&self, spec: &str, subdir: &str) -> Result<CommitInfo> {
+        let repo = self.repository(true);
+        let commit = repo
+            .revparse_single(spec)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(spec.to_string()))?;
+        Ok(commit_info(&commit, subdir.trim_matches('/').to_string(), None))
+    }
 
-        let mut commit_infos = Vec::new();
+    pub fn commit_message(&self, commit_id: &str) -> Result<String> {
+        let repo = self.repository(true);
+        let commit = repo.revparse_single(commit_id)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(commit_id.to_string()))?;
+        let message = commit.message().unwrap_or("").to_string();
+        Ok(message)
+    }
 
-        for id in revwalk {
-            let id = id?;
-            let commit = repo.find_commit(id)?;
-            
-            // Check if commit affects the subdirectory
-            let affects = if subdir.is_empty() || subdir == "." {
-                true
-            } else {
-                self.commit_affects_subdir(&commit, subdir)?
-            };
+    /// URL of the named remote, if it exists
+    pub fn remote_url(&self, is_source: bool, remote: &str) -> Result<Option<String>> {
+        let repo = self.repository(is_source);
+        let url = match repo.find_remote(remote) {
+            Ok(remote) => remote.url().map(str::to_string),
+            Err(_) => None,
+        };
+        Ok(url)
+    }
 
-            if affects {
-                commit_infos.push(CommitInfo {
-                    id: id.to_string(),
-                    subject: commit.summary().unwrap_or("No subject").to_string(),
-                    author: commit.author().name().unwrap_or("Unknown").to_string(),
-                    date: chrono::DateTime::<chrono::Utc>::from_timestamp(commit.time().seconds(), 0)
-                        .unwrap_or_default()
-                        .format("%Y-%m-%d %H:%M:%S")
-                        .to_string(),
-                    is_merge: commit.parents().len() > 1,
-                });
-            }
+    /// Pushes a local branch of the target repository with the git CLI, so the
+    /// user's credential helpers, ssh-agent and transport settings apply
+    #[instrument(skip(self))]
+    pub fn push_branch(&self, remote: &str, branch: &str) -> Result<()> {
+        let mut cmd = std::process::Command::new("git");
+        cmd.arg("-C")
+            .arg(&self.target_repo_info.path)
+            .arg("push")
+            .arg(remote)
+            .arg(format!("refs/heads/{0}:refs/heads/{0}", branch))
+            .stdin(std::process::Stdio::null());
+        let output = credentials::non_interactive(&mut cmd, &self.target_repo_info.path).output()?;
+        if !output.status.success() {
+            return Err(credentials::classify_git_failure(
+                &format!("git push {} {}", remote, branch),
+                &String::from_utf8_lossy(&output.stderr),
+            ));
         }
+        Ok(())
+    }
 
-        Ok(commit_infos)
+    /// Compares a local branch with its configured upstream, as last fetched.
+    /// `None` when the branch tracks nothing.
+    pub fn upstream_status(&self, is_source: bool, branch_name: &str) -> Result<Option<UpstreamStatus>> {
+        let repo = self.repository(is_source);
+        let branch = match repo.find_branch(branch_name, git2::BranchType::Local) {
+            Ok(branch) => branch,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let upstream = match branch.upstream() {
+            Ok(upstream) => upstream,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let (Some(local_id), Some(upstream_id)) = (branch.get().target(), upstream.get().target()) else {
+            return Ok(None);
+        };
+        let (ahead, behind) = repo.graph_ahead_behind(local_id, upstream_id)?;
+        Ok(Some(UpstreamStatus {
+            name: upstream.name()?.unwrap_or_default().to_string(),
+            ahead,
+            behind,
+        }))
     }
 
-    pub fn create_patch_file(&self, commit_id: &str, subdir: &str, output_dir: &Path) -> Result<PathBuf> {
-        let repo_path = &self.source_repo_info.path;
-        let output = std::process::Command::new("git")
-            .arg("-C")
-            .arg(repo_path)
-            .arg("format-patch")
-            .arg("-1")
-            .arg(commit_id)
-            .arg("--binary")
+    /// Commits on the target branch after the newest commit the sync produced
+    /// (one of `synced`) that are not from the sync themselves and change
+    /// `target_dir`, newest first. These are edits made directly to the mirror.
+    /// Empty when no synced commit is reachable from the branch.
+    pub fn unsynced_target_commits(&self, branch_name: &str, synced: &HashSet<String>, target_dir: &str) -> Result<Vec<CommitInfo>> {
+        let repo = self.repository(false);
+        let Some(head) = repo.find_branch(branch_name, git2::BranchType::Local)?.get().target() else {
+            return Ok(Vec::new());
+        };
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(head)?;
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL)?;
+        let mut mapped_point = None;
+        for oid in revwalk {
+            let oid = oid?;
+            if synced.contains(&oid.to_string()) {
+                mapped_point = Some(oid);
+                break;
+            }
+        }
+        let Some(mapped_point) = mapped_point else { return Ok(Vec::new()) };
+
+        let target_dir = target_dir.trim_matches('/');
+        let dir_id = |tree: git2::Tree<'_>| -> Option<git2::Oid> {
+            if target_dir.is_empty() || target_dir == "." {
+                return Some(tree.id());
+            }
+            tree.get_path(Path::new(target_dir)).ok().map(|entry| entry.id())
+        };
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(head)?;
+        revwalk.hide(mapped_point)?;
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL)?;
+        let mut commits = Vec::new();
+        for oid in revwalk {
+            let oid = oid?;
+            if synced.contains(&oid.to_string()) {
+                continue;
+            }
+            let commit = repo.find_commit(oid)?;
+            let before = match commit.parents().next() {
+                Some(parent) => dir_id(parent.tree()?),
+                None => None,
+            };
+            if dir_id(commit.tree()?) != before {
+                commits.push(commit_info(&commit, target_dir.to_string(), None));
+            }
+        }
+        Ok(commits)
+    }
+
+    pub fn branch_exists(&self, is_source: bool, branch_name: &str) -> Result<bool> {
+        let repo = self.repository(is_source);
+        let exists = repo.find_branch(branch_name, git2::BranchType::Local).is_ok();
+        Ok(exists)
+    }
+
+    /// Returns true when `ancestor` is reachable from `descendant` (or equal to it)
+    pub fn is_ancestor(&self, is_source: bool, ancestor: &str, descendant: &str) -> Result<bool> {
+        let ancestor = git2::Oid::from_str(&self.resolve_commit(is_source, ancestor)?)?;
+        let descendant = git2::Oid::from_str(&self.resolve_commit(is_source, descendant)?)?;
+        Ok(ancestor == descendant || self.repository(is_source).graph_descendant_of(descendant, ancestor)?)
+    }
+
+    /// Returns true when a `git am` or rebase session is pending in the target repository
+    pub fn am_in_progress(&self) -> Result<bool> {
+        let repo = self.repository(false);
+        Ok(repo.path().join("rebase-apply").exists() || repo.path().join("rebase-merge").exists())
+    }
+
+    /// Number of commits in the query's range before subdirectory and filter
+    /// checks; cheap upper bound used to catch ranges that select far too much
+    #[instrument(skip_all, fields(start = %query.start, end = %query.end))]
+    pub fn count_commits_in_range(&self, query: &RangeQuery) -> Result<usize> {
+        let repo = self.repository(true);
+        Ok(Self::range_ids(&repo, query)?.len())
+    }
+
+    /// Ids in the query's range, oldest first
+    fn range_ids(repo: &Repository, query: &RangeQuery) -> Result<Vec<git2::Oid>> {
+        let start_commit_obj = repo.revparse_single(&query.start)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(query.start.clone()))?;
+        let end_oid = repo.revparse_single(&query.end)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(query.end.clone()))?
+            .id();
+
+        let start_oid = start_commit_obj.id();
+
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(end_oid)?;
+        if !query.include_start {
+            revwalk.hide(start_oid)?;
+        } else if let Ok(parent) = start_commit_obj.parent(0) {
+            revwalk.hide(parent.id())?;
+        }
+        // Including a root commit hides nothing: the walk runs down to the initial
+        // commit, whose whole tree becomes additions
+        if query.first_parent {
+            revwalk.simplify_first_parent()?;
+        }
+        // Rename tracking needs parents strictly before children, whatever the order
+        if query.order == CommitOrder::Topo || query.follow_dir {
+            revwalk.set_sorting(git2::Sort::REVERSE | git2::Sort::TOPOLOGICAL)?;
+        } else {
+            revwalk.set_sorting(git2::Sort::REVERSE | git2::Sort::TIME)?;
+        }
+        Ok(revwalk.collect::<std::result::Result<Vec<_>, _>>()?)
+    }
+
+    #[instrument(skip_all, fields(subdir = %query.subdir, start = %query.start, end = %query.end))]
+    pub fn get_commits_in_range(&self, query: &RangeQuery, filter: &CommitFilter) -> Result<Vec<CommitInfo>> {
+        self.walk_commits_in_range(query, filter, |_| true)
+    }
+
+    /// [`Self::get_commits_in_range`], reporting to `on_progress` every few
+    /// hundred commits and once at the end. When it returns false the walk
+    /// stops with [`SyncError::Cancelled`].
+    pub fn walk_commits_in_range(
+        &self,
+        query: &RangeQuery,
+        filter: &CommitFilter,
+        mut on_progress: impl FnMut(WalkProgress) -> bool,
+    ) -> Result<Vec<CommitInfo>> {
+        debug!("get_commits_in_range: {:?}", query);
+        let repo = self.repository(true);
+
+        let ids = Self::range_ids(&repo, query)?;
+        let total = ids.len();
+
+        // Walk newest to oldest so directory renames can move the prefix back in time
+        let mut subdir = query.subdir.trim_end_matches('/').to_string();
+        let mut commit_infos = Vec::new();
+
+        for (examined, id) in ids.into_iter().rev().enumerate() {
+            if examined % WALK_PROGRESS_INTERVAL == 0 && !on_progress(WalkProgress { examined, total, matched: commit_infos.len() }) {
+                return Err(SyncError::Cancelled);
+            }
+            let commit = repo.find_commit(id)?;
+            let renamed_from = if query.follow_dir {
+                self.detect_dir_rename(&repo, &commit, &subdir)?
+            } else {
+                None
+            };
+            let commit_subdir = subdir.clone();
+            if let Some(ref old) = renamed_from {
+                debug!("{} renamed {} -> {}", id, old, subdir);
+                subdir = old.clone();
+            }
+
+            if !filter.matches(&commit) {
+                continue;
+            }
+
+            // Check if commit affects the subdirectory
+            let affects = if commit_subdir.is_empty() || commit_subdir == "." || renamed_from.is_some() {
+                true
+            } else {
+                Self::commit_affects_subdir(&commit, &commit_subdir)?
+            };
+
+            if affects {
+                commit_infos.push(commit_info(&commit, commit_subdir, renamed_from));
+            }
+        }
+
+        if !on_progress(WalkProgress { examined: total, total, matched: commit_infos.len() }) {
+            return Err(SyncError::Cancelled);
+        }
+        commit_infos.reverse();
+        Ok(commit_infos)
+    }
+
+    /// Detects whether `commit` moved a directory to `subdir`, returning the old path.
+    /// Only whole-directory moves count: `subdir` must be absent from the first parent.
+    fn detect_dir_rename(&self, repo: &Repository, commit: &Commit, subdir: &str) -> Result<Option<String>> {
+        if subdir.is_empty() || subdir == "." {
+            return Ok(None);
+        }
+        let parent = match commit.parent(0) {
+            Ok(parent) => parent,
+            Err(_) => return Ok(None),
+        };
+        let parent_tree = parent.tree()?;
+        if parent_tree.get_path(Path::new(subdir)).is_ok() || commit.tree()?.get_path(Path::new(subdir)).is_err() {
+            return Ok(None);
+        }
+
+        let mut diff = repo.diff_tree_to_tree(Some(&parent_tree), Some(&commit.tree()?), None)?;
+        diff.find_similar(Some(git2::DiffFindOptions::new().renames(true)))?;
+
+        let prefix = format!("{}/", subdir);
+        let mut candidates: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+        for delta in diff.deltas() {
+            if delta.status() != git2::Delta::Renamed {
+                continue;
+            }
+            let (Some(old), Some(new)) = (
+                delta.old_file().path().and_then(|p| p.to_str()),
+                delta.new_file().path().and_then(|p| p.to_str()),
+            ) else {
+                continue;
+            };
+            if let Some(rest) = new.strip_prefix(&prefix) {
+                if let Some(old_dir) = old.strip_suffix(rest).and_then(|d| d.strip_suffix('/')) {
+                    *candidates.entry(old_dir.to_string()).or_default() += 1;
+                }
+            }
+        }
+
+        Ok(candidates.into_iter().max_by_key(|(_, count)| *count).map(|(dir, _)| dir))
+    }
+
+    /// Collects the `export-ignore` patterns from every `.gitattributes` file under
+    /// `subdir` at the given commit. Patterns are returned relative to `subdir`.
+    pub fn export_ignore_patterns(&self, commit: &str, subdir: &str) -> Result<Vec<String>> {
+        let repo = self.repository(true);
+        let tree = repo.revparse_single(commit)
+            .map_err(|_| SyncError::InvalidCommit(commit.to_string()))?
+            .peel_to_tree()?;
+
+        let subdir = subdir.trim_matches('/');
+        let subdir_tree = if subdir.is_empty() || subdir == "." {
+            tree
+        } else {
+            match tree.get_path(Path::new(subdir)) {
+                Ok(entry) => entry.to_object(&repo)?.peel_to_tree()?,
+                Err(_) => return Ok(Vec::new()),
+            }
+        };
+
+        let mut attr_files = Vec::new();
+        subdir_tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.name() == Some(".gitattributes") {
+                attr_files.push((dir.to_string(), entry.id()));
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+
+        let mut patterns = Vec::new();
+        for (dir, blob_id) in attr_files {
+            let blob = repo.find_blob(blob_id)?;
+            let content = String::from_utf8_lossy(blob.content());
+            for line in content.lines() {
+                let mut fields = line.split_whitespace();
+                let pattern = match fields.next() {
+                    Some(p) if !p.starts_with('#') => p,
+                    _ => continue,
+                };
+                if !fields.any(|attr| attr == "export-ignore") {
+                    continue;
+                }
+
+                // Patterns without a slash match at any depth below the attributes file
+                let relative = if pattern.trim_start_matches('/').contains('/') || pattern.starts_with('/') {
+                    format!("{}{}", dir, pattern.trim_start_matches('/'))
+                } else {
+                    format!("{}**/{}", dir, pattern)
+                };
+                patterns.push(relative.clone());
+                patterns.push(format!("{}/**", relative));
+            }
+        }
+
+        debug!("export-ignore patterns for {}: {:?}", subdir, patterns);
+        Ok(patterns)
+    }
+
+    /// Resolves the tree of `subdir` at `commit` in the source repository.
+    pub fn subdir_tree_id(&self, commit: &str, subdir: &str) -> Result<git2::Oid> {
+        let repo = self.repository(true);
+        let tree = repo.revparse_single(commit)
+            .map_err(|_| SyncError::InvalidCommit(commit.to_string()))?
+            .peel_to_tree()?;
+
+        let subdir = subdir.trim_matches('/');
+        if subdir.is_empty() || subdir == "." {
+            return Ok(tree.id());
+        }
+
+        let entry = tree.get_path(Path::new(subdir))
+            .map_err(|_| SyncError::PathNotFound(PathBuf::from(subdir)))?;
+        let subtree_id = entry.to_object(&repo)?.peel_to_tree()?.id();
+        Ok(subtree_id)
+    }
+
+    /// Paths under `subdir` at `commit` that differ from each other only by case,
+    /// e.g. `README.md` and `Readme.md`. Each group ends up as a single file on a
+    /// case-insensitive filesystem. Paths inside a colliding directory are not
+    /// listed again.
+    pub fn case_collisions(&self, commit: &str, subdir: &str) -> Result<Vec<Vec<String>>> {
+        let tree_id = self.subdir_tree_id(commit, subdir)?;
+        let repo = self.repository(true);
+        let tree = repo.find_tree(tree_id)?;
+
+        let mut paths: HashMap<String, Vec<String>> = HashMap::new();
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if let Some(name) = entry.name() {
+                let path = format!("{}{}", root, name);
+                paths.entry(path.to_lowercase()).or_default().push(path);
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+
+        let colliding: HashSet<&str> = paths
+            .iter()
+            .filter(|(_, group)| group.len() > 1)
+            .map(|(folded, _)| folded.as_str())
+            .collect();
+        let mut collisions: Vec<Vec<String>> = paths
+            .iter()
+            .filter(|(folded, _)| colliding.contains(folded.as_str()))
+            .filter(|(folded, _)| {
+                !folded.match_indices('/').any(|(i, _)| colliding.contains(&folded[..i]))
+            })
+            .map(|(_, group)| {
+                let mut group = group.clone();
+                group.sort();
+                group
+            })
+            .collect();
+        collisions.sort();
+        Ok(collisions)
+    }
+
+    /// Whether the target repository was created on a case-insensitive
+    /// filesystem, as recorded by git in `core.ignorecase`
+    pub fn target_ignores_case(&self) -> Result<bool> {
+        let config = self.repository(false).config()?;
+        Ok(config.get_bool("core.ignorecase").unwrap_or(false))
+    }
+
+    /// First commit of the range, along the first-parent line, whose tree contains
+    /// `query.subdir`. Used when the directory is missing at the start commit, to
+    /// find where it was created; `None` when it never shows up on that line.
+    pub fn subdir_creation_commit(&self, query: &RangeQuery) -> Result<Option<String>> {
+        let repo = self.repository(true);
+        let query = RangeQuery { first_parent: true, order: CommitOrder::Topo, ..query.clone() };
+        let subdir = Path::new(query.subdir.trim_matches('/'));
+        for oid in Self::range_ids(&repo, &query)? {
+            if repo.find_commit(oid)?.tree()?.get_path(subdir).is_ok() {
+                return Ok(Some(oid.to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Commits of `commits` (parents first) undone by a later one, as `(original,
+    /// revert)` index pairs. The revert names the original in git's `This reverts
+    /// commit <id>.` line; a `Revert ...` subject without a usable line is matched
+    /// by the patch id of its inverted change under the synced directory. Each
+    /// commit belongs to at most one pair.
+    pub fn revert_pairs(&self, commits: &[CommitInfo]) -> Result<Vec<(usize, usize)>> {
+        fn find<'r>(repo: &'r Repository, id: &str) -> Result<Commit<'r>> {
+            git2::Oid::from_str(id)
+                .and_then(|oid| repo.find_commit(oid))
+                .map_err(|_| SyncError::InvalidCommit(id.to_string()))
+        }
+
+        let repo = self.repository(true);
+        let mut paired = vec![false; commits.len()];
+        let mut patch_ids: HashMap<usize, git2::Oid> = HashMap::new();
+        let mut pairs = Vec::new();
+        for (i, commit) in commits.iter().enumerate() {
+            if commit.is_merge || paired[i] {
+                continue;
+            }
+            let revert = find(&repo, &commit.id)?;
+            let named = revert.message().unwrap_or("").lines().find_map(|line| {
+                let rest = line.trim().strip_prefix("This reverts commit ")?;
+                let id: String = rest.chars().take_while(char::is_ascii_hexdigit).collect();
+                (id.len() >= SHORT_ID_LEN).then_some(id)
+            });
+            let original = match named {
+                Some(id) => (0..i).rev().find(|&j| !paired[j] && commits[j].id.starts_with(&id)),
+                None if commit.subject.starts_with("Revert ") => {
+                    let inverse = subdir_patch_id(&repo, &revert, &commit.subdir, true)?;
+                    let mut found = None;
+                    for j in (0..i).rev().filter(|&j| !paired[j] && !commits[j].is_merge) {
+                        let forward = match patch_ids.get(&j) {
+                            Some(id) => *id,
+                            None => {
+                                let id = subdir_patch_id(&repo, &find(&repo, &commits[j].id)?, &commits[j].subdir, false)?;
+                                patch_ids.insert(j, id);
+                                id
+                            }
+                        };
+                        if forward == inverse {
+                            found = Some(j);
+                            break;
+                        }
+                    }
+                    found
+                }
+                None => None,
+            };
+            if let Some(j) = original {
+                paired[j] = true;
+                paired[i] = true;
+                pairs.push((j, i));
+            }
+        }
+        Ok(pairs)
+    }
+
+    /// Rules of the CODEOWNERS file at `rev` in the source repository, looked
+    /// up where GitHub looks for it; `None` when there is none
+    pub fn codeowners(&self, rev: &str) -> Result<Option<CodeOwners>> {
+        for location in codeowners::LOCATIONS {
+            if let Some(content) = self.read_source_file(rev, location)? {
+                return CodeOwners::parse(&content).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Content of the file at `path`, relative to the repository root, in `rev`
+    /// of the source repository; `None` when there is no such file
+    pub fn read_source_file(&self, rev: &str, path: &str) -> Result<Option<String>> {
+        let repo = self.repository(true);
+        let tree = repo.revparse_single(rev)
+            .and_then(|obj| obj.peel_to_tree())
+            .map_err(|_| SyncError::InvalidCommit(rev.to_string()))?;
+        let Ok(entry) = tree.get_path(Path::new(path)) else { return Ok(None) };
+        let Ok(blob) = entry.to_object(&repo)?.into_blob() else { return Ok(None) };
+        Ok(Some(String::from_utf8_lossy(blob.content()).into_owned()))
+    }
+
+    /// Entries of directory `dir` (empty for the root) in `rev` of the source
+    /// repository, as names and whether each is a directory
+    pub fn list_source_dir(&self, rev: &str, dir: &str) -> Result<Vec<(String, bool)>> {
+        let repo = self.repository(true);
+        let tree = repo.revparse_single(rev)
+            .and_then(|obj| obj.peel_to_tree())
+            .map_err(|_| SyncError::InvalidCommit(rev.to_string()))?;
+        let tree = match dir.trim_matches('/') {
+            "" | "." => tree,
+            dir => match tree.get_path(Path::new(dir)).and_then(|entry| entry.to_object(&repo)) {
+                Ok(object) => match object.into_tree() {
+                    Ok(tree) => tree,
+                    Err(_) => return Ok(Vec::new()),
+                },
+                Err(_) => return Ok(Vec::new()),
+            },
+        };
+        Ok(tree
+            .iter()
+            .filter_map(|entry| {
+                let name = entry.name()?.to_string();
+                Some((name, entry.kind() == Some(git2::ObjectType::Tree)))
+            })
+            .collect())
+    }
+
+    /// Tags of the source repository that point at a commit, directly or
+    /// through annotated tags
+    pub fn source_tags(&self) -> Result<Vec<SourceTag>> {
+        let repo = self.repository(true);
+        let mut tags = Vec::new();
+        for name in repo.tag_names(None)?.iter().flatten() {
+            let Ok(object) = repo.revparse_single(&format!("refs/tags/{}", name)) else { continue };
+            let message = object
+                .as_tag()
+                .map(|tag| String::from_utf8_lossy(tag.message_bytes().unwrap_or_default()).into_owned());
+            let Ok(commit) = object.peel_to_commit() else { continue };
+            tags.push(SourceTag { name: name.to_string(), commit: commit.id().to_string(), message });
+        }
+        Ok(tags)
+    }
+
+    /// Paths, relative to the source repository root, of the files `commit`
+    /// changed under `subdir`
+    pub fn changed_paths(&self, commit: &str, subdir: &str) -> Result<Vec<String>> {
+        let repo = self.repository(true);
+        let commit = repo.revparse_single(commit)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(commit.to_string()))?;
+        let prefix = match subdir.trim_matches('/') {
+            "" | "." => String::new(),
+            subdir => format!("{}/", subdir),
+        };
+        let diff = subdir_diff(&repo, &commit, subdir)?;
+        Ok(diff
+            .deltas()
+            .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()))
+            .map(|path| format!("{}{}", prefix, path.to_string_lossy()))
+            .collect())
+    }
+
+    /// Whether `commit` only changed whitespace under `subdir`: every changed file
+    /// keeps its mode and has no hunks once whitespace and blank lines are ignored.
+    /// Root commits, merges, added, deleted and binary files never count.
+    pub fn is_whitespace_only(&self, commit: &str, subdir: &str) -> Result<bool> {
+        let repo = self.repository(true);
+        let commit = repo.revparse_single(commit)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(commit.to_string()))?;
+        if commit.parent_count() != 1 {
+            return Ok(false);
+        }
+
+        let subdir = subdir.trim_matches('/');
+        let subtree = |tree: git2::Tree<'_>| -> Option<git2::Oid> {
+            if subdir.is_empty() || subdir == "." {
+                return Some(tree.id());
+            }
+            tree.get_path(Path::new(subdir)).ok().map(|entry| entry.id())
+        };
+        let (Some(old), Some(new)) = (subtree(commit.parent(0)?.tree()?), subtree(commit.tree()?)) else {
+            return Ok(false);
+        };
+        let (Ok(old), Ok(new)) = (repo.find_tree(old), repo.find_tree(new)) else {
+            return Ok(false);
+        };
+
+        let mut opts = git2::DiffOptions::new();
+        opts.ignore_whitespace(true).ignore_blank_lines(true);
+        let diff = repo.diff_tree_to_tree(Some(&old), Some(&new), Some(&mut opts))?;
+        if diff.deltas().len() == 0 {
+            return Ok(false);
+        }
+        for (i, delta) in diff.deltas().enumerate() {
+            if delta.status() != git2::Delta::Modified || delta.old_file().mode() != delta.new_file().mode() {
+                return Ok(false);
+            }
+            match git2::Patch::from_diff(&diff, i)? {
+                Some(patch) if !patch.delta().flags().is_binary() && patch.num_hunks() == 0 => {}
+                _ => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Files changed and lines added and removed under `subdir` by `commit`,
+    /// compared with its first parent (with nothing for a root commit)
+    pub fn commit_stats(&self, commit: &str, subdir: &str) -> Result<DiffStats> {
+        let repo = self.repository(true);
+        let commit = repo.revparse_single(commit)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(commit.to_string()))?;
+
+        let diff = subdir_diff(&repo, &commit, subdir)?;
+        let stats = diff.stats()?;
+        Ok(DiffStats {
+            files: stats.files_changed(),
+            insertions: stats.insertions(),
+            deletions: stats.deletions(),
+        })
+    }
+
+    /// Message of `commit` and the files it changes under `subdir`, compared
+    /// with its first parent like [`GitManager::commit_stats`]
+    pub fn commit_details(&self, commit: &str, subdir: &str) -> Result<CommitDetails> {
+        let repo = self.repository(true);
+        let commit = repo.revparse_single(commit)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(commit.to_string()))?;
+
+        let diff = subdir_diff(&repo, &commit, subdir)?;
+        let mut files = Vec::with_capacity(diff.deltas().len());
+        for (i, delta) in diff.deltas().enumerate() {
+            let path = delta.new_file().path().or_else(|| delta.old_file().path())
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let (insertions, deletions) = match git2::Patch::from_diff(&diff, i)? {
+                Some(patch) => {
+                    let (_, insertions, deletions) = patch.line_stats()?;
+                    (insertions, deletions)
+                }
+                None => (0, 0),
+            };
+            files.push(ChangedFile {
+                path,
+                status: status_letter(delta.status()),
+                insertions,
+                deletions,
+            });
+        }
+        Ok(CommitDetails {
+            message: commit.message().unwrap_or("").to_string(),
+            files,
+        })
+    }
+
+    /// Message and diff of `commit` under `subdir`, compared with its first parent
+    /// like [`GitManager::commit_stats`]. Rendered in memory, so unlike the patches
+    /// of a sync nothing is written to disk.
+    pub fn commit_diff(&self, commit: &str, subdir: &str) -> Result<String> {
+        let repo = self.repository(true);
+        let commit = repo.revparse_single(commit)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(commit.to_string()))?;
+
+        let mut output = format!(
+            "commit {}\nAuthor: {} <{}>\n\n{}\n",
+            commit.id(),
+            commit.author().name().unwrap_or(""),
+            commit.author().email().unwrap_or(""),
+            commit.message().unwrap_or("").trim_end()
+        );
+        let diff = subdir_diff(&repo, &commit, subdir)?;
+        diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                output.push(line.origin());
+            }
+            output.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })?;
+        Ok(output)
+    }
+
+    /// Opens the target repository with the source object database attached as an
+    /// in-memory alternate, so source trees can be diffed against target content.
+    /// Nothing is written to the target repository.
+    pub fn target_repository_with_source_objects(&self) -> Result<Repository> {
+        let objects = self.repository(true).path().join("objects");
+        let target = self.open_repository(false)?;
+        target.odb()?.add_disk_alternate(&objects.to_string_lossy())?;
+        Ok(target)
+    }
+
+    /// Renders the difference between `subdir` at `commit` and `target_dir` in the
+    /// target working tree. Source paths are mapped below `target_dir`, `a/` is the
+    /// source and `b/` the target. Files outside `target_dir` and paths matching
+    /// `excludes` (globs relative to the synced directory) are left out on both sides.
+    pub fn diff_subdir_to_target(&self, commit: &str, subdir: &str, target_dir: &str, excludes: &[String], stat_only: bool) -> Result<String> {
+        let tree_id = self.subdir_tree_id(commit, subdir)?;
+        let target = self.target_repository_with_source_objects()?;
+        let tree = target.find_tree(tree_id)?;
+
+        // The source tree is moved below target_dir in an in-memory index, so
+        // nothing is written to the target repository
+        let prefix = if target_dir.is_empty() { String::new() } else { format!("{}/", target_dir) };
+        let mut source = git2::Index::new()?;
+        source.read_tree(&tree)?;
+        let mut index = git2::Index::new()?;
+        for mut entry in source.iter() {
+            entry.path = [prefix.as_bytes(), &entry.path].concat();
+            index.add(&entry)?;
+        }
+
+        let mut opts = git2::DiffOptions::new();
+        opts.include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .show_untracked_content(true);
+        // libgit2 takes the first pathspec that matches, so the negated
+        // excludes have to come before the directory itself
+        for pattern in excludes {
+            opts.pathspec(format!("!{}{}", prefix, pattern));
+        }
+        if !target_dir.is_empty() {
+            opts.pathspec(target_dir);
+        }
+        let diff = target.diff_index_to_workdir(Some(&index), Some(&mut opts))?;
+
+        if stat_only {
+            let stats = diff.stats()?;
+            let buf = stats.to_buf(git2::DiffStatsFormat::FULL, 80)?;
+            return Ok(String::from_utf8_lossy(&buf).to_string());
+        }
+
+        let mut output = String::new();
+        diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                output.push(line.origin());
+            }
+            output.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })?;
+        Ok(output)
+    }
+
+    /// Builds a mailbox patch by diffing `old_subdir` in the first parent against
+    /// `new_subdir` in the commit. Used for directory moves, where the move itself
+    /// becomes a no-op in the target, and for merges (both paths equal), which
+    /// `format-patch` silently skips. A path missing on either side counts as empty.
+    #[instrument(skip(self, excludes, output_dir))]
+    pub fn create_subtree_patch_file(&self, commit_id: &str, old_subdir: &str, new_subdir: &str, excludes: &[String], output_dir: &Path) -> Result<PathBuf> {
+        let repo = self.repository(true);
+        let commit = repo.revparse_single(commit_id)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(commit_id.to_string()))?;
+        let parent = commit.parent(0)?;
+        let empty_tree_id = self.source_repo_info.object_format.empty_tree_id();
+        let subtree_id = |tree: git2::Tree, subdir: &str| {
+            let subdir = subdir.trim_matches('/');
+            if subdir.is_empty() || subdir == "." {
+                return tree.id().to_string();
+            }
+            tree.get_path(Path::new(subdir))
+                .map(|entry| entry.id().to_string())
+                .unwrap_or_else(|_| empty_tree_id.to_string())
+        };
+
+        let mut cmd = std::process::Command::new("git");
+        cmd.arg("-C")
+            .arg(&self.source_repo_info.path)
+            .arg("diff")
+            .arg("--binary")
             .arg("--full-index")
-            .arg(format!("--relative={}", subdir))
+            .arg(subtree_id(parent.tree()?, old_subdir))
+            .arg(subtree_id(commit.tree()?, new_subdir));
+        // Paths in a tree-to-tree diff are already relative to the subdirectory
+        if !excludes.is_empty() {
+            cmd.arg("--").arg(".");
+            for pattern in excludes {
+                cmd.arg(format!(":(exclude,glob){}", pattern));
+            }
+        }
+        let output = cmd.output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            if is_transient_failure(&stderr) {
+                return Err(SyncError::Transient(stderr));
+            }
+            return Err(SyncError::PatchGenerationFailed(stderr));
+        }
+        if output.stdout.is_empty() {
+            return Err(SyncError::EmptyPatch);
+        }
+
+        let author = commit.author();
+        let offset = chrono::FixedOffset::east_opt(author.when().offset_minutes() * 60)
+            .unwrap_or_else(|| chrono::FixedOffset::east_opt(0).unwrap());
+        let date = chrono::DateTime::from_timestamp(author.when().seconds(), 0)
+            .unwrap_or_default()
+            .with_timezone(&offset);
+        let (subject, body) = crate::patch::split_message(commit.message().unwrap_or(""));
+
+        let mut patch = format!(
+            "From {} Mon Sep 17 00:00:00 2001\nFrom: {} <{}>\nDate: {}\nSubject: [PATCH] {}\n\n{}\n---\n",
+            commit.id(),
+            author.name().unwrap_or("Unknown"),
+            author.email().unwrap_or(""),
+            date.to_rfc2822(),
+            subject,
+            body,
+        ).into_bytes();
+        patch.extend_from_slice(&output.stdout);
+        patch.extend_from_slice(b"--\nsync-subdir\n");
+
+        let patch_path = output_dir.join(format!("0001-{}.patch", short_id(&commit.id().to_string())));
+        std::fs::write(&patch_path, patch)?;
+        Ok(patch_path)
+    }
+
+    #[instrument(skip(self, excludes, output_dir))]
+    pub fn create_patch_file(&self, commit_id: &str, paths: &PathMapping, excludes: &[String], extra_args: &[String], output_dir: &Path) -> Result<PathBuf> {
+        let repo_path = &self.source_repo_info.path;
+        let mut cmd = std::process::Command::new("git");
+        cmd.arg("-C")
+            .arg(repo_path)
+            .arg("format-patch")
+            .arg("-1")
+            .arg(commit_id)
+            .arg("--binary")
+            .arg("--full-index")
+            .args(paths.relative_arg())
+            .args(extra_args)
             .arg("-o")
-            .arg(output_dir)
-            .output()?;
+            .arg(output_dir);
+
+        if !excludes.is_empty() {
+            cmd.arg("--").arg(".");
+            for pattern in excludes {
+                cmd.arg(paths.exclude_pathspec(pattern));
+            }
+        }
+
+        let output = cmd.output()?;
 
         if !output.status.success() {
-            return Err(SyncError::PatchGenerationFailed(String::from_utf8_lossy(&output.stderr).to_string()));
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            if is_transient_failure(&stderr) {
+                return Err(SyncError::Transient(stderr));
+            }
+            return Err(SyncError::PatchGenerationFailed(stderr));
         }
 
         let patch_file_name = String::from_utf8_lossy(&output.stdout).trim().to_string();
         if patch_file_name.is_empty() {
              // Sometimes format-patch outputs nothing to stdout if -o is used, 
              // we need to find the file in output_dir
-             let entries = std::fs::read_dir(output_dir)?;
-             for entry in entries {
-                 let entry = entry?;
-                 return Ok(entry.path());
+             if let Some(entry) = std::fs::read_dir(output_dir)?.next() {
+                 return Ok(entry?.path());
              }
              return Err(SyncError::PatchGenerationFailed("No patch file generated".to_string()));
         }
@@ -309,16 +1928,277 @@ impl GitManager {
         Ok(output_dir.join(patch_file_name))
     }
 
-    pub fn apply_patch_file(&self, patch_path: &Path, target_subdir: Option<&str>) -> Result<()> {
-        let repo_path = &self.target_repo_info.path;
+    /// Rewrites the history of `subdir` up to `end` into the target repository,
+    /// like a `git filter-repo` limited to one directory: each commit that changes
+    /// the directory becomes a target commit holding it at `target_dir`, with the
+    /// same author, committer and message and its parents mapped to their
+    /// rewritten counterparts. Commits that leave the directory as it was are
+    /// dropped. The target branch must not have any commits yet; it ends up at the
+    /// rewritten `end`. `on_commit` is called for every commit created.
+    #[instrument(skip(self, on_commit))]
+    pub fn extract_history(
+        &self,
+        subdir: &str,
+        end: &str,
+        target_dir: &str,
+        mut on_commit: impl FnMut(&ExtractedCommit),
+    ) -> Result<ExtractSummary> {
+        let source = self.repository(true);
+        let target = self.repository(false);
+        let branch_ref = match target.head() {
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+                target.find_reference("HEAD")?.symbolic_target().unwrap_or("HEAD").to_string()
+            }
+            Err(e) => return Err(e.into()),
+            Ok(_) => {
+                return Err(SyncError::Anyhow(anyhow::anyhow!(
+                    "Target branch {} already has commits; extract only creates the initial history",
+                    self.target_repo_info.current_branch
+                )))
+            }
+        };
+        let end_commit = source
+            .revparse_single(end)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|_| SyncError::InvalidCommit(end.to_string()))?;
+
+        let mut walk = source.revwalk()?;
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+        walk.push(end_commit.id())?;
+
+        let target_odb = target.odb()?;
+        let mut copied = HashSet::new();
+        let empty_tree = target.treebuilder(None)?.write()?;
+        // Rewritten commit and its tree for every walked source commit; `None`
+        // while no ancestor touched the directory
+        let mut rewritten: HashMap<git2::Oid, Option<(git2::Oid, git2::Oid)>> = HashMap::new();
+        let mut summary = ExtractSummary::default();
+        for id in walk {
+            let commit = source.find_commit(id?)?;
+            summary.walked += 1;
+
+            let subtree = if subdir.is_empty() {
+                Some(commit.tree_id())
+            } else {
+                commit.tree()?.get_path(Path::new(subdir)).ok().filter(|entry| entry.kind() == Some(git2::ObjectType::Tree)).map(|entry| entry.id())
+            };
+            let tree = match subtree {
+                Some(subtree) => {
+                    copy_objects(&source, &target_odb, subtree, &mut copied)?;
+                    nest_tree(&target, subtree, target_dir)?
+                }
+                None => empty_tree,
+            };
+
+            let mut parents: Vec<(git2::Oid, git2::Oid)> = Vec::new();
+            for parent in commit.parent_ids() {
+                if let Some(Some(mapped)) = rewritten.get(&parent) {
+                    if !parents.contains(mapped) {
+                        parents.push(*mapped);
+                    }
+                }
+            }
+            let unchanged = match parents.as_slice() {
+                [] => tree == empty_tree,
+                [(_, parent_tree)] => *parent_tree == tree,
+                _ => false,
+            };
+            if unchanged {
+                rewritten.insert(commit.id(), parents.first().copied());
+                continue;
+            }
+
+            let parent_commits = parents
+                .iter()
+                .map(|(parent, _)| target.find_commit(*parent))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let message = String::from_utf8_lossy(commit.message_raw_bytes()).into_owned();
+            let new_id = target.commit(
+                None,
+                &commit.author(),
+                &commit.committer(),
+                &message,
+                &target.find_tree(tree)?,
+                &parent_commits.iter().collect::<Vec<_>>(),
+            )?;
+            rewritten.insert(commit.id(), Some((new_id, tree)));
+            let extracted = ExtractedCommit {
+                source: commit.id().to_string(),
+                target: new_id.to_string(),
+                subject: commit.summary().unwrap_or("No subject").to_string(),
+            };
+            on_commit(&extracted);
+            summary.commits.push(extracted);
+        }
+
+        let Some(Some((head, _))) = rewritten.get(&end_commit.id()).copied() else {
+            return Err(SyncError::PathNotFound(PathBuf::from(subdir)));
+        };
+        target.reference(&branch_ref, head, false, "sync-subdir: extract history")?;
+        summary.end = end_commit.id().to_string();
+        update_checkout(&self.target_repo_info.path, &empty_tree.to_string(), &head.to_string())?;
+        Ok(summary)
+    }
+
+    /// Bytes a checkout of the target repository takes, from the file sizes
+    /// recorded in its index
+    pub fn target_checkout_size(&self) -> Result<u64> {
+        let index = self.repository(false).index()?;
+        Ok(index.iter().map(|entry| u64::from(entry.file_size)).sum())
+    }
+
+    /// Commits `edits` on top of the target HEAD, building the tree in the object
+    /// database instead of a worktree, and brings the user's checkout up to date.
+    /// Source blobs are copied into the target repository. On an unborn branch
+    /// this creates its first commit. Returns `None` when the edits leave the
+    /// tree as it was.
+    #[instrument(skip_all, fields(edits = edits.len()))]
+    pub fn commit_to_target(&self, edits: &[TreeEdit], message: &str) -> Result<Option<String>> {
+        let source = self.repository(true);
+        let repo = self.repository(false);
+        let parent = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+            Err(e) => return Err(e.into()),
+        };
+        let baseline = match &parent {
+            Some(parent) => parent.tree()?,
+            None => repo.find_tree(repo.treebuilder(None)?.write()?)?,
+        };
+
+        let mut update = git2::build::TreeUpdateBuilder::new();
+        for edit in edits {
+            match edit {
+                TreeEdit::Write { path, blob, mode } => {
+                    let blob = repo.blob(source.find_blob(*blob)?.content())?;
+                    let mode = match *mode {
+                        0o100755 => git2::FileMode::BlobExecutable,
+                        0o120000 => git2::FileMode::Link,
+                        _ => git2::FileMode::Blob,
+                    };
+                    update.upsert(path.as_slice(), blob, mode);
+                }
+                TreeEdit::Remove(path) => {
+                    update.remove(path.as_slice());
+                }
+            }
+        }
+        let tree_id = update.create_updated(&repo, &baseline)?;
+        if tree_id == baseline.id() {
+            return Ok(None);
+        }
+
+        let signature = repo.signature()?;
+        let parents: Vec<&Commit> = parent.iter().collect();
+        // Fails if HEAD moved since it was read above
+        let commit = repo.commit(Some("HEAD"), &signature, &signature, message, &repo.find_tree(tree_id)?, &parents)?;
+        update_checkout(&self.target_repo_info.path, &baseline.id().to_string(), &commit.to_string())?;
+        Ok(Some(commit.to_string()))
+    }
+
+    /// Creates a detached worktree of the target repository at `dir`, positioned at
+    /// the current target HEAD, in which patches can be applied without touching the
+    /// user's working tree. With `staging`, commits go to a new branch of that name
+    /// until [`ApplyWorktree::promote`] moves the target branch to them.
+    #[instrument(skip(self))]
+    pub fn create_apply_worktree(&self, dir: &Path, staging: Option<&str>) -> Result<ApplyWorktree> {
+        let repo = self.repository(false);
+        let head = repo.head()?;
+        let mut branch_ref = if head.is_branch() {
+            head.name().unwrap_or("HEAD").to_string()
+        } else {
+            "HEAD".to_string()
+        };
+        let original = head.peel_to_commit()?;
+        let mut staged_for = None;
+        if let Some(name) = staging {
+            let staging_branch = repo.branch(name, &original, false)?;
+            staged_for = Some(std::mem::replace(
+                &mut branch_ref,
+                staging_branch.get().name().unwrap_or_default().to_string(),
+            ));
+        }
+        let original = original.id();
+
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&self.target_repo_info.path)
+            .args(["worktree", "add", "--detach"])
+            .arg(dir)
+            .arg(original.to_string())
+            .output()?;
+        if !output.status.success() {
+            return Err(SyncError::Anyhow(anyhow::anyhow!(
+                "Failed to create apply worktree: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(ApplyWorktree {
+            repo_path: self.target_repo_info.path.clone(),
+            path: dir.to_path_buf(),
+            branch_ref,
+            staged_for,
+            original,
+            head: original,
+        })
+    }
+
+    /// Index file seeded with the target's HEAD tree, for dry runs to apply
+    /// patches to without touching the user's checkout or index
+    pub fn create_scratch_index(&self, path: &Path) -> Result<ScratchIndex> {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&self.target_repo_info.path)
+            .env("GIT_INDEX_FILE", path)
+            .args(["read-tree", "HEAD"])
+            .output()?;
+        if !output.status.success() {
+            return Err(SyncError::Anyhow(anyhow::anyhow!(
+                "Failed to create scratch index: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+        Ok(ScratchIndex { path: path.to_path_buf() })
+    }
+
+    /// Applies a patch to a scratch index the way `git am` would apply it to the
+    /// target, so later patches are checked on top of it. `git apply` is all or
+    /// nothing: a patch that does not apply fails with [`SyncError::PatchConflict`]
+    /// and leaves the index as it was.
+    #[instrument(skip(self, index))]
+    pub fn check_patch_file(&self, index: &ScratchIndex, patch_path: &Path, paths: &PathMapping) -> Result<()> {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&self.target_repo_info.path)
+            .env("GIT_INDEX_FILE", &index.path)
+            .args(["apply", "--cached"])
+            .args(paths.directory_arg())
+            .arg(patch_path)
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if is_transient_failure(&stderr) {
+                return Err(SyncError::Transient(stderr.to_string()));
+            }
+            return Err(SyncError::PatchConflict(stderr.to_string()));
+        }
+        Ok(())
+    }
+
+    #[instrument(skip(self, workdir))]
+    pub fn apply_patch_file(&self, workdir: &Path, patch_path: &Path, paths: &PathMapping, extra_args: &[String]) -> Result<()> {
         let mut cmd = std::process::Command::new("git");
-        cmd.arg("-C").arg(repo_path).arg("am");
+        cmd.arg("-C").arg(workdir).arg("am");
         
         cmd.arg("--3way").arg("--committer-date-is-author-date");
-        
-        if let Some(subdir) = target_subdir {
-            cmd.arg(format!("--directory={}", subdir));
-        }
+        // Mailbox splitting drops the CR of CRLF lines otherwise, changing files byte for byte
+        cmd.arg("--keep-cr");
+        // Only `[PATCH ...]` is stripped from the subject, so `--subject-prefix [sync] ` and
+        // subjects that start with their own brackets arrive as written
+        cmd.arg("--keep-non-patch");
+        cmd.args(paths.directory_arg());
+        cmd.args(extra_args);
         
         cmd.arg(patch_path);
 
@@ -326,19 +2206,29 @@ impl GitManager {
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            if stderr.contains("patch does not have a valid index") || stderr.contains("Patch is empty") {
+            if stderr.contains("patch does not have a valid index")
+                || stderr.contains("Patch is empty")
+                || stderr.contains("No valid patches in input")
+            {
+                // Leave no half-finished am session behind for the next commit
+                abort_am(workdir);
                 return Err(SyncError::EmptyPatch);
             }
-            return Err(SyncError::PatchConflict(stderr.to_string()));
+            if is_transient_failure(&stderr) {
+                // A retry starts a fresh session
+                abort_am(workdir);
+                return Err(SyncError::Transient(stderr.to_string()));
+            }
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            return Err(SyncError::PatchConflict(format!("{}{}", stdout, stderr)));
         }
 
         Ok(())
     }
 
-
     #[allow(dead_code)]
     pub fn get_commit_count(&self, subdir: &str, start_commit: &str, end_commit: &str, _exclude_merges: bool) -> Result<(usize, usize)> {
-        let repo = self.get_repository(true)?;
+        let repo = self.repository(true);
 
         // Resolve commit references (supports both OIDs and references like HEAD)
         let start_obj = repo.revparse_single(start_commit)
@@ -360,7 +2250,7 @@ impl GitManager {
             let commit = repo.find_commit(id)?;
 
             // Check if commit affects the subdirectory
-            let affects_subdir = self.commit_affects_subdir(&commit, subdir)?;
+            let affects_subdir = Self::commit_affects_subdir(&commit, subdir)?;
             if !affects_subdir {
                 continue;
             }
@@ -374,73 +2264,250 @@ impl GitManager {
         Ok((total_commits, merge_commits))
     }
 
-    #[allow(dead_code)]
-    fn commit_affects_subdir(&self, commit: &Commit, subdir: &str) -> Result<bool> {
-        let repo = self.get_repository(true)?;
-
-        if let Ok(parent) = commit.parent(0) {
-            let tree_a = parent.tree()?;
-            let tree_b = commit.tree()?;
-
-            let diff = repo.diff_tree_to_tree(Some(&tree_a), Some(&tree_b), None)?;
-            let subdir_pattern = format!("{}/", subdir.trim_end_matches('/'));
-
-            let mut affects_subdir = false;
-            let result = diff.foreach(
-                &mut |delta: DiffDelta, _progress| {
-                    let new_path = delta.new_file().path()
-                        .and_then(|p| p.to_str())
-                        .unwrap_or("");
-
-                    let old_path = delta.old_file().path()
-                        .and_then(|p| p.to_str())
-                        .unwrap_or("");
-
-                    if new_path.starts_with(&subdir_pattern) || old_path.starts_with(&subdir_pattern) {
-                        affects_subdir = true;
-                        return false; // Stop iteration
-                    }
-                    true
-                },
-                None,
-                None,
-                None,
-            );
+    /// Whether `commit` changed anything under `subdir` relative to its first parent.
+    /// Tree ids are content hashes, so comparing the subdirectory's entry in both
+    /// trees answers this without diffing: the cost depends on the path depth,
+    /// not on the size of the repository.
+    fn commit_affects_subdir(commit: &Commit, subdir: &str) -> Result<bool> {
+        let subdir = Path::new(subdir.trim_end_matches('/'));
+        let entry_id = |tree: git2::Tree| tree.get_path(subdir).ok().map(|entry| entry.id());
+
+        let current = entry_id(commit.tree()?);
+        let previous = match commit.parent(0) {
+            Ok(parent) => entry_id(parent.tree()?),
+            // Initial commit: affects the subdir if it has any content there
+            Err(_) => None,
+        };
+        Ok(current != previous)
+    }
+}
 
-            match result {
-                Ok(_) => Ok(affects_subdir),
-                Err(e) if e.code() == git2::ErrorCode::User => Ok(affects_subdir),
-                Err(e) => Err(e.into()),
-            }
-        } else {
-            // Initial commit, check if it contains files in the subdirectory
-            let tree_b = commit.tree()?;
-            let diff = repo.diff_tree_to_tree(None, Some(&tree_b), None)?;
-            let subdir_pattern = format!("{}/", subdir.trim_end_matches('/'));
-
-            let mut affects_subdir = false;
-            let result = diff.foreach(
-                &mut |delta: DiffDelta, _progress| {
-                    let new_path = delta.new_file().path()
-                        .and_then(|p| p.to_str())
-                        .unwrap_or("");
-
-                    if new_path.starts_with(&subdir_pattern) || new_path == subdir {
-                        affects_subdir = true;
-                        return false; // Stop iteration
-                    }
-                    true
-                },
-                None,
-                None,
-                None,
-            );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::SyncConfig;
+    use crate::paths::PathMapping;
+    use crate::testutil::{self, TestRepo, FILE};
+    use git2::Oid;
+
+    fn query(start: Oid, end: Oid, order: CommitOrder) -> RangeQuery {
+        RangeQuery {
+            subdir: "sub".to_string(),
+            start: start.to_string(),
+            end: end.to_string(),
+            include_start: false,
+            first_parent: false,
+            follow_dir: false,
+            order,
+        }
+    }
+
+    fn listed(repo: &TestRepo, query: &RangeQuery) -> Vec<Oid> {
+        let git_manager = GitManager::open_target(repo.path()).unwrap();
+        git_manager
+            .get_commits_in_range(query, &CommitFilter::default())
+            .unwrap()
+            .iter()
+            .map(|commit| Oid::from_str(&commit.id).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn topo_order_puts_parents_first_despite_older_child_timestamp() {
+        let repo = TestRepo::new();
+        let root = repo.commit(&[], &[("sub/a", b"1", FILE)], 1_000, "root");
+        let parent = repo.commit(&[root], &[("sub/a", b"2", FILE)], 3_000, "parent");
+        // Rebased or made on a machine with a slow clock
+        let child = repo.commit(&[parent], &[("sub/a", b"3", FILE)], 2_000, "child");
 
-            match result {
-                Ok(_) => Ok(affects_subdir),
-                Err(e) if e.code() == git2::ErrorCode::User => Ok(affects_subdir),
-                Err(e) => Err(e.into()),
+        assert_eq!(listed(&repo, &query(root, child, CommitOrder::Topo)), vec![parent, child]);
+        assert_eq!(listed(&repo, &query(root, child, CommitOrder::Time)), vec![child, parent]);
+    }
+
+    #[test]
+    fn topo_order_keeps_parents_first_across_branches() {
+        let repo = TestRepo::new();
+        let root = repo.commit(&[], &[("sub/a", b"", FILE)], 1_000, "root");
+        let left = repo.commit(&[root], &[("sub/a", b"left", FILE)], 5_000, "left");
+        let left_child = repo.commit(&[left], &[("sub/a", b"left 2", FILE)], 1_500, "left child");
+        let right = repo.commit(&[root], &[("sub/b", b"right", FILE)], 2_000, "right");
+        let merge = repo.commit(
+            &[left_child, right],
+            &[("sub/a", b"left 2", FILE), ("sub/b", b"right", FILE)],
+            6_000,
+            "merge",
+        );
+
+        let topo = listed(&repo, &query(root, merge, CommitOrder::Topo));
+        assert_eq!(topo.len(), 4);
+        for (i, id) in topo.iter().enumerate() {
+            for parent in repo.repo.find_commit(*id).unwrap().parent_ids() {
+                if let Some(position) = topo.iter().position(|listed| *listed == parent) {
+                    assert!(position < i, "{} listed before its parent {}", id, parent);
+                }
             }
         }
+
+        let time = listed(&repo, &query(root, merge, CommitOrder::Time));
+        let position = |id| time.iter().position(|listed| *listed == id).unwrap();
+        assert!(position(left_child) < position(left));
+    }
+
+    #[test]
+    fn include_start_at_the_root_commit_lists_it() {
+        let repo = TestRepo::new();
+        let root = repo.commit(&[], &[("sub/a", b"1", FILE)], 1_000, "root");
+        let next = repo.commit(&[root], &[("sub/a", b"2", FILE)], 2_000, "next");
+
+        let query = RangeQuery { include_start: true, ..query(root, next, CommitOrder::Topo) };
+        assert_eq!(listed(&repo, &query), vec![root, next]);
+    }
+
+    #[test]
+    fn root_commit_patch_adds_the_initial_tree() {
+        let repo = TestRepo::new();
+        let root = repo.commit(&[], &[("sub/a", b"a\n", FILE), ("sub/dir/b", b"b\n", FILE), ("other", b"", FILE)], 1_000, "root");
+        let git_manager = GitManager::open_target(repo.path()).unwrap();
+        let out = tempdir::TempDir::new("patch").unwrap();
+
+        let paths = PathMapping::new("sub", "").unwrap();
+        let patch = git_manager.create_patch_file(&root.to_string(), &paths, &[], &[], out.path()).unwrap();
+        let patch = std::fs::read_to_string(patch).unwrap();
+        assert!(patch.contains("diff --git a/a b/a\nnew file mode 100644"));
+        assert!(patch.contains("diff --git a/dir/b b/dir/b\nnew file mode 100644"));
+        assert!(!patch.contains("other"));
+    }
+
+    #[tokio::test]
+    async fn root_commit_syncs_as_additions() {
+        let source = TestRepo::new();
+        let root = source.commit(&[], &[("sub/a", b"a\n", FILE), ("sub/dir/b", b"b\n", FILE)], 1_000, "root");
+        let target = TestRepo::new();
+        target.commit(&[], &[("README", b"mirror\n", FILE)], 1_000, "init");
+
+        let report = testutil::sync(&source, &target, testutil::sync_config("sub", ""), &[root]).await;
+        assert_eq!(report.synced_commits, 1);
+        assert_eq!(target.file("a"), Some((b"a\n".to_vec(), FILE)));
+        assert_eq!(target.file("dir/b"), Some((b"b\n".to_vec(), FILE)));
+    }
+
+    /// `root` branching into three commits joined by an octopus merge
+    fn octopus_history(repo: &TestRepo) -> (Oid, Oid, Oid) {
+        let root = repo.commit(&[], &[("sub/a", b"", FILE)], 1_000, "root");
+        let first = repo.commit(&[root], &[("sub/a", b"1\n", FILE)], 2_000, "first");
+        let second = repo.commit(&[root], &[("sub/a", b"", FILE), ("sub/b", b"2\n", FILE)], 3_000, "second");
+        let third = repo.commit(&[root], &[("sub/a", b"", FILE), ("sub/c", b"3\n", FILE)], 4_000, "third");
+        let all = [("sub/a", &b"1\n"[..], FILE), ("sub/b", b"2\n", FILE), ("sub/c", b"3\n", FILE)];
+        let octopus = repo.commit(&[first, second, third], &all, 5_000, "octopus");
+        (root, first, octopus)
+    }
+
+    #[test]
+    fn octopus_merges_are_listed_and_marked() {
+        let repo = TestRepo::new();
+        let (root, first, octopus) = octopus_history(&repo);
+        let query = RangeQuery { first_parent: true, ..query(root, octopus, CommitOrder::Topo) };
+
+        let git_manager = GitManager::open_target(repo.path()).unwrap();
+        let commits = git_manager.get_commits_in_range(&query, &CommitFilter::default()).unwrap();
+        let ids: Vec<_> = commits.iter().map(|commit| commit.id.clone()).collect();
+        assert_eq!(ids, vec![first.to_string(), octopus.to_string()]);
+        assert!(!commits[0].is_octopus());
+        assert!(commits[1].is_merge && commits[1].is_octopus());
+        assert_eq!(commits[1].parents, 3);
+
+        assert!(OctopusPolicy::Skip.check(&commits).is_ok());
+        assert!(OctopusPolicy::FirstParent.check(&commits).is_ok());
+        assert!(matches!(OctopusPolicy::Stop.check(&commits), Err(SyncError::OctopusMerge(id)) if id == octopus.to_string()));
+        assert!(OctopusPolicy::Stop.check(&commits[..1]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn octopus_merges_follow_their_policy() {
+        let source = TestRepo::new();
+        let (_, first, octopus) = octopus_history(&source);
+
+        let target = TestRepo::new();
+        target.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+        let report = testutil::sync(&source, &target, testutil::sync_config("sub", ""), &[first, octopus]).await;
+        assert_eq!(report.commits[1].status, "MERGE (SKIPPED)");
+        assert_eq!(target.file("b"), None);
+
+        let target = TestRepo::new();
+        target.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+        let config = SyncConfig { octopus: OctopusPolicy::FirstParent, ..testutil::sync_config("sub", "") };
+        let report = testutil::sync(&source, &target, config, &[first, octopus]).await;
+        assert_eq!(report.commits[1].status, "OK");
+        // Everything the merge brought in relative to its first parent
+        assert_eq!(target.file("a"), Some((b"1\n".to_vec(), FILE)));
+        assert_eq!(target.file("b"), Some((b"2\n".to_vec(), FILE)));
+        assert_eq!(target.file("c"), Some((b"3\n".to_vec(), FILE)));
+    }
+
+    #[test]
+    fn object_formats_are_read_once_and_compared_on_demand() {
+        let source = TestRepo::new();
+        let target = TestRepo::new();
+        // libgit2 ignores extensions of a version 0 repository, so it still opens
+        target.repo.config().unwrap().set_str("extensions.objectformat", "sha256").unwrap();
+
+        let git_manager = GitManager::new(source.path(), target.path()).unwrap();
+        assert_eq!(git_manager.source_repo_info.object_format, ObjectFormat::Sha1);
+        assert_eq!(git_manager.target_repo_info.object_format, ObjectFormat::Sha256);
+        assert!(matches!(
+            git_manager.check_object_formats(),
+            Err(SyncError::ObjectFormatMismatch { source_format: ObjectFormat::Sha1, target_format: ObjectFormat::Sha256 })
+        ));
+        assert!(GitManager::open_target(source.path()).unwrap().check_object_formats().is_ok());
+
+        let missing = source.path().join("missing");
+        assert!(matches!(GitManager::open_target(&missing), Err(SyncError::NotARepository(path)) if path == missing));
+    }
+
+    #[test]
+    fn repositories_open_from_worktrees_and_separate_git_dirs_but_not_when_broken() {
+        let repo = TestRepo::new();
+        repo.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git").arg("-C").arg(repo.path()).args(args).output().unwrap().status;
+            assert!(status.success(), "git {:?}", args);
+        };
+
+        let worktree = repo.path().join("worktree");
+        git(&["worktree", "add", "-q", worktree.to_str().unwrap()]);
+        assert!(GitManager::open(&worktree).is_ok());
+
+        let separate = repo.path().join("separate");
+        let git_dir = repo.path().join("separate.git");
+        git(&["init", "-q", "--separate-git-dir", git_dir.to_str().unwrap(), separate.to_str().unwrap()]);
+        assert!(!separate.join(".git").is_dir());
+        assert!(GitManager::open(&separate).is_ok());
+
+        std::fs::remove_dir_all(repo.repo.path().join("objects")).unwrap();
+        std::fs::remove_dir_all(repo.repo.path().join("refs")).unwrap();
+        assert!(matches!(GitManager::open(repo.path()), Err(SyncError::NotARepository(_))));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn remote_branches_of_remotes_with_slashes_are_found() {
+        let source = TestRepo::new();
+        source.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+        let target = TestRepo::new();
+        let first = target.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+        let second = target.commit(&[first], &[("a", b"1\n", FILE)], 2_000, "second");
+        target.repo.remote("team/mirror", "https://example.com/mirror.git").unwrap();
+        target.repo.reference("refs/remotes/team/mirror/main", second, false, "test").unwrap();
+        // Left over from a remote that is no longer configured
+        target.repo.reference("refs/remotes/gone/main", first, false, "test").unwrap();
+        let git_manager = GitManager::new(source.path(), target.path()).unwrap();
+
+        let found = git_manager.remote_branch(false, "main").unwrap();
+        assert_eq!(found, Some(("team/mirror/main".to_string(), second.to_string())));
+        assert_eq!(git_manager.remote_branch(false, "mirror/main").unwrap(), None);
+
+        target.repo.remote("origin", "https://example.com/origin.git").unwrap();
+        target.repo.reference("refs/remotes/origin/main", first, false, "test").unwrap();
+        let found = git_manager.remote_branch(false, "main").unwrap();
+        assert_eq!(found, Some(("origin/main".to_string(), first.to_string())));
+    }
+}
@@ -0,0 +1,128 @@
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{self, Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// Longer patches are only coloured by line kind: highlighting them would
+/// stall the UI for seconds
+const MAX_HIGHLIGHTED_LINES: usize = 5_000;
+
+/// Bundled syntect theme used for code in hunks
+const THEME: &str = "base16-ocean.dark";
+
+const ADDED_BG: Color = Color::Rgb(24, 48, 24);
+const REMOVED_BG: Color = Color::Rgb(56, 24, 24);
+
+struct Assets {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+/// Syntax definitions and theme, loaded on the first preview
+fn assets() -> &'static Assets {
+    static ASSETS: OnceLock<Assets> = OnceLock::new();
+    ASSETS.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        Assets {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.themes.remove(THEME).unwrap_or_default(),
+        }
+    })
+}
+
+/// Renders a mailbox patch for the TUI. File and hunk headers, added and
+/// removed lines are coloured, and the code in hunks is highlighted by the
+/// syntax of its file. With `color` off every line is plain text.
+pub fn patch_lines(patch: &str, color: bool) -> Vec<Line<'static>> {
+    if !color {
+        return patch.lines().map(|line| Line::from(line.to_string())).collect();
+    }
+    if patch.lines().count() > MAX_HIGHLIGHTED_LINES {
+        return patch.lines().map(|line| Line::styled(line.to_string(), line_style(line))).collect();
+    }
+
+    let assets = assets();
+    let mut highlighter = None;
+    let mut in_hunk = false;
+    let mut lines = Vec::new();
+    for line in patch.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            highlighter = syntax_for(&assets.syntaxes, paths).map(|syntax| HighlightLines::new(syntax, &assets.theme));
+            in_hunk = false;
+        } else if line.starts_with("@@") {
+            in_hunk = true;
+        } else if !line.starts_with(['+', '-', ' ']) || line == "-- " || line == "--" {
+            // Binary data, "\ No newline" markers and the signature end a hunk
+            in_hunk = false;
+        }
+
+        // The highlighter expects whole lines, newline included, without the diff marker
+        let text = format!("{}\n", line.get(1..).unwrap_or(""));
+        let code = match highlighter.as_mut() {
+            Some(highlighter) if in_hunk && !line.starts_with("@@") => highlighter.highlight_line(&text, &assets.syntaxes).ok(),
+            _ => None,
+        };
+        lines.push(match code {
+            Some(regions) => {
+                let background = match line.as_bytes()[0] {
+                    b'+' => Some(ADDED_BG),
+                    b'-' => Some(REMOVED_BG),
+                    _ => None,
+                };
+                let mut marker = line_style(line);
+                if let Some(bg) = background {
+                    marker = marker.bg(bg);
+                }
+                let mut spans = vec![Span::styled(line[..1].to_string(), marker)];
+                spans.extend(regions.into_iter().map(|(style, text)| {
+                    Span::styled(text.trim_end_matches('\n').to_string(), code_style(style, background))
+                }));
+                Line::from(spans)
+            }
+            None => Line::styled(line.to_string(), line_style(line)),
+        });
+    }
+    lines
+}
+
+/// Picks the syntax from the new path of a `diff --git a/<old> b/<new>` header
+fn syntax_for<'a>(syntaxes: &'a SyntaxSet, paths: &str) -> Option<&'a SyntaxReference> {
+    let path = Path::new(paths.rsplit_once(" b/").map_or(paths, |(_, new)| new));
+    let by_extension = path.extension().and_then(|ext| ext.to_str()).and_then(|ext| syntaxes.find_syntax_by_extension(ext));
+    // Files such as Makefile or Dockerfile are listed by their full name
+    by_extension.or_else(|| path.file_name().and_then(|name| name.to_str()).and_then(|name| syntaxes.find_syntax_by_extension(name)))
+}
+
+fn code_style(style: highlighting::Style, background: Option<Color>) -> Style {
+    let fg = style.foreground;
+    let mut result = Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b));
+    if let Some(bg) = background {
+        result = result.bg(bg);
+    }
+    if style.font_style.contains(highlighting::FontStyle::BOLD) {
+        result = result.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(highlighting::FontStyle::ITALIC) {
+        result = result.add_modifier(Modifier::ITALIC);
+    }
+    result
+}
+
+/// Colours a line of a mailbox patch by its role: file headers, hunk headers,
+/// added and removed lines
+fn line_style(line: &str) -> Style {
+    if line.starts_with("diff --git") || line.starts_with("+++ ") || line.starts_with("--- ") || line.starts_with("index ") {
+        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+    } else if line.starts_with("@@") {
+        Style::default().fg(Color::Cyan)
+    } else if line.starts_with('+') {
+        Style::default().fg(Color::Green)
+    } else if line.starts_with('-') {
+        Style::default().fg(Color::Red)
+    } else {
+        Style::default()
+    }
+}
@@ -0,0 +1,271 @@
+use crate::error::{Result, SyncError};
+use crate::lock::flock;
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// One line of the sync journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JournalEntry {
+    /// A source commit processed by a run; `target` is set when it produced a commit
+    Commit {
+        run_id: String,
+        timestamp: String,
+        source: String,
+        target: Option<String>,
+        status: String,
+        subject: String,
+    },
+    /// Commits of a run folded into one target commit by `--squash-by`
+    Squash {
+        run_id: String,
+        timestamp: String,
+        sources: Vec<String>,
+        target: String,
+    },
+    /// Commit of a run bumping the target's version file (`--bump-version`)
+    VersionBump {
+        run_id: String,
+        timestamp: String,
+        target: String,
+        version: String,
+    },
+    /// Summary written at the end of every run
+    Run {
+        run_id: String,
+        timestamp: String,
+        source_repo: PathBuf,
+        subdir: String,
+        synced: usize,
+        skipped: usize,
+        /// Commits left out of this run (e.g. by `--limit`)
+        pending: usize,
+        /// Last source commit processed; the next run continues after it. Stops
+        /// before the first commit skipped with a conflict, so it is tried again
+        resume_from: Option<String>,
+        /// Extra arguments the run passed to `git format-patch` (`--format-patch-args`)
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        format_patch_args: Vec<String>,
+        /// Extra arguments the run passed to `git am` (`--am-args`)
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        am_args: Vec<String>,
+    },
+}
+
+/// Append-only record of sync runs, stored in the target repository's git dir.
+/// Every read and write holds an advisory lock on `journal.lock` next to it, so
+/// concurrent invocations never interleave their lines; each entry is flushed
+/// to disk before `append` returns.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+/// A journal line `entries` could not parse, kept for `journal repair`
+#[derive(Debug, Clone)]
+pub struct CorruptLine {
+    /// 1-based line number
+    pub number: usize,
+    pub error: String,
+}
+
+impl Journal {
+    pub fn open(target_repo: &Path) -> Result<Self> {
+        let repo = Repository::open(target_repo)?;
+        let dir = repo.path().join("sync-subdir");
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            path: dir.join("journal.jsonl"),
+        })
+    }
+
+    pub fn append(&self, entry: &JournalEntry) -> Result<()> {
+        let mut line = serde_json::to_string(entry).map_err(|e| SyncError::Anyhow(e.into()))?;
+        line.push('\n');
+        let _lock = self.lock(true)?;
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(&self.path)?;
+        // A crash can leave a torn last line; start on a line of our own
+        if file.metadata()?.len() > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.insert(0, '\n');
+            }
+        }
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// All entries in order. A last line without its newline is the remains of
+    /// an interrupted write and is skipped if it does not parse; any other
+    /// unreadable line is an error, fixed by `journal repair`.
+    pub fn entries(&self) -> Result<Vec<JournalEntry>> {
+        let (entries, corrupt) = self.read()?;
+        match corrupt.first() {
+            Some(line) => Err(SyncError::Anyhow(anyhow::anyhow!(
+                "Corrupt journal line {}: {} (see `sync-subdir journal repair`)",
+                line.number,
+                line.error
+            ))),
+            None => Ok(entries),
+        }
+    }
+
+    /// Entries that parse, and the lines that don't
+    pub fn read(&self) -> Result<(Vec<JournalEntry>, Vec<CorruptLine>)> {
+        if !self.path.exists() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let content = {
+            let _lock = self.lock(false)?;
+            std::fs::read_to_string(&self.path)?
+        };
+        let complete = content.ends_with('\n');
+        let lines: Vec<(usize, &str)> = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .collect();
+        let mut entries = Vec::with_capacity(lines.len());
+        let mut corrupt = Vec::new();
+        for (i, &(number, line)) in lines.iter().enumerate() {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) if i + 1 == lines.len() && !complete => {
+                    warn!("Ignoring incomplete last line {} of {}", number + 1, self.path.display());
+                }
+                Err(e) => corrupt.push(CorruptLine { number: number + 1, error: e.to_string() }),
+            }
+        }
+        Ok((entries, corrupt))
+    }
+
+    /// Replaces the whole journal with `entries`, keeping the previous file as
+    /// `journal.jsonl.bak`. The new file is written aside and renamed into place,
+    /// so a crash leaves either the old or the new journal. Returns the backup path.
+    pub fn rewrite(&self, entries: &[JournalEntry]) -> Result<PathBuf> {
+        let mut content = String::new();
+        for entry in entries {
+            content.push_str(&serde_json::to_string(entry).map_err(|e| SyncError::Anyhow(e.into()))?);
+            content.push('\n');
+        }
+        let dir = self.path.parent().expect("journal path has a parent");
+        let backup = self.path.with_extension("jsonl.bak");
+
+        let _lock = self.lock(true)?;
+        if self.path.exists() {
+            std::fs::copy(&self.path, &backup)?;
+        }
+        let mut replacement = tempfile::Builder::new().prefix("journal.").tempfile_in(dir)?;
+        replacement.write_all(content.as_bytes())?;
+        replacement.as_file().sync_data()?;
+        replacement.persist(&self.path).map_err(|e| SyncError::Io(e.error))?;
+        Ok(backup)
+    }
+
+    /// Advisory lock shared by all readers and writers of the journal; held until dropped
+    fn lock(&self, exclusive: bool) -> Result<File> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path.with_file_name("journal.lock"))?;
+        flock(&file, exclusive)?;
+        Ok(file)
+    }
+
+    /// Last source commit a completed run of `subdir` from `source_repo` got to
+    pub fn resume_point(&self, source_repo: &Path, subdir: &str) -> Result<Option<String>> {
+        Ok(self.entries()?.into_iter().rev().find_map(|entry| match entry {
+            JournalEntry::Run { source_repo: repo, subdir: dir, resume_from: Some(commit), .. }
+                if repo == source_repo && dir == subdir => Some(commit),
+            _ => None,
+        }))
+    }
+
+    /// Source commits that produced a target commit in any run, including runs
+    /// that stopped before writing their summary
+    pub fn applied_sources(&self) -> Result<HashSet<String>> {
+        let mut sources = HashSet::new();
+        for entry in self.entries()? {
+            match entry {
+                JournalEntry::Commit { source, target: Some(_), .. } => {
+                    sources.insert(source);
+                }
+                JournalEntry::Squash { sources: squashed, .. } => sources.extend(squashed),
+                _ => {}
+            }
+        }
+        Ok(sources)
+    }
+
+    /// Target commits created by `--bump-version` rather than from a source commit
+    pub fn version_bumps(&self) -> Result<HashSet<String>> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter_map(|entry| match entry {
+                JournalEntry::VersionBump { target, .. } => Some(target),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Source to target ledger in journal order. Commits folded by `--squash-by`
+    /// map to the squashed commit; a source synced more than once has a row per run.
+    pub fn mappings(&self) -> Result<Vec<CommitMapping>> {
+        let mut mappings: Vec<CommitMapping> = Vec::new();
+        for entry in self.entries()? {
+            match entry {
+                JournalEntry::Commit { run_id, timestamp, source, target: Some(target), subject, .. } => {
+                    mappings.push(CommitMapping { source, target, subject, timestamp, run_id });
+                }
+                JournalEntry::Squash { run_id, timestamp, sources, target } => {
+                    for source in sources {
+                        match mappings.iter_mut().find(|m| m.run_id == run_id && m.source == source) {
+                            Some(mapping) => {
+                                mapping.target = target.clone();
+                                mapping.timestamp = timestamp.clone();
+                            }
+                            None => mappings.push(CommitMapping {
+                                source,
+                                target: target.clone(),
+                                subject: String::new(),
+                                timestamp: timestamp.clone(),
+                                run_id: run_id.clone(),
+                            }),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(mappings)
+    }
+}
+
+/// A source commit and the target commit it ended up in
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitMapping {
+    pub source: String,
+    pub target: String,
+    pub subject: String,
+    /// When the target commit was created
+    pub timestamp: String,
+    pub run_id: String,
+}
+
+pub fn new_run_id() -> String {
+    chrono::Local::now().format("%Y%m%d-%H%M%S").to_string()
+}
+
+pub fn timestamp() -> String {
+    chrono::Local::now().to_rfc3339()
+}
@@ -0,0 +1,323 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Something a key does in the commit list of the selection screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Up,
+    Down,
+    Top,
+    Bottom,
+    HalfPageDown,
+    HalfPageUp,
+    Toggle,
+    SelectAll,
+    DeselectAll,
+    Reverts,
+    EditMessage,
+    CycleLabel,
+    PreviewPatch,
+    ToggleLayout,
+    Columns,
+    Start,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 17] = [
+        Action::Up,
+        Action::Down,
+        Action::Top,
+        Action::Bottom,
+        Action::HalfPageDown,
+        Action::HalfPageUp,
+        Action::Toggle,
+        Action::SelectAll,
+        Action::DeselectAll,
+        Action::Reverts,
+        Action::EditMessage,
+        Action::CycleLabel,
+        Action::PreviewPatch,
+        Action::ToggleLayout,
+        Action::Columns,
+        Action::Start,
+        Action::Quit,
+    ];
+
+    /// Name of the action in the `[keys]` section of the preferences file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::HalfPageDown => "half-page-down",
+            Action::HalfPageUp => "half-page-up",
+            Action::Toggle => "toggle",
+            Action::SelectAll => "select-all",
+            Action::DeselectAll => "deselect-all",
+            Action::Reverts => "reverts",
+            Action::EditMessage => "edit-message",
+            Action::CycleLabel => "label",
+            Action::PreviewPatch => "preview",
+            Action::ToggleLayout => "layout",
+            Action::Columns => "columns",
+            Action::Start => "start",
+            Action::Quit => "quit",
+        }
+    }
+
+    /// Default bindings, in the notation of the preferences file
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Up => &["Up", "k"],
+            Action::Down => &["Down", "j"],
+            Action::Top => &["Home", "gg"],
+            Action::Bottom => &["End", "G"],
+            Action::HalfPageDown => &["Ctrl+d", "PageDown"],
+            Action::HalfPageUp => &["Ctrl+u", "PageUp"],
+            Action::Toggle => &["Space"],
+            Action::SelectAll => &["a"],
+            Action::DeselectAll => &["A"],
+            Action::Reverts => &["R"],
+            Action::EditMessage => &["m"],
+            Action::CycleLabel => &["l"],
+            Action::PreviewPatch => &["p"],
+            Action::ToggleLayout => &["v"],
+            Action::Columns => &["c"],
+            Action::Start => &["Enter"],
+            Action::Quit => &["q", "Esc"],
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// Keys of one action in the `[keys]` section: `toggle = "x"` or `down = ["j", "Down"]`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum KeyBinding {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyBinding {
+    pub fn keys(&self) -> &[String] {
+        match self {
+            KeyBinding::One(key) => std::slice::from_ref(key),
+            KeyBinding::Many(keys) => keys,
+        }
+    }
+}
+
+/// Largest count accepted before an action, e.g. the `15` of `15j`
+const MAX_COUNT: usize = 99_999;
+
+/// A key with the modifiers that matter for bindings. Shift is left out since
+/// it is already part of the character, e.g. `G`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyPress {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl From<KeyEvent> for KeyPress {
+    fn from(event: KeyEvent) -> Self {
+        Self {
+            code: event.code,
+            modifiers: event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT),
+        }
+    }
+}
+
+/// Keys typed so far towards a multi-key binding such as `gg`, and the count
+/// typed before them
+#[derive(Debug, Default)]
+pub struct PendingInput {
+    keys: Vec<KeyPress>,
+    count: Option<usize>,
+}
+
+enum Lookup {
+    Action(Action),
+    /// The keys start a longer binding
+    Prefix,
+    None,
+}
+
+/// Which action each key sequence triggers in the commit list
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Vec<KeyPress>, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .into_iter()
+            .flat_map(|action| {
+                action.default_keys().iter().map(move |keys| (parse_keys(keys).expect("valid default key"), action))
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// The default bindings with the actions named in `overrides` bound to the
+    /// given keys instead. A key taken over this way no longer triggers the
+    /// action it was bound to by default.
+    pub fn with_overrides(overrides: &BTreeMap<String, KeyBinding>) -> anyhow::Result<Self> {
+        let mut keymap = Self::default();
+        for (name, binding) in overrides {
+            let action = Action::from_name(name).ok_or_else(|| {
+                let names: Vec<&str> = Action::ALL.iter().map(|action| action.name()).collect();
+                anyhow::anyhow!("Unknown key action: {} (one of {})", name, names.join(", "))
+            })?;
+            let sequences = binding.keys().iter().map(|keys| parse_keys(keys)).collect::<anyhow::Result<Vec<_>>>()?;
+            if sequences.is_empty() {
+                anyhow::bail!("No keys given for {}", name);
+            }
+            keymap.bindings.retain(|_, bound| *bound != action);
+            for keys in sequences {
+                keymap.bindings.insert(keys, action);
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// Feeds one key press. Returns the action it completes, with the count typed
+    /// before it; digits and the start of a multi-key binding are kept in `pending`.
+    pub fn feed(&self, pending: &mut PendingInput, press: KeyPress) -> Option<(Action, Option<usize>)> {
+        pending.keys.push(press);
+        match self.lookup(&pending.keys) {
+            Lookup::Action(action) => {
+                pending.keys.clear();
+                return Some((action, pending.count.take()));
+            }
+            Lookup::Prefix => return None,
+            Lookup::None => {}
+        }
+        // A sequence that went nowhere: the last key may still be a binding of its own
+        if pending.keys.len() > 1 {
+            pending.keys.clear();
+            return self.feed(pending, press);
+        }
+        pending.keys.clear();
+
+        let digit = match press.code {
+            KeyCode::Char(c) if press.modifiers.is_empty() => c.to_digit(10),
+            _ => None,
+        };
+        match digit {
+            // A leading 0 is not a count
+            Some(digit) if digit > 0 || pending.count.is_some() => {
+                let count = pending.count.unwrap_or(0).saturating_mul(10).saturating_add(digit as usize);
+                pending.count = Some(count.min(MAX_COUNT));
+            }
+            _ => pending.count = None,
+        }
+        None
+    }
+
+    fn lookup(&self, keys: &[KeyPress]) -> Lookup {
+        if let Some(action) = self.bindings.get(keys) {
+            return Lookup::Action(*action);
+        }
+        if self.bindings.keys().any(|bound| bound.len() > keys.len() && bound.starts_with(keys)) {
+            return Lookup::Prefix;
+        }
+        Lookup::None
+    }
+
+    /// Keys bound to `action` as shown in the help line, e.g. `j/↓`
+    pub fn describe(&self, action: Action) -> String {
+        let mut keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(keys, _)| keys.iter().map(|press| display_key(*press)).collect::<String>())
+            .collect();
+        keys.sort();
+        keys.join("/")
+    }
+}
+
+/// Parses keys as written in the preferences file: the name of a special key
+/// such as `Enter`, `Space` or `PageDown`, a key with a `Ctrl+` or `Alt+`
+/// prefix, or one or more characters typed in turn, e.g. `gg`
+pub fn parse_keys(name: &str) -> anyhow::Result<Vec<KeyPress>> {
+    if let Some(code) = special_key(name) {
+        return Ok(vec![KeyPress { code, modifiers: KeyModifiers::NONE }]);
+    }
+    let lower = name.to_ascii_lowercase();
+    for (prefix, modifiers) in [("ctrl+", KeyModifiers::CONTROL), ("alt+", KeyModifiers::ALT)] {
+        if lower.starts_with(prefix) && name.len() > prefix.len() {
+            let rest = &name[prefix.len()..];
+            let code = match special_key(rest) {
+                Some(code) => code,
+                None => {
+                    let mut chars = rest.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => KeyCode::Char(c),
+                        _ => anyhow::bail!("Unknown key: {}", name),
+                    }
+                }
+            };
+            return Ok(vec![KeyPress { code, modifiers }]);
+        }
+    }
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        anyhow::bail!("Unknown key: {:?}", name);
+    }
+    Ok(name.chars().map(|c| KeyPress { code: KeyCode::Char(c), modifiers: KeyModifiers::NONE }).collect())
+}
+
+fn special_key(name: &str) -> Option<KeyCode> {
+    let code = match name.to_ascii_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        other => match other.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n @ 1..=12) => KeyCode::F(n),
+            _ => return None,
+        },
+    };
+    Some(code)
+}
+
+fn display_key(press: KeyPress) -> String {
+    let key = match press.code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Up => "↑".to_string(),
+        KeyCode::Down => "↓".to_string(),
+        KeyCode::Left => "←".to_string(),
+        KeyCode::Right => "→".to_string(),
+        KeyCode::PageUp => "PgUp".to_string(),
+        KeyCode::PageDown => "PgDn".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        other => format!("{:?}", other),
+    };
+    if press.modifiers.contains(KeyModifiers::CONTROL) {
+        format!("Ctrl+{}", key)
+    } else if press.modifiers.contains(KeyModifiers::ALT) {
+        format!("Alt+{}", key)
+    } else {
+        key
+    }
+}
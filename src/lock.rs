@@ -0,0 +1,187 @@
+use crate::error::{Result, SyncError};
+use git2::Repository;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, error};
+
+/// How often a waiting run checks whether the lock was released
+const WAIT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Exclusive claim on a target repository, so two runs never switch its
+/// branches or apply patches at the same time. The lock file lives in the
+/// target's git dir and records the owner's PID; a lock whose process is gone
+/// is stale and taken over. Released when dropped.
+///
+/// Creating the lock and removing a stale one happen under an `flock` of
+/// `lock.guard`, so two runs finding the same stale lock cannot both take it
+/// over, nor one remove the lock the other just created.
+#[derive(Debug)]
+pub struct SyncLock {
+    path: PathBuf,
+}
+
+impl SyncLock {
+    /// Takes the lock of `target_repo`. When another live process holds it,
+    /// waits for it with `wait`, or fails right away otherwise.
+    pub fn acquire(target_repo: &Path, wait: bool) -> Result<Self> {
+        let dir = Repository::open(target_repo)?.path().join("sync-subdir");
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("lock");
+
+        // The lock is linked into place complete, so no one ever reads a half-written one
+        let mut claim = tempfile::Builder::new().prefix("lock.").tempfile_in(&dir)?;
+        writeln!(claim, "{}", std::process::id())?;
+        writeln!(claim, "{}", chrono::Local::now().to_rfc3339())?;
+        claim.flush()?;
+
+        let guard_path = dir.join("lock.guard");
+        let mut announced = false;
+        loop {
+            // Released at the end of each attempt, so the owner is never kept waiting on it
+            let guard = OpenOptions::new().create(true).truncate(false).write(true).open(&guard_path)?;
+            flock(&guard, true)?;
+            match std::fs::hard_link(claim.path(), &path) {
+                Ok(()) => {
+                    debug!("Acquired sync lock {}", path.display());
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            let pid = match read_owner(&path) {
+                Some(pid) => pid,
+                // Held by a process that no longer runs, or released in the meantime
+                None => {
+                    debug!("Removing stale sync lock {}", path.display());
+                    match std::fs::remove_file(&path) {
+                        Ok(()) => continue,
+                        Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            };
+            drop(guard);
+            if !wait {
+                return Err(SyncError::Locked { pid, path });
+            }
+            if !announced {
+                eprintln!("目标仓库正被另一个同步进程 (pid {}) 使用，等待其结束...", pid);
+                announced = true;
+            }
+            std::thread::sleep(WAIT_INTERVAL);
+        }
+    }
+}
+
+impl Drop for SyncLock {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            error!("Failed to remove sync lock {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// PID of the live process holding the lock at `path`; `None` when the lock is stale
+fn read_owner(path: &Path) -> Option<u32> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        // Released between our attempt to create it and now
+        Err(_) => return None,
+    };
+    let pid = content.lines().next()?.trim().parse::<u32>().ok()?;
+    process_alive(pid).then_some(pid)
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else { return false };
+    // SAFETY: signal 0 only checks whether the process exists and may be signalled
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Without a way to check, a lock is never considered stale; remove the file by hand
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Takes an advisory lock on `file`, shared or exclusive, waiting for it; it is
+/// released when the file is closed
+#[cfg(unix)]
+pub fn flock(file: &File, exclusive: bool) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let operation = if exclusive { libc::LOCK_EX } else { libc::LOCK_SH };
+    loop {
+        // SAFETY: the descriptor belongs to `file`, which outlives the call
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(());
+        }
+        let e = std::io::Error::last_os_error();
+        if e.kind() != std::io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+/// Without `flock`, journal writes and lock takeovers are not serialized
+#[cfg(not(unix))]
+pub fn flock(_file: &File, _exclusive: bool) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestRepo;
+
+    /// Leaves a lock behind as a crashed run would, with a PID no process has
+    fn stale_lock(repo: &TestRepo) -> PathBuf {
+        let dir = repo.repo.path().join("sync-subdir");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lock");
+        std::fs::write(&path, "999999999\n2024-01-01T00:00:00+00:00\n").unwrap();
+        path
+    }
+
+    #[test]
+    fn stale_lock_is_taken_over_and_released() {
+        let repo = TestRepo::new();
+        let path = stale_lock(&repo);
+
+        let lock = SyncLock::acquire(repo.path(), false).unwrap();
+        let owner = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(owner.lines().next(), Some(std::process::id().to_string().as_str()));
+        assert!(matches!(SyncLock::acquire(repo.path(), false), Err(SyncError::Locked { .. })));
+
+        drop(lock);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn only_one_run_takes_over_a_stale_lock() {
+        let repo = TestRepo::new();
+        let target = repo.path();
+        // The race is narrow; repeating it gives a double takeover a chance to show
+        for _ in 0..100 {
+            stale_lock(&repo);
+            let barrier = std::sync::Barrier::new(8);
+            let acquired = std::thread::scope(|scope| {
+                let attempts: Vec<_> = (0..8)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            barrier.wait();
+                            SyncLock::acquire(target, false)
+                        })
+                    })
+                    .collect();
+                attempts.into_iter().map(|attempt| attempt.join().unwrap()).collect::<Vec<_>>()
+            });
+            // Every lock taken is held until all threads are done, so a second one is a double takeover
+            assert_eq!(acquired.iter().filter(|attempt| attempt.is_ok()).count(), 1);
+        }
+    }
+}
@@ -1,99 +1,556 @@
+mod backend;
+mod bundle;
 mod cli;
+mod codeowners;
+mod commands;
+mod copy;
+mod credentials;
 mod git;
+mod highlight;
+mod keymap;
 mod tui;
 mod sync;
 mod error;
+mod forge;
+mod journal;
+mod lock;
+mod mbox;
+mod notes;
+mod notify;
+mod patch;
+mod paths;
+mod prefetch;
+mod prefs;
+mod progress;
+mod redact;
+mod report;
+mod runs;
+mod schedule;
+mod session;
+mod signals;
+mod tags;
+mod telemetry;
+#[cfg(test)]
+mod testutil;
+mod theme;
+mod tmpdir;
+mod todo;
+mod ui_state;
+mod version;
+mod workspace;
 
 use crate::error::{SyncError, Result};
 use crate::sync::SyncEvent;
-use crossterm::event::{self, Event, KeyCode};
-use tracing::{info, Level};
-use tracing_subscriber;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use tracing::{info, warn};
 use tokio::sync::mpsc;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
-use cli::{build_cli, Config};
-use git::{GitManager, StashGuard, BranchGuard};
+use backend::{Engine, SyncBackend};
+use cli::{build_cli, Config, DirtySourcePolicy, QuickRange};
+use copy::CopyEngine;
+use git::{forge_commit_url, BranchTip, CommitFilter, GitManager, RangeQuery, StashGuard, BranchGuard};
+use forge::{Forge, PullRequest};
+use journal::Journal;
+use keymap::Action;
+use lock::SyncLock;
+use patch::MessageRewrite;
+use prefetch::{Prefetcher, PREFETCH_RADIUS};
+use prefs::Preferences;
+use progress::ProgressServer;
+use redact::{Redaction, SecretScanMode, SecretScanner};
+use report::{FanoutResult, ReportConfig, SyncReport};
+use session::{Label, Session};
+use signals::{Signal, Signals};
 use sync::{SyncEngine, SyncConfig};
-use tui::{App, TuiManager, AppState, ConfirmationAction};
+use tags::TagSync;
+use tui::{App, CommitLoad, LoadEvent, ConfigEditor, ConfigField, PatchPreview, TuiManager, AppState, ConfirmationAction};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
-        .with_target(false)
-        .init();
+    // Exit only once try_main has returned and its branch and stash guards have run
+    let code = try_main().await?;
+    if code != 0 {
+        std::process::exit(code);
+    }
+    Ok(())
+}
 
-    info!("Starting sync-subdir");
+/// Runs the tool and returns the process exit code
+async fn try_main() -> Result<i32> {
+    // Parse command line arguments, with the user preferences as defaults. A broken
+    // preferences file only warns, so `config edit` can still repair it
+    let prefs = Preferences::load().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        Preferences::default()
+    });
+    let matches = prefs.apply(build_cli()).get_matches();
+
+    // Initialize logging (and span export when requested)
+    let _telemetry = telemetry::init(matches.get_one::<String>("otlp_endpoint").map(String::as_str))?;
 
-    // Parse command line arguments
-    let matches = build_cli().get_matches();
-    let config = Config::from_matches(matches).map_err(SyncError::Anyhow)?;
+    info!("Starting sync-subdir");
+    match matches.subcommand() {
+        Some(("diff", sub_matches)) => return commands::diff::run(sub_matches).map(|()| 0),
+        Some(("check", sub_matches)) => return commands::check::run(sub_matches).map(|()| 0),
+        Some(("apply-bundle", sub_matches)) => return commands::apply_bundle::run(sub_matches),
+        Some(("apply-mbox", sub_matches)) => return commands::apply_mbox::run(sub_matches),
+        Some(("daemon", sub_matches)) => return commands::daemon::run(sub_matches).await.map(|()| 0),
+        Some(("extract", sub_matches)) => return commands::extract::run(sub_matches).map(|()| 0),
+        Some(("map", sub_matches)) => return commands::map::run(sub_matches),
+        Some(("journal", sub_matches)) => return commands::journal::run(sub_matches),
+        Some(("notes", sub_matches)) => return commands::notes::run(sub_matches).map(|()| 0),
+        Some(("runs", sub_matches)) => return commands::runs::run(sub_matches),
+        Some(("snapshot", sub_matches)) => return commands::snapshot::run(sub_matches).await,
+        Some(("verify", sub_matches)) => return commands::verify::run(sub_matches),
+        Some(("config", sub_matches)) => return commands::config::run(sub_matches).map(|()| 0),
+        _ => {}
+    }
+    let mut config = Config::from_matches(matches).map_err(SyncError::Anyhow)?;
 
     // Validate configuration
     validate_config(&config)?;
+    build_commit_filter(&config)?;
+    for rule in &config.msg_rewrite {
+        MessageRewrite::parse(rule)?;
+    }
+    Redaction::new(&config.redact_content)?;
+    if let Some(path) = &config.secret_allowlist {
+        SecretScanner::load_allowlist(path)?;
+    }
 
     // Initialize Git manager
     let mut git_manager = GitManager::new(&config.source_repo, &config.target_repo)?;
 
-    // Validate commits
-    git_manager.validate_commit(true, &config.start_commit)?;
-    if let Some(ref end_commit) = config.end_commit {
-        git_manager.validate_commit(true, end_commit)?;
+    // Held until main returns, after the branch and stash guards have run.
+    // --inspect never writes, so it needs no lock and leaves the lock file alone
+    let _sync_lock = if config.inspect { None } else { Some(SyncLock::acquire(&config.target_repo, config.wait)?) };
+
+    // A range given as the start commit brings its own end
+    if let Some(range) = git_manager.split_range(&config.start_commit, config.end_commit.as_deref())? {
+        config.set_range(range);
     }
 
-    // RAII guards for branch restoration
-    let source_original = git_manager.source_repo_info.original_branch.clone();
-    let target_original = git_manager.target_repo_info.original_branch.clone();
-    
-    // Switch branches if specified
-    if let Some(ref source_branch) = config.source_branch {
-        git_manager.switch_branch(true, source_branch)?;
+    if let Some(ref base) = config.base {
+        git_manager.validate_commit(false, base)?;
     }
 
-    // Create a guard for source branch
-    let mut _source_guard = BranchGuard::new(config.source_repo.clone(), true, source_original);
+    // --package names a workspace member, resolved from the manifests at the range end
+    let mut package_note = None;
+    if config.package.is_some() {
+        let rev = config.end_rev();
+        if let Some(package) = workspace::apply(&mut config, &git_manager, &rev)? {
+            info!("Package {} resolved to {:?}", package.name, config.subdir);
+            package_note = match (config.with_path_deps, package.path_deps.is_empty()) {
+                (true, false) => Some(format!(
+                    "包 {} 位于 {}，连同路径依赖 {} 一起从 {} 同步",
+                    package.name,
+                    package.dir,
+                    package.path_deps.join(", "),
+                    if config.subdir.is_empty() { "仓库根目录" } else { config.subdir.as_str() }
+                )),
+                (false, false) => Some(format!(
+                    "包 {} 位于 {}，其路径依赖 {} 不会被同步 (见 --with-path-deps)",
+                    package.name,
+                    package.dir,
+                    package.path_deps.join(", ")
+                )),
+                _ => None,
+            };
+        }
+    }
 
-    let target_branch = config.get_default_target_branch();
+    // --pending and --latest take the start commit from the journal, once the subdir is known
+    let quick_note = match config.quick {
+        Some(quick) => Some(quick_range(quick, &mut config, &git_manager)?),
+        None => None,
+    };
 
-    // Handle target branch creation/switching
-    let target_repo = git_manager.get_repository(false)?;
-    if !target_repo.revparse_single(&format!("refs/heads/{}", target_branch)).is_ok() {
-        if config.create_branch.unwrap_or(true) {
-            git_manager.create_branch(false, &target_branch)?;
+    // Resolve range endpoints (tags are peeled down to their commits)
+    let mut resolved_start = git_manager.resolve_commit(true, &config.start_commit)?;
+
+    // A read-only summary, printed without touching the target
+    if config.stat_only {
+        let mut commits = load_commits(&config, &git_manager)?;
+        if let Some(limit) = config.limit {
+            commits.truncate(limit);
+        }
+        commands::stat::print(&commits, &git_manager)?;
+        return Ok(0);
+    }
+
+    // Like --stat-only, the todo file is written without touching the target
+    if let Some(path) = &config.export_todo {
+        let mut commits = load_commits(&config, &git_manager)?;
+        if let Some(limit) = config.limit {
+            commits.truncate(limit);
+        }
+        mark_reverts(&mut commits, &git_manager);
+        let selected: Vec<bool> = commits.iter().map(git::CommitInfo::selected_by_default).collect();
+        let content = todo::render(&commits, &selected, &config.subdir);
+        if path.as_os_str() == "-" {
+            print!("{}", content);
         } else {
-            return Err(SyncError::BranchNotFound(target_branch));
+            std::fs::write(path, content)?;
+            println!("已写入 {} 个提交到 {}", commits.len(), path.display());
         }
+        return Ok(0);
+    }
+
+    // The source repository is only read from its refs and objects: its HEAD,
+    // index and worktree are never touched, so uncommitted changes there are harmless
+    let mut config_warnings: Vec<String> = package_note.into_iter().chain(quick_note).collect();
+    if config.inspect {
+        config_warnings.push("只读检查模式 (--inspect)：不切换分支、不 stash、不写入任何文件，也不能开始同步".to_string());
+    }
+    if config.source_dirty == DirtySourcePolicy::Stash {
+        config_warnings.push("--source-dirty stash 已弃用且不起作用 (同步不会改动源仓库的工作区)，请改用 proceed".to_string());
+    }
+    if git_manager.has_uncommitted_changes(true)? {
+        match config.source_dirty {
+            DirtySourcePolicy::Proceed | DirtySourcePolicy::Stash => {
+                config_warnings.push("源仓库存在未提交变更 (补丁基于已提交内容生成，不受影响)".to_string());
+            }
+            DirtySourcePolicy::Abort => {
+                return Err(SyncError::DirtyRepository(config.source_repo.clone()));
+            }
+        }
+    }
+
+    // RAII guard for target branch restoration
+    let target_original = git_manager.target_repo_info.original_branch.clone();
+
+    // The source branch is read from its ref, never checked out
+    if let Some(branch) = &config.source_branch {
+        if !git_manager.branch_exists(true, branch)? {
+            return Err(SyncError::BranchNotFound(branch.clone()));
+        }
+    }
+    let resolved_end = git_manager.resolve_commit(true, &config.end_rev())?;
+
+    // The subdir must exist in the end commit's tree; it may legitimately be missing at the start
+    git_manager.subdir_tree_id(&resolved_end, &config.subdir)?;
+
+    let subdir_created = subdir_creation(&config, &git_manager, &resolved_start)?;
+    if let Some(created) = subdir_created.as_ref().filter(|_| config.root) {
+        config.start_commit = created.clone();
+        config.include_start = Some(true);
+        resolved_start = created.clone();
+    }
+    let mut range_notes = range_warnings(&config, &git_manager, &resolved_start, &resolved_end, subdir_created.is_some())?;
+
+    // Link template for the Synced-from trailer
+    let synced_from = if config.synced_from {
+        let url = match &config.source_url {
+            Some(url) if url.contains("{sha}") => Some(url.clone()),
+            Some(url) => Some(format!("{}/commit/{{sha}}", url.trim_end_matches('/'))),
+            None => git_manager.remote_url(true, "origin")?.as_deref().and_then(forge_commit_url),
+        };
+        if url.is_none() {
+            config_warnings.push("无法从 origin 推断源仓库地址，Synced-from 只包含 commit SHA (可用 --source-url 指定)".to_string());
+        }
+        Some(url.unwrap_or_else(|| "{sha}".to_string()))
+    } else {
+        None
+    };
+
+    // Pull requests are opened against the target's origin; fail before syncing if that cannot work
+    let forge = if config.create_pr {
+        let forge = pull_request_forge(&git_manager)?;
+        credentials::api_token(&forge)?;
+        Some(forge)
     } else {
-        git_manager.switch_branch(false, &target_branch)?;
+        None
+    };
+
+    // Handle target branch creation/switching
+    let target_base = if config.inspect { None } else { prepare_target_branch(&config, &mut git_manager)? };
+
+    // Applying onto an outdated branch only moves the conflicts to the push
+    let target_upstream = git_manager.upstream_status(false, &config.get_default_target_branch())?;
+    if let Some(upstream) = target_upstream.as_ref().filter(|upstream| upstream.behind > 0) {
+        warn!(
+            "Target branch is {} commit(s) behind and {} ahead of {}",
+            upstream.behind, upstream.ahead, upstream.name
+        );
     }
 
+    range_notes.extend(unsynced_warnings(&config, &git_manager));
+
     // Create a guard for target branch
-    let mut _target_guard = BranchGuard::new(config.target_repo.clone(), false, target_original);
+    // Restoring would rewrite HEAD even when it never moved
+    let _target_guard = (!config.inspect).then(|| BranchGuard::new(config.target_repo.clone(), false, target_original));
 
     // Handle uncommitted changes in target repo
     let mut _stash_guard = None;
-    if git_manager.has_uncommitted_changes(false)? {
+    if !config.inspect && git_manager.has_uncommitted_changes(false)? {
         if config.auto_stash.unwrap_or(true) {
             let stash_message = format!("sync-subdir auto stash {}", chrono::Local::now().format("%Y%m%d-%H%M%S"));
             git_manager.stash_changes(false, &stash_message)?;
-            _stash_guard = Some(StashGuard::new(git_manager.get_repository(false)?));
+            _stash_guard = Some(StashGuard::new(git_manager.open_repository(false)?));
         } else {
             return Err(SyncError::DirtyRepository(config.target_repo.clone()));
         }
     }
 
+    // Each --target is locked, switched to the target branch and stashed like the main one
+    let mut _fanout_guards = Vec::new();
+    for path in &config.extra_targets {
+        _fanout_guards.push(prepare_fanout_target(&config, path, &mut config_warnings)?);
+    }
+    if !config.extra_targets.is_empty() {
+        let listed: Vec<String> = config.extra_targets.iter().map(|path| path.display().to_string()).collect();
+        config_warnings.push(format!("同一批提交还会同步到 {} 个目标仓库: {}", listed.len(), listed.join(", ")));
+    }
+
+    if config.inspect {
+        // Nothing is recorded
+    } else if let Err(e) = Preferences::remember(&config.source_repo, &config.subdir, &config.target_repo) {
+        warn!("Failed to record recent repositories: {}", e);
+    }
+
     // Initialize TUI
-    let mut tui_manager = TuiManager::new()
+    let mut tui_manager = TuiManager::new(config.appearance)
         .map_err(SyncError::Anyhow)?;
 
     let mut app = App::new(config.clone());
+    app.target_base = target_base;
+    app.target_upstream = target_upstream;
+    app.source_head = git_manager.source_repo_info.original_branch.clone();
+    app.resolved_start = Some(resolved_start);
+    app.resolved_end = Some(resolved_end);
+    app.subdir_created = subdir_created;
+    app.base_warnings = config_warnings.clone();
+    app.config_warnings = config_warnings.into_iter().chain(range_notes).collect();
+    app.synced_from = synced_from;
+    app.forge = forge;
+    app.ui_state = ui_state::UiState::load();
+    app.keymap = prefs.keymap().unwrap_or_default();
+    match Session::open(&config.target_repo) {
+        Ok(session) => app.session = session,
+        Err(e) => warn!("Failed to open selection session: {}", e),
+    }
+    if let Some(endpoint) = &config.progress_socket {
+        app.progress_server = Some(ProgressServer::bind(endpoint).await?);
+    }
 
     // Run the application
     run_application(&mut app, &mut tui_manager, &mut git_manager).await?;
 
-    Ok(())
+    // Leave the alternate screen first so the summary stays visible; the branch
+    // and stash guards run after this, when main returns
+    drop(tui_manager);
+    if let Some(report) = app.sync_report.as_ref().filter(|report| report.aborted) {
+        eprintln!("{}", report);
+    }
+
+    if app.sync_failed {
+        return Ok(1);
+    }
+    Ok(app.sync_report.as_ref().map_or(0, SyncReport::exit_code))
+}
+
+/// Unsynced target commits listed in the ConfigReview warnings; the rest are counted
+const UNSYNCED_LISTED: usize = 5;
+
+/// Commits on the target branch since the last synced one that the sync journal
+/// does not know, i.e. manual edits to the synced directory
+/// Warnings about the selected range: paths colliding by case, a subdir missing
+/// at the start commit and an unusually large range. Run at startup and again
+/// after the range is edited on the review screen; a range over `--max-commits`
+/// or colliding on a case-insensitive target is an error.
+fn range_warnings(
+    config: &Config,
+    git_manager: &GitManager,
+    resolved_start: &str,
+    resolved_end: &str,
+    subdir_created: bool,
+) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+
+    // Paths that differ only by case merge silently on a case-insensitive target
+    let collisions = git_manager.case_collisions(resolved_end, &config.subdir)?;
+    if !collisions.is_empty() {
+        let listed = collisions.iter().map(|group| group.join(" / ")).collect::<Vec<_>>().join("; ");
+        if git_manager.target_ignores_case()? {
+            return Err(SyncError::CaseCollision(listed));
+        }
+        warnings.push(format!(
+            "子目录中有 {} 组路径仅大小写不同，在不区分大小写的文件系统 (macOS、Windows) 上会合并为一个文件: {}",
+            collisions.len(),
+            listed
+        ));
+    }
+
+    if !subdir_created && git_manager.subdir_tree_id(resolved_start, &config.subdir).is_err() {
+        warnings.push(format!(
+            "子目录 {} 在起始 commit {} 中不存在",
+            config.subdir,
+            git::short_id(resolved_start)
+        ));
+    }
+
+    // A mistyped start commit can select the whole history; catch that before loading
+    let range_size = git_manager.count_commits_in_range(&range_query(config))?;
+    if let Some(max) = config.max_commits {
+        if range_size > max {
+            return Err(SyncError::TooManyCommits { count: range_size, max });
+        }
+    }
+    if range_size > config.commit_warning {
+        warnings.push(format!(
+            "提交范围包含 {} 个提交 (超过 {})，预计耗时约 {}，请确认起始 commit 是否正确",
+            range_size,
+            config.commit_warning,
+            format_estimate(range_size)
+        ));
+    }
+    Ok(warnings)
+}
+
+/// Warnings listing the commits made directly to the mirror since the last sync,
+/// the usual cause of conflicts and drift. Opening the journal may create its
+/// directory, so --inspect skips the check; a failing check is only logged.
+fn unsynced_warnings(config: &Config, git_manager: &GitManager) -> Vec<String> {
+    if config.inspect {
+        return Vec::new();
+    }
+    let commits = match unsynced_target_commits(config, git_manager) {
+        Ok(commits) => commits,
+        Err(e) => {
+            warn!("Failed to check the target branch for unsynced commits: {}", e);
+            return Vec::new();
+        }
+    };
+    if commits.is_empty() {
+        return Vec::new();
+    }
+    let mut warnings = vec![format!(
+        "目标分支在上次同步之后有 {} 个不是由同步生成的提交修改了目标目录，可能导致冲突或与源仓库不一致:",
+        commits.len()
+    )];
+    for commit in commits.iter().take(UNSYNCED_LISTED) {
+        warnings.push(format!("  {} {} ({})", git::short_id(&commit.id), commit.subject, commit.author));
+    }
+    if commits.len() > UNSYNCED_LISTED {
+        warnings.push(format!("  … 另有 {} 个", commits.len() - UNSYNCED_LISTED));
+    }
+    warnings
+}
+
+fn unsynced_target_commits(config: &Config, git_manager: &GitManager) -> Result<Vec<git::CommitInfo>> {
+    let journal = Journal::open(&config.target_repo)?;
+    let mut synced: HashSet<String> = journal.mappings()?.into_iter().map(|mapping| mapping.target).collect();
+    // Version bump commits are the sync's own doing too
+    synced.extend(journal.version_bumps()?);
+    if synced.is_empty() {
+        return Ok(Vec::new());
+    }
+    git_manager.unsynced_target_commits(&config.get_default_target_branch(), &synced, &config.target_dir)
+}
+
+/// Forge of the target's `origin`, where `--create-pr` opens pull requests
+fn pull_request_forge(git_manager: &GitManager) -> Result<Forge> {
+    let url = git_manager.remote_url(false, PR_REMOTE)?
+        .ok_or_else(|| SyncError::Remote(format!("Target repository has no '{}' remote", PR_REMOTE)))?;
+    Forge::from_remote_url(&url)
+        .ok_or_else(|| SyncError::Remote(format!("Unsupported forge for remote URL: {}", url)))
+}
+
+/// Held for a `--target` repository until main returns; fields drop in order,
+/// so the stash is popped on the restored branch before the lock is released
+struct FanoutGuards {
+    _stash: Option<StashGuard<'static>>,
+    _branch: BranchGuard,
+    _lock: SyncLock,
+}
+
+/// Readies `--target` repository `path` the way main readies the target
+fn prepare_fanout_target(config: &Config, path: &Path, warnings: &mut Vec<String>) -> Result<FanoutGuards> {
+    let lock = SyncLock::acquire(path, config.wait)?;
+    let mut git_manager = GitManager::new(&config.source_repo, path)?;
+    if config.create_pr {
+        credentials::api_token(&pull_request_forge(&git_manager)?)?;
+    }
+    let original = git_manager.target_repo_info.original_branch.clone();
+    if let Some(base) = prepare_target_branch(config, &mut git_manager)? {
+        warnings.push(format!("{}: 目标分支 {} 已从 {} 创建", path.display(), config.get_default_target_branch(), base));
+    }
+    let branch = BranchGuard::new(path.to_path_buf(), false, original);
+    let mut stash = None;
+    if git_manager.has_uncommitted_changes(false)? {
+        if !config.auto_stash.unwrap_or(true) {
+            return Err(SyncError::DirtyRepository(path.to_path_buf()));
+        }
+        let stash_message = format!("sync-subdir auto stash {}", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+        git_manager.stash_changes(false, &stash_message)?;
+        stash = Some(StashGuard::new(git_manager.open_repository(false)?));
+    }
+    Ok(FanoutGuards { _stash: stash, _branch: branch, _lock: lock })
+}
+
+/// Remote of the target repository that synced branches are pushed to
+const PR_REMOTE: &str = "origin";
+
+const DEFAULT_PR_TITLE: &str = "Sync {subdir} ({count} commits)";
+
+/// Capacity of the sync event channel; the engine waits when the UI falls behind
+const SYNC_CHANNEL_CAPACITY: usize = 256;
+
+/// Commit loading progress events buffered for the interface; older ones are dropped
+const LOAD_CHANNEL_CAPACITY: usize = 16;
+
+/// Capacity of the prefetched commit details channel; one window fits in it
+const DETAILS_CHANNEL_CAPACITY: usize = 2 * PREFETCH_RADIUS + 1;
+
+/// Checks out the target branch, creating it from `--base` or the remote branch
+/// of the same name when it does not exist. Returns a description of the base
+/// commit when a branch was created.
+fn prepare_target_branch(config: &Config, git_manager: &mut GitManager) -> Result<Option<String>> {
+    let target_branch = config.get_default_target_branch();
+    if !git_manager.branch_exists(false, &target_branch)? {
+        if !config.create_branch.unwrap_or(true) {
+            return Err(SyncError::BranchNotFound(target_branch));
+        }
+        let (base_name, base_id) = git_manager.create_branch(false, &target_branch, config.base.as_deref())?;
+        return Ok(Some(format!("{} ({})", base_name, git::short_id(&base_id))));
+    }
+
+    if config.base.is_some() {
+        info!("Target branch {} already exists, ignoring --base", target_branch);
+    }
+    // Syncing onto a branch that misses remote commits only moves the conflicts to the push
+    let remote = git_manager.remote_branch_status(false, &target_branch)?;
+    if let Some(remote) = remote.filter(|remote| remote.behind > 0) {
+        match config.target_tip {
+            Some(BranchTip::Local) => info!("Keeping {} although {} has {} more commit(s)", target_branch, remote.name, remote.behind),
+            // Nothing local is left behind, so there is no choice to make
+            None if remote.ahead == 0 => {
+                info!("Fast-forwarding {} to {} ({} commit(s) behind)", target_branch, remote.name, remote.behind);
+                git_manager.reset_branch(false, &target_branch, &remote.name)?;
+                return Ok(None);
+            }
+            Some(BranchTip::Remote) => {
+                info!("Moving {} to {} ({} local commit(s) left behind)", target_branch, remote.name, remote.ahead);
+                git_manager.reset_branch(false, &target_branch, &remote.name)?;
+                return Ok(None);
+            }
+            None => {
+                return Err(SyncError::BranchDiverged {
+                    branch: target_branch,
+                    remote: remote.name,
+                    ahead: remote.ahead,
+                    behind: remote.behind,
+                });
+            }
+        }
+    }
+    git_manager.switch_branch(false, &target_branch)?;
+    Ok(None)
 }
 
 async fn run_application(
@@ -101,9 +558,19 @@ async fn run_application(
     tui_manager: &mut TuiManager,
     git_manager: &mut GitManager,
 ) -> Result<()> {
-    let (sync_tx, mut sync_rx) = mpsc::unbounded_channel::<SyncEvent>();
-    
+    let (sync_tx, mut sync_rx) = mpsc::channel::<SyncEvent>(SYNC_CHANNEL_CAPACITY);
+    let mut signals = Signals::new()?;
+    let (details_tx, mut details_rx) = mpsc::channel(DETAILS_CHANNEL_CAPACITY);
+    let prefetcher = Prefetcher::spawn(
+        git_manager.source_repo_info.path.clone(),
+        git_manager.target_repo_info.path.clone(),
+        details_tx,
+    );
+
     loop {
+        finish_commit_load(app, git_manager);
+        load_commit_stats(app, git_manager);
+        prefetch_commit_details(app, &prefetcher);
         tui_manager.draw(app).map_err(SyncError::Anyhow)?;
 
         // Handle events (Non-blocking selection between TUI keys and Sync events)
@@ -111,15 +578,51 @@ async fn run_application(
             // TUI Events
             Ok(has_event) = tokio::task::spawn_blocking(|| event::poll(Duration::from_millis(50))) => {
                 if let Ok(true) = has_event {
-                    if let Ok(Event::Key(key_event)) = event::read() {
-                        handle_key_event(app, tui_manager, git_manager, key_event.code, &sync_tx).await?;
+                    match event::read() {
+                        // Raw mode turns Ctrl+Z and Ctrl+C into key presses instead of signals
+                        Ok(Event::Key(key_event))
+                            if key_event.code == KeyCode::Char('z') && key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+                        {
+                            tui_manager.suspend().map_err(SyncError::Anyhow)?;
+                        }
+                        Ok(Event::Key(key_event))
+                            if key_event.code == KeyCode::Char('c') && key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+                        {
+                            request_abort(app);
+                            app.should_quit = true;
+                        }
+                        Ok(Event::Key(key_event)) => {
+                            handle_key_event(app, tui_manager, git_manager, key_event, &sync_tx).await?;
+                        }
+                        Ok(Event::Resize(width, height)) => tui_manager.resize(width, height).map_err(SyncError::Anyhow)?,
+                        _ => {}
                     }
                 }
             }
+
+            // Signals from outside, e.g. `kill -TSTP`
+            signal = signals.recv() => match signal {
+                Signal::Suspend => tui_manager.suspend().map_err(SyncError::Anyhow)?,
+                Signal::Interrupt => {
+                    request_abort(app);
+                    app.should_quit = true;
+                }
+            },
             
-            // Sync Events from background task
+            // Sync Events from background task, coalesced into a single redraw
             Some(event) = sync_rx.recv() => {
                 handle_sync_event(app, event);
+                while let Ok(event) = sync_rx.try_recv() {
+                    handle_sync_event(app, event);
+                }
+            }
+
+            // Commit details computed ahead of the cursor
+            Some((id, details)) = details_rx.recv() => {
+                app.commit_details.insert(id, details);
+                while let Ok((id, details)) = details_rx.try_recv() {
+                    app.commit_details.insert(id, details);
+                }
             }
 
             // Redraw/Idle
@@ -127,6 +630,24 @@ async fn run_application(
         }
 
         if app.should_quit {
+            if let Some(load) = &app.commit_load {
+                load.cancel.store(true, Ordering::Relaxed);
+            }
+            // Let a running sync stop at a commit boundary: its worktree is removed
+            // and the target checkout updated before the branch guards run
+            if let Some(mut task) = app.sync_task.take() {
+                request_abort(app);
+                tui_manager.draw(app).map_err(SyncError::Anyhow)?;
+                loop {
+                    tokio::select! {
+                        _ = &mut task => break,
+                        Some(event) = sync_rx.recv() => handle_sync_event(app, event),
+                    }
+                }
+                while let Ok(event) = sync_rx.try_recv() {
+                    handle_sync_event(app, event);
+                }
+            }
             break;
         }
     }
@@ -134,174 +655,1216 @@ async fn run_application(
     Ok(())
 }
 
+/// Asks a running sync to stop after the commit it is applying. Returns false
+/// when no sync is running.
+fn request_abort(app: &mut App) -> bool {
+    if app.state != AppState::Progress || app.sync_task.as_ref().is_none_or(|task| task.is_finished()) {
+        return false;
+    }
+    if !app.sync_cancel.swap(true, Ordering::Relaxed) {
+        app.push_log("ABORT", "等待当前提交完成");
+        app.status_message = "正在中断，等待当前提交完成...".to_string();
+    }
+    true
+}
+
 async fn handle_key_event(
     app: &mut App,
     tui_manager: &mut TuiManager,
     git_manager: &mut GitManager,
-    code: KeyCode,
-    sync_tx: &mpsc::UnboundedSender<SyncEvent>,
+    key_event: KeyEvent,
+    sync_tx: &mpsc::Sender<SyncEvent>,
 ) -> Result<()> {
+    let code = key_event.code;
     match app.state {
+        AppState::ConfigReview if app.config_editor.is_some() => handle_config_edit_key(app, git_manager, code),
         AppState::ConfigReview => {
             match code {
-                KeyCode::Enter => app.state = AppState::FileSelection,
+                KeyCode::Enter => {
+                    app.state = AppState::FileSelection;
+                    if !app.loaded_changes && app.commit_load.is_none() {
+                        app.commit_load = Some(start_commit_load(&app.config, git_manager));
+                    }
+                }
+                KeyCode::Char('e') => app.config_editor = Some(ConfigEditor::default()),
+                KeyCode::Char('r') if app.starts_before_subdir() => {
+                    if let Some(created) = app.subdir_created.clone() {
+                        app.config.start_commit = created.clone();
+                        app.config.include_start = Some(true);
+                        app.resolved_start = Some(created);
+                    }
+                }
                 KeyCode::Char('q') | KeyCode::Esc => app.should_quit = true,
                 _ => {}
             }
         }
+        AppState::FileSelection if app.patch_preview.is_some() => handle_patch_preview_key(app, code),
+        AppState::FileSelection if app.column_editor.is_some() => handle_column_editor_key(app, code),
         AppState::FileSelection => {
             if !app.loaded_changes {
-                app.status_message = "正在加载提交历史...".to_string();
-                match load_commits(&app.config, git_manager) {
-                    Ok(commits) => {
-                        app.set_commits(commits);
-                        app.loaded_changes = true;
-                        if app.commits.is_empty() {
-                            app.status_message = "未发现任何相关提交历史".to_string();
-                            app.state = AppState::Completed;
-                        } else {
-                            app.list_state.select(Some(0));
-                        }
-                    }
-                    Err(e) => {
-                        app.status_message = format!("加载提交失败: {}", e);
-                        app.state = AppState::Completed;
+                match &app.commit_load {
+                    None => app.commit_load = Some(start_commit_load(&app.config, git_manager)),
+                    Some(load) if matches!(code, KeyCode::Char('q') | KeyCode::Esc) => {
+                        load.cancel.store(true, Ordering::Relaxed);
                     }
+                    Some(_) => {}
                 }
                 return Ok(());
             }
 
-            match code {
-                KeyCode::Up => app.previous(),
-                KeyCode::Down => app.next(),
-                KeyCode::Char(' ') => app.toggle_commit_selection(),
-                KeyCode::Char('a') => app.select_all(),
-                KeyCode::Char('A') => app.deselect_all(),
-                KeyCode::Enter => {
-                    if app.get_selected_count() > 0 {
-                        app.state = AppState::Confirmation;
-                        app.current_confirmation = Some(ConfirmationAction::ExecuteSync);
+            let Some((action, count)) = app.keymap.feed(&mut app.pending_input, key_event.into()) else {
+                return Ok(());
+            };
+            let repeat = count.unwrap_or(1) as isize;
+            match (action, count) {
+                (Action::Up, None) => app.previous(),
+                (Action::Down, None) => app.next(),
+                (Action::Up, _) => app.move_by(-repeat),
+                (Action::Down, _) => app.move_by(repeat),
+                // With a count both jump to that row, as in vim
+                (Action::Top | Action::Bottom, Some(row)) => app.go_to(row.saturating_sub(1)),
+                (Action::Top, None) => app.go_to(0),
+                (Action::Bottom, None) => app.go_to(usize::MAX),
+                (Action::HalfPageDown, _) => app.move_by(half_page() * repeat),
+                (Action::HalfPageUp, _) => app.move_by(-half_page() * repeat),
+                (Action::Toggle, _) => app.toggle_commit_selection(),
+                (Action::SelectAll, _) => app.select_all(),
+                (Action::DeselectAll, _) => app.deselect_all(),
+                (Action::Reverts, _) => {
+                    app.status_message = if app.toggle_reverts() {
+                        "已选择撤销对中的提交".to_string()
+                    } else {
+                        "已取消选择撤销对中的提交".to_string()
+                    };
+                }
+                (Action::Start | Action::EditMessage | Action::CycleLabel, _) if app.config.inspect => {
+                    app.status_message = "只读检查模式 (--inspect) 下不能同步、编辑提交信息或设置标签".to_string();
+                }
+                (Action::EditMessage, _) => edit_commit_message(app, tui_manager, git_manager)?,
+                (Action::CycleLabel, _) => {
+                    app.cycle_label();
+                    if let Err(e) = app.session.save() {
+                        warn!("Failed to save selection session: {}", e);
+                        app.status_message = format!("保存标签失败: {}", e);
                     }
                 }
-                KeyCode::Char('q') | KeyCode::Esc => app.should_quit = true,
+                (Action::PreviewPatch, _) => preview_patch(app, git_manager),
+                (Action::ToggleLayout, _) => {
+                    app.ui_state.layout = app.ui_state.layout.toggled();
+                    save_ui_state(app, "保存布局失败");
+                }
+                (Action::Columns, _) => app.column_editor = Some(0),
+                (Action::Start, _) if app.get_selected_count() > 0 && app.config.yes => {
+                    start_sync(app, tui_manager, git_manager, sync_tx)?;
+                }
+                (Action::Start, _) if app.get_selected_count() > 0 => {
+                    app.state = AppState::Confirmation;
+                    app.current_confirmation = Some(ConfirmationAction::ExecuteSync);
+                }
+                (Action::Quit, _) => app.should_quit = true,
                 _ => {}
             }
         }
         AppState::Confirmation => {
             if let Some(confirmation_type) = &app.current_confirmation {
-                let message = get_confirmation_message(confirmation_type, &app.config)?;
+                let message = get_confirmation_message(confirmation_type, app)?;
                 let result = tui_manager.show_confirmation(&message).map_err(SyncError::Anyhow)?;
 
                 app.confirmation_result = Some(result);
 
-                match confirmation_type {
-                    ConfirmationAction::ExecuteSync => {
-                        if result {
-                            app.state = AppState::Progress;
-                            app.start_time = std::time::Instant::now();
-                            start_background_sync(app, git_manager, sync_tx.clone());
-                        } else {
-                            app.state = AppState::FileSelection;
-                        }
+                if let ConfirmationAction::ExecuteSync = confirmation_type {
+                    if result {
+                        start_sync(app, tui_manager, git_manager, sync_tx)?;
+                    } else {
+                        app.state = AppState::FileSelection;
                     }
-                    _ => {}
                 }
                 app.current_confirmation = None;
             }
         }
         AppState::Progress => {
-            // In progress, we might want to handle 'q' to abort in the future
-            if code == KeyCode::Char('q') || code == KeyCode::Esc {
-                // For now, just mark quit. Real-time abort needs more logic.
+            // Stop after the current commit and show what was synced so far
+            if (code == KeyCode::Char('q') || code == KeyCode::Esc) && !request_abort(app) {
                 app.should_quit = true;
             }
         }
-        AppState::Completed => {
-            if matches!(code, KeyCode::Enter | KeyCode::Char('q') | KeyCode::Esc) {
-                app.should_quit = true;
+        AppState::ConflictQueue => {
+            let count = app.sync_report.as_ref().map_or(0, |r| r.conflicts.len());
+            match code {
+                KeyCode::Up if count > 0 => app.conflict_selected = (app.conflict_selected + count - 1) % count,
+                KeyCode::Down if count > 0 => app.conflict_selected = (app.conflict_selected + 1) % count,
+                KeyCode::Char('r') if app.conflict_retry.is_none() => retry_conflict(app, git_manager, sync_tx),
+                KeyCode::Enter if app.conflict_retry.is_none() => app.state = AppState::Completed,
+                KeyCode::Char('q') | KeyCode::Esc => app.should_quit = true,
+                _ => {}
+            }
+        }
+        AppState::Completed => match code {
+            KeyCode::Enter | KeyCode::Char('q') | KeyCode::Esc => app.should_quit = true,
+            KeyCode::Char('w') => app.show_warnings = !app.show_warnings,
+            _ => {}
+        },
+    }
+    Ok(())
+}
+
+/// Keys of the ConfigReview edit mode: navigate fields, flip toggles, and type
+/// into the inline input of text fields
+fn handle_config_edit_key(app: &mut App, git_manager: &mut GitManager, code: KeyCode) {
+    let Some(editor) = app.config_editor.as_mut() else { return };
+    let field = ConfigField::ALL[editor.selected];
+
+    if let Some(input) = editor.input.as_mut() {
+        match code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => {
+                editor.input = None;
+                editor.error = None;
+            }
+            KeyCode::Enter => {
+                let value = input.clone();
+                let result = apply_config_edit(app, git_manager, field, &value);
+                if let Some(editor) = app.config_editor.as_mut() {
+                    match result {
+                        Ok(()) => {
+                            editor.input = None;
+                            editor.error = None;
+                        }
+                        Err(e) => editor.error = Some(e.to_string()),
+                    }
+                }
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    match code {
+        KeyCode::Up => editor.selected = (editor.selected + ConfigField::ALL.len() - 1) % ConfigField::ALL.len(),
+        KeyCode::Down => editor.selected = (editor.selected + 1) % ConfigField::ALL.len(),
+        KeyCode::Enter | KeyCode::Char(' ') if field.is_toggle() => {
+            let result = apply_config_edit(app, git_manager, field, "");
+            if let Some(editor) = app.config_editor.as_mut() {
+                editor.error = result.err().map(|e| e.to_string());
+            }
+        }
+        KeyCode::Enter => {
+            editor.input = Some(field.value(&app.config));
+            editor.error = None;
+        }
+        KeyCode::Esc | KeyCode::Char('e') => app.config_editor = None,
+        _ => {}
+    }
+}
+
+/// Validates a changed field the same way startup does and, if it passes, stores
+/// it in the config. Toggles ignore `value` and flip the current setting.
+fn apply_config_edit(app: &mut App, git_manager: &mut GitManager, field: ConfigField, value: &str) -> Result<()> {
+    let value = value.trim();
+    let optional = |value: &str| (!value.is_empty()).then(|| value.to_string());
+    let mut config = app.config.clone();
+    match field {
+        ConfigField::Subdir => {
+            if value.trim_matches('/').is_empty() {
+                return Err(SyncError::PathNotFound(value.into()));
+            }
+            config.subdir = value.trim_matches('/').to_string();
+        }
+        ConfigField::SourceBranch => config.source_branch = optional(value),
+        ConfigField::TargetBranch => config.target_branch = optional(value),
+        ConfigField::StartCommit => match git_manager.split_range(value, None)? {
+            Some(range) => config.set_range(range),
+            None => config.start_commit = value.to_string(),
+        },
+        ConfigField::EndCommit => config.end_commit = optional(value),
+        ConfigField::IncludeStart => config.include_start = Some(!config.include_start.unwrap_or(true)),
+        ConfigField::NoMerge => config.no_merge = Some(!config.no_merge.unwrap_or(true)),
+        ConfigField::FollowDir => config.follow_dir = !config.follow_dir,
+        ConfigField::DryRun => config.dry_run = !config.dry_run,
+    }
+
+    // Resolve everything before touching any branch, so a bad value changes nothing
+    if let Some(branch) = &config.source_branch {
+        if !git_manager.branch_exists(true, branch)? {
+            return Err(SyncError::BranchNotFound(branch.clone()));
+        }
+    }
+    let resolved_start = git_manager.resolve_commit(true, &config.start_commit)?;
+    let resolved_end = git_manager.resolve_commit(true, &config.end_rev())?;
+    git_manager.subdir_tree_id(&resolved_end, &config.subdir)?;
+
+    match field {
+        ConfigField::TargetBranch if config.inspect => {}
+        ConfigField::TargetBranch => {
+            app.target_base = prepare_target_branch(&config, git_manager)?;
+            app.target_upstream = git_manager.upstream_status(false, &config.get_default_target_branch())?;
+        }
+        _ => {}
+    }
+
+    // Keep reporting the creation commit after the start was moved onto it
+    let subdir_created = match subdir_creation(&config, git_manager, &resolved_start)? {
+        Some(created) => Some(created),
+        None => app.subdir_created.clone().filter(|created| *created == resolved_start),
+    };
+    let mut range_notes = range_warnings(&config, git_manager, &resolved_start, &resolved_end, subdir_created.is_some())?;
+    range_notes.extend(unsynced_warnings(&config, git_manager));
+    app.config_warnings = app.base_warnings.iter().cloned().chain(range_notes).collect();
+    app.subdir_created = subdir_created;
+    app.resolved_start = Some(resolved_start);
+    app.resolved_end = Some(resolved_end);
+    app.config = config;
+    Ok(())
+}
+
+/// Lines moved by PgUp/PgDn in the patch preview
+const PREVIEW_PAGE: isize = 20;
+
+/// Scrolls or closes the patch preview of the FileSelection screen
+fn handle_patch_preview_key(app: &mut App, code: KeyCode) {
+    let Some(preview) = app.patch_preview.as_mut() else { return };
+    match code {
+        KeyCode::Up | KeyCode::Char('k') => preview.scroll_by(-1),
+        KeyCode::Down | KeyCode::Char('j') => preview.scroll_by(1),
+        KeyCode::PageUp => preview.scroll_by(-PREVIEW_PAGE),
+        KeyCode::PageDown | KeyCode::Char(' ') => preview.scroll_by(PREVIEW_PAGE),
+        KeyCode::Home | KeyCode::Char('g') => preview.scroll = 0,
+        KeyCode::End | KeyCode::Char('G') => preview.scroll_to_end(),
+        KeyCode::Char('p') | KeyCode::Char('q') | KeyCode::Esc => app.patch_preview = None,
+        _ => {}
+    }
+}
+
+/// Starts syncing the selected commits in the background, after the messages
+/// were edited when `--edit` is set
+fn start_sync(
+    app: &mut App,
+    tui_manager: &mut TuiManager,
+    git_manager: &mut GitManager,
+    sync_tx: &mpsc::Sender<SyncEvent>,
+) -> Result<()> {
+    if app.config.edit {
+        edit_selected_messages(app, tui_manager, git_manager)?;
+    }
+    app.state = AppState::Progress;
+    app.start_time = std::time::Instant::now();
+    app.sync_task = Some(start_background_sync(app, git_manager, app.selected(), false, sync_tx.clone()));
+    Ok(())
+}
+
+/// Re-attempts the highlighted conflict against the target as it is now, so
+/// whatever the user fixed there in the meantime is picked up
+fn retry_conflict(app: &mut App, git_manager: &GitManager, sync_tx: &mpsc::Sender<SyncEvent>) {
+    let Some(conflict) = app.sync_report.as_ref().and_then(|r| r.conflicts.get(app.conflict_selected)) else { return };
+    let Some(commit) = app.commits.iter().find(|c| c.id == conflict.id).cloned() else { return };
+    app.push_log("RETRY", format!("{} {}", git::short_id(&commit.id), commit.subject));
+    app.conflict_retry = Some(commit.id.clone());
+    app.sync_task = Some(start_background_sync(app, git_manager, vec![commit], true, sync_tx.clone()));
+}
+
+/// Folds a re-attempted conflict into the run's report, keeping the report file
+/// up to date, and leaves the queue once no conflicts remain
+fn finish_conflict_retry(app: &mut App, retry: SyncReport) {
+    let Some(report) = app.sync_report.as_mut() else { return };
+    report.merge_retry(retry);
+    if let Some(path) = &app.config.report {
+        if let Err(e) = report.write_json(path) {
+            warn!("Failed to update report {}: {}", path.display(), e);
+        }
+    }
+    app.status_message = report.to_string();
+    match report.conflicts.len() {
+        0 => app.state = AppState::Completed,
+        remaining => app.conflict_selected = app.conflict_selected.min(remaining - 1),
+    }
+}
+
+/// Rows `Ctrl+d`/`Ctrl+u` move: half of the commit table on the current terminal
+fn half_page() -> isize {
+    // Header, instructions and the table's borders and title row
+    const CHROME_ROWS: u16 = 9;
+    let (_, rows) = crossterm::terminal::size().unwrap_or((0, 24));
+    (rows.saturating_sub(CHROME_ROWS) / 2).max(1) as isize
+}
+
+fn handle_column_editor_key(app: &mut App, code: KeyCode) {
+    let Some(selected) = app.column_editor else { return };
+    let count = app.ui_state.columns.len();
+    match code {
+        KeyCode::Up => app.column_editor = Some((selected + count - 1) % count),
+        KeyCode::Down => app.column_editor = Some((selected + 1) % count),
+        KeyCode::Char(' ') => app.ui_state.toggle(selected),
+        KeyCode::Left | KeyCode::Char('-') => app.ui_state.resize(selected, -1),
+        KeyCode::Right | KeyCode::Char('+') => app.ui_state.resize(selected, 1),
+        KeyCode::Char('c') | KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter => {
+            app.column_editor = None;
+            save_ui_state(app, "保存列设置失败");
+        }
+        _ => {}
+    }
+}
+
+/// Persists the layout and column settings; under `--inspect` they only last
+/// until exit
+fn save_ui_state(app: &mut App, failure: &str) {
+    if app.config.inspect {
+        return;
+    }
+    if let Err(e) = app.ui_state.save() {
+        warn!("{}", e);
+        app.status_message = format!("{}: {}", failure, e);
+    }
+}
+
+/// Loads the commit history on its own thread: walking a long range and pairing
+/// reverts can take long enough to freeze the interface. Progress arrives as
+/// [`LoadEvent`]s; the thread is never joined on quit, so Ctrl+C does not wait
+/// for it.
+fn start_commit_load(config: &Config, git_manager: &GitManager) -> CommitLoad {
+    let config = config.clone();
+    let source_path = git_manager.source_repo_info.path.clone();
+    let target_path = git_manager.target_repo_info.path.clone();
+    let (events_tx, events) = mpsc::channel(LOAD_CHANNEL_CAPACITY);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let cancelled = cancel.clone();
+    let thread = std::thread::spawn(move || {
+        // Progress is only ever shown as the latest event, so a full channel drops it
+        let mut report = |event| {
+            let _ = events_tx.try_send(event);
+            !cancelled.load(Ordering::Relaxed)
+        };
+        let git_manager = GitManager::new(&source_path, &target_path)?;
+        let mut commits = load_commits_with(&config, &git_manager, &mut report)?;
+        let total = commits.len();
+        if let Some(limit) = config.limit {
+            commits.truncate(limit);
+        }
+        if !report(LoadEvent::Step("查找撤销提交")) {
+            return Err(SyncError::Cancelled);
+        }
+        // Only pairs that are both on the list can be left out together
+        let pairs = mark_reverts(&mut commits, &git_manager);
+        Ok((commits, total, pairs))
+    });
+    CommitLoad { thread, events, cancel, latest: None, started: std::time::Instant::now() }
+}
+
+/// Shows the loaded commits once the load thread has finished
+fn finish_commit_load(app: &mut App, git_manager: &GitManager) {
+    let Some(load) = app.commit_load.as_mut() else { return };
+    while let Ok(event) = load.events.try_recv() {
+        load.latest = Some(event);
+    }
+    if !load.thread.is_finished() {
+        return;
+    }
+    let Some(load) = app.commit_load.take() else { return };
+    let result = load
+        .thread
+        .join()
+        .unwrap_or_else(|_| Err(SyncError::Anyhow(anyhow::anyhow!("commit loading thread panicked"))));
+    match result {
+        Ok((commits, total, pairs)) => {
+            app.total_pending = total;
+            app.set_commits(commits);
+            app.loaded_changes = true;
+            if let Err(e) = apply_todo(app, git_manager) {
+                app.status_message = format!("读取 todo 文件失败: {}", e);
+                app.state = AppState::Completed;
+                return;
+            }
+            if app.commits.is_empty() {
+                app.status_message = "未发现任何相关提交历史".to_string();
+                app.state = AppState::Completed;
+            } else {
+                app.list_state.select(Some(0));
+                let fixups = sync::autosquash_order(&app.commits)
+                    .iter()
+                    .filter(|(i, first)| i != first)
+                    .count();
+                let unowned = app.commits.iter().filter(|commit| commit.unowned).count();
+                if let Some(todo) = &app.todo {
+                    app.status_message = format!(
+                        "已按 todo 文件选择 {} 个提交，同步时按文件中的顺序应用和合并",
+                        todo.entries.len()
+                    );
+                } else if pairs > 0 {
+                    app.status_message = format!(
+                        "发现 {} 对提交与其撤销提交，已取消选择 ({} 重新选择)",
+                        pairs,
+                        app.keymap.describe(Action::Reverts)
+                    );
+                } else if let (true, Some(owner)) = (unowned > 0, &app.config.codeowners_filter) {
+                    app.status_message = format!("{} 个提交没有改动 {} 拥有的文件，已取消选择", unowned, owner);
+                } else if fixups > 0 && app.config.autosquash {
+                    app.status_message = format!("{} 个 fixup!/squash! 提交将并入它们所指的提交", fixups);
+                } else if fixups > 0 && app.config.engine == Engine::Patch && app.config.squash_by.is_none() {
+                    app.status_message = format!("发现 {} 个 fixup!/squash! 提交，可用 --autosquash 把它们并入所指的提交", fixups);
+                }
             }
         }
+        // Back to the review, where the range can be corrected
+        Err(SyncError::Cancelled) => {
+            app.status_message = "已取消加载提交历史".to_string();
+            app.state = AppState::ConfigReview;
+        }
+        Err(e) => {
+            app.status_message = format!("加载提交失败: {}", e);
+            app.state = AppState::Completed;
+        }
+    }
+}
+
+/// Commits whose statistics are computed per redraw, so a long list fills in
+/// without holding up the interface
+const STATS_BATCH: usize = 32;
+
+/// Computes the next batch of missing diff statistics while a statistics column is shown
+fn load_commit_stats(app: &mut App, git_manager: &GitManager) {
+    if app.state != AppState::FileSelection || !app.ui_state.shows_stats() {
+        return;
+    }
+    for commit in app.commits.iter_mut().filter(|commit| commit.stats.is_none()).take(STATS_BATCH) {
+        let stats = git_manager.commit_stats(&commit.id, &commit.subdir).unwrap_or_else(|e| {
+            warn!("Failed to compute statistics of {}: {}", commit.id, e);
+            Default::default()
+        });
+        commit.stats = Some(stats);
+    }
+}
+
+/// Asks the prefetcher for the details of the commits around the cursor that
+/// are not cached yet, nearest first, while the details pane is shown
+fn prefetch_commit_details(app: &mut App, prefetcher: &Prefetcher) {
+    if app.state != AppState::FileSelection || app.ui_state.layout != ui_state::SelectionLayout::Full {
+        return;
+    }
+    let Some(cursor) = app.list_state.selected() else { return };
+    let mut missing = Vec::new();
+    for distance in 0..=PREFETCH_RADIUS {
+        let below = cursor.checked_add(distance);
+        let above = cursor.checked_sub(distance).filter(|_| distance > 0);
+        for i in [below, above].into_iter().flatten() {
+            let Some(commit) = app.commits.get(i) else { continue };
+            if !app.commit_details.touch(&commit.id) {
+                missing.push((commit.id.clone(), commit.subdir.clone()));
+            }
+        }
+    }
+    prefetcher.request(missing);
+}
+
+/// Shows the patch `git am` will get for the highlighted commit, generating it
+/// with the run's settings the first time and from the cache afterwards
+fn preview_patch(app: &mut App, git_manager: &GitManager) {
+    let Some(commit) = app.list_state.selected().and_then(|i| app.commits.get(i)).cloned() else {
+        return;
+    };
+    // The patch of a sync goes through a temporary file; --inspect renders the diff in memory
+    if app.config.inspect && !app.patch_cache.contains_key(&commit.id) {
+        match git_manager.commit_diff(&commit.id, &commit.subdir) {
+            Ok(diff) => app.patch_cache.insert(commit.id.clone(), diff),
+            Err(e) => {
+                app.status_message = format!("生成差异失败: {}", e);
+                return;
+            }
+        };
+    }
+    if !app.patch_cache.contains_key(&commit.id) {
+        let mut sync_config = build_sync_config(app);
+        if app.config.respect_export_ignore {
+            if let Ok(patterns) = git_manager.export_ignore_patterns(&app.config.end_rev(), &sync_config.subdir) {
+                sync_config.excludes.extend(patterns);
+            }
+        }
+        let patch = match SyncEngine::new(sync_config, true).preview_patch(git_manager, &commit) {
+            Ok(Some(patch)) => patch,
+            Ok(None) => "merge 提交，同步时将跳过".to_string(),
+            Err(SyncError::EmptyPatch) => "补丁为空，同步时将跳过".to_string(),
+            Err(e) => {
+                app.status_message = format!("生成补丁失败: {}", e);
+                return;
+            }
+        };
+        app.patch_cache.insert(commit.id.clone(), patch);
+    }
+    app.patch_preview = Some(PatchPreview::new(&commit.id, &app.patch_cache[&commit.id], !app.config.no_color));
+}
+
+/// Opens the editor on the highlighted commit's message and remembers the result
+fn edit_commit_message(app: &mut App, tui_manager: &mut TuiManager, git_manager: &GitManager) -> Result<()> {
+    let Some(commit) = app.list_state.selected().and_then(|i| app.commits.get(i)) else {
+        return Ok(());
+    };
+    let commit_id = commit.id.clone();
+    let current = match app.edited_messages.get(&commit_id) {
+        Some(message) => message.clone(),
+        None => git_manager.commit_message(&commit_id)?,
+    };
+
+    match tui_manager.edit_text(&current) {
+        Ok(edited) if edited.is_empty() => {
+            app.status_message = "提交信息为空，保留原信息".to_string();
+        }
+        Ok(edited) => {
+            if edited != current.trim() {
+                app.patch_cache.remove(&commit_id);
+                app.edited_messages.insert(commit_id, edited);
+            }
+        }
+        Err(e) => app.status_message = format!("编辑提交信息失败: {}", e),
+    }
+    Ok(())
+}
+
+/// Reads `--todo` against the loaded commits: selects the commits it lists and
+/// takes the new subjects of its `reword` lines as edited messages
+fn apply_todo(app: &mut App, git_manager: &GitManager) -> Result<()> {
+    let Some(path) = app.config.todo.clone() else { return Ok(()) };
+    let todo = todo::Todo::load(&path, &app.commits)?;
+    for (commit, selected) in app.commits.iter().zip(app.selected_commits.iter_mut()) {
+        *selected = todo.contains(&commit.id);
+    }
+    for entry in todo.entries.iter().filter(|entry| entry.action == todo::TodoAction::Reword) {
+        let message = git_manager.commit_message(&entry.id)?;
+        app.edited_messages.insert(entry.id.clone(), todo::reword(&message, &entry.text));
+    }
+    app.todo = Some(todo);
+    Ok(())
+}
+
+/// `--edit`: walks every selected commit through the editor before syncing
+fn edit_selected_messages(app: &mut App, tui_manager: &mut TuiManager, git_manager: &GitManager) -> Result<()> {
+    for i in 0..app.commits.len() {
+        if app.selected_commits[i] {
+            app.list_state.select(Some(i));
+            edit_commit_message(app, tui_manager, git_manager)?;
+        }
     }
     Ok(())
 }
 
 fn handle_sync_event(app: &mut App, event: SyncEvent) {
+    if let Some(server) = &app.progress_server {
+        server.publish(&event);
+    }
     match event {
         SyncEvent::Progress { current, total, subject, status } => {
             app.progress = current as f64 / total as f64;
             app.status_message = format!("[{}] {}", status, subject);
+            app.push_log(status, subject);
         }
-        SyncEvent::Completed(stats) => {
+        SyncEvent::Completed(report) if app.conflict_retry.take().is_some() => finish_conflict_retry(app, *report),
+        SyncEvent::Completed(report) => {
             app.progress = 1.0;
             app.end_time = Some(std::time::Instant::now());
-            app.sync_stats = Some(stats.clone());
-            app.status_message = format!(
-                "同步完成: 总计 {}, 同步 {}, 跳过 {}",
-                stats.total_commits,
-                stats.synced_commits,
-                stats.skipped_commits
-            );
-            app.state = AppState::Completed;
+            app.status_message = report.to_string();
+            // The main target of a fan-out run failed, the others may not have
+            if let Some(error) = &report.error {
+                app.push_log("ERROR", error.lines().next().unwrap_or_default());
+                app.sync_failed = true;
+            }
+            // Staged commits never reached the target branch, so there is nothing to retry against
+            app.state = if report.conflicts.is_empty() || report.staging_branch.is_some() {
+                AppState::Completed
+            } else {
+                AppState::ConflictQueue
+            };
+            app.conflict_selected = 0;
+            if app.forge.is_some() && opens_pull_request(&report, app.config.dry_run) {
+                app.status_message.push_str("\n正在推送分支并创建 PR...");
+            }
+            if app.config.notify {
+                let title = if report.error.is_some() {
+                    "同步失败"
+                } else if report.aborted {
+                    "同步已中断"
+                } else {
+                    "同步完成"
+                };
+                notify::finished(title, &app.status_message);
+            }
+            app.sync_report = Some(*report);
+        }
+        SyncEvent::Target { index, event } => {
+            let Some(target) = app.fanout.get_mut(index) else { return };
+            match *event {
+                SyncEvent::Progress { current, total, subject, status } => {
+                    target.progress = current as f64 / total as f64;
+                    target.status = format!("[{}] {}", status, subject);
+                }
+                SyncEvent::Completed(report) => {
+                    target.progress = 1.0;
+                    target.status = format!("完成: 同步 {}, 跳过 {}", report.synced_commits, report.skipped_commits);
+                }
+                SyncEvent::PullRequestCreated(url) => app.push_log("PR", url),
+                SyncEvent::Warning(warning) => app.push_log("WARN", format!("目标 {}: {}", index + 1, warning)),
+                SyncEvent::Error(err) => {
+                    target.failed = true;
+                    target.status = err.lines().next().unwrap_or_default().to_string();
+                }
+                SyncEvent::Target { .. } => {}
+            }
+        }
+        SyncEvent::Warning(warning) => app.push_log("WARN", warning.to_string()),
+        SyncEvent::PullRequestCreated(url) => {
+            app.push_log("PR", url.clone());
+            app.status_message = format!("{}\nPR 已创建: {}", app.status_message, url);
+        }
+        // A failed retry leaves the run's result as it was
+        SyncEvent::Error(err) if app.conflict_retry.take().is_some() => {
+            app.push_log("ERROR", err.lines().next().unwrap_or_default());
         }
         SyncEvent::Error(err) => {
+            app.push_log("ERROR", err.lines().next().unwrap_or_default());
             app.status_message = format!("同步失败: {}", err);
             app.state = AppState::Completed;
+            app.sync_failed = true;
+            if app.config.notify {
+                notify::finished("同步失败", &err);
+            }
         }
     }
 }
 
+/// Engine settings for the current selection, shared by the run and the patch preview
+fn build_sync_config(app: &App) -> SyncConfig {
+    SyncConfig {
+        subdir: app.config.subdir.clone(),
+        target_dir: app.config.target_dir.clone(),
+        excludes: app.config.redact_paths.clone(),
+        redaction: Redaction::new(&app.config.redact_content).unwrap_or_default(),
+        secrets: (app.config.secret_scan != SecretScanMode::Off).then(|| {
+            let allowlist = app.config.secret_allowlist
+                .as_deref()
+                .and_then(|path| SecretScanner::load_allowlist(path).ok())
+                .unwrap_or_default();
+            SecretScanner::new(allowlist)
+        }),
+        block_secrets: app.config.secret_scan == SecretScanMode::Block,
+        deferred_commits: app.total_pending.saturating_sub(app.commits.len()),
+        messages: app.edited_messages.clone(),
+        message_rewrites: app.config.msg_rewrite
+            .iter()
+            .map(|rule| MessageRewrite::parse(rule))
+            .collect::<Result<Vec<_>>>()
+            .unwrap_or_default(),
+        synced_from: app.synced_from.clone(),
+        subject_prefix: app.config.subject_prefix.clone(),
+        octopus: app.config.octopus,
+        squash_by: app.config.squash_by,
+        autosquash: app.config.autosquash,
+        todo: app.todo.clone(),
+        notes: app.config.notes,
+        tags: app.config.sync_tags.then(|| TagSync {
+            prefix: app.config.tag_prefix.clone().unwrap_or_default(),
+            sign: app.config.sign_tags,
+        }),
+        bump_version: app.config.bump_version,
+        version_file: app.config.version_file.clone(),
+        tmp_dir: app.config.tmp_dir.clone(),
+        keep_patches: app.config.keep_patches,
+        retry: app.config.retry,
+        format_patch_args: app.config.format_patch_args.clone(),
+        am_args: app.config.am_args.clone(),
+        continue_on_conflict: app.config.continue_on_conflict,
+        staging_branch: app.config.staging_branch,
+        sync_delete: app.config.sync_delete.unwrap_or(true),
+        verify: app.config.verify,
+        no_mode_changes: app.config.no_mode_changes,
+    }
+}
+
+/// What a background run takes from the App; each fan-out target gets its own copy
+#[derive(Clone)]
+struct RunSettings {
+    sync_config: SyncConfig,
+    engine: Engine,
+    dry_run: bool,
+    retry: bool,
+    respect_export_ignore: bool,
+    end_commit: String,
+    export_dir: Option<PathBuf>,
+    export_mbox: Option<PathBuf>,
+    cancel: Arc<AtomicBool>,
+    warnings: Vec<String>,
+    labels: BTreeMap<String, Label>,
+}
+
+/// `--create-pr` settings, the same for every target
+#[derive(Clone)]
+struct PullRequestSettings {
+    branch: String,
+    base: String,
+    title: String,
+    push_notes: bool,
+}
+
+/// Syncs `commits` in the background. A `retry` re-attempts conflicts from the
+/// conflict queue; the report file and the pull request are left to the UI.
+/// With `--target`, the other targets run alongside and the one `Completed`
+/// event carries their results in [`SyncReport::fanout`].
 fn start_background_sync(
     app: &App,
     git_manager: &GitManager,
-    tx: mpsc::UnboundedSender<SyncEvent>,
-) {
-    let sync_config = SyncConfig {
-        subdir: app.config.subdir.clone(),
+    commits: Vec<git::CommitInfo>,
+    retry: bool,
+    tx: mpsc::Sender<SyncEvent>,
+) -> tokio::task::JoinHandle<()> {
+    let settings = RunSettings {
+        sync_config: build_sync_config(app),
+        engine: app.config.engine,
+        dry_run: app.config.dry_run,
+        retry,
+        respect_export_ignore: app.config.respect_export_ignore,
+        end_commit: app.config.end_rev(),
+        export_dir: app.config.export_bundle.clone(),
+        export_mbox: app.config.export_mbox.clone(),
+        cancel: app.sync_cancel.clone(),
+        warnings: app.config_warnings.clone(),
+        labels: commits
+            .iter()
+            .filter_map(|commit| Some((commit.id.clone(), *app.session.labels.get(&commit.id)?)))
+            .collect(),
+    };
+    let pr = PullRequestSettings {
+        branch: app.config.get_default_target_branch(),
+        base: app.config.pr_base.clone().unwrap_or_else(|| "main".to_string()),
+        title: app.config.pr_title_template.clone().unwrap_or_else(|| DEFAULT_PR_TITLE.to_string()),
+        push_notes: settings.sync_config.notes,
     };
 
-    let selected_commits: Vec<_> = app.commits
-        .iter()
-        .zip(app.selected_commits.iter())
-        .filter_map(|(commit, &selected)| if selected { Some(commit.clone()) } else { None })
-        .collect();
-
-    // Clone git_manager is not possible because it's not Clone, 
-    // and Repository is not thread-safe. 
-    // We need to recreate GitManager in the task or just move it if it's the last sync.
-    // However, GitManager only contains metadata, it doesn't hold Repository long-term.
-    // So we can clone the RepoInfo.
-    
+    // The background task opens its own GitManager: the UI keeps using its handles
+    // while the sync runs, and the two never wait on each other's locks.
     let source_path = git_manager.source_repo_info.path.clone();
     let target_path = git_manager.target_repo_info.path.clone();
     let dry_run = app.config.dry_run;
+    let report_path = app.config.report.clone().filter(|_| !retry);
+    let keep_runs = if retry { 0 } else { app.config.keep_runs };
+    let forge = app.forge.clone().filter(|_| !retry);
+    // The conflict queue only holds conflicts of the main target
+    let fanout = if retry { Vec::new() } else { app.config.extra_targets.clone() };
 
     tokio::spawn(async move {
-        match GitManager::new(&source_path, &target_path) {
-            Ok(gm) => {
-                let mut engine = SyncEngine::new(sync_config, dry_run);
-                if let Err(e) = engine.sync_commits(&gm, &selected_commits, tx.clone()).await {
-                    let _ = tx.send(SyncEvent::Error(e.to_string()));
-                }
+        let gm = match GitManager::new(&source_path, &target_path) {
+            Ok(gm) => gm,
+            Err(e) => {
+                let _ = tx.send(SyncEvent::Error(format!("Failed to initialize GitManager in background: {}", e))).await;
+                return;
             }
+        };
+
+        let fanout_pr = forge.is_some().then(|| pr.clone());
+        let (gm, result) = run_all_targets(settings, gm, commits, fanout, fanout_pr, tx.clone()).await;
+        let report = match result {
+            Ok(report) => report,
             Err(e) => {
-                let _ = tx.send(SyncEvent::Error(format!("Failed to initialize GitManager in background: {}", e)));
+                let _ = tx.send(SyncEvent::Error(e)).await;
+                return;
+            }
+        };
+        if !report.fanout.is_empty() {
+            let _ = tx.send(SyncEvent::Completed(Box::new(report.clone()))).await;
+        }
+        if let Some(path) = report_path {
+            if let Err(e) = report.write_json(&path) {
+                let _ = tx.send(SyncEvent::Error(format!("写入报告失败: {}", e))).await;
+            }
+        }
+        // The archive is a convenience; failing to write it does not fail the run
+        if keep_runs > 0 {
+            if let Err(e) = runs::dir(&target_path).and_then(|dir| runs::archive(&dir, &report, keep_runs)) {
+                warn!("Failed to archive the run report: {}", e);
+            }
+        }
+        if let Some(forge) = forge.filter(|_| opens_pull_request(&report, dry_run)) {
+            let result = tokio::task::spawn_blocking(move || {
+                open_pull_request(&gm, &forge, &pr.branch, &pr.base, &pr.title, pr.push_notes, &report)
+            }).await;
+            let event = match result {
+                Ok(Ok(url)) => SyncEvent::PullRequestCreated(url),
+                Ok(Err(e)) => SyncEvent::Error(format!("创建 PR 失败: {}", e)),
+                Err(e) => SyncEvent::Error(format!("创建 PR 失败: {}", e)),
+            };
+            let _ = tx.send(event).await;
+        }
+    })
+}
+
+/// Syncs `commits` to the target of `gm` and to each of the `fanout` targets at
+/// once. With fan-out the result is the aggregate report, also when the main
+/// target failed: its error is then recorded in [`SyncReport::error`] and the
+/// other targets' results are kept. Errors only come back without fan-out.
+async fn run_all_targets(
+    settings: RunSettings,
+    gm: GitManager,
+    commits: Vec<git::CommitInfo>,
+    fanout: Vec<PathBuf>,
+    pr: Option<PullRequestSettings>,
+    tx: mpsc::Sender<SyncEvent>,
+) -> (GitManager, std::result::Result<SyncReport, String>) {
+    let source_path = gm.source_repo_info.path.clone();
+    let runs: Vec<_> = fanout
+        .into_iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let (target_tx, forwarder) = forward_events(Some(index), tx.clone());
+            let run = run_fanout_target(source_path.clone(), path.clone(), settings.clone(), commits.clone(), pr.clone(), target_tx);
+            let handle = tokio::spawn(async move {
+                let result = run.await;
+                let _ = forwarder.await;
+                result
+            });
+            (path, handle)
+        })
+        .collect();
+    if runs.is_empty() {
+        return run_engine(settings, gm, commits, tx).await;
+    }
+
+    let config = ReportConfig {
+        source_repo: source_path,
+        target_repo: gm.target_repo_info.path.clone(),
+        subdir: settings.sync_config.subdir.clone(),
+        target_dir: settings.sync_config.target_dir.clone(),
+        engine: settings.engine.as_str().to_string(),
+        dry_run: settings.dry_run,
+        export_dir: settings.export_dir.clone().or_else(|| settings.export_mbox.clone()),
+    };
+    let total_commits = commits.len();
+    // The main target's Completed waits for the aggregate report
+    let (main_tx, main_forwarder) = forward_events(None, tx);
+    let (gm, result) = run_engine(settings, gm, commits, main_tx).await;
+    let _ = main_forwarder.await;
+    let mut fanout = Vec::new();
+    for (path, handle) in runs {
+        fanout.push(handle.await.unwrap_or_else(|e| FanoutResult {
+            target_repo: path,
+            report: None,
+            error: Some(e.to_string()),
+            pull_request: None,
+        }));
+    }
+
+    let mut report = result.unwrap_or_else(|error| SyncReport {
+        run_id: journal::new_run_id(),
+        config,
+        total_commits,
+        error: Some(error),
+        ..Default::default()
+    });
+    report.fanout = fanout;
+    (gm, Ok(report))
+}
+
+/// Runs the engine over `commits` against the target of `gm`, off the runtime
+/// workers, and hands `gm` back; errors come back as the message shown to the user
+async fn run_engine(
+    mut settings: RunSettings,
+    gm: GitManager,
+    commits: Vec<git::CommitInfo>,
+    tx: mpsc::Sender<SyncEvent>,
+) -> (GitManager, std::result::Result<SyncReport, String>) {
+    if settings.respect_export_ignore {
+        match gm.export_ignore_patterns(&settings.end_commit, &settings.sync_config.subdir) {
+            Ok(patterns) => settings.sync_config.excludes.extend(patterns),
+            Err(e) => return (gm, Err(format!("读取 export-ignore 规则失败: {}", e))),
+        }
+    }
+    let journal = match Journal::open(&gm.target_repo_info.path) {
+        Ok(journal) => Some(journal),
+        Err(e) => {
+            tracing::error!("Failed to open sync journal: {}", e);
+            None
+        }
+    };
+    let (gm, result) = match settings.engine {
+        Engine::Patch => {
+            let mut engine = SyncEngine::new(settings.sync_config, settings.dry_run)
+                .with_cancel(settings.cancel)
+                .with_warnings(settings.warnings)
+                .with_labels(settings.labels);
+            if let Some(dir) = settings.export_dir {
+                engine = engine.with_export(dir);
+            }
+            if let Some(path) = settings.export_mbox {
+                engine = engine.with_mbox(path);
+            }
+            if settings.retry {
+                engine = engine.retrying_conflicts();
+            }
+            if let Some(journal) = journal {
+                engine = engine.with_journal(journal);
+            }
+            backend::execute_blocking(engine, gm, commits, tx).await
+        }
+        Engine::Copy => {
+            let mut engine = CopyEngine::new(settings.sync_config, settings.dry_run)
+                .with_cancel(settings.cancel)
+                .with_warnings(settings.warnings)
+                .with_labels(settings.labels);
+            if let Some(journal) = journal {
+                engine = engine.with_journal(journal);
+            }
+            backend::execute_blocking(engine, gm, commits, tx).await
+        }
+    };
+    (gm, result.map_err(|e| e.to_string()))
+}
+
+/// Syncs `commits` to fan-out target `target_path` and, with `--create-pr`,
+/// opens a pull request on the forge of that target's own origin
+async fn run_fanout_target(
+    source_path: PathBuf,
+    target_path: PathBuf,
+    settings: RunSettings,
+    commits: Vec<git::CommitInfo>,
+    pr: Option<PullRequestSettings>,
+    tx: mpsc::Sender<SyncEvent>,
+) -> FanoutResult {
+    let mut result = FanoutResult { target_repo: target_path.clone(), report: None, error: None, pull_request: None };
+    let dry_run = settings.dry_run;
+    let run = match GitManager::new(&source_path, &target_path) {
+        Ok(gm) => match run_engine(settings, gm, commits, tx.clone()).await {
+            (gm, Ok(report)) => Ok((gm, report)),
+            (_, Err(e)) => Err(e),
+        },
+        Err(e) => Err(e.to_string()),
+    };
+    let (gm, report) = match run {
+        Ok(run) => run,
+        Err(e) => {
+            let _ = tx.send(SyncEvent::Error(e.clone())).await;
+            result.error = Some(e);
+            return result;
+        }
+    };
+    let Some(pr) = pr.filter(|_| opens_pull_request(&report, dry_run)) else {
+        result.report = Some(report);
+        return result;
+    };
+    let opened = tokio::task::spawn_blocking(move || {
+        let url = pull_request_forge(&gm)
+            .and_then(|forge| open_pull_request(&gm, &forge, &pr.branch, &pr.base, &pr.title, pr.push_notes, &report));
+        (url, report)
+    })
+    .await;
+    match opened {
+        Ok((Ok(url), report)) => {
+            let _ = tx.send(SyncEvent::PullRequestCreated(url.clone())).await;
+            result.pull_request = Some(url);
+            result.report = Some(report);
+        }
+        Ok((Err(e), report)) => {
+            let message = format!("创建 PR 失败: {}", e);
+            let _ = tx.send(SyncEvent::Error(message.clone())).await;
+            result.error = Some(message);
+            result.report = Some(report);
+        }
+        Err(e) => result.error = Some(format!("创建 PR 失败: {}", e)),
+    }
+    result
+}
+
+/// Whether a finished run left a branch to open a pull request for
+fn opens_pull_request(report: &SyncReport, dry_run: bool) -> bool {
+    !dry_run && report.error.is_none() && !report.aborted && report.synced_commits > 0 && report.staging_branch.is_none()
+}
+
+/// Passes the events of one target of a fan-out run on to `tx` until the
+/// returned sender is dropped: those of `--target` number `index` wrapped in
+/// [`SyncEvent::Target`], those of the main target (`None`) as they are, except
+/// `Completed` and `Error`: the aggregate report sent once all targets finished
+/// stands for both
+fn forward_events(index: Option<usize>, tx: mpsc::Sender<SyncEvent>) -> (mpsc::Sender<SyncEvent>, tokio::task::JoinHandle<()>) {
+    let (target_tx, mut target_rx) = mpsc::channel(SYNC_CHANNEL_CAPACITY);
+    let forwarder = tokio::spawn(async move {
+        while let Some(event) = target_rx.recv().await {
+            let event = match (index, event) {
+                (None, SyncEvent::Completed(_) | SyncEvent::Error(_)) => continue,
+                (None, event) => event,
+                (Some(index), event) => SyncEvent::Target { index, event: Box::new(event) },
+            };
+            if tx.send(event).await.is_err() {
+                break;
             }
         }
     });
+    (target_tx, forwarder)
+}
+
+/// Pushes the synced branch, and with `--notes` the notes ref, and opens a pull
+/// request with the run summary as its body
+fn open_pull_request(
+    git_manager: &GitManager,
+    forge: &Forge,
+    branch: &str,
+    base: &str,
+    title_template: &str,
+    push_notes: bool,
+    report: &SyncReport,
+) -> Result<String> {
+    let (token, source) = credentials::api_token(forge)?;
+    info!("Using API token for {} from {}", forge.host(), source);
+    git_manager.push_branch(PR_REMOTE, branch)?;
+    if push_notes {
+        // The pull request doesn't need the notes, so a rejected push only warns
+        if let Err(e) = notes::push(&git_manager.target_repo_info.path, PR_REMOTE) {
+            warn!("Failed to push {}: {}", notes::NOTES_REF, e);
+        }
+    }
+    forge.create_pull_request(&token, &PullRequest {
+        head: branch.to_string(),
+        base: base.to_string(),
+        title: forge::render_title(title_template, &report.config.subdir, branch, report.synced_commits),
+        body: forge::report_body(report),
+    })
+}
+
+fn range_query(config: &Config) -> RangeQuery {
+    RangeQuery {
+        subdir: config.subdir.clone(),
+        start: config.start_commit.clone(),
+        end: config.end_rev(),
+        include_start: config.include_start.unwrap_or(true),
+        first_parent: config.no_merge.unwrap_or(true),
+        follow_dir: config.follow_dir,
+        order: config.order,
+    }
+}
+
+/// Sets the range of `--pending` or `--latest`: the commits after the last
+/// source commit a completed sync of the subdir got to, or only the newest of
+/// them. Returns a note for the configuration review.
+fn quick_range(quick: QuickRange, config: &mut Config, git_manager: &GitManager) -> Result<String> {
+    let option = match quick {
+        QuickRange::Pending => "--pending",
+        QuickRange::Latest => "--latest",
+    };
+    git_manager.check_object_formats()?;
+    let resume = Journal::open(&config.target_repo)?.resume_point(&git_manager.source_repo_info.path, &config.subdir)?;
+    let Some(resume) = resume else {
+        return Err(SyncError::Anyhow(anyhow::anyhow!(
+            "{}: the journal of {} has no completed sync of {} from {}; pass a start commit for the first sync",
+            option,
+            config.target_repo.display(),
+            if config.subdir.is_empty() { "the repository root" } else { config.subdir.as_str() },
+            config.source_repo.display()
+        )));
+    };
+    config.start_commit = resume.clone();
+    config.include_start = Some(false);
+    if quick == QuickRange::Pending {
+        return Ok(format!("{}: 同步上次同步到达的 {} 之后的全部提交", option, git::short_id(&resume)));
+    }
+
+    let commits = load_commits(config, git_manager)?;
+    let Some(newest) = commits.last() else {
+        return Ok(format!("{}: 上次同步到达的 {} 之后没有新的提交", option, git::short_id(&resume)));
+    };
+    let mut note = format!("{}: 只同步最新的提交 {} {}", option, git::short_id(&newest.id), newest.subject);
+    if commits.len() > 1 {
+        note.push_str(&format!("，之前的 {} 个待同步提交会被跳过，下次同步从它之后继续", commits.len() - 1));
+    }
+    config.start_commit = newest.id.clone();
+    config.end_commit = Some(newest.id.clone());
+    config.include_start = Some(true);
+    Ok(note)
+}
+
+/// Commit that created the subdir, when it is missing at the start commit and
+/// appears later in the range
+fn subdir_creation(config: &Config, git_manager: &GitManager, resolved_start: &str) -> Result<Option<String>> {
+    if git_manager.subdir_tree_id(resolved_start, &config.subdir).is_ok() {
+        return Ok(None);
+    }
+    git_manager.subdir_creation_commit(&range_query(config))
+}
+
+/// Links the commits of each commit and revert pair to each other and returns
+/// the number of pairs. Detection is only a suggestion, so a failure is logged
+/// and leaves the list as it was.
+fn mark_reverts(commits: &mut [git::CommitInfo], git_manager: &GitManager) -> usize {
+    let pairs = match git_manager.revert_pairs(commits) {
+        Ok(pairs) => pairs,
+        Err(e) => {
+            warn!("Failed to detect reverted commits: {}", e);
+            return 0;
+        }
+    };
+    for &(original, revert) in &pairs {
+        commits[original].revert_pair = Some(commits[revert].id.clone());
+        commits[revert].revert_pair = Some(commits[original].id.clone());
+    }
+    pairs.len()
 }
 
 fn load_commits(config: &Config, git_manager: &GitManager) -> Result<Vec<git::CommitInfo>> {
-    let end_commit = config.end_commit.as_ref().map(|s| s.as_str()).unwrap_or("HEAD");
-    let include_start = config.include_start.unwrap_or(true);
-    let first_parent = config.no_merge.unwrap_or(true);
+    load_commits_with(config, git_manager, |_| true)
+}
+
+/// [`load_commits`], telling `on_progress` how far it got; when that returns
+/// false loading stops with [`SyncError::Cancelled`]
+fn load_commits_with(
+    config: &Config,
+    git_manager: &GitManager,
+    mut on_progress: impl FnMut(LoadEvent) -> bool,
+) -> Result<Vec<git::CommitInfo>> {
+    let filter = build_commit_filter(config)?;
+    let mut commits = git_manager.walk_commits_in_range(&range_query(config), &filter, |walk| on_progress(LoadEvent::Walk(walk)))?;
+    if config.ignore_whitespace_only {
+        if !on_progress(LoadEvent::Step("检查只改动空白的提交")) {
+            return Err(SyncError::Cancelled);
+        }
+        for commit in &mut commits {
+            commit.whitespace_only = git_manager.is_whitespace_only(&commit.id, &commit.subdir)?;
+        }
+    }
+    if let Some(owner) = &config.codeowners_filter {
+        if !on_progress(LoadEvent::Step("按 CODEOWNERS 检查提交")) {
+            return Err(SyncError::Cancelled);
+        }
+        // Ownership as the range end defines it, the rules the mirror is kept by now
+        let end = config.end_rev();
+        let owners = git_manager.codeowners(&end)?.ok_or_else(|| {
+            SyncError::Anyhow(anyhow::anyhow!(
+                "--codeowners-filter: no CODEOWNERS file at {} (looked for {})",
+                end,
+                codeowners::LOCATIONS.join(", ")
+            ))
+        })?;
+        for commit in &mut commits {
+            let paths = git_manager.changed_paths(&commit.id, &commit.subdir)?;
+            commit.unowned = !paths.iter().any(|path| owners.owned_by(path, owner));
+        }
+    }
+    config.octopus.check(&commits)?;
+    Ok(commits)
+}
 
-    git_manager.get_commits_in_range(
-        &config.subdir,
-        &config.start_commit,
-        end_commit,
-        include_start,
-        first_parent,
-    )
+/// Rough per-commit cost of format-patch + am, used for the large range warning
+const ESTIMATED_SECS_PER_COMMIT: f64 = 0.3;
+
+fn format_estimate(commits: usize) -> String {
+    let secs = (commits as f64 * ESTIMATED_SECS_PER_COMMIT).round() as u64;
+    if secs >= 3600 {
+        format!("{} 小时 {} 分钟", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{} 分钟", secs.div_ceil(60))
+    } else {
+        format!("{} 秒", secs)
+    }
+}
+
+fn build_commit_filter(config: &Config) -> Result<CommitFilter> {
+    Ok(CommitFilter {
+        authors: git::compile_patterns(&config.authors)?,
+        grep: git::compile_patterns(&config.grep)?,
+        invert_grep: config.invert_grep,
+    })
 }
 
 fn validate_config(config: &Config) -> Result<()> {
@@ -317,22 +1880,185 @@ fn validate_config(config: &Config) -> Result<()> {
     if !config.target_repo.join(".git").exists() {
         return Err(SyncError::NotARepository(config.target_repo.clone()));
     }
-
-    let subdir_path = config.source_repo.join(&config.subdir);
-    if !subdir_path.exists() {
-        return Err(SyncError::PathNotFound(subdir_path));
+    for (i, path) in config.extra_targets.iter().enumerate() {
+        if !path.exists() {
+            return Err(SyncError::PathNotFound(path.clone()));
+        }
+        if !path.join(".git").exists() {
+            return Err(SyncError::NotARepository(path.clone()));
+        }
+        if same_repository(path, &config.target_repo) || config.extra_targets[..i].iter().any(|other| same_repository(path, other)) {
+            return Err(SyncError::Anyhow(anyhow::anyhow!("--target {} is given more than once", path.display())));
+        }
+    }
+    if config.engine == Engine::Copy {
+        // The copy is a single commit built from files, not a series of patches
+        let patch_only = [
+            ("--export-bundle", config.export_bundle.is_some()),
+            ("--export-mbox", config.export_mbox.is_some()),
+            ("--squash-by", config.squash_by.is_some()),
+            ("--autosquash", config.autosquash),
+            ("--todo", config.todo.is_some()),
+            ("--bump-version", config.bump_version.is_some()),
+            ("--staging-branch", config.staging_branch),
+        ];
+        if let Some((option, _)) = patch_only.iter().find(|(_, set)| *set) {
+            return Err(SyncError::Anyhow(anyhow::anyhow!("{} cannot be used with --engine copy", option)));
+        }
     }
 
     Ok(())
 }
 
-fn get_confirmation_message(action: &ConfirmationAction, _config: &Config) -> Result<String> {
+fn same_repository(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn get_confirmation_message(action: &ConfirmationAction, app: &App) -> Result<String> {
     match action {
-        ConfirmationAction::ExecuteSync => Ok("确定要执行同步操作吗？".to_string()),
+        ConfirmationAction::ExecuteSync => {
+            let plan = match app.config.engine {
+                Engine::Patch => SyncEngine::new(build_sync_config(app), app.config.dry_run).plan(&app.selected()),
+                Engine::Copy => CopyEngine::new(build_sync_config(app), app.config.dry_run).plan(&app.selected()),
+            };
+            let mut message = format!("确定要执行同步操作吗？\n将同步 {} 个提交", plan.applied);
+            if plan.skipped > 0 {
+                message.push_str(&format!("，跳过 {} 个 merge 提交", plan.skipped));
+            }
+            if plan.target_commits > 0 {
+                message.push_str(&format!("，在目标仓库生成 {} 个提交", plan.target_commits));
+            }
+            Ok(message)
+        }
         ConfirmationAction::CreateBranch => Ok("是否创建新分支？".to_string()),
         ConfirmationAction::StashChanges => Ok("是否自动 Stash 变更？".to_string()),
         ConfirmationAction::IncludeStart => Ok("是否包含起始 commit 的变更？".to_string()),
         ConfirmationAction::ExcludeMerges => Ok("是否排除 merge 引入的变更？".to_string()),
         ConfirmationAction::SyncDelete => Ok("是否同步删除操作？".to_string()),
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testutil::{TestRepo, FILE};
+
+    #[tokio::test]
+    async fn fanout_report_keeps_other_targets_when_the_main_target_fails() {
+        let source = TestRepo::new();
+        let base = source.commit(&[], &[("sub/a", b"1\n", FILE)], 1_000, "base");
+        let change = source.commit(&[base], &[("sub/a", b"2\n", FILE)], 2_000, "Change a");
+        // The main target's copy of `a` differs, so the patch conflicts there
+        let main = TestRepo::new();
+        main.commit(&[], &[("a", b"other\n", FILE)], 1_000, "init");
+        let other = TestRepo::new();
+        other.commit(&[], &[("a", b"1\n", FILE)], 1_000, "init");
+
+        let settings = RunSettings {
+            sync_config: testutil::sync_config("sub", ""),
+            engine: Engine::Patch,
+            dry_run: false,
+            retry: false,
+            respect_export_ignore: false,
+            end_commit: change.to_string(),
+            export_dir: None,
+            export_mbox: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+            warnings: Vec::new(),
+            labels: BTreeMap::new(),
+        };
+        let gm = GitManager::new(source.path(), main.path()).unwrap();
+        let commits = vec![gm.commit_info(&change.to_string(), "sub").unwrap()];
+        let (tx, _rx) = mpsc::channel(1024);
+        let (_, result) = run_all_targets(settings, gm, commits, vec![other.path().to_path_buf()], None, tx).await;
+
+        let report = result.unwrap();
+        assert!(report.error.is_some());
+        assert_eq!(report.config.target_repo, main.path());
+        assert_eq!(report.total_commits, 1);
+        assert!(report.to_string().starts_with("同步失败"));
+        assert_eq!(report.exit_code(), 1);
+        let target = &report.fanout[0];
+        assert_eq!(target.target_repo, other.path());
+        assert_eq!(target.report.as_ref().unwrap().synced_commits, 1);
+        assert_eq!(other.file("a").unwrap().0, b"2\n");
+        assert!(!opens_pull_request(&report, false));
+    }
+
+    /// A target whose `main` is at `local` while `origin/main` is at `remote`
+    fn target_with_remote(target: &TestRepo, local: git2::Oid, remote: git2::Oid) {
+        target.checkout(local);
+        target.repo.remote("origin", "https://example.com/origin.git").unwrap();
+        target.repo.reference("refs/remotes/origin/main", remote, true, "test").unwrap();
+    }
+
+    fn branch_config(source: &TestRepo, target: &TestRepo, extra: &[&str]) -> Config {
+        let args = [
+            "sync-subdir",
+            source.path().to_str().unwrap(),
+            "sub",
+            target.path().to_str().unwrap(),
+            "HEAD",
+        ];
+        let matches = build_cli().get_matches_from(args.iter().chain(extra));
+        Config::from_matches(matches).unwrap()
+    }
+
+    #[test]
+    fn target_branch_behind_its_remote_is_fast_forwarded() {
+        let source = TestRepo::new();
+        source.commit(&[], &[("sub/a", b"", FILE)], 1_000, "init");
+        let target = TestRepo::new();
+        let first = target.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+        let second = target.commit(&[first], &[("a", b"1\n", FILE)], 2_000, "second");
+        target_with_remote(&target, first, second);
+        let mut git_manager = GitManager::new(source.path(), target.path()).unwrap();
+
+        prepare_target_branch(&branch_config(&source, &target, &[]), &mut git_manager).unwrap();
+        assert_eq!(target.repo.head().unwrap().target(), Some(second));
+        assert_eq!(target.file("a").unwrap().0, b"1\n");
+    }
+
+    #[test]
+    fn diverged_target_branch_needs_target_tip() {
+        let source = TestRepo::new();
+        source.commit(&[], &[("sub/a", b"", FILE)], 1_000, "init");
+        let target = TestRepo::new();
+        let first = target.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+        let remote = target.commit(&[first], &[("a", b"remote\n", FILE)], 2_000, "remote");
+        let local = target.commit(&[first], &[("a", b"local\n", FILE)], 2_000, "local");
+        target_with_remote(&target, local, remote);
+        let mut git_manager = GitManager::new(source.path(), target.path()).unwrap();
+
+        let result = prepare_target_branch(&branch_config(&source, &target, &[]), &mut git_manager);
+        assert!(matches!(result, Err(SyncError::BranchDiverged { ahead: 1, behind: 1, .. })));
+        assert_eq!(target.repo.head().unwrap().target(), Some(local));
+
+        prepare_target_branch(&branch_config(&source, &target, &["--target-tip", "remote"]), &mut git_manager).unwrap();
+        assert_eq!(target.repo.head().unwrap().target(), Some(remote));
+    }
+
+    #[test]
+    fn missing_target_branch_is_created_from_its_remote() {
+        let source = TestRepo::new();
+        source.commit(&[], &[("sub/a", b"", FILE)], 1_000, "init");
+        let target = TestRepo::new();
+        let first = target.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+        let release = target.commit(&[first], &[("a", b"1\n", FILE)], 2_000, "release");
+        target.checkout(first);
+        target.repo.remote("team/mirror", "https://example.com/mirror.git").unwrap();
+        target.repo.reference("refs/remotes/team/mirror/release", release, false, "test").unwrap();
+        let mut git_manager = GitManager::new(source.path(), target.path()).unwrap();
+
+        let config = branch_config(&source, &target, &["--target-branch", "release"]);
+        let base = prepare_target_branch(&config, &mut git_manager).unwrap();
+
+        assert_eq!(base, Some(format!("team/mirror/release ({})", git::short_id(&release.to_string()))));
+        let branch = target.repo.find_branch("release", git2::BranchType::Local).unwrap();
+        assert_eq!(branch.get().target(), Some(release));
+        assert_eq!(branch.upstream().unwrap().name().unwrap(), Some("team/mirror/release"));
+    }
+}
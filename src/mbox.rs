@@ -0,0 +1,132 @@
+use crate::bundle::BundlePatch;
+use crate::error::{Result, SyncError};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// A single mbox file the patches of a run are appended to, in the same layout
+/// as `git format-patch --stdout`, ready for `git send-email` or `git am`
+pub struct MboxWriter {
+    file: File,
+}
+
+impl MboxWriter {
+    /// Creates the mbox at `path`; an existing non-empty file is left alone
+    pub fn create(path: &Path) -> Result<Self> {
+        if std::fs::metadata(path).is_ok_and(|m| m.len() > 0) {
+            return Err(SyncError::Anyhow(anyhow::anyhow!("Export file already exists: {}", path.display())));
+        }
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(Self { file: File::create(path)? })
+    }
+
+    /// Appends a patch generated by `git format-patch` (or in its format) as
+    /// the next message
+    pub fn append(&mut self, patch_path: &Path) -> Result<()> {
+        let mut content = std::fs::read(patch_path)?;
+        if !content.ends_with(b"\n") {
+            content.push(b'\n');
+        }
+        self.file.write_all(&content)?;
+        Ok(())
+    }
+}
+
+/// Splits `mbox` into one file per message in `dir` with `git mailsplit` and
+/// describes them in order. The source of a message is the commit id on its
+/// `From <sha>` line when it was written by `git format-patch`, otherwise its
+/// `Message-Id`.
+pub fn split(mbox: &Path, dir: &Path) -> Result<Vec<BundlePatch>> {
+    if !mbox.is_file() {
+        return Err(SyncError::PathNotFound(mbox.to_path_buf()));
+    }
+    std::fs::create_dir_all(dir)?;
+    let output = Command::new("git")
+        .arg("mailsplit")
+        .arg(format!("-o{}", dir.display()))
+        .arg(mbox)
+        .output()?;
+    if !output.status.success() {
+        return Err(SyncError::Anyhow(anyhow::anyhow!(
+            "git mailsplit failed for {}: {}",
+            mbox.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let mut files: Vec<String> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect();
+    files.sort();
+
+    // `git mailinfo` writes the message and patch it extracts somewhere
+    let scratch = tempfile::tempdir()?;
+    let mut patches = Vec::new();
+    for file in files {
+        let path = dir.join(&file);
+        let content = std::fs::read(&path)?;
+        let headers = header_block(&content);
+        let source = from_line_commit(&headers)
+            .or_else(|| header(&headers, "message-id"))
+            .unwrap_or_else(|| file.clone());
+        patches.push(BundlePatch { subject: subject(&path, scratch.path())?, file, source });
+    }
+    Ok(patches)
+}
+
+/// Header lines of a message, continuation lines joined onto their header
+fn header_block(content: &[u8]) -> Vec<String> {
+    let text = String::from_utf8_lossy(content);
+    let mut headers: Vec<String> = Vec::new();
+    for line in text.lines() {
+        if line.is_empty() {
+            break;
+        }
+        match headers.last_mut() {
+            Some(last) if line.starts_with([' ', '\t']) => {
+                last.push(' ');
+                last.push_str(line.trim());
+            }
+            _ => headers.push(line.to_string()),
+        }
+    }
+    headers
+}
+
+/// Commit id on the `From <sha> Mon Sep 17 00:00:00 2001` line `git format-patch` writes
+fn from_line_commit(headers: &[String]) -> Option<String> {
+    let id = headers.first()?.strip_prefix("From ")?.split(' ').next()?;
+    (matches!(id.len(), 40 | 64) && id.bytes().all(|b| b.is_ascii_hexdigit())).then(|| id.to_string())
+}
+
+fn header(headers: &[String], name: &str) -> Option<String> {
+    headers.iter().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.eq_ignore_ascii_case(name).then(|| value.trim().to_string())
+    })
+}
+
+/// Decoded subject of a message without its `[PATCH ...]` prefix, as `git am` would commit it
+fn subject(message: &Path, scratch: &Path) -> Result<String> {
+    let output = Command::new("git")
+        .arg("mailinfo")
+        .arg(scratch.join("msg"))
+        .arg(scratch.join("patch"))
+        .stdin(Stdio::from(File::open(message)?))
+        .output()?;
+    if !output.status.success() {
+        return Err(SyncError::Anyhow(anyhow::anyhow!(
+            "git mailinfo failed for {}: {}",
+            message.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("Subject: "))
+        .unwrap_or_default()
+        .to_string())
+}
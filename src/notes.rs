@@ -0,0 +1,117 @@
+use git2::{ErrorCode, Oid, Repository, Signature};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::credentials;
+use crate::error::Result;
+use crate::journal::{CommitMapping, Journal};
+
+/// Notes ref holding the source commits of each target commit (`--notes`)
+pub const NOTES_REF: &str = "refs/notes/sync-subdir";
+
+/// Starts each note line, `Sync-source: <source id> run <run id>`. One line
+/// per source commit, so merging notes by sorting lines keeps them intact.
+const SOURCE_KEY: &str = "Sync-source";
+
+/// Writes the notes of the target commits created by run `run_id`
+pub fn write_run(journal: &Journal, target_repo: &Path, run_id: &str) -> Result<usize> {
+    let mappings: Vec<CommitMapping> = journal.mappings()?.into_iter().filter(|m| m.run_id == run_id).collect();
+    write(target_repo, &mappings)
+}
+
+/// Puts a note on every target commit of `mappings` listing the source commits
+/// it holds, replacing any earlier note. Target commits that no longer exist are
+/// skipped. Returns the number of notes written.
+pub fn write(target_repo: &Path, mappings: &[CommitMapping]) -> Result<usize> {
+    let repo = Repository::open(target_repo)?;
+    let signature = repo.signature().or_else(|_| Signature::now("sync-subdir", "sync-subdir@example.com"))?;
+
+    let mut sources: BTreeMap<&str, Vec<&CommitMapping>> = BTreeMap::new();
+    for mapping in mappings {
+        sources.entry(mapping.target.as_str()).or_default().push(mapping);
+    }
+    let mut written = 0;
+    for (target, mappings) in sources {
+        let Ok(oid) = Oid::from_str(target) else { continue };
+        if repo.find_commit(oid).is_err() {
+            continue;
+        }
+        let note: String = mappings
+            .iter()
+            .map(|m| format!("{}: {} run {}\n", SOURCE_KEY, m.source, m.run_id))
+            .collect();
+        repo.note(&signature, &signature, Some(NOTES_REF), oid, &note, true)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Source to target mappings recorded in the notes, for clones of the target
+/// that don't have its sync journal. Subject and timestamp are the target
+/// commit's.
+pub fn read(target_repo: &Path) -> Result<Vec<CommitMapping>> {
+    let repo = Repository::open(target_repo)?;
+    let notes = match repo.notes(Some(NOTES_REF)) {
+        Ok(notes) => notes,
+        Err(e) if e.code() == ErrorCode::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut mappings = Vec::new();
+    for note in notes {
+        let (_, target) = note?;
+        let Ok(commit) = repo.find_commit(target) else { continue };
+        let note = repo.find_note(Some(NOTES_REF), target)?;
+        let time = commit.time();
+        let timestamp = chrono::FixedOffset::east_opt(time.offset_minutes() * 60)
+            .and_then(|offset| Some(chrono::DateTime::from_timestamp(time.seconds(), 0)?.with_timezone(&offset)))
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_default();
+
+        for line in note.message().unwrap_or_default().lines() {
+            let Some(value) = line.strip_prefix(SOURCE_KEY).and_then(|rest| rest.strip_prefix(": ")) else { continue };
+            let (source, run_id) = value.trim().split_once(" run ").unwrap_or((value.trim(), ""));
+            mappings.push(CommitMapping {
+                source: source.to_string(),
+                target: target.to_string(),
+                subject: commit.summary().unwrap_or_default().to_string(),
+                timestamp: timestamp.clone(),
+                run_id: run_id.to_string(),
+            });
+        }
+    }
+    mappings.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    Ok(mappings)
+}
+
+/// Pushes the notes ref to `remote`
+pub fn push(target_repo: &Path, remote: &str) -> Result<()> {
+    run_git(target_repo, &["push", remote, &format!("{0}:{0}", NOTES_REF)], &format!("git push {} {}", remote, NOTES_REF))
+}
+
+/// Fetches the notes ref of `remote` and merges it into the local one, keeping
+/// the lines of both sides when the same commit has a note on each
+pub fn fetch(target_repo: &Path, remote: &str) -> Result<()> {
+    let tracking = format!("refs/notes/remotes/{}/sync-subdir", remote);
+    run_git(
+        target_repo,
+        &["fetch", remote, &format!("+{}:{}", NOTES_REF, tracking)],
+        &format!("git fetch {} {}", remote, NOTES_REF),
+    )?;
+    run_git(
+        target_repo,
+        &["notes", "--ref", NOTES_REF, "merge", "--strategy", "cat_sort_uniq", &tracking],
+        &format!("git notes merge {}", tracking),
+    )
+}
+
+fn run_git(target_repo: &Path, args: &[&str], action: &str) -> Result<()> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(target_repo).args(args).stdin(Stdio::null());
+    let output = credentials::non_interactive(&mut cmd, target_repo).output()?;
+    if !output.status.success() {
+        return Err(credentials::classify_git_failure(action, &String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(())
+}
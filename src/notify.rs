@@ -0,0 +1,22 @@
+use std::io::Write;
+
+/// Rings the terminal bell and shows a desktop notification. The notification is
+/// sent from a separate thread so a slow notification daemon never stalls the UI.
+pub fn finished(summary: &str, body: &str) {
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x07");
+    let _ = stdout.flush();
+
+    let summary = summary.to_string();
+    let body = body.to_string();
+    std::thread::spawn(move || {
+        if let Err(e) = notify_rust::Notification::new()
+            .summary(&summary)
+            .body(&body)
+            .appname("sync-subdir")
+            .show()
+        {
+            tracing::warn!("Failed to show desktop notification: {}", e);
+        }
+    });
+}
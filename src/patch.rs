@@ -0,0 +1,350 @@
+use crate::error::{Result, SyncError};
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::path::Path;
+
+/// A sed-style `s/pattern/replacement/flags` rule applied to commit messages
+#[derive(Debug, Clone)]
+pub struct MessageRewrite {
+    regex: Regex,
+    replacement: String,
+    global: bool,
+}
+
+impl MessageRewrite {
+    /// Parses `s<d>pattern<d>replacement<d>[gi]` where `<d>` is any delimiter.
+    /// `\1`-style back references are accepted in the replacement.
+    pub fn parse(rule: &str) -> Result<Self> {
+        let invalid = |reason: &str| SyncError::InvalidPattern(format!("{}: {}", rule, reason));
+
+        let mut chars = rule.chars();
+        if chars.next() != Some('s') {
+            return Err(invalid("rule must start with 's'"));
+        }
+        let delimiter = chars.next().ok_or_else(|| invalid("missing delimiter"))?;
+
+        let mut parts = vec![String::new()];
+        let mut escaped = false;
+        for c in chars {
+            if escaped {
+                if c != delimiter {
+                    parts.last_mut().unwrap().push('\\');
+                }
+                parts.last_mut().unwrap().push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == delimiter {
+                parts.push(String::new());
+            } else {
+                parts.last_mut().unwrap().push(c);
+            }
+        }
+        if parts.len() != 3 {
+            return Err(invalid("expected s/pattern/replacement/flags"));
+        }
+
+        let flags = &parts[2];
+        if let Some(flag) = flags.chars().find(|f| !matches!(f, 'g' | 'i')) {
+            return Err(invalid(&format!("unknown flag '{}'", flag)));
+        }
+        let regex = RegexBuilder::new(&parts[0])
+            .case_insensitive(flags.contains('i'))
+            .build()
+            .map_err(|e| invalid(&e.to_string()))?;
+        let replacement = Regex::new(r"\\(\d)")
+            .unwrap()
+            .replace_all(&parts[1].replace('$', "$$"), "$${$1}")
+            .to_string();
+
+        Ok(Self {
+            regex,
+            replacement,
+            global: flags.contains('g'),
+        })
+    }
+
+    /// Applies the rule to every line, like sed does
+    pub fn apply(&self, message: &str) -> String {
+        message
+            .split('\n')
+            .map(|line| {
+                if self.global {
+                    self.regex.replace_all(line, self.replacement.as_str())
+                } else {
+                    self.regex.replace(line, self.replacement.as_str())
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Replaces the commit message of a `git format-patch` mailbox file with `message`
+/// (subject on the first line, body after a blank line). The diff is left untouched.
+pub fn rewrite_message(patch_path: &Path, message: &str) -> Result<()> {
+    let content = std::fs::read(patch_path)?;
+    let rewritten = replace_message(&content, message);
+    std::fs::write(patch_path, rewritten)?;
+    Ok(())
+}
+
+fn replace_message(content: &[u8], message: &str) -> Vec<u8> {
+    // Only the headers and the old message are decoded; the diff is copied byte
+    // for byte, since the files it changes may be in any encoding
+    let diff_start = diff_start(content);
+    let text = String::from_utf8_lossy(&content[..diff_start]);
+    let (subject, body) = split_message(message);
+
+    let mut output = String::new();
+    let mut lines = text.split_inclusive('\n');
+    let mut in_subject = false;
+    let mut has_content_type = false;
+
+    // Headers: drop the (possibly folded and encoded) Subject and write a raw UTF-8 one
+    for line in lines.by_ref() {
+        let trimmed = line.trim_end_matches(['\r', '\n']);
+        if trimmed.is_empty() {
+            break;
+        }
+        if in_subject && line.starts_with([' ', '\t']) {
+            continue;
+        }
+        in_subject = false;
+        if trimmed.starts_with("Subject:") {
+            in_subject = true;
+            output.push_str(&format!("Subject: [PATCH] {}\n", subject));
+            continue;
+        }
+        if trimmed.to_ascii_lowercase().starts_with("content-type:") {
+            has_content_type = true;
+        }
+        output.push_str(line);
+    }
+    if !has_content_type {
+        output.push_str("MIME-Version: 1.0\nContent-Type: text/plain; charset=UTF-8\nContent-Transfer-Encoding: 8bit\n");
+    }
+    output.push('\n');
+
+    // Body: everything up to the `---` separator is the old message
+    for line in lines.by_ref() {
+        if line.trim_end_matches(['\r', '\n']) == "---" {
+            if !body.is_empty() {
+                output.push_str(body);
+                output.push('\n');
+            }
+            output.push_str(line);
+            break;
+        }
+    }
+    for line in lines {
+        output.push_str(line);
+    }
+
+    let mut output = output.into_bytes();
+    output.extend_from_slice(&content[diff_start..]);
+    output
+}
+
+/// Offset just past the `---` line that ends the message of a `git format-patch`
+/// file, or the end of the file when there is none
+fn diff_start(content: &[u8]) -> usize {
+    let mut offset = 0;
+    for line in content.split_inclusive(|&b| b == b'\n') {
+        offset += line.len();
+        if trim_eol(line) == b"---" {
+            return offset;
+        }
+    }
+    content.len()
+}
+
+fn trim_eol(line: &[u8]) -> &[u8] {
+    let end = line.iter().rposition(|&b| b != b'\r' && b != b'\n').map_or(0, |i| i + 1);
+    &line[..end]
+}
+
+/// Files whose changed lines in a patch file are not valid UTF-8, e.g. GBK
+/// encoded configuration. Their bytes are synced unchanged; they are listed so
+/// reviewers can check them.
+pub fn non_utf8_files(patch_path: &Path) -> Result<Vec<String>> {
+    let content = std::fs::read(patch_path)?;
+    let mut files: Vec<String> = Vec::new();
+    let mut current: Option<String> = None;
+    let mut in_hunk = false;
+    for line in content[diff_start(&content)..].split_inclusive(|&b| b == b'\n') {
+        if let Some(header) = line.strip_prefix(b"diff --git ") {
+            let header = String::from_utf8_lossy(trim_eol(header)).into_owned();
+            current = Some(header.rsplit_once(" b/").map_or(header.clone(), |(_, path)| path.to_string()));
+            in_hunk = false;
+        } else if line.starts_with(b"@@") {
+            in_hunk = true;
+        } else if in_hunk && std::str::from_utf8(line).is_err() {
+            if let Some(path) = current.take() {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Removes the `old mode`/`new mode` lines from a patch file, so the target keeps
+/// the modes its files already have (`--no-mode-changes`). Files whose only
+/// change was their mode are dropped. Returns whether the patch still changes
+/// anything.
+pub fn strip_mode_changes(patch_path: &Path) -> Result<bool> {
+    let content = std::fs::read(patch_path)?;
+    let (stripped, has_changes) = remove_mode_changes(&content);
+    std::fs::write(patch_path, stripped)?;
+    Ok(has_changes)
+}
+
+fn remove_mode_changes(content: &[u8]) -> (Vec<u8>, bool) {
+    let mut output = Vec::with_capacity(content.len());
+    let mut has_changes = false;
+    // Extended header of the current file, from `diff --git` to its first `---`, `@@` or binary line
+    let mut header: Option<Vec<&[u8]>> = None;
+    for line in content.split_inclusive(|&b| b == b'\n') {
+        if let Some(mut lines) = header.take() {
+            if line.starts_with(b"old mode ") || line.starts_with(b"new mode ") {
+                header = Some(lines);
+                continue;
+            }
+            let ends_header = [&b"--- "[..], b"+++ ", b"@@", b"GIT binary patch", b"Binary files "]
+                .iter()
+                .any(|prefix| line.starts_with(prefix));
+            let ends_file = line.starts_with(b"diff --git ") || is_signature(line);
+            if !ends_header && !ends_file {
+                lines.push(line);
+                header = Some(lines);
+                continue;
+            }
+            // A file whose only change was its mode has nothing left past `diff --git`
+            if ends_header || changes_file(&lines) {
+                lines.iter().for_each(|kept| output.extend_from_slice(kept));
+                has_changes = true;
+            }
+        }
+        if line.starts_with(b"diff --git ") {
+            header = Some(vec![line]);
+            continue;
+        }
+        output.extend_from_slice(line);
+    }
+    if let Some(lines) = header.filter(|lines| changes_file(lines)) {
+        lines.iter().for_each(|kept| output.extend_from_slice(kept));
+        has_changes = true;
+    }
+    (output, has_changes)
+}
+
+/// Whether an extended header without a diff still does something, such as a
+/// rename or the creation of an empty file
+fn changes_file(header: &[&[u8]]) -> bool {
+    header.iter().any(|line| !line.starts_with(b"diff --git ") && !line.starts_with(b"index "))
+}
+
+/// The `-- ` line that starts the signature at the end of a `git format-patch` file
+fn is_signature(line: &[u8]) -> bool {
+    matches!(trim_eol(line), b"-- " | b"--")
+}
+
+/// Size of the diff in a `git format-patch` mailbox file, an estimate of the
+/// review it takes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PatchStats {
+    pub files: usize,
+    pub hunks: usize,
+    pub bytes: u64,
+}
+
+impl PatchStats {
+    pub fn read(patch_path: &Path) -> Result<Self> {
+        Ok(Self::parse(&std::fs::read(patch_path)?))
+    }
+
+    fn parse(content: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(content);
+        let mut stats = Self { bytes: content.len() as u64, ..Self::default() };
+        // The diff starts after the `---` that ends the message, like in `replace_message`
+        let diff = text
+            .split_inclusive('\n')
+            .skip_while(|line| line.trim_end_matches(['\r', '\n']) != "---");
+        for line in diff {
+            if line.starts_with("diff --git ") {
+                stats.files += 1;
+            } else if line.starts_with("@@ ") {
+                stats.hunks += 1;
+            }
+        }
+        stats
+    }
+
+    pub fn add(&mut self, other: &PatchStats) {
+        self.files += other.files;
+        self.hunks += other.hunks;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Puts `prefix` in front of the subject line; a subject that already starts
+/// with it is left alone, so prefixing twice changes nothing
+pub fn prefix_subject(message: &str, prefix: &str) -> String {
+    let message = message.trim_start();
+    if message.starts_with(prefix) {
+        message.to_string()
+    } else {
+        format!("{}{}", prefix, message)
+    }
+}
+
+/// Appends a `key: value` trailer, starting a new paragraph unless the message
+/// already ends with a trailer block
+pub fn add_trailer(message: &str, key: &str, value: &str) -> String {
+    let message = message.trim_end();
+    let last_paragraph = message.rsplit("\n\n").next().unwrap_or("");
+    let has_trailers = message.contains("\n\n")
+        && last_paragraph.lines().all(|line| {
+            line.split_once(": ").is_some_and(|(k, _)| !k.is_empty() && !k.contains(' '))
+        });
+    let separator = if has_trailers { "\n" } else { "\n\n" };
+    format!("{}{}{}: {}\n", message, separator, key, value)
+}
+
+/// Splits a commit message into its subject line and trimmed body
+pub fn split_message(message: &str) -> (&str, &str) {
+    let message = message.trim();
+    match message.split_once('\n') {
+        Some((subject, body)) => (subject.trim(), body.trim()),
+        None => (message, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_subject_handles_unicode_prefixes_and_subjects() {
+        assert_eq!(prefix_subject("Add parser\n\nBody\n", "[sync] "), "[sync] Add parser\n\nBody\n");
+        assert_eq!(prefix_subject("修复解析器", "【同步】"), "【同步】修复解析器");
+        assert_eq!(prefix_subject("  Ünïcode fix", "🔄 "), "🔄 Ünïcode fix");
+    }
+
+    #[test]
+    fn prefix_subject_is_idempotent() {
+        let once = prefix_subject("修复解析器", "【同步】");
+        assert_eq!(prefix_subject(&once, "【同步】"), once);
+        assert_eq!(prefix_subject("[sync] [core] Fix", "[sync] "), "[sync] [core] Fix");
+    }
+
+    #[test]
+    fn replaced_message_keeps_unicode_subject_in_raw_utf8() {
+        let patch = b"From 0 Mon Sep 17 00:00:00 2001\nFrom: A <a@example.com>\nSubject: [PATCH] =?UTF-8?q?old?=\n\nold body\n---\n a | 1 +\n";
+        let replaced = replace_message(patch, "【同步】修复 ünïcode\n\n正文\n");
+        let text = String::from_utf8(replaced).unwrap();
+        assert!(text.contains("Subject: [PATCH] 【同步】修复 ünïcode\n"), "{}", text);
+        assert!(text.contains("Content-Type: text/plain; charset=UTF-8\n"), "{}", text);
+        assert!(text.contains("\n正文\n---\n a | 1 +\n"), "{}", text);
+    }
+}
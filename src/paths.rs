@@ -0,0 +1,193 @@
+use crate::error::{Result, SyncError};
+use std::path::PathBuf;
+
+/// Where the synced directory lives in each repository: patches are generated
+/// relative to `source` and applied below `target`. An empty path stands for the
+/// repository root, so root→subdir, subdir→root and subdir→subdir all go
+/// through the same two git arguments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathMapping {
+    source: String,
+    target: String,
+}
+
+impl PathMapping {
+    pub fn new(source: &str, target: &str) -> Result<Self> {
+        Ok(Self {
+            source: normalize_dir(source)?,
+            target: normalize_dir(target)?,
+        })
+    }
+
+    /// `--relative` argument for `git format-patch`, `None` when the whole
+    /// repository is synced. The trailing slash matters: git matches the prefix
+    /// as a plain string, so `--relative=sub` would also pick up `subway/b` and
+    /// write it as `way/b`.
+    pub fn relative_arg(&self) -> Option<String> {
+        (!self.source.is_empty()).then(|| format!("--relative={}/", self.source))
+    }
+
+    /// `--directory` argument for `git am`, `None` when patches apply at the root
+    pub fn directory_arg(&self) -> Option<String> {
+        (!self.target.is_empty()).then(|| format!("--directory={}", self.target))
+    }
+
+    /// Path in the target repository of `path`, a file of a generated patch
+    pub fn target_path(&self, path: &str) -> String {
+        if self.target.is_empty() {
+            path.to_string()
+        } else {
+            format!("{}/{}", self.target, path)
+        }
+    }
+
+    /// Pathspec that leaves `pattern`, a glob relative to the synced directory,
+    /// out of the generated patch
+    pub fn exclude_pathspec(&self, pattern: &str) -> String {
+        if self.source.is_empty() {
+            format!(":(exclude,glob){}", pattern)
+        } else {
+            format!(":(exclude,glob){}/{}", self.source, pattern)
+        }
+    }
+}
+
+/// Normalises a directory inside a repository: slashes trimmed and collapsed,
+/// `.` components dropped and the root returned as an empty string. `..` is
+/// rejected, since it could point outside the repository.
+pub fn normalize_dir(path: &str) -> Result<String> {
+    let mut components = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => return Err(SyncError::InvalidPath(format!("{}: '..' is not allowed", path))),
+            _ => components.push(component),
+        }
+    }
+    Ok(components.join("/"))
+}
+
+/// Per-user directory named by the XDG variable `var`, or `fallback` below the
+/// home directory when it is unset, e.g. `.config` for `XDG_CONFIG_HOME`
+pub fn user_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    match std::env::var_os(var).filter(|dir| !dir.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => Some(PathBuf::from(std::env::var_os("HOME")?).join(fallback)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, TestRepo, FILE};
+
+    #[test]
+    fn subdir_to_root() {
+        let paths = PathMapping::new("libs/core", "").unwrap();
+        assert_eq!(paths.relative_arg().as_deref(), Some("--relative=libs/core/"));
+        assert_eq!(paths.directory_arg(), None);
+        assert_eq!(paths.exclude_pathspec("*.log"), ":(exclude,glob)libs/core/*.log");
+        assert_eq!(paths.target_path("src/lib.rs"), "src/lib.rs");
+    }
+
+    #[test]
+    fn root_to_subdir() {
+        let paths = PathMapping::new("", "vendor/core").unwrap();
+        assert_eq!(paths.relative_arg(), None);
+        assert_eq!(paths.directory_arg().as_deref(), Some("--directory=vendor/core"));
+        assert_eq!(paths.exclude_pathspec("*.log"), ":(exclude,glob)*.log");
+        assert_eq!(paths.target_path("src/lib.rs"), "vendor/core/src/lib.rs");
+    }
+
+    #[test]
+    fn subdir_to_different_subdir() {
+        let paths = PathMapping::new("libs/core/", "/third_party//core").unwrap();
+        assert_eq!(paths.relative_arg().as_deref(), Some("--relative=libs/core/"));
+        assert_eq!(paths.directory_arg().as_deref(), Some("--directory=third_party/core"));
+        assert_eq!(paths.exclude_pathspec("docs/**"), ":(exclude,glob)libs/core/docs/**");
+        assert_eq!(paths.target_path("src/lib.rs"), "third_party/core/src/lib.rs");
+    }
+
+    #[test]
+    fn root_to_root() {
+        let paths = PathMapping::new(".", "/").unwrap();
+        assert_eq!(paths, PathMapping::default());
+        assert_eq!(paths.relative_arg(), None);
+        assert_eq!(paths.directory_arg(), None);
+    }
+
+    #[test]
+    fn normalize_dir_cleans_up_separators_and_dots() {
+        assert_eq!(normalize_dir("").unwrap(), "");
+        assert_eq!(normalize_dir("/").unwrap(), "");
+        assert_eq!(normalize_dir("./").unwrap(), "");
+        assert_eq!(normalize_dir("sub").unwrap(), "sub");
+        assert_eq!(normalize_dir("/sub/").unwrap(), "sub");
+        assert_eq!(normalize_dir("a//b/./c/").unwrap(), "a/b/c");
+    }
+
+    #[test]
+    fn normalize_dir_rejects_parent_components() {
+        assert!(matches!(normalize_dir(".."), Err(SyncError::InvalidPath(_))));
+        assert!(matches!(normalize_dir("sub/../other"), Err(SyncError::InvalidPath(_))));
+        assert!(matches!(normalize_dir("../outside"), Err(SyncError::InvalidPath(_))));
+        assert!(PathMapping::new("sub", "../outside").is_err());
+        // Only whole components count
+        assert_eq!(normalize_dir("a..b/..c").unwrap(), "a..b/..c");
+    }
+
+    #[test]
+    fn relative_arg_keeps_sibling_with_common_prefix_out() {
+        let paths = PathMapping::new("sub", "").unwrap();
+        let relative = paths.relative_arg().unwrap();
+        assert!(relative.ends_with("sub/"), "{} would also match subway/", relative);
+    }
+
+    /// One commit changing `sub/a` and `subway/b`, synced from `source_dir` into `target_dir`
+    async fn sync_mapped(source_dir: &str, target_dir: &str) -> TestRepo {
+        let source = TestRepo::new();
+        let base = source.commit(&[], &[("sub/a", b"", FILE), ("subway/b", b"", FILE)], 1_000, "base");
+        let change = source.commit(&[base], &[("sub/a", b"a\n", FILE), ("subway/b", b"b\n", FILE)], 2_000, "change");
+        let target = TestRepo::new();
+        let init = match target_dir {
+            "" => "a".to_string(),
+            dir => format!("{}/a", dir),
+        };
+        target.commit(&[], &[(&init, b"", FILE)], 1_000, "init");
+
+        let report = testutil::sync(&source, &target, testutil::sync_config(source_dir, target_dir), &[change]).await;
+        assert_eq!(report.synced_commits, 1);
+        target
+    }
+
+    #[tokio::test]
+    async fn subdir_patches_land_at_the_target_root() {
+        let target = sync_mapped("sub", "").await;
+        assert_eq!(target.file("a"), Some((b"a\n".to_vec(), FILE)));
+        // subway/b shares the prefix but is outside the synced directory
+        assert_eq!(target.file("way/b"), None);
+        assert_eq!(target.file("b"), None);
+    }
+
+    #[tokio::test]
+    async fn subdir_patches_land_in_a_different_subdir() {
+        let target = sync_mapped("sub", "third_party/sub").await;
+        assert_eq!(target.file("third_party/sub/a"), Some((b"a\n".to_vec(), FILE)));
+        assert_eq!(target.file("a"), None);
+        assert_eq!(target.file("third_party/sub/way/b"), None);
+    }
+
+    #[tokio::test]
+    async fn root_patches_land_in_a_subdir() {
+        let source = TestRepo::new();
+        let base = source.commit(&[], &[("a", b"", FILE)], 1_000, "base");
+        let change = source.commit(&[base], &[("a", b"a\n", FILE), ("dir/b", b"b\n", FILE)], 2_000, "change");
+        let target = TestRepo::new();
+        target.commit(&[], &[("vendor/a", b"", FILE), ("a", b"", FILE)], 1_000, "init");
+
+        testutil::sync(&source, &target, testutil::sync_config("", "vendor"), &[change]).await;
+        assert_eq!(target.file("vendor/a"), Some((b"a\n".to_vec(), FILE)));
+        assert_eq!(target.file("vendor/dir/b"), Some((b"b\n".to_vec(), FILE)));
+        assert_eq!(target.file("a"), Some((Vec::new(), FILE)));
+    }
+}
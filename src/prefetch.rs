@@ -0,0 +1,130 @@
+use crate::git::{CommitDetails, GitManager};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use tokio::sync::{mpsc, watch};
+use tracing::warn;
+
+/// Commit details kept in memory; a few hundred commits cost little and cover
+/// the neighbourhood of anything browsed recently
+pub const DETAILS_CACHE_CAPACITY: usize = 256;
+
+/// Commits on each side of the cursor whose details are computed ahead
+pub const PREFETCH_RADIUS: usize = 8;
+
+/// Commit details keyed by commit id, dropping the least recently used entry
+/// once `capacity` is reached
+#[derive(Debug)]
+pub struct DetailsCache {
+    capacity: usize,
+    entries: HashMap<String, CommitDetails>,
+    /// Commit ids, least recently used first
+    order: VecDeque<String>,
+}
+
+impl DetailsCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Looks up `id` without counting it as a use, so drawing does not reorder the cache
+    pub fn peek(&self, id: &str) -> Option<&CommitDetails> {
+        self.entries.get(id)
+    }
+
+    /// Marks `id` as just used; returns false when it is not cached
+    pub fn touch(&mut self, id: &str) -> bool {
+        if !self.entries.contains_key(id) {
+            return false;
+        }
+        if let Some(position) = self.order.iter().position(|entry| entry == id) {
+            if let Some(entry) = self.order.remove(position) {
+                self.order.push_back(entry);
+            }
+        }
+        true
+    }
+
+    pub fn insert(&mut self, id: String, details: CommitDetails) {
+        if self.entries.insert(id.clone(), details).is_some() {
+            self.touch(&id);
+            return;
+        }
+        self.order.push_back(id);
+        while self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+/// Computes commit details in the background for the commits around the
+/// cursor, so moving through the selection screen never waits on a diff.
+/// Results arrive on the channel given to [`Prefetcher::spawn`].
+pub struct Prefetcher {
+    /// Commits (id and synced directory) still to compute, nearest to the cursor first
+    window: watch::Sender<Vec<(String, String)>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Prefetcher {
+    /// Starts the task. Like the background sync it opens its own `GitManager`,
+    /// so the interface never waits on its repository locks.
+    pub fn spawn(source_path: PathBuf, target_path: PathBuf, results: mpsc::Sender<(String, CommitDetails)>) -> Self {
+        let (window, mut requests) = watch::channel(Vec::<(String, String)>::new());
+        let task = tokio::spawn(async move {
+            let git_manager = match GitManager::new(&source_path, &target_path) {
+                Ok(git_manager) => git_manager,
+                Err(e) => {
+                    warn!("Commit details prefetch disabled: {}", e);
+                    return;
+                }
+            };
+            while requests.changed().await.is_ok() {
+                let commits = requests.borrow_and_update().clone();
+                for (id, subdir) in commits {
+                    // The cursor moved: start over from the new window
+                    if requests.has_changed().unwrap_or(true) {
+                        break;
+                    }
+                    // A large diff takes a while; let the worker's other tasks move elsewhere meanwhile
+                    let details = tokio::task::block_in_place(|| git_manager.commit_details(&id, &subdir)).unwrap_or_else(|e| {
+                        warn!("Failed to load details of {}: {}", id, e);
+                        Default::default()
+                    });
+                    if results.send((id, details)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Self { window, task }
+    }
+
+    /// Replaces the commits waiting to be computed; an unchanged window does not
+    /// interrupt the one in progress
+    pub fn request(&self, commits: Vec<(String, String)>) {
+        self.window.send_if_modified(|current| {
+            if *current == commits {
+                return false;
+            }
+            *current = commits;
+            true
+        });
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
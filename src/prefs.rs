@@ -0,0 +1,187 @@
+use crate::keymap::{KeyBinding, Keymap};
+use crate::paths;
+use crate::theme::Theme;
+use clap::Command;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, Value};
+
+/// Repository combinations kept in the `recent` list
+const MAX_RECENT: usize = 10;
+
+/// Keys `sync-subdir config set` accepts; `recent` is kept up to date by the tool
+pub const SETTABLE_KEYS: &[&str] = &["theme", "ascii", "confirm"];
+
+/// User-level defaults from `$XDG_CONFIG_HOME/sync-subdir/config.toml`
+/// (`~/.config/sync-subdir/config.toml` by default). They become the defaults
+/// of the matching command line options, so an option given on the command
+/// line still wins.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preferences {
+    /// Default of `--theme`
+    pub theme: Option<String>,
+    /// Default of `--ascii`
+    pub ascii: Option<bool>,
+    /// Ask before starting a sync; `false` makes `--yes` the default
+    pub confirm: Option<bool>,
+    /// Repositories of recent runs, most recent first
+    pub recent: Vec<RecentRepos>,
+    /// Commit list key bindings replacing the defaults, keyed by action name
+    pub keys: BTreeMap<String, KeyBinding>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RecentRepos {
+    pub source: PathBuf,
+    pub subdir: String,
+    pub target: PathBuf,
+}
+
+impl Preferences {
+    pub fn path() -> Option<PathBuf> {
+        Some(paths::user_dir("XDG_CONFIG_HOME", ".config")?.join("sync-subdir").join("config.toml"))
+    }
+
+    /// Reads the preferences file; no file means no preferences
+    pub fn load() -> anyhow::Result<Self> {
+        match Self::path() {
+            Some(path) if path.exists() => Self::load_from(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read preferences file {}: {}", path.display(), e))?;
+        let prefs: Self = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid preferences file {}: {}", path.display(), e))?;
+        if let Some(theme) = &prefs.theme {
+            Theme::parse(theme).map_err(|e| anyhow::anyhow!("Invalid preferences file {}: {}", path.display(), e))?;
+        }
+        prefs.keymap().map_err(|e| anyhow::anyhow!("Invalid preferences file {}: {}", path.display(), e))?;
+        Ok(prefs)
+    }
+
+    /// Key bindings of the commit list with the `[keys]` overrides applied
+    pub fn keymap(&self) -> anyhow::Result<Keymap> {
+        Keymap::with_overrides(&self.keys)
+    }
+
+    /// Makes the preferences the defaults of the command line options
+    pub fn apply(&self, mut cmd: Command) -> Command {
+        if let Some(theme) = &self.theme {
+            cmd = cmd.mut_arg("theme", |arg| arg.default_value(theme.clone()));
+        }
+        if self.ascii == Some(true) {
+            cmd = cmd.mut_arg("ascii", |arg| arg.default_value("true"));
+        }
+        if self.confirm == Some(false) {
+            cmd = cmd.mut_arg("yes", |arg| arg.default_value("true"));
+        }
+        cmd
+    }
+
+    /// Value of `key` as shown by `config get`, `None` when it is not set
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        match key {
+            "theme" => Ok(self.theme.clone()),
+            "ascii" => Ok(self.ascii.map(|v| v.to_string())),
+            "confirm" => Ok(self.confirm.map(|v| v.to_string())),
+            "recent" if self.recent.is_empty() => Ok(None),
+            "recent" => Ok(Some(
+                self.recent
+                    .iter()
+                    .map(|r| format!("{} {} {}", r.source.display(), r.subdir, r.target.display()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )),
+            "keys" if self.keys.is_empty() => Ok(None),
+            "keys" => Ok(Some(
+                self.keys
+                    .iter()
+                    .map(|(action, binding)| format!("{} = {}", action, binding.keys().join(", ")))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )),
+            other => Err(unknown_key(other)),
+        }
+    }
+
+    /// Stores `value` under `key`, keeping the rest of the file and its comments as they are
+    pub fn set(key: &str, value: &str) -> anyhow::Result<()> {
+        let value = match key {
+            "theme" => {
+                Theme::parse(value)?;
+                Value::from(value)
+            }
+            "ascii" | "confirm" => Value::from(
+                value
+                    .parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid value for {}: {} (expected true or false)", key, value))?,
+            ),
+            other => return Err(unknown_key(other)),
+        };
+        Self::update(|doc| doc[key] = Item::Value(value))
+    }
+
+    /// Moves the repositories of a run to the front of the `recent` list
+    pub fn remember(source: &Path, subdir: &str, target: &Path) -> anyhow::Result<()> {
+        let entry = RecentRepos {
+            source: source.to_path_buf(),
+            subdir: subdir.to_string(),
+            target: target.to_path_buf(),
+        };
+        Self::update(|doc| {
+            let mut tables = vec![entry.to_table()];
+            if let Some(recent) = doc.get("recent").and_then(Item::as_array_of_tables) {
+                tables.extend(recent.iter().filter(|table| !entry.matches(table)).cloned());
+            }
+            tables.truncate(MAX_RECENT);
+            let mut recent = ArrayOfTables::new();
+            tables.into_iter().for_each(|table| recent.push(table));
+            doc["recent"] = Item::ArrayOfTables(recent);
+        })
+    }
+
+    fn update(edit: impl FnOnce(&mut DocumentMut)) -> anyhow::Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("Neither XDG_CONFIG_HOME nor HOME is set"))?;
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => anyhow::bail!("Failed to read preferences file {}: {}", path.display(), e),
+        };
+        let mut doc = content
+            .parse::<DocumentMut>()
+            .map_err(|e| anyhow::anyhow!("Invalid preferences file {}: {}", path.display(), e))?;
+        edit(&mut doc);
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, doc.to_string())
+            .map_err(|e| anyhow::anyhow!("Failed to write preferences file {}: {}", path.display(), e))
+    }
+}
+
+impl RecentRepos {
+    fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        table["source"] = toml_edit::value(self.source.to_string_lossy().as_ref());
+        table["subdir"] = toml_edit::value(self.subdir.as_str());
+        table["target"] = toml_edit::value(self.target.to_string_lossy().as_ref());
+        table
+    }
+
+    fn matches(&self, table: &Table) -> bool {
+        let field = |key: &str| table.get(key).and_then(Item::as_str).unwrap_or_default().to_string();
+        field("source") == self.source.to_string_lossy()
+            && field("subdir") == self.subdir
+            && field("target") == self.target.to_string_lossy()
+    }
+}
+
+fn unknown_key(key: &str) -> anyhow::Error {
+    anyhow::anyhow!("Unknown preference: {} (one of {}, recent, keys)", key, SETTABLE_KEYS.join(", "))
+}
@@ -0,0 +1,102 @@
+use crate::error::Result;
+use crate::sync::SyncEvent;
+use std::path::PathBuf;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast;
+
+/// Events buffered per client; a client that falls further behind skips ahead
+const CLIENT_BUFFER: usize = 256;
+
+/// Streams sync events as JSON lines to every client connected to a local
+/// endpoint, so a dashboard or editor can mirror the run. The endpoint is a
+/// unix socket path, or a TCP address when it parses as `host:port`.
+#[derive(Debug)]
+pub struct ProgressServer {
+    events: broadcast::Sender<String>,
+    accept_task: tokio::task::JoinHandle<()>,
+    /// Socket file to remove once the server stops
+    socket_path: Option<PathBuf>,
+}
+
+impl ProgressServer {
+    pub async fn bind(endpoint: &str) -> Result<Self> {
+        let (events, _) = broadcast::channel(CLIENT_BUFFER);
+        match endpoint.parse::<std::net::SocketAddr>() {
+            Ok(addr) => {
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                let clients = events.clone();
+                let accept_task = tokio::spawn(async move {
+                    while let Ok((stream, _)) = listener.accept().await {
+                        tokio::spawn(stream_events(stream, clients.subscribe()));
+                    }
+                });
+                Ok(Self { events, accept_task, socket_path: None })
+            }
+            Err(_) => Self::bind_unix(PathBuf::from(endpoint), events),
+        }
+    }
+
+    #[cfg(unix)]
+    fn bind_unix(path: PathBuf, events: broadcast::Sender<String>) -> Result<Self> {
+        if path.exists() {
+            // A socket left behind by a crashed run refuses connections and can go
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AddrInUse,
+                    format!("Progress socket {} is in use", path.display()),
+                ).into());
+            }
+            std::fs::remove_file(&path)?;
+        }
+        let listener = tokio::net::UnixListener::bind(&path)?;
+        let clients = events.clone();
+        let accept_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(stream_events(stream, clients.subscribe()));
+            }
+        });
+        Ok(Self { events, accept_task, socket_path: Some(path) })
+    }
+
+    #[cfg(not(unix))]
+    fn bind_unix(path: PathBuf, _events: broadcast::Sender<String>) -> Result<Self> {
+        Err(crate::error::SyncError::InvalidPath(format!(
+            "{}: unix sockets are not supported on this platform, use host:port",
+            path.display()
+        )))
+    }
+
+    /// Sends `event` to the connected clients; without clients it is dropped
+    pub fn publish(&self, event: &SyncEvent) {
+        match serde_json::to_string(event) {
+            Ok(json) => {
+                let _ = self.events.send(json);
+            }
+            Err(e) => tracing::error!("Failed to serialize sync event: {}", e),
+        }
+    }
+}
+
+impl Drop for ProgressServer {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+        if let Some(path) = &self.socket_path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Writes every published event to one client until it disconnects
+async fn stream_events(mut stream: impl AsyncWrite + Unpin, mut events: broadcast::Receiver<String>) {
+    loop {
+        match events.recv().await {
+            Ok(json) => {
+                if stream.write_all(format!("{}\n", json).as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
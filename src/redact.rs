@@ -0,0 +1,457 @@
+use crate::error::Result;
+use crate::git::compile_patterns;
+use regex::Regex;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Directory, relative to the working directory, where redaction reports are stored
+const REDACTIONS_DIR: &str = ".sync-subdir/redactions";
+
+/// Label used for findings in the commit message rather than in a file
+const MESSAGE_LOCATION: &str = "<commit message>";
+
+/// Content rules that must never reach the target repository. Path rules are
+/// enforced separately, by excluding the paths when the patch is generated.
+#[derive(Debug, Clone, Default)]
+pub struct Redaction {
+    content: Vec<Regex>,
+}
+
+/// A redaction or secret rule that matched an outgoing patch or file
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    /// File the matching line is added to, or the commit message
+    pub location: String,
+    /// Line number within the patch file, or within the file or message itself
+    /// when it was scanned whole (`--engine copy`)
+    pub patch_line: usize,
+    pub pattern: String,
+    #[serde(skip)]
+    whole_file: bool,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line = if self.whole_file { "line" } else { "patch line" };
+        write!(f, "{} ({} {}) matches '{}'", self.location, line, self.patch_line, self.pattern)
+    }
+}
+
+impl Redaction {
+    pub fn new(content_patterns: &[String]) -> Result<Self> {
+        Ok(Self {
+            content: compile_patterns(content_patterns)?,
+        })
+    }
+
+    /// Whether there are no content rules to check
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    /// Reads the patch at `path` and scans it
+    pub fn scan_patch_file(&self, path: &Path) -> Result<Vec<Finding>> {
+        if self.content.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(self.scan_patch(&std::fs::read(path)?))
+    }
+
+    /// Checks the commit message and every added line of a `git format-patch` file
+    pub fn scan_patch(&self, patch: &[u8]) -> Vec<Finding> {
+        self.scan_lines(&outgoing_lines(&String::from_utf8_lossy(patch)))
+    }
+
+    /// Checks every line of `content`, the file at `location` of the target or the
+    /// commit message when `None`
+    pub fn scan_file(&self, location: Option<&str>, content: &str) -> Vec<Finding> {
+        self.scan_lines(&file_lines(location, content))
+    }
+
+    fn scan_lines(&self, lines: &[OutgoingLine]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for line in lines {
+            for regex in &self.content {
+                if regex.is_match(&line.text) {
+                    findings.push(line.finding(regex.as_str()));
+                }
+            }
+        }
+        findings
+    }
+}
+
+/// How credentials detected in outgoing patches are handled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SecretScanMode {
+    Off,
+    Warn,
+    Block,
+}
+
+impl SecretScanMode {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "block" => Ok(Self::Block),
+            other => Err(anyhow::anyhow!("Invalid secret scan mode: {}", other)),
+        }
+    }
+}
+
+/// Well-known credential formats, as (rule name, pattern)
+const SECRET_RULES: &[(&str, &str)] = &[
+    ("aws-access-key", r"\b(AKIA|ASIA)[0-9A-Z]{16}\b"),
+    ("github-token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+    ("gitlab-token", r"\bglpat-[A-Za-z0-9_\-]{20,}\b"),
+    ("slack-token", r"\bxox[abprs]-[A-Za-z0-9\-]{10,}"),
+    ("google-api-key", r"\bAIza[0-9A-Za-z_\-]{35}\b"),
+    ("private-key", r"-----BEGIN [A-Z ]*PRIVATE KEY-----"),
+    ("credential-assignment", r#"(?i)(api[_-]?key|secret|token|passw(or)?d)["']?\s*[:=]\s*["'][^"'\s]{12,}["']"#),
+];
+
+/// Minimum length of a token considered for the entropy check
+const ENTROPY_MIN_LEN: usize = 24;
+/// Shannon entropy, in bits per character, above which a token looks random
+const ENTROPY_THRESHOLD: f64 = 4.0;
+
+/// Lightweight detector for credentials in outgoing patches: known token formats
+/// plus long, high-entropy strings. Matches of any allowlist pattern are ignored.
+#[derive(Debug, Clone)]
+pub struct SecretScanner {
+    rules: Vec<(&'static str, Regex)>,
+    allowlist: Vec<Regex>,
+}
+
+impl SecretScanner {
+    pub fn new(allowlist: Vec<Regex>) -> Self {
+        let rules = SECRET_RULES
+            .iter()
+            .map(|(name, pattern)| (*name, Regex::new(pattern).expect("built-in secret rule")))
+            .collect();
+        Self { rules, allowlist }
+    }
+
+    /// Loads allowlist patterns from `path`, one regex per line. Blank lines and
+    /// lines starting with `#` are skipped.
+    pub fn load_allowlist(path: &Path) -> Result<Vec<Regex>> {
+        let content = std::fs::read_to_string(path)?;
+        let patterns: Vec<String> = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect();
+        compile_patterns(&patterns)
+    }
+
+    pub fn scan_patch_file(&self, path: &Path) -> Result<Vec<Finding>> {
+        Ok(self.scan_patch(&std::fs::read(path)?))
+    }
+
+    /// Checks the commit message and every added line of a `git format-patch` file
+    pub fn scan_patch(&self, patch: &[u8]) -> Vec<Finding> {
+        self.scan_lines(&outgoing_lines(&String::from_utf8_lossy(patch)))
+    }
+
+    /// Checks every line of `content`, the file at `location` of the target or the
+    /// commit message when `None`
+    pub fn scan_file(&self, location: Option<&str>, content: &str) -> Vec<Finding> {
+        self.scan_lines(&file_lines(location, content))
+    }
+
+    fn scan_lines(&self, lines: &[OutgoingLine]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for line in lines {
+            for (name, regex) in &self.rules {
+                if regex.find_iter(&line.text).any(|m| !self.allowed(m.as_str(), line)) {
+                    findings.push(line.finding(name));
+                }
+            }
+            let random = line.text
+                .split(|c: char| !(c.is_ascii_alphanumeric() || "+/=_-".contains(c)))
+                .any(|token| looks_random(token) && !self.allowed(token, line));
+            if random {
+                findings.push(line.finding("high-entropy-string"));
+            }
+        }
+        findings
+    }
+
+    fn allowed(&self, secret: &str, line: &OutgoingLine) -> bool {
+        self.allowlist
+            .iter()
+            .any(|regex| regex.is_match(secret) || line.location.as_deref().is_some_and(|l| regex.is_match(l)))
+    }
+}
+
+fn looks_random(token: &str) -> bool {
+    if token.len() < ENTROPY_MIN_LEN
+        || !token.chars().any(|c| c.is_ascii_uppercase())
+        || !token.chars().any(|c| c.is_ascii_lowercase())
+        || !token.chars().any(|c| c.is_ascii_digit())
+    {
+        return false;
+    }
+
+    let mut counts = std::collections::HashMap::new();
+    for c in token.chars() {
+        *counts.entry(c).or_insert(0usize) += 1;
+    }
+    let len = token.len() as f64;
+    let entropy: f64 = counts
+        .values()
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum();
+    entropy >= ENTROPY_THRESHOLD
+}
+
+/// A line of a patch or file that would end up in the target repository
+struct OutgoingLine<'a> {
+    /// File the line is added to, `None` for the commit message
+    location: Option<String>,
+    /// Line number within the patch file, or within the file scanned whole
+    patch_line: usize,
+    text: Cow<'a, str>,
+    whole_file: bool,
+}
+
+impl OutgoingLine<'_> {
+    fn finding(&self, pattern: &str) -> Finding {
+        Finding {
+            location: self.location.clone().unwrap_or_else(|| MESSAGE_LOCATION.to_string()),
+            patch_line: self.patch_line,
+            pattern: pattern.to_string(),
+            whole_file: self.whole_file,
+        }
+    }
+}
+
+/// Every line of a file or message that is written as a whole
+fn file_lines<'a>(location: Option<&str>, content: &'a str) -> Vec<OutgoingLine<'a>> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| OutgoingLine {
+            location: location.map(str::to_string),
+            patch_line: i + 1,
+            text: Cow::Borrowed(line),
+            whole_file: true,
+        })
+        .collect()
+}
+
+/// The subject, message body and added lines of a `git format-patch` file. Context
+/// and removed lines are skipped, they are already in the target.
+fn outgoing_lines(text: &str) -> Vec<OutgoingLine<'_>> {
+    let mut lines = Vec::new();
+    let mut location: Option<String> = None;
+    let mut in_headers = true;
+    let mut in_stat = false;
+    // Line number and unfolded value of the subject while its header is read
+    let mut subject: Option<(usize, String)> = None;
+    for (i, line) in text.lines().enumerate() {
+        if in_headers {
+            // Long subjects are folded onto lines starting with whitespace
+            if let Some((_, value)) = subject.as_mut().filter(|_| line.starts_with([' ', '\t'])) {
+                value.push_str(line);
+                continue;
+            }
+            if let Some((patch_line, value)) = subject.take() {
+                lines.push(OutgoingLine { location: None, patch_line, text: Cow::Owned(decode_header(&value)), whole_file: false });
+            }
+            // Mail headers are only checked for the subject
+            in_headers = !line.is_empty();
+            subject = line.strip_prefix("Subject:").map(|value| (i + 1, value.to_string()));
+            continue;
+        }
+
+        let candidate = if let Some(paths) = line.strip_prefix("diff --git ") {
+            location = Some(paths.rsplit(" b/").next().unwrap_or(paths).to_string());
+            None
+        } else if location.is_some() {
+            if line.starts_with("+++") {
+                None
+            } else {
+                line.strip_prefix('+')
+            }
+        } else if in_stat || line == "---" {
+            in_stat = true;
+            None
+        } else {
+            Some(line)
+        };
+
+        if let Some(text) = candidate {
+            lines.push(OutgoingLine {
+                location: location.clone(),
+                patch_line: i + 1,
+                text: Cow::Borrowed(text),
+                whole_file: false,
+            });
+        }
+    }
+    if let Some((patch_line, value)) = subject {
+        lines.push(OutgoingLine { location: None, patch_line, text: Cow::Owned(decode_header(&value)), whole_file: false });
+    }
+    lines
+}
+
+/// Decodes the RFC 2047 encoded words (`=?UTF-8?q?...?=`) of an unfolded header
+/// value, which `git format-patch` writes for non-ASCII subjects. Other
+/// charsets than UTF-8 are decoded lossily, which is enough to match rules.
+fn decode_header(value: &str) -> String {
+    static ENCODED_WORD: OnceLock<Regex> = OnceLock::new();
+    let encoded_word = ENCODED_WORD.get_or_init(|| Regex::new(r"=\?[^?\s]+\?([qQbB])\?([^?\s]*)\?=").unwrap());
+    let mut decoded = String::new();
+    let mut last = 0;
+    for captures in encoded_word.captures_iter(value) {
+        let word = captures.get(0).unwrap();
+        let between = &value[last..word.start()];
+        // Whitespace between two encoded words only separates them
+        if last == 0 || !between.trim().is_empty() {
+            decoded.push_str(between);
+        }
+        let bytes = if captures[1].eq_ignore_ascii_case("b") {
+            decode_base64(&captures[2])
+        } else {
+            decode_q(&captures[2])
+        };
+        decoded.push_str(&String::from_utf8_lossy(&bytes));
+        last = word.end();
+    }
+    decoded.push_str(&value[last..]);
+    decoded
+}
+
+/// The "Q" encoding of RFC 2047: `_` is a space and `=XX` a byte in hex
+fn decode_q(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'=', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'_', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    decoded
+}
+
+/// The "B" encoding of RFC 2047, standard base64; invalid characters are skipped
+fn decode_base64(text: &str) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => continue,
+        };
+        buffer = ((buffer << 6) | u32::from(value)) & 0xffff;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+    decoded
+}
+
+/// Writes the blocking findings for `commit` under `.sync-subdir/redactions/`. The matching
+/// text itself is deliberately left out of the report.
+pub fn write_report(commit: &str, findings: &[Finding]) -> Result<PathBuf> {
+    let dir = std::env::current_dir()?.join(REDACTIONS_DIR);
+    std::fs::create_dir_all(&dir)?;
+
+    let path = dir.join(format!("{}.txt", commit));
+    let mut report = format!("Commit {} blocked by {} finding(s):\n", commit, findings.len());
+    for finding in findings {
+        report.push_str(&format!("  {}\n", finding));
+    }
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(subject: &str, added: &str) -> Vec<u8> {
+        format!(
+            "From 0123456789abcdef0123456789abcdef01234567 Mon Sep 17 00:00:00 2001\n\
+             From: Test <test@example.com>\n\
+             Date: Thu, 1 Jan 1970 00:16:40 +0000\n\
+             Subject: {}\n\
+             \n\
+             ---\n \
+             a | 1 +\n \
+             1 file changed, 1 insertion(+)\n\
+             \n\
+             diff --git a/a b/a\n\
+             index e69de29..d00491f 100644\n\
+             --- a/a\n\
+             +++ b/a\n\
+             @@ -0,0 +1 @@\n\
+             +{}\n",
+            subject, added
+        )
+        .into_bytes()
+    }
+
+    fn locations(findings: &[Finding]) -> Vec<&str> {
+        findings.iter().map(|f| f.location.as_str()).collect()
+    }
+
+    #[test]
+    fn folded_subjects_are_unfolded() {
+        let redaction = Redaction::new(&["INTERNAL-4242 rollout".to_string()]).unwrap();
+        let findings = redaction.scan_patch(&patch("[PATCH] Prepare the customer INTERNAL-4242\n rollout", "1"));
+        assert_eq!(locations(&findings), [MESSAGE_LOCATION]);
+        assert_eq!(findings[0].patch_line, 4);
+    }
+
+    #[test]
+    fn encoded_subjects_are_decoded() {
+        let redaction = Redaction::new(&["INTERNAL-\\d+".to_string(), "机密".to_string()]).unwrap();
+        // "修复 INTERNAL-4242 问题" in q encoding, folded between two encoded words
+        let q = "[PATCH] =?UTF-8?q?=E4=BF=AE=E5=A4=8D=20INTERNAL-4242=20?=\n =?UTF-8?q?=E9=97=AE=E9=A2=98?=";
+        assert_eq!(locations(&redaction.scan_patch(&patch(q, "1"))), [MESSAGE_LOCATION]);
+        // "机密" in b encoding
+        let b = "[PATCH] =?UTF-8?B?5py65a+G?=";
+        assert_eq!(locations(&redaction.scan_patch(&patch(b, "1"))), [MESSAGE_LOCATION]);
+        assert!(redaction.scan_patch(&patch("[PATCH] Plain subject", "1")).is_empty());
+    }
+
+    #[test]
+    fn decode_header_joins_adjacent_words() {
+        assert_eq!(decode_header(" =?UTF-8?q?a_b?= =?utf-8?Q?=E4=BF=AE?= c"), " a b修 c");
+        assert_eq!(decode_header("=?UTF-8?b?SGVsbG8=?=\t=?UTF-8?b?IHdvcmxk?="), "Hello world");
+        assert_eq!(decode_header("no =? words here"), "no =? words here");
+    }
+
+    #[test]
+    fn secrets_in_encoded_subjects_are_found() {
+        // An underscore is written as =5F, a bare one stands for a space
+        let subject = format!("[PATCH] =?UTF-8?q?=E4=BF=AE=E5=A4=8D_ghp=5F{}?=", "a1B2".repeat(9));
+        let findings = SecretScanner::new(Vec::new()).scan_patch(&patch(&subject, "1"));
+        assert!(findings.iter().any(|f| f.location == MESSAGE_LOCATION && f.pattern == "github-token"));
+    }
+}
@@ -0,0 +1,451 @@
+use crate::error::{Result, SyncError};
+use crate::git::{short_id, CommitInfo};
+use crate::patch::PatchStats;
+use crate::redact::Finding;
+use crate::session::Label;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Exit code of a run that was cancelled before all commits were processed
+pub const EXIT_ABORTED: i32 = 2;
+/// Exit code of a completed run that synced commits flagged by the secret scanner
+pub const EXIT_SECRETS_FLAGGED: i32 = 3;
+/// Exit code of a run that skipped conflicting commits with `--continue-on-conflict`
+pub const EXIT_CONFLICTS: i32 = 4;
+
+/// Outcome of a sync run. The Completed screen, the `--report` file, the
+/// process exit code and the pull request description are all rendered from it.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    /// Run id shared by the run's journal entries
+    pub run_id: String,
+    pub config: ReportConfig,
+    pub total_commits: usize,
+    pub synced_commits: usize,
+    pub skipped_commits: usize,
+    /// Processed commits in order; commits after the last one were not reached
+    pub commits: Vec<CommitResult>,
+    /// Commits whose patches look like they contain credentials
+    pub flagged_commits: Vec<FlaggedCommit>,
+    /// Commits changing files that are not UTF-8 encoded; their bytes were
+    /// synced unchanged, but deserve a second look
+    pub non_utf8_commits: Vec<NonUtf8Commit>,
+    /// Commits skipped because their patch conflicted (`--continue-on-conflict`)
+    pub conflicts: Vec<ConflictedCommit>,
+    /// Commits whose patch a dry run found would not apply to the target
+    pub predicted_conflicts: Vec<ConflictedCommit>,
+    /// Files, hunks and bytes of all patches a dry run generated; `None` for
+    /// runs that applied them
+    pub patch_totals: Option<PatchStats>,
+    /// Warnings shown before the run started
+    pub warnings: Vec<String>,
+    /// Non-fatal issues met during the run, also sent as `Warning` events
+    pub run_warnings: Vec<RunWarning>,
+    /// Labels the run's commits were given on the selection screen, keyed by source commit id
+    pub labels: BTreeMap<String, Label>,
+    /// The run was cancelled; commits after the last result were not processed
+    pub aborted: bool,
+    /// Staging branch kept because not every commit applied (`--staging-branch`);
+    /// the target branch was left as it was
+    pub staging_branch: Option<String>,
+    /// Directory the run's patches were saved to by `--keep-patches all`
+    pub kept_patches: Option<PathBuf>,
+    /// Paths where the target directory still differs from the source directory
+    /// after the run (`--verify`); `None` when the check did not run
+    pub divergence: Option<Vec<Divergence>>,
+    /// Version bump committed after the synced commits (`--bump-version`)
+    pub version_bump: Option<VersionBump>,
+    /// Tags created on the target commits of source commits with tags (`--sync-tags`)
+    pub tags: Vec<SyncedTag>,
+    /// Source tags that got no target tag
+    pub skipped_tags: Vec<SkippedTag>,
+    /// The same run on each `--target` fan-out repository, in the order given
+    pub fanout: Vec<FanoutResult>,
+    /// Error the run on the main target failed with. Only the aggregate report
+    /// of a fan-out run has one, so the other targets' results are not lost.
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Settings the run was made with
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReportConfig {
+    pub source_repo: PathBuf,
+    pub target_repo: PathBuf,
+    pub subdir: String,
+    pub target_dir: String,
+    /// Backend that ran the sync, e.g. `patch`
+    pub engine: String,
+    pub dry_run: bool,
+    /// Bundle directory or mbox file the patches were exported to instead of being applied
+    pub export_dir: Option<PathBuf>,
+}
+
+/// Result of a single processed commit
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitResult {
+    pub id: String,
+    pub subject: String,
+    /// Status shown in the progress log, e.g. `OK` or `EMPTY (SKIPPED)`
+    pub status: String,
+    /// Commit created in the target repository, if any
+    pub target: Option<String>,
+    pub duration_ms: u64,
+    pub patch_bytes: u64,
+    /// Size of the generated patch, counted by dry runs
+    pub patch_stats: Option<PatchStats>,
+}
+
+/// A commit whose patch matched the secret scanner
+#[derive(Debug, Clone, Serialize)]
+pub struct FlaggedCommit {
+    pub id: String,
+    pub subject: String,
+    pub findings: Vec<Finding>,
+}
+
+/// A commit whose patch changes lines that are not valid UTF-8
+#[derive(Debug, Clone, Serialize)]
+pub struct NonUtf8Commit {
+    pub id: String,
+    pub subject: String,
+    /// Changed files, relative to the target repository
+    pub paths: Vec<String>,
+}
+
+/// A commit left for manual attention because its patch did not apply
+#[derive(Debug, Clone, Serialize)]
+pub struct ConflictedCommit {
+    pub id: String,
+    pub subject: String,
+    /// Why `git am` failed, with the place its state was saved to
+    pub error: String,
+    /// Directory the patch and the conflicted state were saved to
+    pub artifacts: Option<PathBuf>,
+}
+
+/// Version file change committed at the end of a run
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionBump {
+    /// Version file, relative to the target repository root
+    pub file: String,
+    pub from: String,
+    pub to: String,
+    /// Commit created in the target repository
+    pub commit: String,
+}
+
+/// A non-fatal issue met during a run, such as a journal entry that could
+/// not be written; the run went on without it
+#[derive(Debug, Clone, Serialize)]
+pub struct RunWarning {
+    /// Source commit it concerns, if any
+    pub commit: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for RunWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.commit {
+            Some(commit) => write!(f, "{}: {}", short_id(commit), self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Outcome of the run on one `--target` fan-out repository
+#[derive(Debug, Clone, Serialize)]
+pub struct FanoutResult {
+    pub target_repo: PathBuf,
+    /// `None` when the run failed before finishing, see `error`
+    pub report: Option<SyncReport>,
+    pub error: Option<String>,
+    /// Pull request opened for the target (`--create-pr`)
+    pub pull_request: Option<String>,
+}
+
+/// A target tag created for a source tag
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncedTag {
+    pub name: String,
+    /// Name of the source tag
+    pub source: String,
+    /// Target commit it points at
+    pub commit: String,
+}
+
+/// A source tag left out of the target, with the reason shown to the user
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedTag {
+    /// Target tag name it would have had
+    pub name: String,
+    pub reason: String,
+}
+
+/// A path where the target directory differs from the source directory
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub path: String,
+    /// `missing` from the target, `extra` in the target, or `modified`
+    /// (content or mode)
+    pub kind: &'static str,
+}
+
+impl Divergence {
+    /// Kind shown to the user
+    pub fn label(&self) -> &'static str {
+        match self.kind {
+            "missing" => "缺少",
+            "extra" => "多出",
+            _ => "不同",
+        }
+    }
+}
+
+/// Divergent paths listed in the summary; the `--report` file has all of them
+const DIVERGENCE_SHOWN: usize = 20;
+
+impl SyncReport {
+    pub fn slowest(&self, n: usize) -> Vec<&CommitResult> {
+        let mut commits: Vec<_> = self.commits.iter().collect();
+        commits.sort_by_key(|c| std::cmp::Reverse(c.duration_ms));
+        commits.truncate(n);
+        commits
+    }
+
+    /// Sums the patch statistics of the processed commits into `patch_totals`
+    pub(crate) fn total_patch_stats(&mut self) {
+        let mut totals = PatchStats::default();
+        for stats in self.commits.iter().filter_map(|c| c.patch_stats.as_ref()) {
+            totals.add(stats);
+        }
+        self.patch_totals = Some(totals);
+    }
+
+    pub fn largest(&self, n: usize) -> Vec<&CommitResult> {
+        let mut commits: Vec<_> = self.commits.iter().collect();
+        commits.sort_by_key(|c| std::cmp::Reverse(c.patch_bytes));
+        commits.truncate(n);
+        commits
+    }
+
+    /// Folds in a run that re-attempted some of this run's conflicts. A commit
+    /// that applied leaves the conflict list; one that conflicted again keeps
+    /// its place with the new error.
+    pub fn merge_retry(&mut self, retry: SyncReport) {
+        for result in retry.commits {
+            match retry.conflicts.iter().find(|c| c.id == result.id) {
+                Some(conflict) => {
+                    if let Some(existing) = self.conflicts.iter_mut().find(|c| c.id == conflict.id) {
+                        *existing = conflict.clone();
+                    }
+                }
+                None => self.conflicts.retain(|c| c.id != result.id),
+            }
+            self.flagged_commits.retain(|c| c.id != result.id);
+            self.non_utf8_commits.retain(|c| c.id != result.id);
+            match self.commits.iter_mut().find(|c| c.id == result.id) {
+                Some(existing) => *existing = result,
+                None => self.commits.push(result),
+            }
+        }
+        self.flagged_commits.extend(retry.flagged_commits);
+        self.non_utf8_commits.extend(retry.non_utf8_commits);
+        self.labels.extend(retry.labels);
+        if retry.divergence.is_some() {
+            self.divergence = retry.divergence;
+        }
+        self.synced_commits += retry.synced_commits;
+        self.skipped_commits += retry.skipped_commits;
+        self.duration_ms += retry.duration_ms;
+    }
+
+    pub(crate) fn flag(&mut self, commit: &CommitInfo, findings: Vec<Finding>) {
+        if !findings.is_empty() {
+            self.flagged_commits.push(FlaggedCommit {
+                id: commit.id.clone(),
+                subject: commit.subject.clone(),
+                findings,
+            });
+        }
+    }
+
+    pub(crate) fn note_non_utf8(&mut self, commit: &CommitInfo, paths: Vec<String>) {
+        if !paths.is_empty() {
+            self.non_utf8_commits.push(NonUtf8Commit {
+                id: commit.id.clone(),
+                subject: commit.subject.clone(),
+                paths,
+            });
+        }
+    }
+
+    /// Process exit code: 0 for a clean run, [`EXIT_ABORTED`], [`EXIT_CONFLICTS`] (also
+    /// for conflicts a dry run predicted) or [`EXIT_SECRETS_FLAGGED`] otherwise.
+    /// Failed runs produce no report and exit with 1.
+    pub fn exit_code(&self) -> i32 {
+        let code = self.own_exit_code();
+        if code != 0 {
+            return code;
+        }
+        // A failed fan-out target fails the run like a failed main target
+        self.fanout
+            .iter()
+            .map(|target| target.report.as_ref().map_or(1, SyncReport::exit_code))
+            .find(|&code| code != 0)
+            .unwrap_or(0)
+    }
+
+    fn own_exit_code(&self) -> i32 {
+        if self.error.is_some() {
+            1
+        } else if self.aborted {
+            EXIT_ABORTED
+        } else if !self.conflicts.is_empty() || !self.predicted_conflicts.is_empty() {
+            EXIT_CONFLICTS
+        } else if !self.flagged_commits.is_empty() {
+            EXIT_SECRETS_FLAGGED
+        } else {
+            0
+        }
+    }
+
+    /// Writes the report as pretty-printed JSON to `path`
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| SyncError::Anyhow(e.into()))?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Summary shown when the run ends, one fact per line
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(error) = &self.error {
+            write!(f, "同步失败: {}", error)?;
+        } else if self.aborted {
+            write!(
+                f,
+                "同步已中断: 已处理 {}/{}, 同步 {}, 跳过 {}",
+                self.commits.len(),
+                self.total_commits,
+                self.synced_commits,
+                self.skipped_commits
+            )?;
+        } else {
+            write!(
+                f,
+                "同步完成: 总计 {}, 同步 {}, 跳过 {}",
+                self.total_commits, self.synced_commits, self.skipped_commits
+            )?;
+        }
+        if let Some(totals) = &self.patch_totals {
+            write!(
+                f,
+                "\n补丁合计: {} 个文件, {} 个 hunk, {}",
+                totals.files,
+                totals.hunks,
+                format_bytes(totals.bytes)
+            )?;
+        }
+        if let Some(dir) = &self.config.export_dir {
+            write!(f, "\n补丁已导出到: {}", dir.display())?;
+        }
+        if let Some(dir) = &self.kept_patches {
+            write!(f, "\n补丁已保存到: {}", dir.display())?;
+        }
+        if !self.flagged_commits.is_empty() {
+            write!(f, "\n{} 个提交疑似包含密钥", self.flagged_commits.len())?;
+        }
+        if !self.run_warnings.is_empty() {
+            write!(f, "\n同步过程中有 {} 条警告", self.run_warnings.len())?;
+        }
+        if !self.non_utf8_commits.is_empty() {
+            write!(f, "\n{} 个提交改动了非 UTF-8 编码的文件 (按原字节同步，请检查):", self.non_utf8_commits.len())?;
+            for commit in &self.non_utf8_commits {
+                write!(f, "\n  {} {}: {}", short_id(&commit.id), commit.subject, commit.paths.join(", "))?;
+            }
+        }
+        if !self.conflicts.is_empty() {
+            write!(f, "\n{} 个提交因冲突未同步，需要手动处理:", self.conflicts.len())?;
+            for conflict in &self.conflicts {
+                write!(f, "\n  {} {}", short_id(&conflict.id), conflict.subject)?;
+                if let Some(dir) = &conflict.artifacts {
+                    write!(f, " ({})", dir.display())?;
+                }
+            }
+        }
+        if !self.predicted_conflicts.is_empty() {
+            write!(f, "\n预计 {} 个提交同步时会冲突:", self.predicted_conflicts.len())?;
+            for conflict in &self.predicted_conflicts {
+                write!(f, "\n  {} {}", short_id(&conflict.id), conflict.subject)?;
+            }
+        }
+        if !self.tags.is_empty() {
+            let names: Vec<&str> = self.tags.iter().map(|tag| tag.name.as_str()).collect();
+            write!(f, "\n创建了 {} 个标签: {}", self.tags.len(), names.join(", "))?;
+        }
+        for tag in &self.skipped_tags {
+            write!(f, "\n未创建标签 {}: {}", tag.name, tag.reason)?;
+        }
+        if let Some(bump) = &self.version_bump {
+            write!(f, "\n版本号: {} → {} ({}, 提交 {})", bump.from, bump.to, bump.file, short_id(&bump.commit))?;
+        }
+        if let Some(branch) = &self.staging_branch {
+            write!(f, "\n提交保留在暂存分支 {}，目标分支未改动", branch)?;
+        }
+        match self.divergence.as_deref() {
+            Some([]) => write!(f, "\n校验通过: 目标目录与源子目录一致")?,
+            Some(divergence) => {
+                write!(f, "\n校验发现 {} 个文件与源子目录不一致:", divergence.len())?;
+                for entry in divergence.iter().take(DIVERGENCE_SHOWN) {
+                    write!(f, "\n  {} {}", entry.label(), entry.path)?;
+                }
+                if divergence.len() > DIVERGENCE_SHOWN {
+                    write!(f, "\n  ... 另有 {} 个", divergence.len() - DIVERGENCE_SHOWN)?;
+                }
+            }
+            None => {}
+        }
+        for target in &self.fanout {
+            write!(f, "\n目标 {}: ", target.target_repo.display())?;
+            match (&target.report, &target.error) {
+                (Some(report), _) => {
+                    write!(f, "同步 {}, 跳过 {}", report.synced_commits, report.skipped_commits)?;
+                    if report.aborted {
+                        write!(f, ", 已中断")?;
+                    }
+                    if !report.conflicts.is_empty() {
+                        write!(f, ", {} 个冲突", report.conflicts.len())?;
+                    }
+                    if let Some(branch) = &report.staging_branch {
+                        write!(f, ", 提交保留在暂存分支 {}", branch)?;
+                    }
+                }
+                (None, error) => write!(f, "失败: {}", error.as_deref().unwrap_or_default())?,
+            }
+            if let Some(url) = &target.pull_request {
+                write!(f, "\n  PR: {}", url)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Byte count in the largest unit that keeps it at or above 1, e.g. `12.3 KiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
@@ -0,0 +1,113 @@
+use crate::error::{Result, SyncError};
+use crate::report::SyncReport;
+use git2::Repository;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Directory, in the target's git dir, where run reports are archived
+const RUNS_DIR: &str = "sync-subdir/runs";
+
+/// Suffix of an archived report
+const EXTENSION: &str = ".json.zst";
+
+/// zstd level of the archived reports; they are small and written once per run
+const COMPRESSION_LEVEL: i32 = 19;
+
+/// Archived runs kept by default (`--keep-runs`)
+pub const DEFAULT_KEEP: usize = 100;
+
+/// A report in the archive, named after the time its run ended
+#[derive(Debug, Clone)]
+pub struct ArchivedRun {
+    /// Local time as `YYYYMMDD-HHMMSS-mmm`; names sort in time order
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl ArchivedRun {
+    /// The time in the name, readable
+    pub fn time(&self) -> String {
+        chrono::NaiveDateTime::parse_from_str(&self.name, "%Y%m%d-%H%M%S-%3f")
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|_| self.name.clone())
+    }
+
+    /// The archived report, as JSON; reports of older versions may lack fields
+    pub fn read(&self) -> Result<serde_json::Value> {
+        let compressed = std::fs::read(&self.path)?;
+        let json = zstd::decode_all(compressed.as_slice())?;
+        serde_json::from_slice(&json).map_err(|e| SyncError::Anyhow(e.into()))
+    }
+}
+
+/// The archive of the target repository at `target_repo`, next to its journal
+pub fn dir(target_repo: &Path) -> Result<PathBuf> {
+    Ok(Repository::open(target_repo)?.path().join(RUNS_DIR))
+}
+
+/// Stores `report` compressed in `dir`, then removes the oldest archived runs
+/// beyond the newest `keep`. Returns the path written.
+pub fn archive(dir: &Path, report: &SyncReport, keep: usize) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let json = serde_json::to_vec(report).map_err(|e| SyncError::Anyhow(e.into()))?;
+    let compressed = zstd::encode_all(json.as_slice(), COMPRESSION_LEVEL)?;
+
+    let name = chrono::Local::now().format("%Y%m%d-%H%M%S-%3f").to_string();
+    let path = dir.join(format!("{}{}", name, EXTENSION));
+    // Never replace another run's report
+    let mut file = OpenOptions::new().write(true).create_new(true).open(&path)?;
+    file.write_all(&compressed)?;
+
+    let runs = list(dir)?;
+    for run in &runs[..runs.len().saturating_sub(keep)] {
+        std::fs::remove_file(&run.path)?;
+    }
+    Ok(path)
+}
+
+/// Archived runs in `dir`, oldest first; none when the directory does not exist
+pub fn list(dir: &Path) -> Result<Vec<ArchivedRun>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut runs = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()?.strip_suffix(EXTENSION)) else {
+            continue;
+        };
+        runs.push(ArchivedRun { name: name.to_string(), path });
+    }
+    runs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TestRepo;
+
+    #[test]
+    fn runs_are_archived_in_the_target_and_pruned_to_keep() {
+        let target = TestRepo::new();
+        let dir = dir(target.path()).unwrap();
+        assert_eq!(dir, target.repo.path().join("sync-subdir/runs"));
+
+        let report = SyncReport { run_id: "first".to_string(), ..Default::default() };
+        let first = archive(&dir, &report, 2).unwrap();
+        for run_id in ["second", "third"] {
+            // Names have millisecond resolution
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            archive(&dir, &SyncReport { run_id: run_id.to_string(), ..Default::default() }, 2).unwrap();
+        }
+
+        let runs = list(&dir).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert!(!first.exists());
+        let run_ids: Vec<_> = runs.iter().map(|run| run.read().unwrap()["run_id"].as_str().unwrap().to_string()).collect();
+        assert_eq!(run_ids, ["second", "third"]);
+    }
+}
@@ -0,0 +1,131 @@
+use chrono::{DateTime, Datelike, Duration, Local, TimeZone, Timelike};
+
+/// Limit for the search of the next run; every valid schedule fires within
+/// four years (a job on 29 February)
+const MAX_SEARCH_DAYS: i64 = 4 * 366;
+
+/// A cron-style schedule of five fields: minute, hour, day of month, month and
+/// day of week (0 or 7 is Sunday). Each field is `*`, a number, a range `a-b`,
+/// a step `*/n` or `a-b/n`, or a comma separated list of these. `@hourly`,
+/// `@daily` and `@weekly` are accepted as shorthands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days: Vec<bool>,
+    months: Vec<bool>,
+    weekdays: Vec<bool>,
+    /// Whether day of month and day of week were `*`; cron fires on either day
+    /// field when both are restricted
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        let expr = match value.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            other => other,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(anyhow::anyhow!("Invalid schedule: {} (expected 5 fields)", value));
+        };
+
+        let mut weekdays = parse_field(weekday, 0, 7).map_err(|e| invalid(value, e))?;
+        // 7 is another name for Sunday
+        if weekdays[7] {
+            weekdays[0] = true;
+        }
+        weekdays.truncate(7);
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59).map_err(|e| invalid(value, e))?,
+            hours: parse_field(hour, 0, 23).map_err(|e| invalid(value, e))?,
+            days: parse_field(day, 1, 31).map_err(|e| invalid(value, e))?,
+            months: parse_field(month, 1, 12).map_err(|e| invalid(value, e))?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    /// First minute strictly after `time` the schedule fires at
+    pub fn next_after(&self, time: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = time.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let end = start + Duration::days(MAX_SEARCH_DAYS);
+        let mut candidate = start;
+        while candidate < end {
+            if !self.matches_day(candidate.date()) {
+                candidate = candidate.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.hours[candidate.hour() as usize] {
+                candidate = candidate.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if self.minutes[candidate.minute() as usize] {
+                // Local times skipped by a DST change never happen; ambiguous ones run once
+                if let Some(local) = Local.from_local_datetime(&candidate).earliest() {
+                    return Some(local);
+                }
+            }
+            candidate += Duration::minutes(1);
+        }
+        None
+    }
+
+    fn matches_day(&self, date: chrono::NaiveDate) -> bool {
+        if !self.months[date.month() as usize] {
+            return false;
+        }
+        let day = self.days[date.day() as usize];
+        let weekday = self.weekdays[date.weekday().num_days_from_sunday() as usize];
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+fn invalid(value: &str, reason: String) -> anyhow::Error {
+    anyhow::anyhow!("Invalid schedule: {} ({})", value, reason)
+}
+
+/// Parses one field into a table indexed by value, sized `max + 1`
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>, String> {
+    let mut table = vec![false; max as usize + 1];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0).ok_or(format!("bad step in '{}'", part))?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (parse_value(a, min, max)?, parse_value(b, min, max)?),
+                None => {
+                    let value = parse_value(range, min, max)?;
+                    // `5/15` runs from 5 to the end of the field, like `5-59/15`
+                    (value, if step > 1 { max } else { value })
+                }
+            },
+        };
+        if from > to {
+            return Err(format!("empty range '{}'", part));
+        }
+        for value in (from..=to).step_by(step as usize) {
+            table[value as usize] = true;
+        }
+    }
+    Ok(table)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|v| (min..=max).contains(v))
+        .ok_or(format!("'{}' is not within {}-{}", value, min, max))
+}
@@ -0,0 +1,91 @@
+use crate::error::{Result, SyncError};
+use git2::Repository;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Tag a commit can be given on the selection screen, so whoever curates the
+/// sync can leave notes for the next person
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Label {
+    NeedsReview,
+    Hold,
+    Security,
+}
+
+impl Label {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Label::NeedsReview => "needs-review",
+            Label::Hold => "hold",
+            Label::Security => "security",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Label::NeedsReview => Color::Yellow,
+            Label::Hold => Color::Red,
+            Label::Security => Color::Cyan,
+        }
+    }
+
+    /// Label after `current` when cycling with the label key; no label follows the last one
+    pub fn cycle(current: Option<Label>) -> Option<Label> {
+        match current {
+            None => Some(Label::NeedsReview),
+            Some(Label::NeedsReview) => Some(Label::Hold),
+            Some(Label::Hold) => Some(Label::Security),
+            Some(Label::Security) => None,
+        }
+    }
+}
+
+/// Curation of the selection screen kept next to the sync journal, in the target
+/// repository's git dir, so it survives restarts and can be handed on
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    #[serde(skip)]
+    path: PathBuf,
+    /// Labels keyed by source commit id
+    pub labels: BTreeMap<String, Label>,
+}
+
+impl Session {
+    /// The saved session of `target_repo`, empty when there is none yet. An
+    /// unreadable file is reported and replaced on the next save.
+    pub fn open(target_repo: &Path) -> Result<Self> {
+        let repo = Repository::open(target_repo)?;
+        let path = repo.path().join("sync-subdir").join("selection.json");
+        let mut session = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str::<Self>(&content).unwrap_or_else(|e| {
+                warn!("Ignoring invalid selection session {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        session.path = path;
+        Ok(session)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| SyncError::Anyhow(e.into()))?;
+        std::fs::write(&self.path, json)?;
+        Ok(())
+    }
+
+    /// Moves `commit` to its next label
+    pub fn cycle_label(&mut self, commit: &str) {
+        match Label::cycle(self.labels.get(commit).copied()) {
+            Some(label) => self.labels.insert(commit.to_string(), label),
+            None => self.labels.remove(commit),
+        };
+    }
+}
@@ -0,0 +1,67 @@
+use crate::error::Result;
+
+/// Process signals the TUI reacts to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    /// SIGTSTP sent by something other than our own Ctrl+Z handling
+    Suspend,
+    /// SIGINT or SIGTERM: stop the sync gracefully and quit
+    Interrupt,
+}
+
+/// Listens for process signals while the TUI owns the terminal. Registering a
+/// handler replaces the default action, so the TUI can restore the terminal
+/// before the process actually stops.
+pub struct Signals {
+    #[cfg(unix)]
+    suspend: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl Signals {
+    #[cfg(unix)]
+    pub fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+        Ok(Self {
+            suspend: signal(SignalKind::from_raw(libc::SIGTSTP))?,
+            interrupt: signal(SignalKind::interrupt())?,
+            terminate: signal(SignalKind::terminate())?,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn new() -> Result<Self> {
+        Ok(Self {})
+    }
+
+    #[cfg(unix)]
+    pub async fn recv(&mut self) -> Signal {
+        tokio::select! {
+            _ = self.suspend.recv() => Signal::Suspend,
+            _ = self.interrupt.recv() => Signal::Interrupt,
+            _ = self.terminate.recv() => Signal::Interrupt,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub async fn recv(&mut self) -> Signal {
+        let _ = tokio::signal::ctrl_c().await;
+        Signal::Interrupt
+    }
+}
+
+/// Stops the current process until it receives SIGCONT. SIGSTOP cannot be
+/// caught, so this does not loop back into the SIGTSTP handler.
+#[cfg(unix)]
+pub fn stop_process() {
+    // SAFETY: raise has no preconditions; the call returns once the process continues
+    unsafe {
+        libc::raise(libc::SIGSTOP);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn stop_process() {}
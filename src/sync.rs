@@ -1,10 +1,33 @@
+use crate::backend::{SyncBackend, SyncPlan};
+use crate::bundle::{BundlePatch, Manifest, MANIFEST_VERSION};
+use crate::copy;
 use crate::error::{SyncError, Result};
-use crate::git::{CommitInfo, GitManager};
-use tokio::time::{sleep, Duration};
-use tokio::sync::mpsc::UnboundedSender;
-use tempfile::tempdir;
+use crate::git::{short_id, ApplyWorktree, CommitInfo, GitManager, OctopusPolicy};
+use crate::journal::{self, Journal, JournalEntry};
+use crate::mbox::MboxWriter;
+use crate::notes;
+use crate::paths::PathMapping;
+use crate::patch::{self, MessageRewrite, PatchStats};
+use crate::redact::{self, Finding, Redaction, SecretScanner};
+use crate::report::{CommitResult, ConflictedCommit, ReportConfig, RunWarning, SyncReport, VersionBump};
+use crate::session::Label;
+use crate::tags::{self, TagSync};
+use crate::tmpdir;
+use crate::todo::Todo;
+use crate::version::{BumpPolicy, VersionFile};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::Sender;
+use tracing::{field, info_span, instrument};
 
-#[derive(Debug, Clone)]
+/// Serialized as `{"event": "progress", "data": {...}}` for `--progress-socket` clients
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
 pub enum SyncEvent {
     Progress {
         current: usize,
@@ -12,35 +35,278 @@ pub enum SyncEvent {
         subject: String,
         status: String,
     },
-    Completed(SyncStats),
+    Completed(Box<SyncReport>),
+    /// Sent after `Completed` when a pull request was opened for the synced branch
+    PullRequestCreated(String),
+    /// A non-fatal issue; the run goes on and the report lists it too
+    Warning(RunWarning),
     Error(String),
+    /// Event of the run on `--target` fan-out repository `index`, counting from 0
+    Target {
+        index: usize,
+        event: Box<SyncEvent>,
+    },
+}
+
+pub struct SyncEngine {
+    config: SyncConfig,
+    dry_run: bool,
+    journal: Option<Journal>,
+    /// Write patches and a manifest here instead of applying them
+    export_dir: Option<PathBuf>,
+    /// Write the patches into this mbox file instead of applying them
+    export_mbox: Option<PathBuf>,
+    cancel: Option<Arc<AtomicBool>>,
+    /// Carried into the run's report
+    warnings: Vec<String>,
+    /// Carried into the run's report
+    labels: BTreeMap<String, Label>,
+    /// Re-attempts conflicts of an earlier run, see [`SyncEngine::retrying_conflicts`]
+    retrying_conflicts: bool,
+    /// Journal entries of commits on the staging branch, written once it is promoted
+    held_entries: Option<Vec<JournalEntry>>,
+    /// Non-fatal issues not yet in the report, see [`SyncEngine::warn`]
+    issues: Vec<RunWarning>,
 }
 
 #[derive(Debug, Clone)]
-pub struct SyncStats {
-    pub total_commits: usize,
-    pub synced_commits: usize,
-    pub skipped_commits: usize,
+pub struct SyncConfig {
+    pub subdir: String,
+    /// Directory of the target repository the synced files land in, empty for the root
+    pub target_dir: String,
+    /// Glob patterns, relative to `subdir`, excluded from generated patches
+    pub excludes: Vec<String>,
+    /// Content rules checked against every patch before it is applied
+    pub redaction: Redaction,
+    /// Credential detector run on every patch, `None` when scanning is off
+    pub secrets: Option<SecretScanner>,
+    /// Stop the sync instead of only reporting commits flagged by `secrets`
+    pub block_secrets: bool,
+    /// Pending commits deliberately left for a later run (e.g. by `--limit`)
+    pub deferred_commits: usize,
+    /// Replacement commit messages keyed by source commit id
+    pub messages: HashMap<String, String>,
+    /// Rewrite rules applied to every commit message, after any manual edit
+    pub message_rewrites: Vec<MessageRewrite>,
+    /// Adds a `Synced-from` trailer; the value is a link template with a `{sha}`
+    /// placeholder, or just `{sha}` when the source has no known forge URL
+    pub synced_from: Option<String>,
+    /// Put in front of the subject of every target commit (`--subject-prefix`)
+    pub subject_prefix: Option<String>,
+    /// Whether octopus merges are synced as one commit or skipped like other merges
+    pub octopus: OctopusPolicy,
+    /// Folds consecutive applied commits into one target commit per group
+    pub squash_by: Option<SquashBy>,
+    /// Folds `fixup!`/`squash!` commits into the commit they name (`--autosquash`)
+    pub autosquash: bool,
+    /// Order and folding of the commits from a todo file (`--todo`)
+    pub todo: Option<Todo>,
+    /// Notes the source commits on each target commit once the run is recorded (`--notes`)
+    pub notes: bool,
+    /// Tags the target commits of tagged source commits once the run is
+    /// recorded (`--sync-tags`)
+    pub tags: Option<TagSync>,
+    /// Bumps the version file of the target after the synced commits and
+    /// commits the change (`--bump-version`)
+    pub bump_version: Option<BumpPolicy>,
+    /// Version file to bump, relative to the target repository root; found in
+    /// `target_dir` when `None` (`--version-file`)
+    pub version_file: Option<String>,
+    /// Where the run's patches and apply worktree are created, instead of the system temp
+    pub tmp_dir: Option<PathBuf>,
+    /// Copies generated patches out of the temp directory before it is removed
+    pub keep_patches: Option<KeepPatches>,
+    /// Retries of patch generation and application after transient failures
+    pub retry: RetryPolicy,
+    /// Extra arguments appended to `git format-patch`; merges and directory
+    /// moves are diffed with `git diff` and don't get them
+    pub format_patch_args: Vec<String>,
+    /// Extra arguments appended to `git am`
+    pub am_args: Vec<String>,
+    /// Skip commits whose patch conflicts, listing them in the report, instead
+    /// of stopping the run at the first one
+    pub continue_on_conflict: bool,
+    /// Apply to a new branch and fast-forward the target branch to it only when
+    /// every commit applied, leaving the target untouched otherwise
+    pub staging_branch: bool,
+    /// Remove target files the source directory doesn't have when copying
+    /// (`--engine copy`); patches carry their own deletions
+    pub sync_delete: bool,
+    /// Compare the target directory with the source directory of the last
+    /// processed commit once the run is over (`--verify`)
+    pub verify: bool,
+    /// Leave file modes out of the sync, so target files keep the mode they
+    /// already have (`--no-mode-changes`)
+    pub no_mode_changes: bool,
 }
 
-impl Default for SyncStats {
-    fn default() -> Self {
-        Self {
-            total_commits: 0,
-            synced_commits: 0,
-            skipped_commits: 0,
+impl SyncConfig {
+    /// Message of source commit `commit_id`, `original`, as edited on the
+    /// selection screen and changed by the `--msg-rewrite` rules
+    pub(crate) fn edited_message(&self, commit_id: &str, original: &str) -> String {
+        let mut message = self.messages.get(commit_id).cloned().unwrap_or_else(|| original.to_string());
+        for rule in &self.message_rewrites {
+            message = rule.apply(&message);
         }
+        message
+    }
+
+    /// Whether [`SyncConfig::edited_message`] can differ from the original message
+    pub(crate) fn edits_message(&self, commit_id: &str) -> bool {
+        self.messages.contains_key(commit_id) || !self.message_rewrites.is_empty()
     }
 }
 
-pub struct SyncEngine {
-    config: SyncConfig,
-    dry_run: bool,
+/// Room reserved per commit for its patch when checking free space; patches
+/// stay on disk until the run ends
+const PATCH_SPACE_ESTIMATE: u64 = 64 * 1024;
+
+/// How often a retry backoff checks whether the run was cancelled
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
+/// How `--squash-by` groups consecutive commits into one target commit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SquashBy {
+    /// Commits made on the same (UTC) calendar day
+    Day,
+    /// Commits by the same author
+    Author,
+    /// Every `n` commits of the selection
+    Count(usize),
 }
 
-#[derive(Debug, Clone)]
-pub struct SyncConfig {
-    pub subdir: String,
+impl SquashBy {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "day" => Ok(Self::Day),
+            "author" => Ok(Self::Author),
+            n => match n.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(Self::Count(n)),
+                _ => Err(anyhow::anyhow!("Invalid squash grouping: {} (expected day, author or a positive number)", value)),
+            },
+        }
+    }
+
+    /// Group of the `index`-th selected commit; a commit whose key differs from
+    /// its predecessor's starts a new target commit
+    fn key(&self, index: usize, commit: &CommitInfo) -> String {
+        match self {
+            Self::Day => commit.date.get(..10).unwrap_or(&commit.date).to_string(),
+            Self::Author => commit.author.clone(),
+            Self::Count(n) => (index / n).to_string(),
+        }
+    }
+}
+
+/// Subject prefixes `git commit --fixup` and `--squash` give a commit meant to
+/// be folded into an earlier one
+const AUTOSQUASH_PREFIXES: [&str; 3] = ["fixup! ", "squash! ", "amend! "];
+
+/// `--autosquash` order of the selected commits, like `git rebase --autosquash`:
+/// a `fixup!`, `squash!` or `amend!` commit moves right after the commit its
+/// subject names (by subject, or by id prefix). Returns the positions in the new
+/// order, each with the position of the commit its group starts with. Fixups
+/// whose target isn't selected stay where they are, as commits of their own.
+pub fn autosquash_order(commits: &[CommitInfo]) -> Vec<(usize, usize)> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of: Vec<usize> = Vec::with_capacity(commits.len());
+    for (i, commit) in commits.iter().enumerate() {
+        let mut subject = commit.subject.as_str();
+        while let Some(rest) = AUTOSQUASH_PREFIXES.iter().find_map(|prefix| subject.strip_prefix(prefix)) {
+            subject = rest.trim_start();
+        }
+        let target = (subject.len() < commit.subject.len())
+            .then(|| {
+                let earlier = &commits[..i];
+                earlier.iter().position(|c| c.subject == subject).or_else(|| {
+                    let is_id = subject.len() >= 4 && subject.chars().all(|c| c.is_ascii_hexdigit());
+                    earlier.iter().position(|c| is_id && c.id.starts_with(subject))
+                })
+            })
+            .flatten();
+        match target {
+            Some(target) => {
+                let group = group_of[target];
+                groups[group].push(i);
+                group_of.push(group);
+            }
+            None => {
+                group_of.push(groups.len());
+                groups.push(vec![i]);
+            }
+        }
+    }
+    groups
+        .into_iter()
+        .flat_map(|group| {
+            let first = group[0];
+            group.into_iter().map(move |i| (i, first))
+        })
+        .collect()
+}
+
+/// Message of a commit folded by `--autosquash` from the messages of the
+/// applied commits, oldest first: `fixup!` messages are dropped, `squash!`
+/// bodies appended and an `amend!` body replaces the message so far
+fn autosquash_message(messages: &[String]) -> String {
+    let mut message = String::new();
+    for (i, current) in messages.iter().enumerate() {
+        let (subject, body) = current.split_once('\n').unwrap_or((current, ""));
+        let body = body.trim();
+        if i == 0 {
+            message = current.trim_end().to_string();
+        } else if subject.starts_with("amend! ") {
+            message = body.to_string();
+        } else if subject.starts_with("squash! ") && !body.is_empty() {
+            message.push_str("\n\n");
+            message.push_str(body);
+        }
+    }
+    message.push('\n');
+    message
+}
+
+/// Which generated patches `--keep-patches` saves for debugging
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeepPatches {
+    /// Every patch of the run
+    All,
+    /// Only the patch of the commit the run failed on
+    Failed,
+}
+
+impl KeepPatches {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "all" => Ok(Self::All),
+            "failed" => Ok(Self::Failed),
+            other => Err(anyhow::anyhow!("Invalid keep-patches mode: {}", other)),
+        }
+    }
+}
+
+/// How often a step that failed transiently is tried again (see [`SyncError::is_transient`])
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts after the first one; 0 disables retrying
+    pub retries: u32,
+    /// Wait before the first retry, doubled before each further one
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { retries: 3, delay: Duration::from_millis(500) }
+    }
+}
+
+/// Applied commits waiting to be folded into one target commit
+struct SquashBatch {
+    key: String,
+    /// Target commit the batch was applied on top of
+    base: String,
+    /// Ids and subjects of the applied source commits
+    sources: Vec<(String, String)>,
 }
 
 impl SyncEngine {
@@ -48,70 +314,1235 @@ impl SyncEngine {
         Self {
             config,
             dry_run,
+            journal: None,
+            export_dir: None,
+            export_mbox: None,
+            cancel: None,
+            warnings: Vec::new(),
+            labels: BTreeMap::new(),
+            retrying_conflicts: false,
+            held_entries: None,
+            issues: Vec::new(),
+        }
+    }
+
+    /// Stops the run before the next commit once `flag` is set. The commit being
+    /// applied at that moment is finished, so no `git am` is left half done.
+    pub fn with_cancel(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// Sleeps for `delay`, waking early when the run is cancelled
+    fn pause(&self, delay: Duration) {
+        let until = Instant::now() + delay;
+        while !self.cancelled() {
+            let left = until.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            std::thread::sleep(left.min(CANCEL_POLL));
+        }
+    }
+
+    /// Exports the patches as a bundle in `dir` instead of applying them
+    pub fn with_export(mut self, dir: PathBuf) -> Self {
+        self.export_dir = Some(dir);
+        self
+    }
+
+    /// Exports the patches into one mbox file at `path` instead of applying them
+    pub fn with_mbox(mut self, path: PathBuf) -> Self {
+        self.export_mbox = Some(path);
+        self
+    }
+
+    /// Whether this run writes its patches out instead of applying them
+    fn exports(&self) -> bool {
+        self.export_dir.is_some() || self.export_mbox.is_some()
+    }
+
+    /// Whether this run changes the target repository
+    fn applies(&self) -> bool {
+        !self.dry_run && !self.exports()
+    }
+
+    /// Records every processed commit and the run summary in `journal`
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Warnings the user confirmed before the run, listed in its report
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Labels the user gave the commits on the selection screen, listed in the run's report
+    pub fn with_labels(mut self, labels: BTreeMap<String, Label>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Re-attempts commits an earlier run skipped with a conflict. They no longer
+    /// follow the range in order, so the run's summary leaves the resume point
+    /// where the earlier run put it, before its first conflict.
+    pub fn retrying_conflicts(mut self) -> Self {
+        self.retrying_conflicts = true;
+        self
+    }
+
+    /// Starts the report of a run over `total` commits
+    fn new_report(&self, run_id: &str, total: usize, source_repo: &Path, target_repo: &Path) -> SyncReport {
+        SyncReport {
+            run_id: run_id.to_string(),
+            config: ReportConfig {
+                source_repo: source_repo.to_path_buf(),
+                target_repo: target_repo.to_path_buf(),
+                subdir: self.config.subdir.clone(),
+                target_dir: self.config.target_dir.clone(),
+                engine: self.name().to_string(),
+                dry_run: self.dry_run,
+                export_dir: self.export_dir.clone().or_else(|| self.export_mbox.clone()),
+            },
+            total_commits: total,
+            warnings: self.warnings.clone(),
+            labels: self.labels.clone(),
+            ..Default::default()
         }
     }
 
+    fn record(&mut self, entry: JournalEntry) {
+        if let Some(held) = self.held_entries.as_mut() {
+            held.push(entry);
+            return;
+        }
+        if let Some(Err(e)) = self.journal.as_ref().map(|journal| journal.append(&entry)) {
+            self.warn(None, format!("写入同步日志失败: {}", e));
+        }
+    }
+
+    /// Notes a non-fatal issue; it reaches the report and the interface at the
+    /// next commit boundary
+    fn warn(&mut self, commit: Option<&str>, message: String) {
+        tracing::warn!("{}", message);
+        self.issues.push(RunWarning { commit: commit.map(str::to_string), message });
+    }
+
+    /// Counts the result of applying one commit in `report`, for sync runs and
+    /// bundles alike. Returns the status and the target commit, or the error that
+    /// stops the run; with `--continue-on-conflict` a conflict only skips the commit.
+    fn settle(
+        &self,
+        git_manager: &GitManager,
+        applied: Result<String>,
+        id: &str,
+        subject: &str,
+        worktree: &mut ApplyWorktree,
+        report: &mut SyncReport,
+    ) -> Result<(&'static str, Option<String>)> {
+        match applied {
+            Ok(target_id) => {
+                report.synced_commits += 1;
+                Ok(("OK", Some(target_id)))
+            }
+            Err(SyncError::EmptyPatch) => {
+                report.skipped_commits += 1;
+                Ok(("EMPTY (SKIPPED)", None))
+            }
+            Err(SyncError::PatchConflict(error)) if self.config.continue_on_conflict => {
+                worktree.abort_am()?;
+                report.conflicts.push(ConflictedCommit {
+                    id: id.to_string(),
+                    subject: subject.to_string(),
+                    error,
+                    artifacts: Some(conflict_dir(git_manager, &report.run_id, id)).filter(|dir| dir.exists()),
+                });
+                Ok((CONFLICT_SKIPPED, None))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Journals a processed commit when the run changes the target, and lists it in the report
+    fn record_commit(&mut self, run_id: &str, result: CommitResult, report: &mut SyncReport) {
+        if self.applies() {
+            self.record(JournalEntry::Commit {
+                run_id: run_id.to_string(),
+                timestamp: journal::timestamp(),
+                source: result.id.clone(),
+                target: result.target.clone(),
+                status: result.status.clone(),
+                subject: result.subject.clone(),
+            });
+        }
+        report.commits.push(result);
+    }
+
+    #[instrument(name = "sync", skip_all, fields(commits = commits.len(), dry_run = self.dry_run))]
     pub async fn sync_commits(
         &mut self, 
         git_manager: &GitManager,
         commits: &[CommitInfo], 
-        tx: UnboundedSender<SyncEvent>,
-    ) -> Result<SyncStats> {
-        let mut stats = SyncStats::default();
-        stats.total_commits = commits.len();
+        tx: Sender<SyncEvent>,
+    ) -> Result<SyncReport> {
+        let started_run = Instant::now();
+        let run_id = journal::new_run_id();
+        let selected = commits;
+        let (arranged, squash_keys) = self.arrange(selected);
+        let commits: &[CommitInfo] = &arranged;
+        let mut report = self.new_report(
+            &run_id,
+            commits.len(),
+            &git_manager.source_repo_info.path,
+            &git_manager.target_repo_info.path,
+        );
 
-        if stats.total_commits == 0 {
-            let _ = tx.send(SyncEvent::Completed(stats.clone()));
-            return Ok(stats);
+        if report.total_commits == 0 {
+            let _ = tx.send(SyncEvent::Completed(Box::new(report.clone()))).await;
+            return Ok(report);
+        }
+        if (self.journal.is_some() || self.config.synced_from.is_some()) && self.applies() {
+            git_manager.check_object_formats()?;
         }
 
-        let tmp_dir = tempdir().map_err(|e| SyncError::Io(e))?;
+        let tmp_dir = tmpdir::create(self.config.tmp_dir.as_deref())?;
+        let patch_dir = tmp_dir.path().join("patches");
+        std::fs::create_dir_all(&patch_dir)?;
+        // Every run writes its patches; only applying runs also check out the target
+        let worktree_bytes = if self.applies() { git_manager.target_checkout_size()? } else { 0 };
+        tmpdir::ensure_space(tmp_dir.path(), worktree_bytes + commits.len() as u64 * PATCH_SPACE_ESTIMATE)?;
 
+        let mut manifest = match &self.export_dir {
+            Some(dir) => Some(self.start_export(git_manager, dir)?),
+            None => None,
+        };
+        let mut mbox = match &self.export_mbox {
+            Some(path) => Some(MboxWriter::create(path)?),
+            None => None,
+        };
+
+        // Patches are applied in a detached worktree, never in the user's checkout
+        let mut worktree = if !self.applies() {
+            None
+        } else {
+            let staging = self.config.staging_branch.then(|| staging_branch_name(&run_id));
+            let worktree = git_manager.create_apply_worktree(&tmp_dir.path().join("worktree"), staging.as_deref())?;
+            if staging.is_some() {
+                self.held_entries = Some(Vec::new());
+            }
+            // A missing or unparsable version file fails the run before anything is applied
+            if self.config.bump_version.is_some() {
+                VersionFile::locate(&worktree.path, &self.config.target_dir, self.config.version_file.as_deref())?;
+            }
+            Some(worktree)
+        };
+
+        // Dry runs apply every patch to a scratch index to predict the conflicts
+        let scratch_index = if self.dry_run && !self.exports() {
+            Some(git_manager.create_scratch_index(&tmp_dir.path().join("dry-run.index"))?)
+        } else {
+            None
+        };
+
+        let mut processed = 0;
+        // Index of the first commit skipped with a conflict; the next run resumes before it
+        let mut first_conflict = None;
+        let mut batch: Option<SquashBatch> = None;
         for (i, commit) in commits.iter().enumerate() {
-            let status = if self.dry_run {
-                stats.synced_commits += 1;
-                "PREVIEW"
-            } else {
-                // 1. Create patch
-                match git_manager.create_patch_file(&commit.id, &self.config.subdir, tmp_dir.path()) {
-                    Ok(patch_path) => {
-                        // 2. Apply patch
-                        match git_manager.apply_patch_file(&patch_path, None) {
-                            Ok(_) => {
-                                stats.synced_commits += 1;
-                                "OK"
+            if self.cancelled() {
+                report.aborted = true;
+                break;
+            }
+            if let (Some(keys), Some(worktree)) = (&squash_keys, worktree.as_mut()) {
+                let key = keys[i].clone();
+                if let Some(done) = batch.take_if(|batch| batch.key != key) {
+                    self.squash(done, worktree, &run_id)?;
+                }
+                batch.get_or_insert_with(|| SquashBatch { key, base: worktree.head(), sources: Vec::new() });
+            }
+            let started = Instant::now();
+            // One directory per commit, so the patch of a failed commit can be found again
+            let commit_patch_dir = patch_dir.join(format!("{:04}", i + 1));
+            std::fs::create_dir_all(&commit_patch_dir)?;
+            let commit_span = info_span!("commit", id = %commit.id, status = field::Empty);
+            let mut patch_bytes = 0;
+            let mut patch_stats = None;
+            let mut target_commit = None;
+            let status = match worktree.as_mut() {
+                _ if self.skips_merge(commit) => {
+                    report.skipped_commits += 1;
+                    "MERGE (SKIPPED)"
+                }
+                // Dry runs generate and check every patch without applying it
+                None => match commit_span.in_scope(|| {
+                    let paths = PathMapping::new(&commit.subdir, &self.config.target_dir)?;
+                    let patch = self.prepare_patch(git_manager, commit, &paths, &commit_patch_dir)?;
+                    patch_bytes = patch.bytes;
+                    patch_stats = Some(PatchStats::read(&patch.path)?);
+                    if let Some(index) = scratch_index.as_ref() {
+                        self.retrying("Checking patch", &commit.id, || git_manager.check_patch_file(index, &patch.path, &paths))?;
+                    }
+                    if let Some(manifest) = manifest.as_mut() {
+                        self.export_patch(commit, &patch.path, manifest)?;
+                    }
+                    if let Some(mbox) = mbox.as_mut() {
+                        mbox.append(&patch.path)?;
+                    }
+                    Ok((patch.secrets, patch.non_utf8))
+                }) {
+                    Ok((secrets, non_utf8)) => {
+                        report.flag(commit, secrets);
+                        report.note_non_utf8(commit, non_utf8);
+                        report.synced_commits += 1;
+                        if self.exports() { "EXPORTED" } else { "PREVIEW" }
+                    }
+                    Err(SyncError::EmptyPatch) => {
+                        report.skipped_commits += 1;
+                        "EMPTY (SKIPPED)"
+                    }
+                    Err(SyncError::PatchConflict(error)) if scratch_index.is_some() => {
+                        report.predicted_conflicts.push(ConflictedCommit {
+                            id: commit.id.clone(),
+                            subject: commit.subject.clone(),
+                            error,
+                            artifacts: None,
+                        });
+                        "CONFLICT (PREDICTED)"
+                    }
+                    Err(e) => {
+                        let e = self.keep_failed_patch(e, &run_id, i, commit, &commit_patch_dir);
+                        let err_msg = format!("同步提交失败 {}: {}", commit.id, e);
+                        let _ = tx.send(SyncEvent::Error(err_msg)).await;
+                        return Err(e);
+                    }
+                },
+                Some(worktree) => {
+                    let applied = commit_span.in_scope(|| {
+                        let paths = PathMapping::new(&commit.subdir, &self.config.target_dir)?;
+                        let patch = self.prepare_patch(git_manager, commit, &paths, &commit_patch_dir)?;
+                        patch_bytes = patch.bytes;
+                        let target_id = self.apply_commit(git_manager, &run_id, &commit.id, &patch.path, &paths, worktree)?;
+                        Ok((target_id, patch.secrets, patch.non_utf8))
+                    });
+                    let applied = applied.map(|(target_id, secrets, non_utf8)| {
+                        report.flag(commit, secrets);
+                        report.note_non_utf8(commit, non_utf8);
+                        target_id
+                    });
+                    match self.settle(git_manager, applied, &commit.id, &commit.subject, worktree, &mut report) {
+                        Ok((status, target_id)) => {
+                            if let (Some(batch), Some(_)) = (batch.as_mut(), &target_id) {
+                                batch.sources.push((commit.id.clone(), commit.subject.clone()));
                             }
-                            Err(SyncError::EmptyPatch) => {
-                                stats.skipped_commits += 1;
-                                "EMPTY (SKIPPED)"
+                            if status == CONFLICT_SKIPPED {
+                                first_conflict.get_or_insert(i);
                             }
-                            Err(e) => {
-                                let err_msg = format!("同步提交失败 {}: {}", commit.id, e);
-                                let _ = tx.send(SyncEvent::Error(err_msg));
-                                return Err(e);
+                            target_commit = target_id;
+                            status
+                        }
+                        Err(e) => {
+                            // Keep the commits that did land visible in the user's checkout
+                            if let Some(done) = batch.take() {
+                                if let Err(squash_err) = self.squash(done, worktree, &run_id) {
+                                    tracing::error!("{}", squash_err);
+                                }
                             }
+                            let e = self.keep_failed_patch(e, &run_id, i, commit, &commit_patch_dir);
+                            let e = abandon(worktree, e);
+                            let err_msg = format!("同步提交失败 {}: {}", commit.id, e);
+                            let _ = tx.send(SyncEvent::Error(err_msg)).await;
+                            return Err(e);
                         }
                     }
-                    Err(e) => {
-                        let err_msg = format!("生成补丁失败 {}: {}", commit.id, e);
-                        let _ = tx.send(SyncEvent::Error(err_msg));
-                        return Err(e);
-                    }
                 }
             };
+            commit_span.record("status", status);
+
+            if self.config.keep_patches == Some(KeepPatches::All) {
+                match keep_patch(&run_id, i, commit, &commit_patch_dir) {
+                    Ok(Some(path)) => report.kept_patches = path.parent().map(Path::to_path_buf),
+                    Ok(None) => {}
+                    Err(e) => self.warn(Some(&commit.id), format!("保留补丁失败: {}", e)),
+                }
+            }
+
+            self.record_commit(&run_id, CommitResult {
+                id: commit.id.clone(),
+                subject: commit.subject.clone(),
+                status: status.to_string(),
+                target: target_commit,
+                duration_ms: started.elapsed().as_millis() as u64,
+                patch_bytes,
+                patch_stats,
+            }, &mut report);
 
+            processed = i + 1;
+            flush_warnings(&mut self.issues, &mut report, &tx).await;
             let _ = tx.send(SyncEvent::Progress {
                 current: i + 1,
-                total: stats.total_commits,
+                total: report.total_commits,
                 subject: commit.subject.clone(),
                 status: status.to_string(),
+            }).await;
+        }
+
+        if let (Some(dir), Some(manifest)) = (&self.export_dir, &manifest) {
+            manifest.write(dir)?;
+        }
+        if !self.applies() {
+            report.total_patch_stats();
+        }
+
+        if let (Some(done), Some(worktree)) = (batch, worktree.as_mut()) {
+            self.squash(done, worktree, &run_id)?;
+        }
+        // Only a complete run leaves a releasable state worth a new version
+        if let (Some(policy), Some(worktree)) = (self.config.bump_version, worktree.as_mut()) {
+            if report.synced_commits > 0 && !report.aborted && report.conflicts.is_empty() {
+                report.version_bump = Some(self.bump_version(policy, worktree, &run_id)?);
+            }
+        }
+        if let Some(worktree) = worktree.as_mut() {
+            // All or nothing: the staged commits reach the target branch only when none was left out
+            if let Some(held) = self.held_entries.take() {
+                if report.aborted || !report.conflicts.is_empty() {
+                    report.staging_branch = worktree.staging_branch().map(str::to_string);
+                } else {
+                    worktree.promote()?;
+                    held.into_iter().for_each(|entry| self.record(entry));
+                }
+            }
+            worktree.finish()?;
+        }
+        if worktree.is_some() && report.staging_branch.is_none() {
+            self.write_notes(git_manager, &run_id);
+            self.write_tags(git_manager, &run_id, &mut report);
+            self.record(JournalEntry::Run {
+                run_id,
+                timestamp: journal::timestamp(),
+                source_repo: git_manager.source_repo_info.path.clone(),
+                subdir: self.config.subdir.clone(),
+                synced: report.synced_commits,
+                skipped: report.skipped_commits,
+                pending: self.config.deferred_commits + commits.len() - processed,
+                resume_from: synced_prefix(selected, &commits[..first_conflict.unwrap_or(processed)])
+                    .filter(|_| !self.retrying_conflicts)
+                    .map(|c| c.id.clone()),
+                format_patch_args: self.config.format_patch_args.clone(),
+                am_args: self.config.am_args.clone(),
             });
+            if let Some(last) = synced_prefix(selected, &commits[..processed]).filter(|_| self.config.verify) {
+                report.divergence = copy::verify(git_manager, last, &self.config);
+            }
+        }
 
-            // Small delay for UI updates (reduced from 50ms to 20ms for better responsiveness)
-            sleep(Duration::from_millis(20)).await;
+        report.duration_ms = started_run.elapsed().as_millis() as u64;
+        flush_warnings(&mut self.issues, &mut report, &tx).await;
+        let _ = tx.send(SyncEvent::Completed(Box::new(report.clone()))).await;
+        Ok(report)
+    }
+
+    /// Applies the patches of an exported bundle in `dir` in order, recording them
+    /// in the journal like a regular run. `on_progress` is called after each patch.
+    #[instrument(skip_all, fields(patches = manifest.patches.len()))]
+    pub fn apply_bundle(
+        &mut self,
+        git_manager: &GitManager,
+        manifest: &Manifest,
+        dir: &Path,
+        mut on_progress: impl FnMut(usize, &BundlePatch, &str),
+    ) -> Result<SyncReport> {
+        let started_run = Instant::now();
+        let run_id = journal::new_run_id();
+        let mut report = self.new_report(
+            &run_id,
+            manifest.patches.len(),
+            &manifest.source_repo,
+            &git_manager.target_repo_info.path,
+        );
+        if manifest.patches.is_empty() {
+            return Ok(report);
         }
 
-        let _ = tx.send(SyncEvent::Completed(stats.clone()));
-        Ok(stats)
+        let tmp_dir = tmpdir::create(self.config.tmp_dir.as_deref())?;
+        tmpdir::ensure_space(tmp_dir.path(), git_manager.target_checkout_size()?)?;
+        let mut worktree = git_manager.create_apply_worktree(&tmp_dir.path().join("worktree"), None)?;
+
+        let paths = PathMapping::new(&manifest.subdir, &manifest.target_dir)?;
+        for (i, patch) in manifest.patches.iter().enumerate() {
+            let started = Instant::now();
+            let patch_path = dir.join(&patch.file);
+            let commit_span = info_span!("commit", id = %patch.source, status = field::Empty);
+            let applied = commit_span.in_scope(|| {
+                self.apply_commit(git_manager, &run_id, &patch.source, &patch_path, &paths, &mut worktree)
+            });
+            let (status, target_commit) = match self.settle(git_manager, applied, &patch.source, &patch.subject, &mut worktree, &mut report) {
+                Ok(outcome) => outcome,
+                Err(e) => return Err(abandon(&mut worktree, e)),
+            };
+            commit_span.record("status", status);
+
+            self.record_commit(&run_id, CommitResult {
+                id: patch.source.clone(),
+                subject: patch.subject.clone(),
+                status: status.to_string(),
+                target: target_commit,
+                duration_ms: started.elapsed().as_millis() as u64,
+                patch_bytes: std::fs::metadata(&patch_path).map(|m| m.len()).unwrap_or(0),
+                patch_stats: None,
+            }, &mut report);
+            on_progress(i + 1, patch, status);
+        }
+
+        worktree.finish()?;
+        self.record(JournalEntry::Run {
+            run_id,
+            timestamp: journal::timestamp(),
+            source_repo: manifest.source_repo.clone(),
+            subdir: manifest.subdir.clone(),
+            synced: report.synced_commits,
+            skipped: report.skipped_commits,
+            pending: 0,
+            resume_from: manifest.patches.last().map(|p| p.source.clone()),
+            format_patch_args: Vec::new(),
+            am_args: self.config.am_args.clone(),
+        });
+
+        report.run_warnings.append(&mut self.issues);
+        report.duration_ms = started_run.elapsed().as_millis() as u64;
+        Ok(report)
+    }
+
+    /// Creates the (empty) bundle directory and the manifest describing it
+    fn start_export(&self, git_manager: &GitManager, dir: &Path) -> Result<Manifest> {
+        if dir.exists() && std::fs::read_dir(dir)?.next().is_some() {
+            return Err(SyncError::Anyhow(anyhow::anyhow!(
+                "Export directory is not empty: {}",
+                dir.display()
+            )));
+        }
+        std::fs::create_dir_all(dir)?;
+
+        Ok(Manifest {
+            version: MANIFEST_VERSION,
+            created: journal::timestamp(),
+            source_repo: git_manager.source_repo_info.path.clone(),
+            subdir: self.config.subdir.clone(),
+            target_dir: self.config.target_dir.clone(),
+            target_base: git_manager.resolve_commit(false, "HEAD")?,
+            patches: Vec::new(),
+        })
+    }
+
+    /// Copies a prepared patch into the bundle under its sequence number
+    fn export_patch(&self, commit: &CommitInfo, patch_path: &Path, manifest: &mut Manifest) -> Result<()> {
+        let Some(dir) = &self.export_dir else { return Ok(()) };
+        let file = format!("{:04}-{}.patch", manifest.patches.len() + 1, short_id(&commit.id));
+        std::fs::copy(patch_path, dir.join(&file))?;
+        manifest.patches.push(BundlePatch {
+            file,
+            source: commit.id.clone(),
+            subject: commit.subject.clone(),
+        });
+        Ok(())
+    }
+
+    /// Writes the notes of the run's target commits when `--notes` is set; a
+    /// failure only costs the notes, the commits stay
+    fn write_notes(&mut self, git_manager: &GitManager, run_id: &str) {
+        let Some(journal) = self.journal.as_ref().filter(|_| self.config.notes) else { return };
+        if let Err(e) = notes::write_run(journal, &git_manager.target_repo_info.path, run_id) {
+            self.warn(None, format!("写入 git notes 失败: {}", e));
+        }
+    }
+
+    fn write_tags(&mut self, git_manager: &GitManager, run_id: &str, report: &mut SyncReport) {
+        let (Some(journal), Some(tags)) = (self.journal.as_ref(), self.config.tags.as_ref()) else { return };
+        match tags::sync_run(journal, git_manager, run_id, tags) {
+            Ok((synced, skipped)) => {
+                report.tags = synced;
+                report.skipped_tags = skipped;
+            }
+            Err(e) => self.warn(None, format!("同步标签失败: {}", e)),
+        }
     }
-}
\ No newline at end of file
+
+    /// Selected commits in the order they are applied and, when commits are
+    /// folded, the squash group of each; a commit whose group differs from its
+    /// predecessor's starts a new target commit
+    fn arrange<'c>(&self, commits: &'c [CommitInfo]) -> (Cow<'c, [CommitInfo]>, Option<Vec<String>>) {
+        let order = match &self.config.todo {
+            Some(todo) => Some(todo.order(commits)),
+            None => self.config.autosquash.then(|| autosquash_order(commits)),
+        };
+        if let Some(order) = order {
+            let arranged = order.iter().map(|&(i, _)| commits[i].clone()).collect();
+            let keys = order.iter().map(|&(_, first)| commits[first].id.clone()).collect();
+            return (Cow::Owned(arranged), Some(keys));
+        }
+        let keys = self
+            .config
+            .squash_by
+            .map(|squash_by| commits.iter().enumerate().map(|(i, commit)| squash_by.key(i, commit)).collect());
+        (Cow::Borrowed(commits), keys)
+    }
+
+    /// Folds the applied commits of `batch` into one target commit whose message
+    /// lists them, or for `--autosquash` and `--todo` combines their messages. Batches of a
+    /// single commit are left as they are.
+    fn squash(&mut self, batch: SquashBatch, worktree: &mut ApplyWorktree, run_id: &str) -> Result<()> {
+        if batch.sources.len() < 2 {
+            return Ok(());
+        }
+        if self.config.todo.is_some() || self.config.autosquash {
+            // Without the prefix the fixup!/squash!/amend! markers are at the start again
+            let messages: Vec<String> = worktree
+                .messages_since(&batch.base)?
+                .into_iter()
+                .map(|message| match &self.config.subject_prefix {
+                    Some(prefix) => message.strip_prefix(prefix.as_str()).map(str::to_string).unwrap_or(message),
+                    None => message,
+                })
+                .collect();
+            let message = match &self.config.todo {
+                // Only applied commits have a message, in the order of their sources
+                Some(todo) => {
+                    let ids = batch.sources.iter().map(|(id, _)| id.clone());
+                    todo.message(&ids.zip(messages).collect::<Vec<_>>())
+                }
+                None => autosquash_message(&messages),
+            };
+            let message = self.link_sources(self.prefixed(message), &batch.sources);
+            let target = worktree.fixup_since(&batch.base, &message)?;
+            self.record(JournalEntry::Squash {
+                run_id: run_id.to_string(),
+                timestamp: journal::timestamp(),
+                sources: batch.sources.into_iter().map(|(id, _)| id).collect(),
+                target,
+            });
+            return Ok(());
+        }
+        let subdir = match self.config.subdir.as_str() {
+            "" | "." => "repository root",
+            subdir => subdir,
+        };
+        let mut message = match self.config.squash_by {
+            Some(SquashBy::Day) => format!("Sync {} commits of {} from {}", batch.sources.len(), subdir, batch.key),
+            Some(SquashBy::Author) => format!("Sync {} commits of {} by {}", batch.sources.len(), subdir, batch.key),
+            _ => format!("Sync {} commits of {}", batch.sources.len(), subdir),
+        };
+        message.push_str("\n\n");
+        for (id, subject) in &batch.sources {
+            message.push_str(&format!("- {} {}\n", short_id(id), subject));
+        }
+
+        let message = self.link_sources(self.prefixed(message), &batch.sources);
+        let target = worktree.squash_since(&batch.base, &message)?;
+        self.record(JournalEntry::Squash {
+            run_id: run_id.to_string(),
+            timestamp: journal::timestamp(),
+            sources: batch.sources.into_iter().map(|(id, _)| id).collect(),
+            target,
+        });
+        Ok(())
+    }
+
+    /// Commits the next version, by `policy`, of the target's version file on
+    /// top of the applied commits
+    fn bump_version(&mut self, policy: BumpPolicy, worktree: &mut ApplyWorktree, run_id: &str) -> Result<VersionBump> {
+        let file = VersionFile::locate(&worktree.path, &self.config.target_dir, self.config.version_file.as_deref())?;
+        let version = policy.next(&file.version)?;
+        let target = worktree.commit_file(&file.path, &file.with_version(&version), &format!("Bump version to {}", version))?;
+        self.record(JournalEntry::VersionBump {
+            run_id: run_id.to_string(),
+            timestamp: journal::timestamp(),
+            target: target.clone(),
+            version: version.clone(),
+        });
+        Ok(VersionBump { file: file.path, from: file.version, to: version, commit: target })
+    }
+
+    /// Saves the patch of the commit the run failed on when `--keep-patches` is
+    /// set, adding its path to the error
+    fn keep_failed_patch(&self, error: SyncError, run_id: &str, index: usize, commit: &CommitInfo, dir: &Path) -> SyncError {
+        if self.config.keep_patches.is_none() {
+            return error;
+        }
+        match keep_patch(run_id, index, commit, dir) {
+            Ok(Some(path)) => SyncError::PatchKept { error: Box::new(error), path },
+            Ok(None) => error,
+            Err(e) => {
+                tracing::error!("Failed to keep patch of {}: {}", commit.id, e);
+                error
+            }
+        }
+    }
+
+    /// Merges bring in changes made on other branches, which are excluded; only
+    /// octopus merges under `OctopusPolicy::FirstParent` are synced, as one commit
+    fn skips_merge(&self, commit: &CommitInfo) -> bool {
+        commit.is_merge && !(commit.is_octopus() && self.config.octopus == OctopusPolicy::FirstParent)
+    }
+
+    /// The patch `commit` would be applied with, for previews; `None` for merges
+    /// the run skips
+    pub fn preview_patch(&self, git_manager: &GitManager, commit: &CommitInfo) -> Result<Option<String>> {
+        if self.skips_merge(commit) {
+            return Ok(None);
+        }
+        let tmp_dir = tmpdir::create(self.config.tmp_dir.as_deref())?;
+        let paths = PathMapping::new(&commit.subdir, &self.config.target_dir)?;
+        let patch = self.prepare_patch(git_manager, commit, &paths, tmp_dir.path())?;
+        Ok(Some(String::from_utf8_lossy(&std::fs::read(&patch.path)?).into_owned()))
+    }
+
+    /// `message` with the `--subject-prefix`, if any
+    fn prefixed(&self, message: String) -> String {
+        match &self.config.subject_prefix {
+            Some(prefix) => patch::prefix_subject(&message, prefix),
+            None => message,
+        }
+    }
+
+    /// Adds a `Synced-from` trailer to a folded commit's message for every source
+    /// it does not link yet, when `--synced-from` is set
+    fn link_sources(&self, mut message: String, sources: &[(String, String)]) -> String {
+        let Some(template) = &self.config.synced_from else { return message };
+        for (id, _) in sources {
+            let link = template.replace("{sha}", id);
+            if !message.contains(&link) {
+                message = patch::add_trailer(&message, "Synced-from", &link);
+            }
+        }
+        message
+    }
+
+    /// The message the target commit should carry, or `None` to keep the original
+    fn final_message(&self, git_manager: &GitManager, commit: &CommitInfo) -> Result<Option<String>> {
+        if !self.config.edits_message(&commit.id) && self.config.synced_from.is_none() && self.config.subject_prefix.is_none() {
+            return Ok(None);
+        }
+
+        let original = git_manager.commit_message(&commit.id)?;
+        let mut message = self.config.edited_message(&commit.id, &original);
+        if let Some(prefix) = &self.config.subject_prefix {
+            message = patch::prefix_subject(&message, prefix);
+        }
+        if let Some(template) = &self.config.synced_from {
+            message = patch::add_trailer(&message, "Synced-from", &template.replace("{sha}", &commit.id));
+        }
+
+        Ok((message != original).then_some(message))
+    }
+
+    /// Generates the patch for `commit` with its final message and checks it
+    /// against the redaction rules and the secret scanner
+    #[instrument(name = "patch_generation", skip_all)]
+    fn prepare_patch(&self, git_manager: &GitManager, commit: &CommitInfo, paths: &PathMapping, patch_dir: &Path) -> Result<PreparedPatch> {
+        let path = self.retrying("Generating patch", &commit.id, || match commit.renamed_from {
+            Some(ref old_subdir) => git_manager.create_subtree_patch_file(&commit.id, old_subdir, &commit.subdir, &self.config.excludes, patch_dir),
+            None if commit.is_merge => git_manager.create_subtree_patch_file(&commit.id, &commit.subdir, &commit.subdir, &self.config.excludes, patch_dir),
+            None => git_manager.create_patch_file(&commit.id, paths, &self.config.excludes, &self.config.format_patch_args, patch_dir),
+        })?;
+        if let Some(message) = self.final_message(git_manager, commit)? {
+            patch::rewrite_message(&path, &message)?;
+        }
+        if self.config.no_mode_changes && !patch::strip_mode_changes(&path)? {
+            return Err(SyncError::EmptyPatch);
+        }
+        let bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+
+        let findings = self.config.redaction.scan_patch_file(&path)?;
+        if !findings.is_empty() {
+            return Err(SyncError::RedactionViolation(blocked_message(commit, &findings)));
+        }
+
+        let secrets = match &self.config.secrets {
+            Some(scanner) => scanner.scan_patch_file(&path)?,
+            None => Vec::new(),
+        };
+        if self.config.block_secrets && !secrets.is_empty() {
+            return Err(SyncError::SecretsDetected(blocked_message(commit, &secrets)));
+        }
+
+        let non_utf8: Vec<String> = patch::non_utf8_files(&path)?.iter().map(|file| paths.target_path(file)).collect();
+        if !non_utf8.is_empty() {
+            tracing::warn!("{} changes files that are not UTF-8: {}", commit.id, non_utf8.join(", "));
+        }
+
+        Ok(PreparedPatch { path, bytes, secrets, non_utf8 })
+    }
+
+    /// Applies a prepared patch in the worktree, advancing the target branch once
+    /// the commit exists. Returns the new target commit id.
+    #[instrument(name = "apply", skip_all)]
+    fn apply_commit(
+        &self,
+        git_manager: &GitManager,
+        run_id: &str,
+        commit_id: &str,
+        patch_path: &Path,
+        paths: &PathMapping,
+        worktree: &mut ApplyWorktree,
+    ) -> Result<String> {
+        match self.retrying("Applying patch", commit_id, || git_manager.apply_patch_file(&worktree.path, patch_path, paths, &self.config.am_args)) {
+            Err(SyncError::PatchConflict(output)) => {
+                let message = match collect_conflict_artifacts(git_manager, run_id, commit_id, patch_path, &worktree.path, &output) {
+                    Ok(dir) => format!("{}\n冲突现场已保存到: {}", output, dir.display()),
+                    Err(e) => format!("{}\n保存冲突现场失败: {}", output, e),
+                };
+                return Err(SyncError::PatchConflict(message));
+            }
+            result => result?,
+        }
+
+        // Move the target branch to the new commit
+        worktree.commit_applied()
+    }
+
+    /// Runs `step` again after transient failures, waiting longer each time,
+    /// until it succeeds, fails for good or the retry policy gives up
+    fn retrying<T>(&self, what: &str, commit_id: &str, mut step: impl FnMut() -> Result<T>) -> Result<T> {
+        let policy = self.config.retry;
+        let mut delay = policy.delay;
+        let mut attempt = 0;
+        loop {
+            match step() {
+                // A cancelled run gives up instead of waiting out the backoff
+                Err(e) if e.is_transient() && attempt < policy.retries && !self.cancelled() => {
+                    attempt += 1;
+                    tracing::warn!(
+                        "{} for {} failed, retry {}/{} in {:?}: {}",
+                        what, short_id(commit_id), attempt, policy.retries, delay, e
+                    );
+                    self.pause(delay);
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A generated patch that passed the redaction rules
+struct PreparedPatch {
+    path: PathBuf,
+    bytes: u64,
+    /// Secret scanner findings, only reported because blocking is off
+    secrets: Vec<Finding>,
+    /// Files the patch changes that are not UTF-8 encoded
+    non_utf8: Vec<String>,
+}
+
+/// Summarises blocking findings and saves the full report
+pub(crate) fn blocked_message(commit: &CommitInfo, findings: &[Finding]) -> String {
+    let summary = findings.iter().map(|f| f.to_string()).collect::<Vec<_>>().join("; ");
+    match redact::write_report(&commit.id, findings) {
+        Ok(path) => format!("{}\n报告已保存到: {}", summary, path.display()),
+        Err(e) => format!("{}\n保存报告失败: {}", summary, e),
+    }
+}
+
+/// Moves `issues` into `report`, sending each as a [`SyncEvent::Warning`]
+pub(crate) async fn flush_warnings(issues: &mut Vec<RunWarning>, report: &mut SyncReport, tx: &Sender<SyncEvent>) {
+    for warning in issues.drain(..) {
+        let _ = tx.send(SyncEvent::Warning(warning.clone())).await;
+        report.run_warnings.push(warning);
+    }
+}
+
+/// Directory, relative to the working directory, where `--keep-patches` saves
+/// each run's patches
+const KEPT_PATCHES_DIR: &str = ".sync-subdir/patches";
+
+/// Copies the patch generated for the `index`-th commit from `dir` to the run's
+/// kept patches directory as `<index>-<sha>.patch`. `None` when no patch was
+/// written, e.g. for skipped merges.
+fn keep_patch(run_id: &str, index: usize, commit: &CommitInfo, dir: &Path) -> Result<Option<PathBuf>> {
+    let patch = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| path.extension().is_some_and(|ext| ext == "patch"));
+    let Some(patch) = patch else { return Ok(None) };
+
+    let kept_dir = std::env::current_dir()?.join(KEPT_PATCHES_DIR).join(run_id);
+    std::fs::create_dir_all(&kept_dir)?;
+    let dest = kept_dir.join(format!("{:04}-{}.patch", index + 1, short_id(&commit.id)));
+    std::fs::copy(&patch, &dest)?;
+    Ok(Some(dest))
+}
+
+impl SyncBackend for SyncEngine {
+    fn name(&self) -> &'static str {
+        "patch"
+    }
+
+    fn plan(&self, commits: &[CommitInfo]) -> SyncPlan {
+        let mut plan = SyncPlan::default();
+        let mut last_key = None;
+        let (commits, squash_keys) = self.arrange(commits);
+        for (i, commit) in commits.iter().enumerate() {
+            if self.skips_merge(commit) {
+                plan.skipped += 1;
+                continue;
+            }
+            plan.applied += 1;
+            match &squash_keys {
+                Some(keys) => {
+                    let key = &keys[i];
+                    if last_key != Some(key) {
+                        plan.target_commits += 1;
+                        last_key = Some(key);
+                    }
+                }
+                None => plan.target_commits += 1,
+            }
+        }
+        if !self.applies() {
+            plan.target_commits = 0;
+        }
+        plan
+    }
+
+    async fn execute(&mut self, git_manager: &GitManager, commits: &[CommitInfo], tx: Sender<SyncEvent>) -> Result<SyncReport> {
+        self.sync_commits(git_manager, commits, tx).await
+    }
+}
+
+/// Newest selected commit up to which every commit is among `done`, the point a
+/// later run resumes from when `--autosquash` processed commits out of order
+fn synced_prefix<'c>(selected: &'c [CommitInfo], done: &[CommitInfo]) -> Option<&'c CommitInfo> {
+    let done: HashSet<&str> = done.iter().map(|commit| commit.id.as_str()).collect();
+    selected.iter().take_while(|commit| done.contains(commit.id.as_str())).last()
+}
+
+/// Branch a `--staging-branch` run applies its commits to
+fn staging_branch_name(run_id: &str) -> String {
+    format!("sync-staging/{}", run_id)
+}
+
+/// Removes the worktree of a run stopped by `error`. A staging branch is left in
+/// place and named in the returned error.
+fn abandon(worktree: &mut ApplyWorktree, error: SyncError) -> SyncError {
+    if let Err(finish_err) = worktree.finish() {
+        tracing::error!("{}", finish_err);
+    }
+    match worktree.staging_branch() {
+        Some(branch) => SyncError::StagingKept { error: Box::new(error), branch: branch.to_string() },
+        None => error,
+    }
+}
+
+/// Status of a commit skipped with `--continue-on-conflict`
+const CONFLICT_SKIPPED: &str = "CONFLICT (SKIPPED)";
+
+/// Directory, relative to the target's git directory, where conflict bundles are
+/// stored, next to the journal
+const CONFLICTS_DIR: &str = "sync-subdir/conflicts";
+
+/// Bundle of one commit in one run, so a later run of the same commit keeps the
+/// earlier bundle
+fn conflict_dir(git_manager: &GitManager, run_id: &str, commit_id: &str) -> PathBuf {
+    git_manager.repository(false).path().join(CONFLICTS_DIR).join(run_id).join(commit_id)
+}
+
+/// Saves everything needed to reproduce a failed application: the patch, the
+/// `git am` output, the conflicted worktree diff and any `.rej` files.
+fn collect_conflict_artifacts(
+    git_manager: &GitManager,
+    run_id: &str,
+    commit_id: &str,
+    patch_path: &Path,
+    worktree: &Path,
+    am_output: &str,
+) -> Result<PathBuf> {
+    let dir = conflict_dir(git_manager, run_id, commit_id);
+    std::fs::create_dir_all(&dir)?;
+
+    if let Some(name) = patch_path.file_name() {
+        std::fs::copy(patch_path, dir.join(name))?;
+    }
+    std::fs::write(dir.join("am-output.txt"), am_output)?;
+
+    let diff = std::process::Command::new("git")
+        .arg("-C")
+        .arg(worktree)
+        .arg("diff")
+        .output()?;
+    std::fs::write(dir.join("worktree.diff"), diff.stdout)?;
+
+    for entry in walkdir::WalkDir::new(worktree)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+        .filter_map(|e| e.ok())
+    {
+        if entry.path().extension().is_some_and(|ext| ext == "rej") {
+            let relative = entry.path().strip_prefix(worktree).unwrap_or(entry.path());
+            let dest = dir.join("rej").join(relative);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(entry.path(), dest)?;
+        }
+    }
+
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, TestRepo, EXECUTABLE, FILE};
+
+    #[tokio::test]
+    async fn squashed_commits_link_every_source() {
+        let source = TestRepo::new();
+        let base = source.commit(&[], &[("sub/a", b"", FILE)], 1_000, "base");
+        let first = source.commit(&[base], &[("sub/a", b"1\n", FILE)], 2_000, "first");
+        let second = source.commit(&[first], &[("sub/a", b"1\n", FILE), ("sub/b", b"2\n", FILE)], 3_000, "second");
+        let target = TestRepo::new();
+        target.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+
+        let config = SyncConfig {
+            squash_by: Some(SquashBy::Count(2)),
+            synced_from: Some("https://example.com/commit/{sha}".to_string()),
+            ..testutil::sync_config("sub", "")
+        };
+        testutil::sync(&source, &target, config, &[first, second]).await;
+
+        let message = target.head_message();
+        assert!(message.starts_with("Sync 2 commits of sub"), "{}", message);
+        let trailers: Vec<&str> = message.lines().filter(|line| line.starts_with("Synced-from: ")).collect();
+        assert_eq!(
+            trailers,
+            vec![
+                format!("Synced-from: https://example.com/commit/{}", first),
+                format!("Synced-from: https://example.com/commit/{}", second),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn autosquashed_fixups_keep_their_source_link() {
+        let source = TestRepo::new();
+        let base = source.commit(&[], &[("sub/a", b"", FILE)], 1_000, "base");
+        let change = source.commit(&[base], &[("sub/a", b"1\n", FILE)], 2_000, "Add a");
+        let fixup = source.commit(&[change], &[("sub/a", b"one\n", FILE)], 3_000, "fixup! Add a");
+        let target = TestRepo::new();
+        target.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+
+        let config = SyncConfig { autosquash: true, synced_from: Some("{sha}".to_string()), ..testutil::sync_config("sub", "") };
+        testutil::sync(&source, &target, config, &[change, fixup]).await;
+
+        let message = target.head_message();
+        assert!(message.starts_with("Add a"), "{}", message);
+        assert!(message.contains(&format!("Synced-from: {}", change)), "{}", message);
+        assert!(message.contains(&format!("Synced-from: {}", fixup)), "{}", message);
+        assert_eq!(target.file("a"), Some((b"one\n".to_vec(), FILE)));
+    }
+
+    /// Source history with every kind of mode change: `run.sh` only becomes
+    /// executable, `tool` becomes executable and changes, and `old` is renamed to
+    /// `new` and becomes executable
+    fn mode_change_source() -> (TestRepo, git2::Oid) {
+        let source = TestRepo::new();
+        let base = source.commit(
+            &[],
+            &[("sub/run.sh", b"echo\n", FILE), ("sub/tool", b"v1\n", FILE), ("sub/old", b"renamed file\nwith a few lines\n", FILE)],
+            1_000,
+            "base",
+        );
+        let change = source.commit(
+            &[base],
+            &[
+                ("sub/run.sh", b"echo\n", EXECUTABLE),
+                ("sub/tool", b"v2\n", EXECUTABLE),
+                ("sub/new", b"renamed file\nwith a few lines\n", EXECUTABLE),
+            ],
+            2_000,
+            "Make the scripts executable",
+        );
+        (source, change)
+    }
+
+    fn mode_change_target() -> TestRepo {
+        let target = TestRepo::new();
+        target.commit(
+            &[],
+            &[("run.sh", b"echo\n", FILE), ("tool", b"v1\n", FILE), ("old", b"renamed file\nwith a few lines\n", FILE)],
+            1_000,
+            "init",
+        );
+        target
+    }
+
+    #[tokio::test]
+    async fn mode_changes_survive_the_pipeline() {
+        let (source, change) = mode_change_source();
+        let target = mode_change_target();
+
+        let report = testutil::sync(&source, &target, testutil::sync_config("sub", ""), &[change]).await;
+
+        assert_eq!(report.commits[0].status, "OK");
+        assert_eq!(target.file("run.sh"), Some((b"echo\n".to_vec(), EXECUTABLE)));
+        assert_eq!(target.file("tool"), Some((b"v2\n".to_vec(), EXECUTABLE)));
+        assert_eq!(target.file("new"), Some((b"renamed file\nwith a few lines\n".to_vec(), EXECUTABLE)));
+        assert_eq!(target.file("old"), None);
+    }
+
+    #[tokio::test]
+    async fn no_mode_changes_keeps_the_target_modes() {
+        let (source, change) = mode_change_source();
+        let target = mode_change_target();
+
+        let config = SyncConfig { no_mode_changes: true, ..testutil::sync_config("sub", "") };
+        let report = testutil::sync(&source, &target, config, &[change]).await;
+
+        assert_eq!(report.commits[0].status, "OK");
+        assert_eq!(target.file("run.sh"), Some((b"echo\n".to_vec(), FILE)));
+        assert_eq!(target.file("tool"), Some((b"v2\n".to_vec(), FILE)));
+        assert_eq!(target.file("new"), Some((b"renamed file\nwith a few lines\n".to_vec(), FILE)));
+        assert_eq!(target.file("old"), None);
+    }
+
+    #[tokio::test]
+    async fn mode_only_commit_is_empty_without_mode_changes() {
+        let source = TestRepo::new();
+        let base = source.commit(&[], &[("sub/run.sh", b"echo\n", FILE)], 1_000, "base");
+        let change = source.commit(&[base], &[("sub/run.sh", b"echo\n", EXECUTABLE)], 2_000, "chmod +x run.sh");
+        let target = TestRepo::new();
+        let init = target.commit(&[], &[("run.sh", b"echo\n", FILE)], 1_000, "init");
+
+        let config = SyncConfig { no_mode_changes: true, ..testutil::sync_config("sub", "") };
+        let report = testutil::sync(&source, &target, config, &[change]).await;
+
+        assert_eq!(report.commits[0].status, "EMPTY (SKIPPED)");
+        assert_eq!(report.skipped_commits, 1);
+        assert_eq!(target.repo.head().unwrap().target(), Some(init));
+        assert_eq!(target.file("run.sh"), Some((b"echo\n".to_vec(), FILE)));
+    }
+
+    #[tokio::test]
+    async fn gbk_content_is_synced_byte_for_byte_and_reported() {
+        // "名称=中文配置" in GBK, with a CRLF line end and a lone high byte, none of it UTF-8
+        let gbk: &[u8] = b"name=old\n\xc3\xfb\xb3\xc6=\xd6\xd0\xce\xc4\xc5\xe4\xd6\xc3\r\nend\x80\n";
+        let source = TestRepo::new();
+        let base = source.commit(&[], &[("sub/config.ini", b"name=old\n", FILE), ("sub/readme", b"old\n", FILE)], 1_000, "base");
+        let change = source.commit(&[base], &[("sub/config.ini", gbk, FILE), ("sub/readme", "新\n".as_bytes(), FILE)], 2_000, "Add GBK names");
+        let target = TestRepo::new();
+        target.commit(&[], &[("conf/config.ini", b"name=old\n", FILE), ("conf/readme", b"old\n", FILE)], 1_000, "init");
+
+        let report = testutil::sync(&source, &target, testutil::sync_config("sub", "conf"), &[change]).await;
+
+        assert_eq!(report.commits[0].status, "OK");
+        assert_eq!(target.file("conf/config.ini"), Some((gbk.to_vec(), FILE)));
+        assert_eq!(target.file("conf/readme"), Some(("新\n".as_bytes().to_vec(), FILE)));
+        assert_eq!(report.non_utf8_commits.len(), 1);
+        assert_eq!(report.non_utf8_commits[0].id, change.to_string());
+        assert_eq!(report.non_utf8_commits[0].paths, vec!["conf/config.ini".to_string()]);
+    }
+
+    /// Syncs a commit with `subject` using `--subject-prefix prefix` and returns
+    /// the subject the target commit ends up with
+    async fn synced_subject(subject: &str, prefix: Option<&str>) -> String {
+        let source = TestRepo::new();
+        let base = source.commit(&[], &[("sub/a", b"", FILE)], 1_000, "base");
+        let change = source.commit(&[base], &[("sub/a", b"1\n", FILE)], 2_000, subject);
+        let target = TestRepo::new();
+        target.commit(&[], &[("a", b"", FILE)], 1_000, "init");
+
+        let config = SyncConfig { subject_prefix: prefix.map(str::to_string), ..testutil::sync_config("sub", "") };
+        testutil::sync(&source, &target, config, &[change]).await;
+        target.head_message().lines().next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn bracketed_subject_prefix_reaches_the_target() {
+        assert_eq!(synced_subject("Add a", Some("[sync] ")).await, "[sync] Add a");
+        assert_eq!(synced_subject("[core] 修复 ünïcode", Some("[sync] ")).await, "[sync] [core] 修复 ünïcode");
+    }
+
+    #[tokio::test]
+    async fn unicode_subject_prefix_reaches_the_target() {
+        assert_eq!(synced_subject("修复解析器", Some("【同步】")).await, "【同步】修复解析器");
+        assert_eq!(synced_subject("Ünïcode fix", Some("🔄 ")).await, "🔄 Ünïcode fix");
+    }
+
+    #[tokio::test]
+    async fn bracketed_source_subject_is_kept_without_a_prefix() {
+        assert_eq!(synced_subject("[core] Fix the parser", None).await, "[core] Fix the parser");
+    }
+
+    #[tokio::test]
+    async fn conflict_bundles_are_kept_per_run() {
+        let source = TestRepo::new();
+        let base = source.commit(&[], &[("sub/a", b"1\n", FILE)], 1_000, "base");
+        let change = source.commit(&[base], &[("sub/a", b"2\n", FILE)], 2_000, "Change a");
+        let target = TestRepo::new();
+        let init = target.commit(&[], &[("a", b"other\n", FILE)], 1_000, "init");
+
+        let config = SyncConfig { continue_on_conflict: true, ..testutil::sync_config("sub", "") };
+        let report = testutil::sync(&source, &target, config, &[change]).await;
+
+        assert_eq!(report.commits[0].status, CONFLICT_SKIPPED);
+        assert_eq!(target.repo.head().unwrap().target(), Some(init));
+        let expected = target.repo.path().join(CONFLICTS_DIR).join(&report.run_id).join(change.to_string());
+        assert_eq!(report.conflicts[0].artifacts.as_deref(), Some(expected.as_path()));
+        assert!(expected.join("am-output.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn redaction_rules_block_encoded_subjects() {
+        let source = TestRepo::new();
+        let base = source.commit(&[], &[("sub/a", b"1\n", FILE)], 1_000, "base");
+        let change = source.commit(&[base], &[("sub/a", b"2\n", FILE)], 2_000, "修复机密客户的问题");
+        let target = TestRepo::new();
+        let init = target.commit(&[], &[("a", b"1\n", FILE)], 1_000, "init");
+
+        let redaction = Redaction::new(&["机密客户".to_string()]).unwrap();
+        let config = SyncConfig { redaction, ..testutil::sync_config("sub", "") };
+        let result = testutil::try_sync(&source, &target, config, &[change]).await;
+
+        assert!(matches!(result, Err(SyncError::RedactionViolation(ref message)) if message.contains("<commit message>")));
+        assert_eq!(target.repo.head().unwrap().target(), Some(init));
+        let report = std::env::current_dir().unwrap().join(".sync-subdir/redactions").join(format!("{}.txt", change));
+        std::fs::remove_file(report).unwrap();
+    }
+
+    #[tokio::test]
+    async fn secret_scan_blocks_unless_allowlisted() {
+        let token = format!("ghp_{}", "a1B2".repeat(9));
+        let source = TestRepo::new();
+        let base = source.commit(&[], &[("sub/a", b"1\n", FILE)], 1_000, "base");
+        let content = format!("token = {}\n", token);
+        let change = source.commit(&[base], &[("sub/a", content.as_bytes(), FILE)], 2_000, "Add a token");
+        let secrets = |allowlist: &[&str]| Some(SecretScanner::new(allowlist.iter().map(|p| regex::Regex::new(p).unwrap()).collect()));
+
+        let target = TestRepo::new();
+        let init = target.commit(&[], &[("a", b"1\n", FILE)], 1_000, "init");
+        let config = SyncConfig { secrets: secrets(&[]), block_secrets: true, ..testutil::sync_config("sub", "") };
+        let result = testutil::try_sync(&source, &target, config, &[change]).await;
+        assert!(matches!(result, Err(SyncError::SecretsDetected(ref message)) if message.contains("github-token")));
+        assert_eq!(target.repo.head().unwrap().target(), Some(init));
+        let report = std::env::current_dir().unwrap().join(".sync-subdir/redactions").join(format!("{}.txt", change));
+        std::fs::remove_file(report).unwrap();
+
+        // Without blocking the commit is synced and flagged
+        let config = SyncConfig { secrets: secrets(&[]), ..testutil::sync_config("sub", "") };
+        let report = testutil::sync(&source, &target, config, &[change]).await;
+        assert_eq!(report.flagged_commits[0].id, change.to_string());
+        assert_eq!(target.file("a").unwrap().0, content.as_bytes());
+
+        let target = TestRepo::new();
+        target.commit(&[], &[("a", b"1\n", FILE)], 1_000, "init");
+        let config = SyncConfig { secrets: secrets(&["^ghp_(a1B2)+$"]), block_secrets: true, ..testutil::sync_config("sub", "") };
+        let report = testutil::sync(&source, &target, config, &[change]).await;
+        assert!(report.flagged_commits.is_empty());
+        assert_eq!(target.file("a").unwrap().0, content.as_bytes());
+    }
+}
@@ -0,0 +1,112 @@
+use git2::Repository;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::error::{Result, SyncError};
+use crate::git::{short_id, GitManager, SourceTag};
+use crate::journal::Journal;
+use crate::report::{SkippedTag, SyncedTag};
+
+/// How `--sync-tags` names and creates the target tags
+#[derive(Debug, Clone, Default)]
+pub struct TagSync {
+    /// Source prefix replaced by a target prefix (`--tag-prefix`); source tags
+    /// without the source prefix are not synced
+    pub prefix: (String, String),
+    /// Create GPG-signed tags (`--sign-tags`)
+    pub sign: bool,
+}
+
+impl TagSync {
+    /// Parses a `--tag-prefix` value: `<source>=<target>`, e.g. `v=mono-v`, or
+    /// just `<target>`, which is put in front of every tag name
+    pub fn parse_prefix(value: &str) -> anyhow::Result<(String, String)> {
+        let (source, target) = value.split_once('=').unwrap_or(("", value));
+        if target.is_empty() && source.is_empty() {
+            return Err(anyhow::anyhow!("Invalid tag prefix: {} (expected <source>=<target> or <target>)", value));
+        }
+        Ok((source.to_string(), target.to_string()))
+    }
+
+    /// Target name of source tag `name`, `None` when it is not synced
+    pub fn target_name(&self, name: &str) -> Option<String> {
+        let (source, target) = &self.prefix;
+        name.strip_prefix(source.as_str()).map(|rest| format!("{}{}", target, rest))
+    }
+}
+
+/// Creates target tags for the source tags pointing at commits synced by run
+/// `run_id`. A tag is only carried over when its source commit is the last one
+/// of its target commit, so commits folded by `--squash-by`, `--autosquash` or
+/// `--engine copy` don't get tags of content they already moved past. Tags
+/// already pointing at the same commit are left as they are.
+pub fn sync_run(journal: &Journal, git_manager: &GitManager, run_id: &str, tags: &TagSync) -> Result<(Vec<SyncedTag>, Vec<SkippedTag>)> {
+    // Journal order is apply order, so later sources replace earlier ones
+    let mut last_source: HashMap<String, String> = HashMap::new();
+    for mapping in journal.mappings()?.into_iter().filter(|m| m.run_id == run_id) {
+        last_source.insert(mapping.target, mapping.source);
+    }
+    let targets: HashMap<String, String> = last_source.into_iter().map(|(target, source)| (source, target)).collect();
+    if targets.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let target_repo = &git_manager.target_repo_info.path;
+    let repo = Repository::open(target_repo)?;
+    let mut synced = Vec::new();
+    let mut skipped = Vec::new();
+    for tag in git_manager.source_tags()? {
+        let Some(target) = targets.get(&tag.commit) else { continue };
+        let Some(name) = tags.target_name(&tag.name) else { continue };
+        match repo.revparse_single(&format!("refs/tags/{}", name)).and_then(|object| object.peel_to_commit()) {
+            Ok(existing) if existing.id().to_string() == *target => continue,
+            Ok(existing) => {
+                skipped.push(SkippedTag { name, reason: format!("已存在，指向 {}", short_id(&existing.id().to_string())) });
+                continue;
+            }
+            Err(_) => {}
+        }
+        match create(target_repo, &name, target, &tag, tags.sign) {
+            Ok(()) => synced.push(SyncedTag { name, source: tag.name, commit: target.clone() }),
+            Err(e) => skipped.push(SkippedTag { name, reason: e.to_string() }),
+        }
+    }
+    synced.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok((synced, skipped))
+}
+
+/// Tags `commit` as `name` with `git tag`, which knows how to sign. Annotated
+/// source tags keep their message; a signed tag of a lightweight one gets the
+/// source tag name as its message.
+fn create(target_repo: &Path, name: &str, commit: &str, tag: &SourceTag, sign: bool) -> Result<()> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(target_repo).arg("tag");
+    let message = match (&tag.message, sign) {
+        (Some(message), _) => Some(message.clone()),
+        (None, true) => Some(format!("{}\n", tag.name)),
+        (None, false) => None,
+    };
+    if message.is_some() {
+        cmd.args([if sign { "-s" } else { "-a" }, "--cleanup=verbatim", "-F", "-"]);
+    }
+    cmd.args(["--", name, commit])
+        .stdin(if message.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+
+    let mut child = cmd.spawn()?;
+    if let (Some(message), Some(mut stdin)) = (message, child.stdin.take()) {
+        stdin.write_all(message.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(SyncError::Anyhow(anyhow::anyhow!(
+            "git tag {} failed: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
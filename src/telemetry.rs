@@ -0,0 +1,76 @@
+use crate::error::{Result, SyncError};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// Keeps the OTLP exporter alive; dropping it flushes the spans still queued
+pub struct Telemetry {
+    exporting: bool,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if self.exporting {
+            #[cfg(feature = "otlp")]
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Sets up logging to stderr and, when `otlp_endpoint` is given, exports the
+/// sync spans to an OpenTelemetry collector over OTLP/gRPC
+pub fn init(otlp_endpoint: Option<&str>) -> Result<Telemetry> {
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .with_filter(LevelFilter::INFO);
+
+    let otlp = match otlp_endpoint {
+        Some(endpoint) => Some(otlp_layer(endpoint)?),
+        None => None,
+    };
+    let exporting = otlp.is_some();
+
+    tracing_subscriber::registry()
+        .with(otlp)
+        .with(fmt)
+        .try_init()
+        .map_err(|e| SyncError::Anyhow(e.into()))?;
+    Ok(Telemetry { exporting })
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_layer<S>(endpoint: &str) -> Result<Box<dyn Layer<S> + Send + Sync>>
+where
+    S: tracing::Subscriber + Send + Sync + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{trace, Resource};
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(trace::config().with_resource(Resource::new(vec![
+            KeyValue::new("service.name", "sync-subdir"),
+            KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+        ])))
+        .install_batch(opentelemetry_sdk::runtime::Tokio)
+        .map_err(|e| SyncError::Anyhow(anyhow::anyhow!("Failed to set up OTLP exporter: {}", e)))?;
+
+    Ok(tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(LevelFilter::INFO)
+        .boxed())
+}
+
+#[cfg(not(feature = "otlp"))]
+fn otlp_layer<S>(_endpoint: &str) -> Result<Box<dyn Layer<S> + Send + Sync>>
+where
+    S: tracing::Subscriber + Send + Sync + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    Err(SyncError::Anyhow(anyhow::anyhow!(
+        "--otlp-endpoint requires a build with the `otlp` feature (cargo build --features otlp)"
+    )))
+}
@@ -0,0 +1,163 @@
+//! Throwaway repositories for the unit tests. Commits are built with git2, so
+//! their times, parents and file modes are exactly what a test asks for.
+
+use crate::backend::SyncBackend;
+use crate::copy::CopyEngine;
+use crate::error::Result;
+use crate::git::{GitManager, OctopusPolicy};
+use crate::redact::Redaction;
+use crate::report::SyncReport;
+use crate::sync::{RetryPolicy, SyncConfig, SyncEngine};
+use git2::{Oid, Repository, Signature, Time};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+use tempdir::TempDir;
+
+/// Mode of a regular file
+pub const FILE: i32 = 0o100644;
+
+/// Mode of an executable file
+pub const EXECUTABLE: i32 = 0o100755;
+
+/// A file of a commit's tree: path, content and mode
+pub type Entry<'a> = (&'a str, &'a [u8], i32);
+
+pub struct TestRepo {
+    pub repo: Repository,
+    dir: TempDir,
+}
+
+impl TestRepo {
+    pub fn new() -> Self {
+        let dir = TempDir::new("sync-subdir-test").unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        Self { repo, dir }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Creates a commit holding exactly `files`, made at `time` (seconds since
+    /// the epoch), and points `main` and the working tree at it
+    pub fn commit(&self, parents: &[Oid], files: &[Entry], time: i64, message: &str) -> Oid {
+        let signature = Signature::new("Test", "test@example.com", &Time::new(time, 0)).unwrap();
+        let tree = self.repo.find_tree(write_tree(&self.repo, files)).unwrap();
+        let parents: Vec<_> = parents.iter().map(|id| self.repo.find_commit(*id).unwrap()).collect();
+        let parents: Vec<_> = parents.iter().collect();
+        let id = self.repo.commit(None, &signature, &signature, message, &tree, &parents).unwrap();
+        self.checkout(id);
+        id
+    }
+
+    /// Moves `main` to `id` and checks it out
+    pub fn checkout(&self, id: Oid) {
+        self.repo.reference("refs/heads/main", id, true, "test").unwrap();
+        self.repo.set_head("refs/heads/main").unwrap();
+        self.repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+    }
+
+    /// Content and mode of `path` in the tree of `main`
+    pub fn file(&self, path: &str) -> Option<(Vec<u8>, i32)> {
+        let tree = self.repo.head().ok()?.peel_to_tree().ok()?;
+        let entry = tree.get_path(Path::new(path)).ok()?;
+        let blob = self.repo.find_blob(entry.id()).ok()?;
+        Some((blob.content().to_vec(), entry.filemode()))
+    }
+
+    /// Message of the commit at the tip of `main`
+    pub fn head_message(&self) -> String {
+        self.repo.head().unwrap().peel_to_commit().unwrap().message().unwrap().to_string()
+    }
+}
+
+fn write_tree(repo: &Repository, files: &[Entry]) -> Oid {
+    let mut builder = repo.treebuilder(None).unwrap();
+    let mut dirs: BTreeMap<&str, Vec<Entry>> = BTreeMap::new();
+    for &(path, content, mode) in files {
+        match path.split_once('/') {
+            Some((dir, rest)) => dirs.entry(dir).or_default().push((rest, content, mode)),
+            None => {
+                let blob = repo.blob(content).unwrap();
+                builder.insert(path, blob, mode).unwrap();
+            }
+        }
+    }
+    for (dir, files) in dirs {
+        builder.insert(dir, write_tree(repo, &files), 0o040000).unwrap();
+    }
+    builder.write().unwrap()
+}
+
+/// Settings of a plain sync of `subdir` into `target_dir`, as the command line
+/// gives them without options
+pub fn sync_config(subdir: &str, target_dir: &str) -> SyncConfig {
+    SyncConfig {
+        subdir: subdir.to_string(),
+        target_dir: target_dir.to_string(),
+        excludes: Vec::new(),
+        redaction: Redaction::default(),
+        secrets: None,
+        block_secrets: false,
+        deferred_commits: 0,
+        messages: Default::default(),
+        message_rewrites: Vec::new(),
+        synced_from: None,
+        subject_prefix: None,
+        octopus: OctopusPolicy::Skip,
+        squash_by: None,
+        autosquash: false,
+        todo: None,
+        notes: false,
+        tags: None,
+        bump_version: None,
+        version_file: None,
+        tmp_dir: None,
+        keep_patches: None,
+        retry: RetryPolicy { retries: 0, delay: Duration::ZERO },
+        format_patch_args: Vec::new(),
+        am_args: Vec::new(),
+        continue_on_conflict: false,
+        staging_branch: false,
+        sync_delete: true,
+        verify: false,
+        no_mode_changes: false,
+    }
+}
+
+/// Syncs the commits `ids` of `source` onto the checked out branch of `target`
+pub async fn sync(source: &TestRepo, target: &TestRepo, config: SyncConfig, ids: &[Oid]) -> SyncReport {
+    try_sync(source, target, config, ids).await.unwrap()
+}
+
+/// Like [`sync`], for tests of syncs that are expected to fail
+pub async fn try_sync(source: &TestRepo, target: &TestRepo, config: SyncConfig, ids: &[Oid]) -> Result<SyncReport> {
+    let git_manager = GitManager::new(source.path(), target.path()).unwrap();
+    let commits: Vec<_> = ids
+        .iter()
+        .map(|id| git_manager.commit_info(&id.to_string(), &config.subdir).unwrap())
+        .collect();
+    let (tx, _rx) = tokio::sync::mpsc::channel(1024);
+    SyncEngine::new(config, false).sync_commits(&git_manager, &commits, tx).await
+}
+
+/// Copies `source` as of the last of `ids` onto the checked out branch of
+/// `target` with the copy engine
+pub async fn copy(source: &TestRepo, target: &TestRepo, config: SyncConfig, ids: &[Oid]) -> SyncReport {
+    try_copy(source, target, config, ids).await.unwrap()
+}
+
+/// Like [`copy`], for tests of copies that are expected to fail
+pub async fn try_copy(source: &TestRepo, target: &TestRepo, config: SyncConfig, ids: &[Oid]) -> Result<SyncReport> {
+    let git_manager = GitManager::new(source.path(), target.path()).unwrap();
+    let commits: Vec<_> = ids
+        .iter()
+        .map(|id| git_manager.commit_info(&id.to_string(), &config.subdir).unwrap())
+        .collect();
+    let (tx, _rx) = tokio::sync::mpsc::channel(1024);
+    CopyEngine::new(config, false).execute(&git_manager, &commits, tx).await
+}
@@ -0,0 +1,94 @@
+use clap::ArgMatches;
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier};
+
+/// Colour scheme of the interface
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Theme {
+    #[default]
+    Default,
+    /// White on black only, with the highlighted row reversed and errors in bold
+    HighContrast,
+}
+
+impl Theme {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "default" => Ok(Self::Default),
+            "high-contrast" => Ok(Self::HighContrast),
+            other => Err(anyhow::anyhow!("Invalid theme: {}", other)),
+        }
+    }
+}
+
+/// How screens are drawn, from `--theme` and `--ascii`. Screens are rendered
+/// as usual and the finished frame is rewritten, so every screen and popup
+/// follows the settings without drawing code of its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Appearance {
+    pub theme: Theme,
+    /// Plain ASCII borders, marks and progress bars for terminals without
+    /// box-drawing glyphs
+    pub ascii: bool,
+}
+
+impl Appearance {
+    pub fn from_matches(matches: &ArgMatches) -> anyhow::Result<Self> {
+        Ok(Self {
+            theme: matches.get_one::<String>("theme").map_or(Ok(Theme::Default), |v| Theme::parse(v))?,
+            ascii: matches.get_flag("ascii"),
+        })
+    }
+
+    pub fn apply(&self, buffer: &mut Buffer) {
+        if *self == Self::default() {
+            return;
+        }
+        for cell in &mut buffer.content {
+            if self.ascii {
+                let mut chars = cell.symbol.chars();
+                if let (Some(c), None) = (chars.next(), chars.next()) {
+                    if let Some(replacement) = ascii_glyph(c) {
+                        cell.set_char(replacement);
+                    }
+                }
+            }
+            if self.theme == Theme::HighContrast {
+                let emphasized = matches!(cell.fg, Color::Red | Color::LightRed);
+                if cell.bg != Color::Reset {
+                    cell.fg = Color::Black;
+                    cell.bg = Color::White;
+                } else {
+                    cell.fg = Color::White;
+                    cell.bg = Color::Black;
+                }
+                if emphasized {
+                    cell.modifier |= Modifier::BOLD;
+                }
+                cell.modifier.remove(Modifier::DIM);
+            }
+        }
+    }
+}
+
+/// ASCII stand-in for a glyph that limited terminals cannot show
+fn ascii_glyph(c: char) -> Option<char> {
+    let replacement = match c {
+        '─' | '━' | '═' | '┄' | '┈' | '╌' => '-',
+        '│' | '┃' | '║' | '┆' | '┊' | '╎' => '|',
+        // Corners, junctions and the remaining box-drawing glyphs
+        '\u{2500}'..='\u{257f}' => '+',
+        // Block elements of gauges and bars
+        '\u{2580}'..='\u{259f}' => '#',
+        '✓' | '✔' => 'x',
+        '✎' => '*',
+        '…' => '.',
+        '−' => '-',
+        '←' => '<',
+        '→' => '>',
+        '↑' => '^',
+        '↓' => 'v',
+        _ => return None,
+    };
+    Some(replacement)
+}
@@ -0,0 +1,55 @@
+use crate::error::{Result, SyncError};
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Creates the scratch directory of a run below `root`, or below the system
+/// temp directory (`$TMPDIR`) when no root is configured
+pub fn create(root: Option<&Path>) -> Result<TempDir> {
+    let mut builder = tempfile::Builder::new();
+    builder.prefix("sync-subdir-");
+    match root {
+        Some(root) => {
+            std::fs::create_dir_all(root)?;
+            Ok(builder.tempdir_in(root)?)
+        }
+        None => Ok(builder.tempdir()?),
+    }
+}
+
+/// Fails when the filesystem holding `dir` has less than `needed` bytes free.
+/// Platforms without a way to query free space always pass.
+pub fn ensure_space(dir: &Path, needed: u64) -> Result<()> {
+    match available_space(dir)? {
+        Some(available) if available < needed => Err(SyncError::InsufficientSpace {
+            path: dir.to_path_buf(),
+            needed_mib: needed.div_ceil(MIB),
+            available_mib: available / MIB,
+        }),
+        _ => Ok(()),
+    }
+}
+
+const MIB: u64 = 1024 * 1024;
+
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Result<Option<u64>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes())
+        .map_err(|e| SyncError::InvalidPath(e.to_string()))?;
+    // SAFETY: `path` is NUL terminated and `stat` is a plain struct statvfs fills in
+    let stat = unsafe {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        if libc::statvfs(path.as_ptr(), &mut stat) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        stat
+    };
+    #[allow(clippy::unnecessary_cast)]
+    Ok(Some(stat.f_bavail as u64 * stat.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> Result<Option<u64>> {
+    Ok(None)
+}
@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::error::{Result, SyncError};
+use crate::git::{short_id, CommitInfo};
+
+/// What a todo line does with its commit, as in `git rebase -i`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TodoAction {
+    Pick,
+    /// Pick with the subject replaced by the rest of the line
+    Reword,
+    /// Fold into the previous commit, appending the message
+    Squash,
+    /// Fold into the previous commit, dropping the message
+    Fixup,
+    Drop,
+}
+
+impl TodoAction {
+    fn parse(word: &str) -> Option<Self> {
+        match word {
+            "p" | "pick" => Some(Self::Pick),
+            "r" | "reword" => Some(Self::Reword),
+            "s" | "squash" => Some(Self::Squash),
+            "f" | "fixup" => Some(Self::Fixup),
+            "d" | "drop" => Some(Self::Drop),
+            _ => None,
+        }
+    }
+
+    fn folds(self) -> bool {
+        matches!(self, Self::Squash | Self::Fixup)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TodoEntry {
+    pub action: TodoAction,
+    /// Full id of the commit, from the loaded range
+    pub id: String,
+    /// Rest of the line; the new subject of a `reword`
+    pub text: String,
+}
+
+/// Commits to sync, their order and how they fold together (`--todo`);
+/// dropped and deleted lines are not in it
+#[derive(Debug, Clone, Default)]
+pub struct Todo {
+    pub entries: Vec<TodoEntry>,
+}
+
+const HELP: &str = "\
+#
+# 命令:
+# p, pick <提交> = 同步该提交
+# r, reword <提交> <标题> = 同步该提交，标题换成这一行提交 ID 之后的内容
+# s, squash <提交> = 并入上一个提交，提交信息追加在后面
+# f, fixup <提交> = 并入上一个提交，丢弃它的提交信息
+# d, drop <提交> = 不同步该提交
+#
+# 行可以调整顺序，删除一行与 drop 相同。pick 行提交 ID 之后的标题只是说明，修改它没有效果。
+";
+
+/// Todo file listing `commits` in order, picking the selected ones and dropping the others
+pub fn render(commits: &[CommitInfo], selected: &[bool], subdir: &str) -> String {
+    let mut content = String::new();
+    for (commit, selected) in commits.iter().zip(selected) {
+        let action = if *selected { "pick" } else { "drop" };
+        content.push_str(&format!("{} {} {}\n", action, short_id(&commit.id), commit.subject));
+    }
+    let picked = selected.iter().filter(|s| **s).count();
+    let subdir = if subdir.is_empty() { "仓库根目录" } else { subdir };
+    content.push_str(&format!("\n# 同步 {}: 共 {} 个提交，选择了 {} 个\n", subdir, commits.len(), picked));
+    content.push_str(HELP);
+    content
+}
+
+impl Todo {
+    /// Reads a todo file whose commits are ids, or unique id prefixes, of `commits`
+    pub fn load(path: &Path, commits: &[CommitInfo]) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| SyncError::Anyhow(anyhow::anyhow!("Failed to read todo file {}: {}", path.display(), e)))?;
+        Self::parse(&content, commits)
+    }
+
+    pub fn parse(content: &str, commits: &[CommitInfo]) -> Result<Self> {
+        let invalid = |number: usize, message: String| SyncError::Anyhow(anyhow::anyhow!("--todo line {}: {}", number, message));
+        let mut entries: Vec<TodoEntry> = Vec::new();
+        let mut seen = HashSet::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.splitn(3, char::is_whitespace);
+            let word = fields.next().unwrap_or_default();
+            let action = TodoAction::parse(word).ok_or_else(|| invalid(i + 1, format!("unknown command {}", word)))?;
+            let prefix = fields.next().ok_or_else(|| invalid(i + 1, format!("{} needs a commit", word)))?;
+            let text = fields.next().unwrap_or_default().trim().to_string();
+
+            if prefix.len() < 4 {
+                return Err(invalid(i + 1, format!("commit id {} is too short", prefix)));
+            }
+            let mut matching = commits.iter().filter(|commit| commit.id.starts_with(prefix));
+            let id = match (matching.next(), matching.next()) {
+                (Some(commit), None) => commit.id.clone(),
+                (Some(_), Some(_)) => return Err(invalid(i + 1, format!("{} is ambiguous", prefix))),
+                (None, _) => return Err(invalid(i + 1, format!("{} is not a commit of the range", prefix))),
+            };
+            if !seen.insert(id.clone()) {
+                return Err(invalid(i + 1, format!("{} is listed twice", prefix)));
+            }
+            match action {
+                TodoAction::Drop => continue,
+                TodoAction::Reword if text.is_empty() => {
+                    return Err(invalid(i + 1, format!("reword {} needs the new subject after the commit", prefix)))
+                }
+                action if action.folds() && entries.is_empty() => {
+                    return Err(invalid(i + 1, format!("{} {} has no previous commit to fold into", word, prefix)))
+                }
+                _ => {}
+            }
+            entries.push(TodoEntry { action, id, text });
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.entries.iter().any(|entry| entry.id == id)
+    }
+
+    fn action(&self, id: &str) -> Option<TodoAction> {
+        self.entries.iter().find(|entry| entry.id == id).map(|entry| entry.action)
+    }
+
+    /// Positions in `commits` in todo order, each with the position of the
+    /// commit its group folds into, like [`crate::sync::autosquash_order`].
+    /// Commits missing from `commits` are left out, so a squash follows the
+    /// previous commit still there; commits not in the todo come last.
+    pub fn order(&self, commits: &[CommitInfo]) -> Vec<(usize, usize)> {
+        let mut order: Vec<(usize, usize)> = Vec::new();
+        for entry in &self.entries {
+            let Some(i) = commits.iter().position(|commit| commit.id == entry.id) else { continue };
+            let first = match order.last() {
+                Some(&(_, first)) if entry.action.folds() => first,
+                _ => i,
+            };
+            order.push((i, first));
+        }
+        for (i, commit) in commits.iter().enumerate() {
+            if !self.contains(&commit.id) {
+                order.push((i, i));
+            }
+        }
+        order
+    }
+
+    /// Message of a folded group from its applied commits, oldest first:
+    /// `squash` messages are appended and `fixup` messages dropped
+    pub fn message(&self, commits: &[(String, String)]) -> String {
+        let mut message = String::new();
+        for (id, commit_message) in commits {
+            let keep = message.is_empty() || !matches!(self.action(id), Some(TodoAction::Fixup));
+            if !keep {
+                continue;
+            }
+            if !message.is_empty() {
+                message.push_str("\n\n");
+            }
+            message.push_str(commit_message.trim_end());
+        }
+        message.push('\n');
+        message
+    }
+}
+
+/// `message` with its subject line replaced by `subject`
+pub fn reword(message: &str, subject: &str) -> String {
+    match message.split_once('\n') {
+        Some((_, body)) => format!("{}\n{}", subject, body),
+        None => format!("{}\n", subject),
+    }
+}
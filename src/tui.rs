@@ -8,18 +8,32 @@ use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{
-        Block, Borders, Clear, Gauge, ListState, Paragraph, Wrap,
+        Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Wrap,
         Table, Row, Cell
     },
     Frame, Terminal,
 };
-use std::io::stdout;
+use std::collections::{HashMap, VecDeque};
+use std::io::{stdout, Write};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use crate::cli::Config;
-use crate::git::CommitInfo;
-use crate::sync::{SyncStats};
+use crate::forge::Forge;
+use crate::git::{short_id, CommitInfo, DiffStats, UpstreamStatus, WalkProgress};
+use crate::highlight;
+use crate::keymap::{Action, Keymap, PendingInput};
+use crate::prefetch::{DetailsCache, DETAILS_CACHE_CAPACITY};
+use crate::progress::ProgressServer;
+use crate::signals;
+use crate::report::SyncReport;
+use crate::session::Session;
+use crate::theme::Appearance;
+use crate::todo::Todo;
+use crate::ui_state::{Column, SelectionLayout, UiState};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum AppState {
@@ -27,6 +41,9 @@ pub enum AppState {
     FileSelection,
     Progress,
     Confirmation,
+    /// After a `--continue-on-conflict` run, lists the skipped commits so they
+    /// can be re-attempted one by one
+    ConflictQueue,
     Completed,
 }
 
@@ -41,6 +58,189 @@ pub enum ConfirmationAction {
     ExecuteSync,
 }
 
+/// Fields that can be changed in the ConfigReview edit mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigField {
+    Subdir,
+    SourceBranch,
+    TargetBranch,
+    StartCommit,
+    EndCommit,
+    IncludeStart,
+    NoMerge,
+    FollowDir,
+    DryRun,
+}
+
+impl ConfigField {
+    pub const ALL: [ConfigField; 9] = [
+        Self::Subdir,
+        Self::SourceBranch,
+        Self::TargetBranch,
+        Self::StartCommit,
+        Self::EndCommit,
+        Self::IncludeStart,
+        Self::NoMerge,
+        Self::FollowDir,
+        Self::DryRun,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Subdir => "子目录",
+            Self::SourceBranch => "源分支",
+            Self::TargetBranch => "目标分支",
+            Self::StartCommit => "起始 Commit",
+            Self::EndCommit => "结束 Commit",
+            Self::IncludeStart => "包含起始 Commit",
+            Self::NoMerge => "排除 Merge",
+            Self::FollowDir => "跟踪目录重命名",
+            Self::DryRun => "预览模式",
+        }
+    }
+
+    pub fn is_toggle(self) -> bool {
+        matches!(self, Self::IncludeStart | Self::NoMerge | Self::FollowDir | Self::DryRun)
+    }
+
+    /// Current value as shown (and initially edited) in the form
+    pub fn value(self, config: &Config) -> String {
+        let toggle = |on: bool| if on { "是" } else { "否" }.to_string();
+        match self {
+            Self::Subdir => config.subdir.clone(),
+            Self::SourceBranch => config.source_branch.clone().unwrap_or_default(),
+            Self::TargetBranch => config.get_default_target_branch(),
+            Self::StartCommit => config.start_commit.clone(),
+            Self::EndCommit => config.end_commit.clone().unwrap_or_default(),
+            Self::IncludeStart => toggle(config.include_start.unwrap_or(true)),
+            Self::NoMerge => toggle(config.no_merge.unwrap_or(true)),
+            Self::FollowDir => toggle(config.follow_dir),
+            Self::DryRun => toggle(config.dry_run),
+        }
+    }
+}
+
+/// State of the ConfigReview edit mode
+#[derive(Debug, Default)]
+pub struct ConfigEditor {
+    /// Index into `ConfigField::ALL`
+    pub selected: usize,
+    /// Text being typed for the selected field, `None` when not editing it
+    pub input: Option<String>,
+    /// Why the last change was rejected
+    pub error: Option<String>,
+}
+
+/// Patch of one commit shown in the FileSelection pager
+#[derive(Debug)]
+pub struct PatchPreview {
+    pub commit_id: String,
+    /// Rendered once when the preview opens; highlighting is too slow to redo per frame
+    pub lines: Vec<Line<'static>>,
+    /// Index of the first visible line
+    pub scroll: usize,
+}
+
+impl PatchPreview {
+    pub fn new(commit_id: &str, patch: &str, color: bool) -> Self {
+        Self {
+            commit_id: commit_id.to_string(),
+            lines: highlight::patch_lines(patch, color),
+            scroll: 0,
+        }
+    }
+
+    pub fn scroll_by(&mut self, delta: isize) {
+        let last = self.lines.len().saturating_sub(1);
+        self.scroll = self.scroll.saturating_add_signed(delta).min(last);
+    }
+
+    pub fn scroll_to_end(&mut self) {
+        self.scroll = self.lines.len().saturating_sub(1);
+    }
+}
+
+/// Columns the subject keeps when the other columns of the commit table overflow the terminal
+const MIN_SUBJECT_WIDTH: u16 = 10;
+
+/// Narrowest terminal the full selection layout is used on; the details pane
+/// would squeeze the commit table below that
+const MIN_FULL_LAYOUT_WIDTH: u16 = 100;
+
+/// Share of the selection screen width taken by the details pane
+const DETAILS_PANE_PERCENT: u16 = 40;
+
+/// Number of entries kept in the progress log pane
+const SYNC_LOG_CAPACITY: usize = 20;
+
+/// A line in the progress log pane
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: String,
+    pub status: String,
+    pub message: String,
+}
+
+/// Progress of loading the commit history
+#[derive(Debug, Clone)]
+pub enum LoadEvent {
+    /// Walking the range
+    Walk(WalkProgress),
+    /// A check of the commits kept by the walk
+    Step(&'static str),
+}
+
+/// Frames of the spinner shown while the commit history loads
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Commit history loading on its own thread
+#[derive(Debug)]
+pub struct CommitLoad {
+    /// Returns the commits to show, the number pending before `--limit` and the
+    /// number of revert pairs found
+    pub thread: std::thread::JoinHandle<crate::error::Result<(Vec<CommitInfo>, usize, usize)>>,
+    pub events: tokio::sync::mpsc::Receiver<LoadEvent>,
+    /// Stops the thread at its next progress report
+    pub cancel: Arc<AtomicBool>,
+    pub latest: Option<LoadEvent>,
+    pub started: Instant,
+}
+
+impl CommitLoad {
+    /// Status line for the latest progress, with a spinner driven by the redraws
+    pub fn describe(&self) -> String {
+        let frame = SPINNER[(self.started.elapsed().as_millis() / 100) as usize % SPINNER.len()];
+        let stopping = self.cancel.load(std::sync::atomic::Ordering::Relaxed);
+        let progress = match &self.latest {
+            _ if stopping => "正在取消加载...".to_string(),
+            None => "正在加载提交历史...".to_string(),
+            Some(LoadEvent::Walk(walk)) => format!(
+                "正在加载提交历史: 已检查 {}/{} 个提交，匹配 {} 个",
+                walk.examined, walk.total, walk.matched
+            ),
+            Some(LoadEvent::Step(step)) => format!("{}...", step),
+        };
+        format!("{} {}", frame, progress)
+    }
+
+    /// Share of the range walked so far
+    pub fn ratio(&self) -> f64 {
+        match &self.latest {
+            Some(LoadEvent::Walk(walk)) if walk.total > 0 => walk.examined as f64 / walk.total as f64,
+            Some(LoadEvent::Step(_)) => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Progress of the run on one `--target` fan-out repository
+#[derive(Debug, Clone, Default)]
+pub struct FanoutProgress {
+    pub progress: f64,
+    pub status: String,
+    pub failed: bool,
+}
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct App {
@@ -50,6 +250,8 @@ pub struct App {
     pub selected_commits: Vec<bool>,
     pub current_confirmation: Option<ConfirmationAction>,
     pub progress: f64,
+    /// Progress of each `--target` repository, in the order given
+    pub fanout: Vec<FanoutProgress>,
     pub status_message: String,
     pub current_tab: usize,
     pub list_state: ListState,
@@ -58,11 +260,72 @@ pub struct App {
     pub start_time: Instant,
     pub end_time: Option<Instant>,
     pub loaded_changes: bool,
-    pub sync_stats: Option<SyncStats>,
+    /// Commit history being loaded off the interface thread
+    pub commit_load: Option<CommitLoad>,
+    pub sync_report: Option<SyncReport>,
+    /// The Completed screen lists every warning instead of their count
+    pub show_warnings: bool,
+    /// The sync or the steps after it failed; the process exits with 1
+    pub sync_failed: bool,
+    pub target_base: Option<String>,
+    /// Target branch compared with its upstream, when it tracks one
+    pub target_upstream: Option<UpstreamStatus>,
+    /// Branch checked out in the source repository at startup, used when no
+    /// source branch is given
+    pub source_head: String,
+    pub resolved_start: Option<String>,
+    pub resolved_end: Option<String>,
+    /// Commit that created the subdir, when it is missing at the given start commit
+    pub subdir_created: Option<String>,
+    pub config_warnings: Vec<String>,
+    /// The part of `config_warnings` that does not depend on the range, kept to
+    /// rebuild the list when the range is edited on the review screen
+    pub base_warnings: Vec<String>,
+    /// Number of pending commits before `--limit` was applied
+    pub total_pending: usize,
+    /// Commit messages edited by the user, keyed by source commit id
+    pub edited_messages: HashMap<String, String>,
+    /// Order and folding of the selected commits read from `--todo`
+    pub todo: Option<Todo>,
+    /// Commit labels, saved whenever one changes
+    pub session: Session,
+    /// Link template for the Synced-from trailer, when enabled
+    pub synced_from: Option<String>,
+    /// Where to open a pull request after syncing, when `--create-pr` is set
+    pub forge: Option<Forge>,
+    /// Set while the ConfigReview screen is in edit mode
+    pub config_editor: Option<ConfigEditor>,
+    /// Most recent sync events, oldest first
+    pub sync_log: VecDeque<LogEntry>,
+    /// Running background sync, awaited before quitting so its cleanup runs
+    pub sync_task: Option<tokio::task::JoinHandle<()>>,
+    /// Asks the running sync to stop before its next commit
+    pub sync_cancel: Arc<AtomicBool>,
+    /// Set while the FileSelection screen shows a commit's patch
+    pub patch_preview: Option<PatchPreview>,
+    /// Previewed patches keyed by source commit id
+    pub patch_cache: HashMap<String, String>,
+    /// Details pane contents keyed by source commit id, filled by the prefetcher
+    pub commit_details: DetailsCache,
+    /// Mirrors sync events to external clients, when `--progress-socket` is set
+    pub progress_server: Option<ProgressServer>,
+    /// Commit table layout, saved when the column settings are closed
+    pub ui_state: UiState,
+    /// Commit list key bindings, from the `[keys]` section of the preferences
+    pub keymap: Keymap,
+    /// Count and keys typed towards the next commit list action
+    pub pending_input: PendingInput,
+    /// Highlighted row while the FileSelection screen shows the column settings
+    pub column_editor: Option<usize>,
+    /// Highlighted conflict on the ConflictQueue screen
+    pub conflict_selected: usize,
+    /// Source commit being re-attempted from the ConflictQueue screen
+    pub conflict_retry: Option<String>,
 }
 
 impl App {
     pub fn new(config: Config) -> Self {
+        let fanout = vec![FanoutProgress::default(); config.extra_targets.len()];
         Self {
             state: AppState::ConfigReview,
             config,
@@ -70,6 +333,7 @@ impl App {
             selected_commits: Vec::new(),
             current_confirmation: None,
             progress: 0.0,
+            fanout,
             status_message: String::new(),
             current_tab: 0,
             list_state: ListState::default(),
@@ -78,14 +342,51 @@ impl App {
             start_time: Instant::now(),
             end_time: None,
             loaded_changes: false,
-            sync_stats: None,
+            commit_load: None,
+            sync_report: None,
+            show_warnings: false,
+            sync_failed: false,
+            target_base: None,
+            target_upstream: None,
+            source_head: String::new(),
+            resolved_start: None,
+            resolved_end: None,
+            subdir_created: None,
+            config_warnings: Vec::new(),
+            base_warnings: Vec::new(),
+            total_pending: 0,
+            edited_messages: HashMap::new(),
+            todo: None,
+            session: Session::default(),
+            synced_from: None,
+            forge: None,
+            config_editor: None,
+            sync_log: VecDeque::new(),
+            sync_task: None,
+            sync_cancel: Arc::new(AtomicBool::new(false)),
+            patch_preview: None,
+            patch_cache: HashMap::new(),
+            commit_details: DetailsCache::new(DETAILS_CACHE_CAPACITY),
+            progress_server: None,
+            ui_state: UiState::default(),
+            keymap: Keymap::default(),
+            pending_input: PendingInput::default(),
+            column_editor: None,
+            conflict_selected: 0,
+            conflict_retry: None,
         }
     }
 
+    /// Whether the range starts before the commit that created the subdir
+    pub fn starts_before_subdir(&self) -> bool {
+        self.subdir_created.is_some() && self.subdir_created != self.resolved_start
+    }
+
     pub fn set_commits(&mut self, commits: Vec<CommitInfo>) {
-        let count = commits.len();
+        self.selected_commits = commits.iter().map(CommitInfo::selected_by_default).collect();
         self.commits = commits;
-        self.selected_commits = vec![true; count];
+        self.patch_cache.clear();
+        self.commit_details.clear();
     }
 
     pub fn next(&mut self) {
@@ -116,6 +417,28 @@ impl App {
         self.list_state.select(Some(i));
     }
 
+    /// Commit under the cursor on the FileSelection screen
+    pub fn highlighted_commit(&self) -> Option<&CommitInfo> {
+        self.list_state.selected().and_then(|i| self.commits.get(i))
+    }
+
+    /// Moves the cursor `delta` rows, stopping at either end of the list
+    pub fn move_by(&mut self, delta: isize) {
+        if self.commits.is_empty() {
+            return;
+        }
+        let current = self.list_state.selected().unwrap_or(0);
+        let target = current.saturating_add_signed(delta).min(self.commits.len() - 1);
+        self.list_state.select(Some(target));
+    }
+
+    /// Moves the cursor to row `index`, or to the last row past the end
+    pub fn go_to(&mut self, index: usize) {
+        if !self.commits.is_empty() {
+            self.list_state.select(Some(index.min(self.commits.len() - 1)));
+        }
+    }
+
     pub fn toggle_commit_selection(&mut self) {
         if let Some(i) = self.list_state.selected() {
             if i < self.selected_commits.len() {
@@ -124,6 +447,13 @@ impl App {
         }
     }
 
+    /// Moves the highlighted commit to its next label
+    pub fn cycle_label(&mut self) {
+        if let Some(commit) = self.list_state.selected().and_then(|i| self.commits.get(i)) {
+            self.session.cycle_label(&commit.id);
+        }
+    }
+
     pub fn select_all(&mut self) {
         self.selected_commits.fill(true);
     }
@@ -132,47 +462,106 @@ impl App {
         self.selected_commits.fill(false);
     }
 
+    /// Selects every commit of a commit and revert pair, or deselects them all
+    /// when they are all selected already. Returns whether they are now selected.
+    pub fn toggle_reverts(&mut self) -> bool {
+        let pairs: Vec<usize> = (0..self.commits.len()).filter(|&i| self.commits[i].revert_pair.is_some()).collect();
+        let select = !pairs.iter().all(|&i| self.selected_commits[i]);
+        for i in pairs {
+            self.selected_commits[i] = select;
+        }
+        select
+    }
+
+    pub fn push_log(&mut self, status: impl Into<String>, message: impl Into<String>) {
+        if self.sync_log.len() == SYNC_LOG_CAPACITY {
+            self.sync_log.pop_front();
+        }
+        self.sync_log.push_back(LogEntry {
+            time: chrono::Local::now().format("%H:%M:%S").to_string(),
+            status: status.into(),
+            message: message.into(),
+        });
+    }
+
     pub fn get_selected_count(&self) -> usize {
         self.selected_commits.iter().filter(|&&selected| selected).count()
     }
+
+    /// Selected commits in list order
+    pub fn selected(&self) -> Vec<CommitInfo> {
+        self.commits
+            .iter()
+            .zip(self.selected_commits.iter())
+            .filter(|(_, &selected)| selected)
+            .map(|(commit, _)| commit.clone())
+            .collect()
+    }
 }
 
 pub struct TuiManager {
     terminal: Terminal<CrosstermBackend<std::io::Stdout>>,
+    appearance: Appearance,
+    /// Bottom line of the last screen drawn for a sync, kept for the popups
+    /// drawn outside of [`TuiManager::draw`]
+    status_bar: Option<Line<'static>>,
 }
 
 impl TuiManager {
-    pub fn new() -> Result<Self> {
+    pub fn new(appearance: Appearance) -> Result<Self> {
         enable_raw_mode()?;
         let mut stdout = stdout();
         execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
         let backend = CrosstermBackend::new(stdout);
         let terminal = Terminal::new(backend)?;
-        Ok(Self { terminal })
+        Ok(Self { terminal, appearance, status_bar: None })
     }
 
-    pub fn draw(&mut self, app: &App) -> Result<()> {
+    /// Draws a screen that is not part of the sync flow, e.g. the daemon status
+    pub fn draw_with(&mut self, render: impl FnOnce(&mut Frame)) -> Result<()> {
+        let appearance = self.appearance;
         self.terminal.draw(|f| {
-            match app.state {
-                AppState::ConfigReview => Self::draw_config_review(f, app),
-                AppState::FileSelection => Self::draw_file_selection(f, app),
-                AppState::Progress => Self::draw_progress(f, app),
-                AppState::Confirmation => Self::draw_confirmation(f, app),
-                AppState::Completed => Self::draw_completed(f, app),
-            }
+            render(f);
+            appearance.apply(f.buffer_mut());
         })?;
         Ok(())
     }
 
-    fn draw_config_review(f: &mut Frame, app: &App) {
+    /// Draws the screen of the current state above the status bar
+    pub fn draw(&mut self, app: &App) -> Result<()> {
+        self.status_bar = Some(status_bar(app));
+        let status_bar = self.status_bar.clone();
+        self.draw_with(|f| {
+            let (area, bar) = split_status_bar(f.size());
+            match app.state {
+                AppState::ConfigReview => Self::draw_config_review(f, app, area),
+                AppState::FileSelection => Self::draw_file_selection(f, app, area),
+                AppState::Progress => Self::draw_progress(f, app, area),
+                AppState::Confirmation => Self::draw_confirmation(f, app, area),
+                AppState::ConflictQueue => Self::draw_conflict_queue(f, app, area),
+                AppState::Completed => Self::draw_completed(f, app, area),
+            }
+            if let Some(line) = status_bar {
+                f.render_widget(Paragraph::new(line), bar);
+            }
+        })
+    }
+
+    fn draw_config_review(f: &mut Frame, app: &App, area: Rect) {
+        let warning_height = if app.config_warnings.is_empty() {
+            0
+        } else {
+            app.config_warnings.len() as u16 + 2
+        };
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Length(3),
                 Constraint::Min(10),
+                Constraint::Length(warning_height),
                 Constraint::Length(3),
             ])
-            .split(f.size());
+            .split(area);
 
         // Title
         let title = Paragraph::new("配置审查")
@@ -181,8 +570,13 @@ impl TuiManager {
             .alignment(ratatui::layout::Alignment::Center);
         f.render_widget(title, chunks[0]);
 
+        if let Some(editor) = &app.config_editor {
+            Self::draw_config_editor(f, app, editor, chunks[1], chunks[3]);
+            return;
+        }
+
         // Configuration table
-        let config_rows = vec![
+        let mut config_rows = vec![
             Row::new(vec![
                 Cell::from("源仓库"),
                 Cell::from(app.config.source_repo.to_string_lossy()),
@@ -197,13 +591,54 @@ impl TuiManager {
             ]),
             Row::new(vec![
                 Cell::from("起始 Commit"),
-                Cell::from(app.config.start_commit.clone()),
+                Cell::from(format_resolved(&app.config.start_commit, app.resolved_start.as_deref())),
             ]),
             Row::new(vec![
                 Cell::from("结束 Commit"),
-                Cell::from(app.config.end_commit.clone().unwrap_or_else(|| "HEAD".to_string())),
+                Cell::from(format_resolved(
+                    &app.config.end_rev(),
+                    app.resolved_end.as_deref(),
+                )),
             ]),
         ];
+        if !app.config.target_dir.is_empty() {
+            config_rows.insert(3, Row::new(vec![
+                Cell::from("目标目录"),
+                Cell::from(app.config.target_dir.clone()),
+            ]));
+        }
+        if let Some(created) = &app.subdir_created {
+            let row = if app.starts_before_subdir() {
+                Row::new(vec![
+                    Cell::from("子目录创建于"),
+                    Cell::from(format!("{} (起始 commit 中不存在该子目录，按 r 从此处开始)", created)),
+                ]).style(Style::default().fg(Color::Yellow))
+            } else {
+                Row::new(vec![Cell::from("子目录创建于"), Cell::from(created.clone())])
+            };
+            config_rows.push(row);
+        }
+        if let Some(base) = &app.target_base {
+            config_rows.push(Row::new(vec![
+                Cell::from("新分支基点"),
+                Cell::from(base.clone()),
+            ]));
+        }
+        if let Some(upstream) = &app.target_upstream {
+            let counts = format!("{} (领先 {}, 落后 {})", upstream.name, upstream.ahead, upstream.behind);
+            let row = match (upstream.ahead, upstream.behind) {
+                (_, 0) => Row::new(vec![Cell::from("上游分支"), Cell::from(counts)]),
+                (0, _) => Row::new(vec![
+                    Cell::from("上游分支"),
+                    Cell::from(format!("{}，目标分支已过时，建议先拉取上游更新", counts)),
+                ]).style(Style::default().fg(Color::Yellow)),
+                _ => Row::new(vec![
+                    Cell::from("上游分支"),
+                    Cell::from(format!("{}，目标分支与上游已分叉，推送前需要合并或变基", counts)),
+                ]).style(Style::default().fg(Color::Yellow)),
+            };
+            config_rows.push(row);
+        }
 
         let table = Table::new(config_rows)
             .widths(&[Constraint::Length(15), Constraint::Percentage(80)])
@@ -211,14 +646,60 @@ impl TuiManager {
             .style(Style::default().fg(Color::White));
         f.render_widget(table, chunks[1]);
 
+        // Warnings
+        if !app.config_warnings.is_empty() {
+            let warnings = Paragraph::new(app.config_warnings.join("\n"))
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title("警告"))
+                .wrap(Wrap { trim: true });
+            f.render_widget(warnings, chunks[2]);
+        }
+
         // Instructions
-        let instructions = Paragraph::new("按 Enter 继续 | 按 q 退出")
+        let instructions = if app.starts_before_subdir() {
+            "按 Enter 继续 | 按 r 从子目录创建处开始 | 按 e 编辑配置 | 按 q 退出"
+        } else {
+            "按 Enter 继续 | 按 e 编辑配置 | 按 q 退出"
+        };
+        let instructions = Paragraph::new(instructions)
             .style(Style::default().fg(Color::Gray))
             .alignment(ratatui::layout::Alignment::Center);
-        f.render_widget(instructions, chunks[2]);
+        f.render_widget(instructions, chunks[3]);
     }
 
-    fn draw_file_selection(f: &mut Frame, app: &App) {
+    fn draw_config_editor(f: &mut Frame, app: &App, editor: &ConfigEditor, area: Rect, footer: Rect) {
+        let rows: Vec<Row> = ConfigField::ALL.iter().enumerate().map(|(i, field)| {
+            let selected = i == editor.selected;
+            let value = match &editor.input {
+                Some(input) if selected => format!("{}▏", input),
+                _ => field.value(&app.config),
+            };
+            let style = if selected {
+                Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            Row::new(vec![Cell::from(field.label()), Cell::from(value)]).style(style)
+        }).collect();
+
+        let table = Table::new(rows)
+            .widths(&[Constraint::Length(16), Constraint::Percentage(80)])
+            .block(Block::default().borders(Borders::ALL).title("编辑配置"))
+            .style(Style::default().fg(Color::White));
+        f.render_widget(table, area);
+
+        let (text, color) = match (&editor.error, &editor.input) {
+            (Some(error), _) => (format!("无效: {}", error), Color::Red),
+            (None, Some(_)) => ("输入新值, Enter 确认 | Esc 取消".to_string(), Color::Gray),
+            (None, None) => ("↑/↓ 选择 | Enter 编辑 | Space 切换开关 | Esc 完成编辑".to_string(), Color::Gray),
+        };
+        let instructions = Paragraph::new(text)
+            .style(Style::default().fg(color))
+            .alignment(ratatui::layout::Alignment::Center);
+        f.render_widget(instructions, footer);
+    }
+
+    fn draw_file_selection(f: &mut Frame, app: &App, area: Rect) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
@@ -226,74 +707,326 @@ impl TuiManager {
                 Constraint::Min(10),
                 Constraint::Length(3),
             ])
-            .split(f.size());
+            .split(area);
+
+        if let Some(load) = &app.commit_load {
+            Self::draw_commit_load(f, load, &chunks);
+            return;
+        }
 
         // Header
-        let header_text = format!(
-            "待同步提交列表 (总计: {}, 已选择: {})",
-            app.commits.len(),
-            app.get_selected_count()
-        );
+        let header_text = if app.total_pending > app.commits.len() {
+            format!(
+                "待同步提交列表 (显示前 {} 个, 共 {} 个, 已选择: {})",
+                app.commits.len(),
+                app.total_pending,
+                app.get_selected_count()
+            )
+        } else {
+            format!(
+                "待同步提交列表 (总计: {}, 已选择: {})",
+                app.commits.len(),
+                app.get_selected_count()
+            )
+        };
         let header = Paragraph::new(header_text)
             .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
             .block(Block::default().borders(Borders::ALL));
         f.render_widget(header, chunks[0]);
 
-        // Commit Table
+        // Commit Table: the hash leads the subject, the other columns follow it
+        let leading: Vec<Column> = app.ui_state.visible_columns()
+            .filter(|layout| layout.column == Column::Hash)
+            .map(|layout| layout.column)
+            .collect();
+        let trailing: Vec<Column> = app.ui_state.visible_columns()
+            .filter(|layout| layout.column != Column::Hash)
+            .map(|layout| layout.column)
+            .collect();
+
         let rows: Vec<Row> = app.commits.iter().enumerate().map(|(i, commit)| {
             let selected_symbol = if app.selected_commits[i] { "✓" } else { " " };
             let style = if Some(i) == app.list_state.selected() {
                 Style::default().bg(Color::DarkGray).fg(Color::White)
+            } else if commit.whitespace_only || commit.revert_pair.is_some() || commit.unowned {
+                Style::default().fg(Color::DarkGray)
+            } else if commit.is_octopus() {
+                Style::default().fg(Color::Magenta)
             } else if commit.is_merge {
                 Style::default().fg(Color::Blue)
             } else {
                 Style::default().fg(Color::White)
             };
 
-            Row::new(vec![
-                Cell::from(selected_symbol),
-                Cell::from(commit.id[..7].to_string()),
-                Cell::from(commit.subject.clone()),
-                Cell::from(commit.author.clone()),
-                Cell::from(commit.date.clone()),
-            ]).style(style)
+            let subject = match app.edited_messages.get(&commit.id) {
+                Some(message) => format!("✎ {}", message.lines().next().unwrap_or("")),
+                None => commit.subject.clone(),
+            };
+            let subject = if commit.is_octopus() {
+                format!("[octopus {}] {}", commit.parents, subject)
+            } else if commit.revert_pair.is_some() {
+                format!("↺ {}", subject)
+            } else {
+                subject
+            };
+
+            let subject = match app.session.labels.get(&commit.id) {
+                Some(label) => Line::from(vec![
+                    Span::styled(format!("[{}] ", label.as_str()), Style::default().fg(label.color())),
+                    Span::raw(subject),
+                ]),
+                None => Line::from(subject),
+            };
+
+            let mut cells = vec![Cell::from(selected_symbol)];
+            cells.extend(leading.iter().map(|column| Self::commit_cell(app, commit, *column)));
+            cells.push(Cell::from(subject));
+            cells.extend(trailing.iter().map(|column| Self::commit_cell(app, commit, *column)));
+            Row::new(cells).style(style)
         }).collect();
 
+        let mut titles = vec![" "];
+        titles.extend(leading.iter().map(|column| column.title()));
+        titles.push("Subject");
+        titles.extend(trailing.iter().map(|column| column.title()));
+
+        // The patch preview takes the lower part of the screen, the details pane
+        // the right of what is left when the terminal is wide enough
+        let (upper_area, preview_area) = match &app.patch_preview {
+            Some(_) => {
+                let panes = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
+                    .split(chunks[1]);
+                (panes[0], Some(panes[1]))
+            }
+            None => (chunks[1], None),
+        };
+        let (table_area, details_area) =
+            if app.ui_state.layout == SelectionLayout::Full && upper_area.width >= MIN_FULL_LAYOUT_WIDTH {
+                let panes = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([
+                        Constraint::Percentage(100 - DETAILS_PANE_PERCENT),
+                        Constraint::Percentage(DETAILS_PANE_PERCENT),
+                    ])
+                    .split(upper_area);
+                (panes[0], Some(panes[1]))
+            } else {
+                (upper_area, None)
+            };
+
+        let width = |column: &Column| {
+            let layout = app.ui_state.columns.iter().find(|layout| layout.column == *column);
+            layout.map_or(8, |layout| layout.width)
+        };
+        // The subject gets the width left inside the borders after the other
+        // columns and the spacing between them
+        let fixed: u16 = 2 + leading.iter().chain(&trailing).map(width).sum::<u16>();
+        let spacing = (leading.len() + trailing.len() + 1) as u16;
+        let subject_width = table_area.width.saturating_sub(2 + fixed + spacing).max(MIN_SUBJECT_WIDTH);
+        let mut widths = vec![Constraint::Length(2)];
+        widths.extend(leading.iter().map(|column| Constraint::Length(width(column))));
+        widths.push(Constraint::Length(subject_width));
+        widths.extend(trailing.iter().map(|column| Constraint::Length(width(column))));
+
         let table = Table::new(rows)
             .header(
-                Row::new(vec![" ", "Hash", "Subject", "Author", "Date"])
+                Row::new(titles)
                     .style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
             )
-            .widths(&[
-                Constraint::Length(2),
-                Constraint::Length(8),
-                Constraint::Percentage(50),
-                Constraint::Percentage(15),
-                Constraint::Percentage(25),
-            ])
+            .widths(&widths)
             .block(Block::default().borders(Borders::ALL).title("提交详情"))
             .highlight_style(Style::default().add_modifier(Modifier::BOLD));
         
-        f.render_widget(table, chunks[1]);
+        f.render_widget(table, table_area);
+        if let Some(area) = details_area {
+            Self::draw_commit_details(f, app, area);
+        }
+
+        let commit_list_help = commit_list_help(&app.keymap);
+        let instructions = match (&app.patch_preview, preview_area) {
+            (Some(preview), Some(area)) => {
+                Self::draw_patch_preview(f, preview, area);
+                "↑/↓: 滚动 | PgUp/PgDn: 翻页 | g/G: 开头/结尾 | p/q: 关闭预览"
+            }
+            _ => match app.column_editor {
+                Some(selected) => {
+                    Self::draw_column_editor(f, &app.ui_state, selected, chunks[1]);
+                    "↑/↓: 选择列 | Space: 显示/隐藏 | ←/→: 调整宽度 | Esc/c: 完成"
+                }
+                None => commit_list_help.as_str(),
+            },
+        };
 
         // Instructions
-        let instructions = Paragraph::new(
-            "↑/↓: 导航 | Space: 选择/取消 | a: 全选 | A: 取消全选 | Enter: 开始同步 | q: 退出"
-        )
-        .style(Style::default().fg(Color::Gray))
-        .wrap(Wrap { trim: true });
+        let instructions = Paragraph::new(instructions)
+            .style(Style::default().fg(Color::Gray))
+            .wrap(Wrap { trim: true });
+        f.render_widget(instructions, chunks[2]);
+    }
+
+    fn draw_commit_load(f: &mut Frame, load: &CommitLoad, chunks: &[Rect]) {
+        let header = Paragraph::new(load.describe())
+            .style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+            .block(Block::default().borders(Borders::ALL));
+        f.render_widget(header, chunks[0]);
+
+        let area = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(chunks[1]);
+        let gauge = Gauge::default()
+            .block(Block::default().borders(Borders::ALL).title("加载进度"))
+            .gauge_style(Style::default().fg(Color::Green).bg(Color::Gray))
+            .ratio(load.ratio().clamp(0.0, 1.0));
+        f.render_widget(gauge, area[0]);
+
+        let instructions = Paragraph::new("q/Esc: 取消加载并返回配置审查 | Ctrl+C: 退出")
+            .style(Style::default().fg(Color::Gray))
+            .wrap(Wrap { trim: true });
         f.render_widget(instructions, chunks[2]);
     }
 
-    fn draw_progress(f: &mut Frame, app: &App) {
+    fn commit_cell<'a>(app: &App, commit: &CommitInfo, column: Column) -> Cell<'a> {
+        // Statistics not computed yet show as an ellipsis
+        let stat = |value: fn(&DiffStats) -> usize| commit.stats.map_or("…".to_string(), |stats| value(&stats).to_string());
+        match column {
+            Column::Hash => Cell::from(short_id(&commit.id).to_string()),
+            Column::Author if app.config.authors.is_empty() => Cell::from(commit.author.clone()),
+            Column::Author => Cell::from(commit.author.clone()).style(Style::default().fg(Color::Green)),
+            Column::Date => Cell::from(commit.date.clone()),
+            Column::Changes => match commit.stats {
+                Some(stats) => Cell::from(Line::from(vec![
+                    Span::styled(format!("+{}", compact_count(stats.insertions)), Style::default().fg(Color::Green)),
+                    Span::raw(" "),
+                    Span::styled(format!("−{}", compact_count(stats.deletions)), Style::default().fg(Color::Red)),
+                ])),
+                None => Cell::from("…"),
+            },
+            Column::Files => Cell::from(stat(|stats| stats.files)),
+            Column::Insertions => Cell::from(stat(|stats| stats.insertions)).style(Style::default().fg(Color::Green)),
+            Column::Deletions => Cell::from(stat(|stats| stats.deletions)).style(Style::default().fg(Color::Red)),
+        }
+    }
+
+    /// Metadata, message and changed files of the highlighted commit
+    fn draw_commit_details(f: &mut Frame, app: &App, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title("提交信息");
+        let Some(commit) = app.highlighted_commit() else {
+            f.render_widget(block, area);
+            return;
+        };
+
+        let label_style = Style::default().fg(Color::Yellow);
+        let field = |name: &'static str, value: String| {
+            Line::from(vec![Span::styled(format!("{}: ", name), label_style), Span::raw(value)])
+        };
+        let mut lines = vec![
+            field("提交 ID", commit.id.clone()),
+            field("作者", commit.author.clone()),
+            field("日期", commit.date.clone()),
+        ];
+        if commit.is_merge {
+            lines.push(field("父提交数", commit.parents.to_string()));
+        }
+        if let Some(from) = &commit.renamed_from {
+            lines.push(field("目录移动", format!("{} → {}", from, commit.subdir)));
+        }
+        if let Some(pair) = &commit.revert_pair {
+            lines.push(field("撤销对", short_id(pair).to_string()));
+        }
+        if let (true, Some(owner)) = (commit.unowned, &app.config.codeowners_filter) {
+            lines.push(field("CODEOWNERS", format!("未改动 {} 拥有的文件", owner)));
+        }
+        if let Some(label) = app.session.labels.get(&commit.id) {
+            lines.push(Line::from(vec![
+                Span::styled("标签: ", label_style),
+                Span::styled(label.as_str(), Style::default().fg(label.color())),
+            ]));
+        }
+        lines.push(Line::from(""));
+
+        let details = app.commit_details.peek(&commit.id);
+        let message = match (app.edited_messages.get(&commit.id), details) {
+            (Some(edited), _) => edited.as_str(),
+            (None, Some(details)) => details.message.trim_end(),
+            (None, None) => commit.subject.as_str(),
+        };
+        lines.extend(message.lines().map(|line| Line::from(line.to_string())));
+        lines.push(Line::from(""));
+
+        match details {
+            Some(details) => {
+                lines.push(Line::from(Span::styled(format!("变更文件 ({})", details.files.len()), label_style)));
+                lines.extend(details.files.iter().map(|file| {
+                    Line::from(vec![
+                        Span::styled(format!("{} ", file.status), Style::default().fg(Color::Cyan)),
+                        Span::raw(file.path.clone()),
+                        Span::styled(format!(" +{}", compact_count(file.insertions)), Style::default().fg(Color::Green)),
+                        Span::styled(format!(" −{}", compact_count(file.deletions)), Style::default().fg(Color::Red)),
+                    ])
+                }));
+            }
+            None => lines.push(Line::from(Span::styled("正在加载变更文件…", Style::default().fg(Color::DarkGray)))),
+        }
+
+        let pane = Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false });
+        f.render_widget(pane, area);
+    }
+
+    fn draw_column_editor(f: &mut Frame, ui_state: &UiState, selected: usize, area: Rect) {
+        let popup_area = centered_rect(50, 60, area);
+        f.render_widget(Clear, popup_area);
+
+        let rows: Vec<Row> = ui_state.columns.iter().enumerate().map(|(i, layout)| {
+            let style = if i == selected {
+                Style::default().bg(Color::DarkGray).fg(Color::White)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            Row::new(vec![
+                Cell::from(if layout.visible { "✓" } else { " " }),
+                Cell::from(layout.column.label()),
+                Cell::from(format!("宽度 {}", layout.width)),
+            ]).style(style)
+        }).collect();
+
+        let table = Table::new(rows)
+            .widths(&[Constraint::Length(2), Constraint::Min(8), Constraint::Length(8)])
+            .block(Block::default().borders(Borders::ALL).title("列设置"));
+        f.render_widget(table, popup_area);
+    }
+
+    fn draw_patch_preview(f: &mut Frame, preview: &PatchPreview, area: Rect) {
+        // Only the visible window is handed to the widget
+        let height = area.height.saturating_sub(2) as usize;
+        let start = preview.scroll.min(preview.lines.len());
+        let end = (start + height).min(preview.lines.len());
+        let lines = preview.lines[start..end].to_vec();
+        let title = format!(
+            "补丁预览 {} ({}/{})",
+            short_id(&preview.commit_id),
+            (preview.scroll + 1).min(preview.lines.len()),
+            preview.lines.len()
+        );
+        let pager = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(pager, area);
+    }
+
+    fn draw_progress(f: &mut Frame, app: &App, area: Rect) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
+                Constraint::Length(3),
                 Constraint::Length(3),
                 Constraint::Length(3),
                 Constraint::Min(5),
             ])
-            .split(f.size());
+            .split(area);
 
         // Title
         let title = Paragraph::new("同步进度")
@@ -302,12 +1035,34 @@ impl TuiManager {
             .alignment(ratatui::layout::Alignment::Center);
         f.render_widget(title, chunks[0]);
 
-        // Progress bar
-        let gauge = Gauge::default()
-            .block(Block::default().borders(Borders::ALL).title("进度"))
-            .gauge_style(Style::default().fg(Color::Green).bg(Color::Gray))
-            .percent((app.progress * 100.0) as u16);
-        f.render_widget(gauge, chunks[1]);
+        // Progress bar, one column per target with --target
+        if app.fanout.is_empty() {
+            let gauge = Gauge::default()
+                .block(Block::default().borders(Borders::ALL).title("进度"))
+                .gauge_style(Style::default().fg(Color::Green).bg(Color::Gray))
+                .percent((app.progress * 100.0) as u16);
+            f.render_widget(gauge, chunks[1]);
+        } else {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Ratio(1, app.fanout.len() as u32 + 1); app.fanout.len() + 1])
+                .split(chunks[1]);
+            let targets = std::iter::once((&app.config.target_repo, app.progress, None))
+                .chain(app.config.extra_targets.iter().zip(&app.fanout).map(|(path, target)| {
+                    (path, target.progress, target.failed.then_some(target.status.as_str()))
+                }));
+            for ((path, progress, failure), column) in targets.zip(columns.iter()) {
+                let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned());
+                let color = if failure.is_some() { Color::Red } else { Color::Green };
+                let label = failure.map_or_else(|| format!("{}%", (progress * 100.0) as u16), |status| format!("失败: {}", status));
+                let gauge = Gauge::default()
+                    .block(Block::default().borders(Borders::ALL).title(name))
+                    .gauge_style(Style::default().fg(color).bg(Color::Gray))
+                    .percent((progress * 100.0) as u16)
+                    .label(label);
+                f.render_widget(gauge, *column);
+            }
+        }
 
         // Status message
         let status = Paragraph::new(app.status_message.clone())
@@ -315,13 +1070,105 @@ impl TuiManager {
             .block(Block::default().borders(Borders::ALL).title("当前操作"))
             .wrap(Wrap { trim: true });
         f.render_widget(status, chunks[2]);
+
+        // Recent events, newest at the bottom
+        let visible = chunks[3].height.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = app.sync_log
+            .iter()
+            .skip(app.sync_log.len().saturating_sub(visible))
+            .map(|entry| {
+                let color = match entry.status.as_str() {
+                    "OK" => Color::Green,
+                    "ERROR" => Color::Red,
+                    "WARN" => Color::Yellow,
+                    status if status.starts_with("EMPTY") => Color::Yellow,
+                    status if status.starts_with("CONFLICT") => Color::Red,
+                    _ => Color::White,
+                };
+                ListItem::new(format!("{} [{}] {}", entry.time, entry.status, entry.message))
+                    .style(Style::default().fg(color))
+            })
+            .collect();
+        let log = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("日志"));
+        f.render_widget(log, chunks[3]);
     }
 
-    fn draw_confirmation(f: &mut Frame, app: &App) {
+    fn draw_conflict_queue(f: &mut Frame, app: &App, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(5),
+                Constraint::Length(12),
+                Constraint::Length(3),
+                Constraint::Length(2),
+            ])
+            .split(area);
+
+        let conflicts = app.sync_report.as_ref().map(|r| r.conflicts.as_slice()).unwrap_or_default();
+        let title = Paragraph::new(format!("冲突队列 ({} 个提交未同步)", conflicts.len()))
+            .style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))
+            .block(Block::default().borders(Borders::ALL))
+            .alignment(ratatui::layout::Alignment::Center);
+        f.render_widget(title, chunks[0]);
+
+        let items: Vec<ListItem> = conflicts
+            .iter()
+            .map(|conflict| {
+                let retrying = app.conflict_retry.as_deref() == Some(conflict.id.as_str());
+                let mark = if retrying { "…" } else { " " };
+                ListItem::new(format!("{} {} {}", mark, short_id(&conflict.id), conflict.subject))
+            })
+            .collect();
+        let mut list_state = ListState::default();
+        list_state.select((!conflicts.is_empty()).then(|| app.conflict_selected.min(conflicts.len() - 1)));
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("冲突的提交"))
+            .highlight_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD))
+            .highlight_symbol("> ");
+        f.render_stateful_widget(list, chunks[1], &mut list_state);
+
+        // Why the highlighted commit did not apply and where its state was kept
+        let details = list_state.selected().map(|i| &conflicts[i]).map(|conflict| {
+            let mut text = conflict.error.clone();
+            if let Some(dir) = &conflict.artifacts {
+                text.push_str(&format!("\n\n补丁与冲突现场: {}", dir.display()));
+            }
+            text
+        });
+        let details = Paragraph::new(details.unwrap_or_default())
+            .style(Style::default().fg(Color::Yellow))
+            .block(Block::default().borders(Borders::ALL).title("冲突详情"))
+            .wrap(Wrap { trim: false });
+        f.render_widget(details, chunks[2]);
+
+        // Outcome of the latest retry
+        let status = match (&app.conflict_retry, app.sync_log.back()) {
+            (Some(id), _) => format!("正在重试 {}...", short_id(id)),
+            (None, Some(entry)) if app.sync_log.iter().any(|e| e.status == "RETRY") => {
+                format!("[{}] {}", entry.status, entry.message)
+            }
+            _ => "修复目标仓库后按 r 重试所选提交".to_string(),
+        };
+        let status = Paragraph::new(status)
+            .style(Style::default().fg(Color::White))
+            .block(Block::default().borders(Borders::ALL).title("状态"));
+        f.render_widget(status, chunks[3]);
+
+        let instructions = Paragraph::new(
+            "↑/↓: 选择 | r: 重试所选提交 | Ctrl+Z: 挂起以在目标仓库中处理 | Enter: 完成 | q: 退出",
+        )
+        .style(Style::default().fg(Color::Gray))
+        .wrap(Wrap { trim: true });
+        f.render_widget(instructions, chunks[4]);
+    }
+
+    fn draw_confirmation(f: &mut Frame, app: &App, area: Rect) {
         // Darken the background
-        f.render_widget(Clear, f.size());
+        f.render_widget(Clear, area);
 
-        let popup_area = centered_rect(60, 20, f.size());
+        let popup_area = centered_rect(60, 20, area);
 
         let chunks = Layout::default()
             .direction(Direction::Vertical)
@@ -362,7 +1209,7 @@ impl TuiManager {
         f.render_widget(instructions, chunks[2]);
     }
 
-    fn draw_completed(f: &mut Frame, app: &App) {
+    fn draw_completed(f: &mut Frame, app: &App, area: Rect) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
@@ -370,11 +1217,13 @@ impl TuiManager {
                 Constraint::Min(10),
                 Constraint::Length(3),
             ])
-            .split(f.size());
+            .split(area);
 
         // Title
-        let title = Paragraph::new("同步完成!")
-            .style(Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))
+        let aborted = app.sync_report.as_ref().is_some_and(|r| r.aborted);
+        let (title, color) = if aborted { ("同步已中断", Color::Yellow) } else { ("同步完成!", Color::Green) };
+        let title = Paragraph::new(title)
+            .style(Style::default().fg(color).add_modifier(Modifier::BOLD))
             .block(Block::default().borders(Borders::ALL))
             .alignment(ratatui::layout::Alignment::Center);
         f.render_widget(title, chunks[0]);
@@ -386,17 +1235,108 @@ impl TuiManager {
             app.start_time.elapsed()
         };
         
+        // Pre-run warnings and the issues met during the run
+        let warning_lines: Vec<String> = app
+            .sync_report
+            .as_ref()
+            .map(|r| r.warnings.iter().cloned().chain(r.run_warnings.iter().map(|w| w.to_string())).collect())
+            .unwrap_or_default();
         let summary_text = format!(
-            "同步完成!\n\n状态消息: {}\n\n用时: {:.2} 秒\n\n按 Enter 退出",
+            "{}\n\n状态消息: {}\n\n用时: {:.2} 秒\n\n按 Enter 退出{}",
+            if aborted { "同步已中断" } else { "同步完成!" },
             app.status_message,
-            elapsed.as_secs_f32()
+            elapsed.as_secs_f32(),
+            if warning_lines.is_empty() { "" } else { " | w: 展开/收起警告" }
         );
 
+        // Copies (`--engine copy`) produce no per-commit timings or patches
+        let has_timings = app
+            .sync_report
+            .as_ref()
+            .is_some_and(|r| r.commits.iter().any(|c| c.duration_ms > 0 || c.patch_bytes > 0));
+        let flagged = app.sync_report.as_ref().map(|r| r.flagged_commits.as_slice()).unwrap_or_default();
+        let flagged_height = flagged
+            .iter()
+            .map(|c| c.findings.len() + 1)
+            .sum::<usize>()
+            .min(10) as u16;
+        let body = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(9),
+                Constraint::Length(match warning_lines.len() {
+                    0 => 0,
+                    _ if !app.show_warnings => 3,
+                    count => count.min(10) as u16 + 2,
+                }),
+                Constraint::Length(if flagged.is_empty() { 0 } else { flagged_height + 2 }),
+                Constraint::Length(if has_timings { 8 } else { 0 }),
+            ])
+            .split(chunks[1]);
+
         let summary = Paragraph::new(summary_text)
             .style(Style::default().fg(Color::White))
             .block(Block::default().borders(Borders::ALL).title("完成"))
             .wrap(Wrap { trim: true });
-        f.render_widget(summary, chunks[1]);
+        f.render_widget(summary, body[0]);
+
+        if !warning_lines.is_empty() {
+            let text = if app.show_warnings {
+                warning_lines.join("\n")
+            } else {
+                format!("{} 条警告, 按 w 展开", warning_lines.len())
+            };
+            let warnings = Paragraph::new(text)
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title(format!("警告 ({})", warning_lines.len())))
+                .wrap(Wrap { trim: false });
+            f.render_widget(warnings, body[1]);
+        }
+
+        // Secret scanner warnings
+        if !flagged.is_empty() {
+            let mut lines = Vec::new();
+            for commit in flagged {
+                lines.push(format!("{} {}", short_id(&commit.id), commit.subject));
+                lines.extend(commit.findings.iter().map(|finding| format!("    {}", finding)));
+            }
+            let warnings = Paragraph::new(lines.join("\n"))
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title(format!("疑似密钥 ({} 个提交)", flagged.len())));
+            f.render_widget(warnings, body[2]);
+        }
+
+        // Slowest / largest commits
+        if let Some(report) = app.sync_report.as_ref().filter(|_| has_timings) {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(body[3]);
+
+            let slowest: Vec<Row> = report.slowest(5).into_iter().map(|t| {
+                Row::new(vec![
+                    Cell::from(short_id(&t.id).to_string()),
+                    Cell::from(format!("{} ms", t.duration_ms)),
+                    Cell::from(t.subject.clone()),
+                ])
+            }).collect();
+            let slowest_table = Table::new(slowest)
+                .widths(&[Constraint::Length(8), Constraint::Length(10), Constraint::Min(10)])
+                .block(Block::default().borders(Borders::ALL).title("最慢的提交"));
+            f.render_widget(slowest_table, columns[0]);
+
+            let largest: Vec<Row> = report.largest(5).into_iter().map(|t| {
+                Row::new(vec![
+                    Cell::from(short_id(&t.id).to_string()),
+                    Cell::from(format!("{} B", t.patch_bytes)),
+                    Cell::from(t.subject.clone()),
+                ])
+            }).collect();
+            let largest_table = Table::new(largest)
+                .widths(&[Constraint::Length(8), Constraint::Length(10), Constraint::Min(10)])
+                .block(Block::default().borders(Borders::ALL).title("最大的补丁"));
+            f.render_widget(largest_table, columns[1]);
+        }
 
         // Instructions
         let instructions = Paragraph::new("按 Enter 退出")
@@ -405,12 +1345,51 @@ impl TuiManager {
         f.render_widget(instructions, chunks[2]);
     }
 
-    pub fn show_confirmation(&mut self, message: &str) -> Result<bool> {
-        let popup_area = centered_rect(60, 20, self.terminal.size()?);
+    /// Leaves the alternate screen, opens `$VISUAL`/`$EDITOR` on `initial` and
+    /// restores the TUI afterwards. Lines starting with `#` are dropped.
+    pub fn edit_text(&mut self, initial: &str) -> Result<String> {
+        let mut file = tempfile::Builder::new()
+            .prefix("sync-subdir-msg-")
+            .suffix(".txt")
+            .tempfile()?;
+        file.write_all(initial.as_bytes())?;
+        file.write_all("\n# 编辑提交信息，以 # 开头的行会被忽略\n".as_bytes())?;
+        file.flush()?;
+
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+
+        self.leave()?;
+
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(file.path())
+            .status();
 
+        self.enter()?;
+
+        if !status?.success() {
+            anyhow::bail!("Editor {} exited with an error", editor);
+        }
+
+        let content = std::fs::read_to_string(file.path())?;
+        let message: Vec<&str> = content.lines().filter(|line| !line.starts_with('#')).collect();
+        Ok(message.join("\n").trim().to_string())
+    }
+
+    pub fn show_confirmation(&mut self, message: &str) -> Result<bool> {
         loop {
-            self.terminal.draw(|f| {
+            // Recomputed every frame so the popup follows terminal resizes
+            let popup_area = centered_rect(60, 20, self.terminal.size()?);
+            let status_bar = self.status_bar.clone();
+            self.draw_with(|f| {
                 f.render_widget(Clear, f.size());
+                if let Some(line) = status_bar {
+                    f.render_widget(Paragraph::new(line), split_status_bar(f.size()).1);
+                }
 
                 let chunks = Layout::default()
                     .direction(Direction::Vertical)
@@ -454,6 +1433,34 @@ impl TuiManager {
         }
     }
 
+    /// Hands the terminal back to the shell (Ctrl+Z / SIGTSTP) and stops the
+    /// process; the TUI is redrawn from scratch once the shell resumes it
+    pub fn suspend(&mut self) -> Result<()> {
+        self.leave()?;
+        self.terminal.show_cursor()?;
+        signals::stop_process();
+        self.enter()
+    }
+
+    /// Adopts the new terminal size and forces a full redraw, so layouts are
+    /// recomputed instead of leaving clipped remains of the old frame
+    pub fn resize(&mut self, width: u16, height: u16) -> Result<()> {
+        self.terminal.resize(Rect::new(0, 0, width, height))?;
+        Ok(())
+    }
+
+    fn leave(&mut self) -> Result<()> {
+        disable_raw_mode()?;
+        execute!(self.terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
+        Ok(())
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        enable_raw_mode()?;
+        execute!(self.terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
+        self.terminal.clear()?;
+        Ok(())
+    }
 }
 
 impl Drop for TuiManager {
@@ -468,6 +1475,95 @@ impl Drop for TuiManager {
     }
 }
 
+/// Line count shortened to a few characters: `999`, `1.2k`, `12k`, `1.2M`
+/// Help line of the commit list, naming the keys currently bound
+fn commit_list_help(keymap: &Keymap) -> String {
+    let entries: [(&[Action], &str); 14] = [
+        (&[Action::Up, Action::Down], "导航"),
+        (&[Action::HalfPageDown, Action::HalfPageUp], "翻半页"),
+        (&[Action::Toggle], "选择/取消"),
+        (&[Action::SelectAll], "全选"),
+        (&[Action::DeselectAll], "取消全选"),
+        (&[Action::Reverts], "选择/取消撤销对"),
+        (&[Action::EditMessage], "编辑提交信息"),
+        (&[Action::CycleLabel], "切换标签"),
+        (&[Action::PreviewPatch], "预览补丁"),
+        (&[Action::ToggleLayout], "切换布局"),
+        (&[Action::Columns], "列设置"),
+        (&[Action::Start], "开始同步"),
+        (&[Action::Top, Action::Bottom], "开头/结尾"),
+        (&[Action::Quit], "退出"),
+    ];
+    entries
+        .iter()
+        .map(|(actions, label)| {
+            let keys: Vec<String> = actions.iter().map(|action| keymap.describe(*action)).collect();
+            format!("{}: {}", keys.join("/"), label)
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn compact_count(count: usize) -> String {
+    match count {
+        0..=999 => count.to_string(),
+        1_000..=9_999 => format!("{}.{}k", count / 1_000, count % 1_000 / 100),
+        10_000..=999_999 => format!("{}k", count / 1_000),
+        1_000_000..=9_999_999 => format!("{}.{}M", count / 1_000_000, count % 1_000_000 / 100_000),
+        _ => format!("{}M", count / 1_000_000),
+    }
+}
+
+/// Formats a user supplied ref together with the commit it resolved to
+fn format_resolved(spec: &str, resolved: Option<&str>) -> String {
+    match resolved {
+        Some(id) if id.starts_with(spec) => id.to_string(),
+        Some(id) => format!("{} → {}", spec, id),
+        None => spec.to_string(),
+    }
+}
+
+/// Splits off the bottom line of `area` for the status bar
+fn split_status_bar(area: Rect) -> (Rect, Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(area);
+    (chunks[0], chunks[1])
+}
+
+/// Repositories, branches, subdirectory and mode of the run, shown on every
+/// screen so a sync is never started against the wrong branch
+fn status_bar(app: &App) -> Line<'static> {
+    let repo_name = |path: &std::path::Path| {
+        path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned())
+    };
+    let source_branch = app.config.source_branch.clone().unwrap_or_else(|| app.source_head.clone());
+    let mut subdir = app.config.subdir.clone();
+    if !app.config.target_dir.is_empty() {
+        subdir = format!("{} → {}", subdir, app.config.target_dir);
+    }
+
+    let dim = Style::default().fg(Color::DarkGray);
+    let mut spans = vec![
+        Span::styled(format!(" {}@{}", repo_name(&app.config.source_repo), source_branch), Style::default().fg(Color::Cyan)),
+        Span::styled(" → ", dim),
+        Span::styled(
+            format!("{}@{}", repo_name(&app.config.target_repo), app.config.get_default_target_branch()),
+            Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(" | 子目录 ", dim),
+        Span::raw(subdir),
+        Span::styled(" | 引擎 ", dim),
+        Span::raw(app.config.engine.as_str()),
+    ];
+    if app.config.dry_run {
+        spans.push(Span::styled(" | ", dim));
+        spans.push(Span::styled("预览模式", Style::default().fg(Color::Black).bg(Color::Yellow)));
+    }
+    Line::from(spans)
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -486,4 +1582,4 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
             Constraint::Percentage((100 - percent_x) / 2),
         ])
         .split(popup_layout[1])[1]
-}
\ No newline at end of file
+}
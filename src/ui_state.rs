@@ -0,0 +1,202 @@
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Narrowest and widest a commit table column can be made
+const MIN_COLUMN_WIDTH: u16 = 3;
+const MAX_COLUMN_WIDTH: u16 = 60;
+
+/// Optional column of the commit table. The selection mark and the subject are
+/// always shown; the subject takes whatever width the other columns leave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Hash,
+    Author,
+    Date,
+    /// Lines added and removed, e.g. `+123 −45`
+    Changes,
+    Files,
+    Insertions,
+    Deletions,
+}
+
+impl Column {
+    pub const ALL: [Column; 7] = [
+        Column::Hash,
+        Column::Author,
+        Column::Date,
+        Column::Changes,
+        Column::Files,
+        Column::Insertions,
+        Column::Deletions,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Column::Hash => "Hash",
+            Column::Author => "Author",
+            Column::Date => "Date",
+            Column::Changes => "Changes",
+            Column::Files => "Files",
+            Column::Insertions => "+",
+            Column::Deletions => "-",
+        }
+    }
+
+    /// Name shown in the column settings
+    pub fn label(self) -> &'static str {
+        match self {
+            Column::Hash => "提交 ID",
+            Column::Author => "作者",
+            Column::Date => "日期",
+            Column::Changes => "增删行",
+            Column::Files => "文件数",
+            Column::Insertions => "新增行",
+            Column::Deletions => "删除行",
+        }
+    }
+
+    /// Whether the column needs the commit's diff statistics
+    pub fn needs_stats(self) -> bool {
+        matches!(self, Column::Changes | Column::Files | Column::Insertions | Column::Deletions)
+    }
+
+    fn default_layout(self) -> ColumnLayout {
+        let (visible, width) = match self {
+            Column::Hash => (true, 8),
+            Column::Author => (true, 16),
+            Column::Date => (true, 19),
+            Column::Changes => (true, 11),
+            Column::Files => (false, 5),
+            Column::Insertions => (false, 6),
+            Column::Deletions => (false, 6),
+        };
+        ColumnLayout { column: self, visible, width }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColumnLayout {
+    pub column: Column,
+    pub visible: bool,
+    pub width: u16,
+}
+
+/// Arrangement of the selection screen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectionLayout {
+    /// Commit table only
+    Compact,
+    /// Commit table on the left, highlighted commit's details on the right
+    #[default]
+    Full,
+}
+
+impl SelectionLayout {
+    pub fn toggled(self) -> Self {
+        match self {
+            SelectionLayout::Compact => SelectionLayout::Full,
+            SelectionLayout::Full => SelectionLayout::Compact,
+        }
+    }
+}
+
+/// Interface settings kept between runs in `$XDG_STATE_HOME/sync-subdir/ui.toml`
+/// (`~/.local/state/sync-subdir/ui.toml` by default)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    /// Optional columns of the commit table, in display order
+    pub columns: Vec<ColumnLayout>,
+    pub layout: SelectionLayout,
+}
+
+impl Default for UiState {
+    fn default() -> Self {
+        Self {
+            columns: Column::ALL.iter().map(|column| column.default_layout()).collect(),
+            layout: SelectionLayout::default(),
+        }
+    }
+}
+
+impl UiState {
+    pub fn path() -> Option<PathBuf> {
+        Some(paths::user_dir("XDG_STATE_HOME", ".local/state")?.join("sync-subdir").join("ui.toml"))
+    }
+
+    /// Saved settings, or the defaults when there are none or they cannot be read
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else { return Self::default() };
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => return Self::default(),
+        };
+        match toml::from_str::<Self>(&content) {
+            Ok(mut state) => {
+                state.normalize();
+                state
+            }
+            Err(e) => {
+                warn!("Ignoring invalid UI state file {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("Neither XDG_STATE_HOME nor HOME is set"))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, toml::to_string(self)?)
+            .map_err(|e| anyhow::anyhow!("Failed to write UI state file {}: {}", path.display(), e))
+    }
+
+    /// Visible optional columns in display order
+    pub fn visible_columns(&self) -> impl Iterator<Item = &ColumnLayout> {
+        self.columns.iter().filter(|layout| layout.visible)
+    }
+
+    pub fn shows_stats(&self) -> bool {
+        self.visible_columns().any(|layout| layout.column.needs_stats())
+    }
+
+    pub fn toggle(&mut self, index: usize) {
+        if let Some(layout) = self.columns.get_mut(index) {
+            layout.visible = !layout.visible;
+        }
+    }
+
+    pub fn resize(&mut self, index: usize, delta: i16) {
+        if let Some(layout) = self.columns.get_mut(index) {
+            layout.width = layout
+                .width
+                .saturating_add_signed(delta)
+                .clamp(MIN_COLUMN_WIDTH, MAX_COLUMN_WIDTH);
+        }
+    }
+
+    /// Drops duplicate columns, appends columns missing from an older file and
+    /// clamps widths edited by hand
+    fn normalize(&mut self) {
+        let mut columns: Vec<ColumnLayout> = Vec::new();
+        for layout in &self.columns {
+            if !columns.iter().any(|c| c.column == layout.column) {
+                columns.push(*layout);
+            }
+        }
+        for column in Column::ALL {
+            if !columns.iter().any(|c| c.column == column) {
+                columns.push(column.default_layout());
+            }
+        }
+        for layout in &mut columns {
+            layout.width = layout.width.clamp(MIN_COLUMN_WIDTH, MAX_COLUMN_WIDTH);
+        }
+        self.columns = columns;
+    }
+}
@@ -0,0 +1,158 @@
+use chrono::Datelike;
+use regex::Regex;
+use std::path::Path;
+
+use crate::error::{Result, SyncError};
+
+/// Version files looked for in the target directory when `--version-file` is
+/// not given, first match wins
+const CANDIDATES: [&str; 3] = ["Cargo.toml", "package.json", "VERSION"];
+
+/// How `--bump-version` computes the next version
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BumpPolicy {
+    /// `1.2.3` becomes `1.2.4`
+    Patch,
+    /// `1.2.3` becomes `1.3.0`
+    Minor,
+    /// `<year>.<month><day>.<n>`, e.g. `2024.315.0`, counting up `n` for more
+    /// syncs on the same day; still a valid semver version
+    Date,
+}
+
+impl BumpPolicy {
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "patch" => Ok(Self::Patch),
+            "minor" => Ok(Self::Minor),
+            "date" => Ok(Self::Date),
+            other => Err(anyhow::anyhow!("Invalid version bump policy: {} (expected patch, minor or date)", other)),
+        }
+    }
+
+    /// Version following `current`
+    pub fn next(self, current: &str) -> Result<String> {
+        let parts = semver_parts(current);
+        // A `v1.2.3` style version keeps its prefix
+        let prefix = if current.trim().starts_with('v') { "v" } else { "" };
+        match self {
+            Self::Patch => {
+                let (major, minor, patch) = parts.ok_or_else(|| not_semver(current))?;
+                Ok(format!("{}{}.{}.{}", prefix, major, minor, patch + 1))
+            }
+            Self::Minor => {
+                let (major, minor, _) = parts.ok_or_else(|| not_semver(current))?;
+                Ok(format!("{}{}.{}.0", prefix, major, minor + 1))
+            }
+            Self::Date => {
+                let today = chrono::Local::now().date_naive();
+                let (year, day) = (today.year() as u64, (today.month() * 100 + today.day()) as u64);
+                let n = match parts {
+                    Some((major, minor, patch)) if major == year && minor == day => patch + 1,
+                    _ => 0,
+                };
+                Ok(format!("{}.{}.{}", year, day, n))
+            }
+        }
+    }
+}
+
+/// A version file of the target and the version it holds
+#[derive(Debug, Clone)]
+pub struct VersionFile {
+    /// Relative to the target repository root
+    pub path: String,
+    pub version: String,
+    content: String,
+}
+
+impl VersionFile {
+    /// Reads the version file of a checkout at `root`: `file` (relative to the
+    /// root) when given, otherwise the first of `Cargo.toml`, `package.json`
+    /// and `VERSION` in `target_dir`
+    pub fn locate(root: &Path, target_dir: &str, file: Option<&str>) -> Result<Self> {
+        let path = match file {
+            Some(file) => file.trim_start_matches('/').to_string(),
+            None => {
+                let in_dir = |name: &str| match target_dir.trim_matches('/') {
+                    "" | "." => name.to_string(),
+                    dir => format!("{}/{}", dir, name),
+                };
+                CANDIDATES
+                    .iter()
+                    .map(|name| in_dir(name))
+                    .find(|path| root.join(path).is_file())
+                    .ok_or_else(|| {
+                        SyncError::Anyhow(anyhow::anyhow!(
+                            "--bump-version: no Cargo.toml, package.json or VERSION in {} (see --version-file)",
+                            in_dir("")
+                        ))
+                    })?
+            }
+        };
+        let content = std::fs::read_to_string(root.join(&path)).map_err(|e| {
+            SyncError::Anyhow(anyhow::anyhow!("--bump-version: failed to read {}: {}", path, e))
+        })?;
+        let version = match version_span(&path, &content) {
+            Some((start, end)) => content[start..end].to_string(),
+            None => return Err(SyncError::Anyhow(anyhow::anyhow!("--bump-version: no version found in {}", path))),
+        };
+        Ok(Self { path, version, content })
+    }
+
+    /// The file's content with its version replaced by `version`; everything
+    /// else keeps its formatting
+    pub fn with_version(&self, version: &str) -> String {
+        let (start, end) = version_span(&self.path, &self.content).expect("checked by locate");
+        format!("{}{}{}", &self.content[..start], version, &self.content[end..])
+    }
+}
+
+/// Byte range of the version string in a version file: the `[package]`
+/// version of a `Cargo.toml`, the top-level `"version"` of a `package.json`
+/// and the whole trimmed content of anything else
+fn version_span(path: &str, content: &str) -> Option<(usize, usize)> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match name {
+        "Cargo.toml" => {
+            let mut offset = 0;
+            let mut in_package = false;
+            let version = Regex::new(r#"^\s*version\s*=\s*"([^"]*)""#).expect("valid regex");
+            for line in content.split_inclusive('\n') {
+                let trimmed = line.trim();
+                if trimmed.starts_with('[') {
+                    in_package = trimmed == "[package]";
+                } else if in_package {
+                    if let Some(m) = version.captures(line).and_then(|c| c.get(1)) {
+                        return Some((offset + m.start(), offset + m.end()));
+                    }
+                }
+                offset += line.len();
+            }
+            None
+        }
+        "package.json" => {
+            let version = Regex::new(r#""version"\s*:\s*"([^"]*)""#).expect("valid regex");
+            let m = version.captures(content)?.get(1)?;
+            Some((m.start(), m.end()))
+        }
+        _ => {
+            let start = content.len() - content.trim_start().len();
+            let end = content.trim_end().len();
+            (start < end).then_some((start, end))
+        }
+    }
+}
+
+/// Major, minor and patch of a `x.y.z` version, ignoring any `-pre` or `+build` suffix
+fn semver_parts(version: &str) -> Option<(u64, u64, u64)> {
+    let core = version.trim().trim_start_matches('v');
+    let core = core.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let parsed = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(parsed)
+}
+
+fn not_semver(version: &str) -> SyncError {
+    SyncError::Anyhow(anyhow::anyhow!("--bump-version: {} is not a major.minor.patch version", version))
+}
@@ -0,0 +1,294 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::cli::Config;
+use crate::error::{Result, SyncError};
+use crate::git::GitManager;
+
+/// Workspace member named by `--package`
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    /// Directory of the member, relative to the repository root
+    pub dir: String,
+    /// Directories of the workspace members and other in-repository packages it
+    /// depends on by path, directly or through each other
+    pub path_deps: Vec<String>,
+}
+
+/// A Cargo or npm workspace member as read from its manifest
+struct Member {
+    name: String,
+    dir: String,
+    deps: Vec<Dep>,
+}
+
+enum Dep {
+    /// Directory relative to the repository root
+    Path(String),
+    /// Another member, by package name
+    Name(String),
+}
+
+/// Finds workspace member `name` in the Cargo workspace (`Cargo.toml`) or npm
+/// workspaces (`package.json`) at the root of the source repository at `rev`
+pub fn resolve(git_manager: &GitManager, rev: &str, name: &str) -> Result<Package> {
+    let mut members = cargo_members(git_manager, rev)?;
+    members.extend(npm_members(git_manager, rev)?);
+    if members.is_empty() {
+        return Err(SyncError::Anyhow(anyhow::anyhow!(
+            "--package: no Cargo workspace or npm workspaces at the root of {}",
+            rev
+        )));
+    }
+    let Some(member) = members.iter().find(|member| member.name == name) else {
+        let known: Vec<&str> = members.iter().map(|member| member.name.as_str()).collect();
+        return Err(SyncError::Anyhow(anyhow::anyhow!(
+            "--package: no workspace member named {} (members: {})",
+            name,
+            known.join(", ")
+        )));
+    };
+
+    let by_name: HashMap<&str, &Member> = members.iter().map(|member| (member.name.as_str(), member)).collect();
+    let by_dir: HashMap<&str, &Member> = members.iter().map(|member| (member.dir.as_str(), member)).collect();
+    let mut path_deps = BTreeSet::new();
+    let mut pending: Vec<&Member> = vec![member];
+    while let Some(current) = pending.pop() {
+        for dep in &current.deps {
+            let (dir, next) = match dep {
+                Dep::Path(dir) => (dir.clone(), by_dir.get(dir.as_str()).copied()),
+                Dep::Name(name) => match by_name.get(name.as_str()) {
+                    Some(dep) => (dep.dir.clone(), Some(*dep)),
+                    None => continue,
+                },
+            };
+            if dir != member.dir && path_deps.insert(dir) {
+                pending.extend(next);
+            }
+        }
+    }
+    Ok(Package {
+        name: member.name.clone(),
+        dir: member.dir.clone(),
+        path_deps: path_deps.into_iter().collect(),
+    })
+}
+
+fn cargo_members(git_manager: &GitManager, rev: &str) -> Result<Vec<Member>> {
+    let Some(root) = read_toml(git_manager, rev, "Cargo.toml")? else { return Ok(Vec::new()) };
+    let Some(workspace) = root.get("workspace") else { return Ok(Vec::new()) };
+    let strings = |key: &str| -> Vec<String> {
+        workspace
+            .get(key)
+            .and_then(toml::Value::as_array)
+            .map(|values| values.iter().filter_map(|v| v.as_str().map(str::to_string)).collect())
+            .unwrap_or_default()
+    };
+    let excluded: Vec<String> = strings("exclude").iter().filter_map(|dir| normalize("", dir)).collect();
+    // `workspace = true` dependencies take their path from the root
+    let workspace_deps = workspace.get("dependencies").and_then(toml::Value::as_table);
+
+    let mut dirs = expand_members(git_manager, rev, &strings("members"))?;
+    if root.get("package").is_some() {
+        dirs.insert(0, String::new());
+    }
+    let mut members = Vec::new();
+    for dir in dirs.into_iter().filter(|dir| !excluded.contains(dir)) {
+        let manifest = if dir.is_empty() { Some(root.clone()) } else { read_toml(git_manager, rev, &format!("{}/Cargo.toml", dir))? };
+        let Some(manifest) = manifest else { continue };
+        let Some(name) = manifest.get("package").and_then(|p| p.get("name")).and_then(toml::Value::as_str) else { continue };
+
+        let mut tables = vec![&manifest];
+        if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+            tables.extend(targets.values());
+        }
+        let mut deps = Vec::new();
+        for table in tables {
+            for kind in ["dependencies", "dev-dependencies", "build-dependencies"] {
+                let Some(entries) = table.get(kind).and_then(toml::Value::as_table) else { continue };
+                for (key, spec) in entries {
+                    let path = if spec.get("workspace").and_then(toml::Value::as_bool) == Some(true) {
+                        workspace_deps
+                            .and_then(|deps| deps.get(key))
+                            .and_then(|dep| dep.get("path"))
+                            .and_then(toml::Value::as_str)
+                            .and_then(|path| normalize("", path))
+                    } else {
+                        spec.get("path").and_then(toml::Value::as_str).and_then(|path| normalize(&dir, path))
+                    };
+                    deps.extend(path.map(Dep::Path));
+                }
+            }
+        }
+        members.push(Member { name: name.to_string(), dir, deps });
+    }
+    Ok(members)
+}
+
+fn npm_members(git_manager: &GitManager, rev: &str) -> Result<Vec<Member>> {
+    let Some(root) = read_json(git_manager, rev, "package.json")? else { return Ok(Vec::new()) };
+    // Either a list of globs or `{"packages": [...]}`
+    let patterns = match root.get("workspaces") {
+        Some(serde_json::Value::Object(workspaces)) => workspaces.get("packages"),
+        workspaces => workspaces,
+    };
+    let patterns: Vec<String> = patterns
+        .and_then(serde_json::Value::as_array)
+        .map(|values| values.iter().filter_map(|v| v.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+
+    let mut members = Vec::new();
+    for dir in expand_members(git_manager, rev, &patterns)? {
+        let Some(manifest) = read_json(git_manager, rev, &format!("{}/package.json", dir))? else { continue };
+        let Some(name) = manifest.get("name").and_then(serde_json::Value::as_str) else { continue };
+        let mut deps = Vec::new();
+        for kind in ["dependencies", "devDependencies", "peerDependencies", "optionalDependencies"] {
+            let Some(entries) = manifest.get(kind).and_then(serde_json::Value::as_object) else { continue };
+            for (key, spec) in entries {
+                let spec = spec.as_str().unwrap_or_default();
+                match spec.strip_prefix("file:").or_else(|| spec.strip_prefix("link:")) {
+                    Some(path) => deps.extend(normalize(&dir, path).map(Dep::Path)),
+                    // Workspaces link members by name, whatever version they ask for
+                    None => deps.push(Dep::Name(key.clone())),
+                }
+            }
+        }
+        members.push(Member { name: name.to_string(), dir, deps });
+    }
+    Ok(members)
+}
+
+/// Directories matching the workspace member globs; `*` and `?` match within
+/// one path component
+fn expand_members(git_manager: &GitManager, rev: &str, patterns: &[String]) -> Result<Vec<String>> {
+    let mut dirs = Vec::new();
+    for pattern in patterns {
+        let Some(pattern) = normalize("", pattern) else { continue };
+        let mut matched = vec![String::new()];
+        for component in pattern.split('/') {
+            let mut next = Vec::new();
+            for parent in &matched {
+                let join = |name: &str| if parent.is_empty() { name.to_string() } else { format!("{}/{}", parent, name) };
+                if !component.contains(['*', '?']) {
+                    next.push(join(component));
+                    continue;
+                }
+                for (name, is_dir) in git_manager.list_source_dir(rev, parent)? {
+                    if is_dir && wildcard_match(component, &name) {
+                        next.push(join(&name));
+                    }
+                }
+            }
+            matched = next;
+        }
+        for dir in matched {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    Ok(dirs)
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    match pattern.chars().next() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len())
+            .filter(|&i| name.is_char_boundary(i))
+            .any(|i| wildcard_match(&pattern[1..], &name[i..])),
+        Some(c) => {
+            let Some(first) = name.chars().next() else { return false };
+            (c == '?' || c == first) && wildcard_match(&pattern[c.len_utf8()..], &name[first.len_utf8()..])
+        }
+    }
+}
+
+/// `path` relative to directory `base`, both relative to the repository root,
+/// without `.` and `..` components; `None` when it leaves the repository
+fn normalize(base: &str, path: &str) -> Option<String> {
+    let mut components: Vec<&str> = if path.starts_with('/') { Vec::new() } else { base.split('/').filter(|c| !c.is_empty()).collect() };
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            component => components.push(component),
+        }
+    }
+    Some(components.join("/"))
+}
+
+/// Deepest directory containing all of `dirs`, empty for the repository root
+pub fn common_dir(dirs: &[String]) -> String {
+    let mut common: Vec<&str> = match dirs.first() {
+        Some(dir) => dir.split('/').filter(|c| !c.is_empty()).collect(),
+        None => return String::new(),
+    };
+    for dir in &dirs[1..] {
+        let components: Vec<&str> = dir.split('/').filter(|c| !c.is_empty()).collect();
+        let shared = common.iter().zip(&components).take_while(|(a, b)| a == b).count();
+        common.truncate(shared);
+    }
+    common.join("/")
+}
+
+/// Exclude patterns, relative to `base`, for everything under `base` outside
+/// `dirs`, so syncing `base` only brings the given directories along. Entries
+/// are listed from `rev`; directories get a `/**` pattern.
+pub fn exclusions(git_manager: &GitManager, rev: &str, base: &str, dirs: &[String]) -> Result<Vec<String>> {
+    let relative: Vec<String> = dirs
+        .iter()
+        .map(|dir| dir.strip_prefix(base).unwrap_or(dir).trim_start_matches('/').to_string())
+        .collect();
+    let mut excludes = Vec::new();
+    let mut pending = vec![String::new()];
+    while let Some(dir) = pending.pop() {
+        let listed = if base.is_empty() { dir.clone() } else if dir.is_empty() { base.to_string() } else { format!("{}/{}", base, dir) };
+        for (name, is_dir) in git_manager.list_source_dir(rev, &listed)? {
+            let path = if dir.is_empty() { name } else { format!("{}/{}", dir, name) };
+            if relative.iter().any(|kept| kept.is_empty() || *kept == path || path.starts_with(&format!("{}/", kept))) {
+                continue;
+            }
+            if is_dir && relative.iter().any(|kept| kept.starts_with(&format!("{}/", path))) {
+                pending.push(path);
+            } else if is_dir {
+                excludes.push(format!("{}/**", path));
+            } else {
+                excludes.push(path);
+            }
+        }
+    }
+    excludes.sort();
+    Ok(excludes)
+}
+
+fn read_toml(git_manager: &GitManager, rev: &str, path: &str) -> Result<Option<toml::Value>> {
+    let Some(content) = git_manager.read_source_file(rev, path)? else { return Ok(None) };
+    let value = toml::from_str(&content).map_err(|e| SyncError::Anyhow(anyhow::anyhow!("Failed to parse {}: {}", path, e)))?;
+    Ok(Some(value))
+}
+
+fn read_json(git_manager: &GitManager, rev: &str, path: &str) -> Result<Option<serde_json::Value>> {
+    let Some(content) = git_manager.read_source_file(rev, path)? else { return Ok(None) };
+    let value = serde_json::from_str(&content).map_err(|e| SyncError::Anyhow(anyhow::anyhow!("Failed to parse {}: {}", path, e)))?;
+    Ok(Some(value))
+}
+
+/// Points `config` at its `--package` member: the member's directory becomes
+/// the synced directory, or with `--with-path-deps` the directory it shares with
+/// its path dependencies, with everything else under that directory excluded
+pub fn apply(config: &mut Config, git_manager: &GitManager, rev: &str) -> Result<Option<Package>> {
+    let Some(name) = config.package.clone() else { return Ok(None) };
+    let package = resolve(git_manager, rev, &name)?;
+    if !config.with_path_deps || package.path_deps.is_empty() {
+        config.subdir = package.dir.clone();
+        return Ok(Some(package));
+    }
+    let mut dirs = vec![package.dir.clone()];
+    dirs.extend(package.path_deps.iter().cloned());
+    let base = common_dir(&dirs);
+    config.redact_paths.extend(exclusions(git_manager, rev, &base, &dirs)?);
+    config.subdir = base;
+    Ok(Some(package))
+}